@@ -390,7 +390,7 @@ pub fn path_all_edits(name: &MapName) -> String {
 
 pub fn path_ltn_proposals(name: &MapName, proposal_name: &str) -> String {
     path(format!(
-        "player/ltn_proposals/{}/{}/{}/{}.bin",
+        "player/ltn_proposals/{}/{}/{}/{}.json",
         name.city.country, name.city.city, name.map, proposal_name
     ))
 }
@@ -401,6 +401,13 @@ pub fn path_all_ltn_proposals(name: &MapName) -> String {
     ))
 }
 
+pub fn path_parking_mapper_session(name: &MapName) -> String {
+    path(format!(
+        "player/parking_mapper/{}/{}/{}.json",
+        name.city.country, name.city.city, name.map
+    ))
+}
+
 pub fn path_save(name: &MapName, edits_name: &str, run_name: &str, time: String) -> String {
     path(format!(
         "player/saves/{}/{}/{}/{}_{}/{}.bin",
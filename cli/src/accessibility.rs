@@ -0,0 +1,83 @@
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use map_model::connectivity::{self, AccessibilityIndex, AccessibilityOptions, Mode};
+use map_model::pois::PoiSource;
+use map_model::{Map, MapEdits};
+
+pub fn run(
+    map: String,
+    edits: Option<String>,
+    pois: Option<String>,
+    nearest: usize,
+    format: String,
+    output: String,
+) -> Result<()> {
+    let mut timer = Timer::new("calculate accessibility index");
+    let mut map = Map::load_synchronously(map, &mut timer);
+    if let Some(path) = pois {
+        let sources: Vec<PoiSource> = abstio::maybe_read_json(path, &mut timer)?;
+        for source in sources {
+            let (pois, problems) = source.read()?;
+            let results = map_model::pois::import_pois(&mut map, &source, pois);
+            println!(
+                "Added {} amenities from {}, skipping {} duplicates and {} problems",
+                results.added,
+                source.path,
+                results.duplicates,
+                problems.len() + results.problems.len()
+            );
+        }
+    }
+    let opts = AccessibilityOptions {
+        nearest,
+        ..Default::default()
+    };
+
+    timer.start("accessibility before edits");
+    let before = AccessibilityIndex::new(&map, &opts, &mut timer);
+    timer.stop("accessibility before edits");
+    for mode in Mode::all() {
+        println!(
+            "Average {} score per person: {:.1}",
+            mode.describe(),
+            before.population_weighted_score(mode)
+        );
+    }
+
+    let contents = if let Some(path) = edits {
+        if format != "geojson" {
+            bail!("Changes from edits can only be written as GeoJSON");
+        }
+        let edits = MapEdits::load_from_file(&map, path, &mut timer)?;
+        let mut edited_map = map.clone();
+        edited_map.must_apply_edits(edits, &mut timer);
+        edited_map.recalculate_pathfinding_after_edits(&mut timer);
+
+        timer.start("accessibility after edits");
+        let after = AccessibilityIndex::new(&edited_map, &opts, &mut timer);
+        timer.stop("accessibility after edits");
+        for mode in Mode::all() {
+            println!(
+                "Average {} score per person after edits: {:.1}",
+                mode.describe(),
+                after.population_weighted_score(mode)
+            );
+        }
+
+        let changes = before.changes(&after);
+        for line in connectivity::describe_changes(&changes) {
+            println!("{}", line);
+        }
+        connectivity::changes_to_geojson(&map, &changes).to_string()
+    } else {
+        match format.as_ref() {
+            "csv" => before.to_csv(&map)?,
+            "geojson" => before.to_geojson(&map).to_string(),
+            _ => unreachable!(),
+        }
+    };
+    fs_err::write(&output, contents)?;
+    println!("Wrote {}", output);
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+
+use abstutil::Timer;
+use ltn_model::{Proposal, ProposalAnalysis};
+use map_model::Map;
+use synthpop::Scenario;
+
+pub fn run(
+    map: String,
+    proposal: String,
+    scenario: Option<String>,
+    format: String,
+    output: String,
+) -> Result<()> {
+    let mut timer = Timer::new("analyze LTN proposal");
+    let map = Map::load_synchronously(map, &mut timer);
+    let proposal = Proposal::load_from_file(&map, proposal, &mut timer)?;
+    let scenario: Option<Scenario> = match scenario {
+        Some(path) => Some(abstio::read_object(path, &mut timer)?),
+        None => None,
+    };
+
+    let analysis = ProposalAnalysis::new(&map, &proposal, scenario.as_ref(), &mut timer);
+    let contents = match format.as_ref() {
+        "json" => abstutil::to_json(&analysis),
+        "geojson" => analysis.to_geojson(&map, &proposal)?.to_string(),
+        _ => unreachable!(),
+    };
+    fs_err::write(&output, contents)?;
+
+    for n in &analysis.neighborhoods {
+        println!(
+            "{:?}: {} rat-runs, {} / {} quiet streets, {} cells ({} disconnected)",
+            n.id,
+            n.rat_runs,
+            n.quiet_roads,
+            n.interior_roads,
+            n.cells.len(),
+            n.disconnected_cells
+        );
+    }
+    println!("Wrote {}", output);
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Result};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::Duration;
+use map_model::{Map, PathRequest, PathfinderCaching};
+use sim::{AgentType, Sim, SimFlags, SimOptions};
+use synthpop::{CalibrationConfig, CountLocation, Scenario, TrafficCounts, TripEndpoint, TripMode};
+
+pub fn run(
+    map: String,
+    scenario: String,
+    counts: String,
+    config: CalibrationConfig,
+    simulate: bool,
+    output_name: String,
+) -> Result<()> {
+    let mut timer = Timer::new("calibrate scenario");
+    let map = Map::load_synchronously(map, &mut timer);
+    let scenario: Scenario = abstio::read_object(scenario, &mut timer)?;
+    let observed: TrafficCounts = abstio::read_json(counts, &mut timer);
+    if observed.map != *map.get_name() {
+        bail!(
+            "The counts are for {}, but the map is {}",
+            observed.map.describe(),
+            map.get_name().describe()
+        );
+    }
+
+    if config.modes.contains(&TripMode::Transit) {
+        bail!("Transit trips can't be calibrated against traffic counts");
+    }
+    let modes = config.modes.clone();
+    let result = synthpop::calibrate(
+        &map,
+        &scenario,
+        &observed,
+        &config,
+        |candidate, timer| {
+            if simulate {
+                simulated_counts(&map, candidate, &modes, timer)
+            } else {
+                routed_counts(&map, candidate, &modes, timer)
+            }
+        },
+        &mut timer,
+    );
+
+    println!("location, observed, modeled, GEH");
+    for c in &result.comparisons {
+        let location = match c.location {
+            CountLocation::Road(r) => r.to_string(),
+            CountLocation::Intersection(i) => i.to_string(),
+        };
+        println!("{}, {}, {}, {:.2}", location, c.observed, c.modeled, c.geh);
+    }
+    println!(
+        "After {} iterations, {:.1}% of {} count locations have GEH < {} ({} people, originally {})",
+        result.iterations,
+        100.0 * result.fraction_matching,
+        result.comparisons.len(),
+        config.target_geh,
+        prettyprint_usize(result.scenario.people.len()),
+        prettyprint_usize(scenario.people.len())
+    );
+    if result.fraction_matching < config.target_fraction {
+        println!(
+            "The target of {:.1}% wasn't met",
+            100.0 * config.target_fraction
+        );
+    }
+
+    let mut calibrated = result.scenario;
+    calibrated.scenario_name = output_name;
+    calibrated.save();
+    println!(
+        "Wrote {}",
+        abstio::path_scenario(&calibrated.map_name, &calibrated.scenario_name)
+    );
+    Ok(())
+}
+
+/// Quickly estimate counts by routing every trip on the empty map
+fn routed_counts(
+    map: &Map,
+    scenario: &Scenario,
+    modes: &BTreeSet<TripMode>,
+    timer: &mut Timer,
+) -> TrafficCounts {
+    let requests = scenario
+        .all_trips()
+        .filter_map(|trip| {
+            if modes.contains(&trip.mode) {
+                TripEndpoint::path_req(trip.origin, trip.destination, trip.mode, map)
+            } else {
+                None
+            }
+        })
+        .collect();
+    TrafficCounts::from_path_requests(
+        map,
+        scenario.scenario_name.clone(),
+        &PathRequest::deduplicate(map, requests),
+        map.routing_params().clone(),
+        PathfinderCaching::NoCache,
+        timer,
+    )
+}
+
+/// Simulate the full day and count the agents using each road and intersection
+fn simulated_counts(
+    map: &Map,
+    scenario: &Scenario,
+    modes: &BTreeSet<TripMode>,
+    timer: &mut Timer,
+) -> TrafficCounts {
+    let agent_types: BTreeSet<AgentType> = modes
+        .iter()
+        .filter_map(|mode| match mode {
+            TripMode::Drive => Some(AgentType::Car),
+            TripMode::Bike | TripMode::Micromobility => Some(AgentType::Bike),
+            TripMode::Walk => Some(AgentType::Pedestrian),
+            // Riders are only counted while walking, and buses and trains aren't scenario trips
+            TripMode::Transit => None,
+        })
+        .collect();
+
+    let mut sim = Sim::new(map, SimOptions::new("calibrate"));
+    let mut rng = XorShiftRng::seed_from_u64(SimFlags::RNG_SEED);
+    sim.instantiate(scenario, map, &mut rng, timer);
+    sim.timed_step(map, Duration::hours(24), &mut None, timer);
+
+    sim.get_analytics()
+        .traffic_counts(map, scenario.scenario_name.clone(), &agent_types)
+}
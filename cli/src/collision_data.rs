@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Result};
+
+use abstutil::{prettyprint_usize, Timer};
+use collisions::{CollisionDataset, CsvMapping, Severity};
+use geom::Pt2D;
+use map_model::crash_rates::CrashRates;
+use map_model::Map;
+use sim::{AgentType, Analytics};
+
+/// How many of the worst roads and intersections to print
+const NUM_WORST: usize = 10;
+
+pub fn import(
+    input: String,
+    format: String,
+    mapping: Option<String>,
+    source_url: String,
+    map: String,
+) -> Result<()> {
+    let mut timer = Timer::new("import collisions");
+    let map = Map::load_synchronously(map, &mut timer);
+    let bounds = map.get_gps_bounds();
+
+    let data = match format.as_ref() {
+        "stats19" => collisions::import_stats19(
+            kml::ExtraShapes::load_csv(input, bounds, &mut timer)?,
+            &source_url,
+        ),
+        "seattle" => {
+            collisions::import_seattle(kml::load(input, bounds, true, &mut timer)?, &source_url)
+        }
+        "fars" => collisions::import_fars(
+            kml::ExtraShapes::load_csv_with_coordinates(
+                input, "LONGITUD", "LATITUDE", bounds, &mut timer,
+            )?,
+            &source_url,
+        ),
+        "csv" => {
+            let path = match mapping {
+                Some(path) => path,
+                None => bail!("--mapping is required for --format=csv"),
+            };
+            let mapping: CsvMapping = abstio::maybe_read_json(path, &mut timer)?;
+            collisions::import_csv(
+                kml::ExtraShapes::load_csv_with_coordinates(
+                    input,
+                    &mapping.longitude,
+                    &mapping.latitude,
+                    bounds,
+                    &mut timer,
+                )?,
+                &mapping,
+                &source_url,
+            )
+        }
+        x => bail!("Unknown format {}", x),
+    };
+
+    let output = map.get_city_name().input_path("collisions.bin");
+    println!(
+        "Imported {} collisions, writing {}",
+        prettyprint_usize(data.collisions.len()),
+        output
+    );
+    abstio::write_binary(output, &data);
+    Ok(())
+}
+
+pub fn crash_rates(map: String, scenario: String, days: Option<usize>, ksi: bool) -> Result<()> {
+    let mut timer = Timer::new("calculate crash rates");
+    let map = Map::load_synchronously(map, &mut timer);
+    let data: CollisionDataset =
+        abstio::maybe_read_binary(map.get_city_name().input_path("collisions.bin"), &mut timer)?;
+    let prebaked: Analytics = abstio::maybe_read_binary(
+        abstio::path_prebaked_results(map.get_name(), &scenario),
+        &mut timer,
+    )?;
+
+    let days = match days.or_else(|| data.days_covered()) {
+        Some(days) => days,
+        None => bail!("The collisions don't have dates; pass --days"),
+    };
+    let pts: Vec<Pt2D> = data
+        .collisions
+        .iter()
+        .filter(|c| !ksi || c.severity >= Severity::Serious)
+        .map(|c| c.location.to_pt(map.get_gps_bounds()))
+        .filter(|pt| map.get_boundary_polygon().contains_pt(*pt))
+        .collect();
+
+    // Crash rates are conventionally per motor vehicle
+    let vehicles: BTreeSet<AgentType> = vec![AgentType::Car, AgentType::Bus].into_iter().collect();
+    let rates = CrashRates::new(
+        &map,
+        &pts,
+        days,
+        &prebaked.road_thruput.all_total_counts(&vehicles),
+        &prebaked.intersection_thruput.all_total_counts(&vehicles),
+    );
+    println!(
+        "{} collisions over {} days, {} not near any road",
+        prettyprint_usize(pts.len()),
+        prettyprint_usize(days),
+        prettyprint_usize(rates.unmatched)
+    );
+
+    let path = format!("road_crash_rates_{}.csv", map.get_name().as_filename());
+    let mut f = csv::Writer::from_path(&path)?;
+    f.write_record(&[
+        "road",
+        "osm_way_id",
+        "name",
+        "collisions",
+        "per_million_vehicle_miles",
+    ])?;
+    for (r, rate) in &rates.per_road {
+        let road = map.get_r(*r);
+        f.write_record(&[
+            r.0.to_string(),
+            road.orig_id.osm_way_id.0.to_string(),
+            road.get_name(None),
+            rates.road_collisions.get(*r).to_string(),
+            rate.to_string(),
+        ])?;
+    }
+    f.flush()?;
+    println!("Wrote {}", path);
+
+    let path = format!(
+        "intersection_crash_rates_{}.csv",
+        map.get_name().as_filename()
+    );
+    let mut f = csv::Writer::from_path(&path)?;
+    f.write_record(&[
+        "intersection",
+        "collisions",
+        "per_million_entering_vehicles",
+    ])?;
+    for (i, rate) in &rates.per_intersection {
+        f.write_record(&[
+            i.0.to_string(),
+            rates.intersection_collisions.get(*i).to_string(),
+            rate.to_string(),
+        ])?;
+    }
+    f.flush()?;
+    println!("Wrote {}", path);
+
+    println!("Highest crash rates along roads:");
+    let mut roads: Vec<_> = rates.per_road.iter().collect();
+    roads.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+    for (r, rate) in roads.into_iter().take(NUM_WORST) {
+        println!(
+            "  {} ({}): {:.2} per million vehicle-miles",
+            map.get_r(*r).get_name(None),
+            r,
+            rate
+        );
+    }
+    println!("Highest crash rates at intersections:");
+    let mut intersections: Vec<_> = rates.per_intersection.iter().collect();
+    intersections.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+    for (i, rate) in intersections.into_iter().take(NUM_WORST) {
+        println!("  {}: {:.2} per million entering vehicles", i, rate);
+    }
+    Ok(())
+}
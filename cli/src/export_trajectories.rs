@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::Map;
+use sim::{Sim, SimFlags, SimOptions, TrajectoryRecorder};
+use synthpop::Scenario;
+
+pub fn run(
+    map: String,
+    scenario: String,
+    start: Time,
+    end: Time,
+    interval: f64,
+    format: String,
+    output: String,
+) -> Result<()> {
+    if end <= start {
+        bail!("--end must be after --start");
+    }
+    if interval <= 0.0 {
+        bail!("--interval must be positive");
+    }
+
+    let mut timer = Timer::new("export trajectories");
+    let map = Map::load_synchronously(map, &mut timer);
+    let scenario: Scenario = abstio::read_object(scenario, &mut timer)?;
+    let mut sim = Sim::new(&map, SimOptions::new("export_trajectories"));
+    let mut rng = XorShiftRng::seed_from_u64(SimFlags::RNG_SEED);
+    sim.instantiate(&scenario, &map, &mut rng, &mut timer);
+    sim.timed_step(&map, start - Time::START_OF_DAY, &mut None, &mut timer);
+
+    let recorder =
+        TrajectoryRecorder::record(&mut sim, &map, end, Duration::seconds(interval), &mut timer);
+    let contents = match format.as_ref() {
+        "deckgl" => recorder.to_deckgl_trips(&map)?,
+        "fcd" => recorder.to_sumo_fcd(&map),
+        "kepler" => recorder.to_kepler_csv(&map)?,
+        _ => unreachable!(),
+    };
+    fs_err::write(&output, contents)?;
+    println!(
+        "Wrote {} trajectories to {}",
+        recorder.get_trajectories().len(),
+        output
+    );
+    Ok(())
+}
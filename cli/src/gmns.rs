@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+use abstutil::Timer;
+use map_model::Map;
+
+pub fn export(map: String, output: String) -> Result<()> {
+    let map = Map::load_synchronously(map, &mut Timer::throwaway());
+    map_model::gmns::export(&map, &output)?;
+    println!("Wrote GMNS files to {}", output);
+    Ok(())
+}
+
+pub fn import(map: String, input: String, edits_name: String) -> Result<()> {
+    let mut timer = Timer::new("import GMNS");
+    let mut map = Map::load_synchronously(map, &mut timer);
+    // The import starts from the map's current edits
+    let existing = map.get_edits().commands.len();
+    let (mut edits, problems) = map_model::gmns::import_link_attributes(&map, &input)?;
+    for problem in problems {
+        println!("Skipped {}", problem);
+    }
+    println!("{} roads changed", edits.commands.len() - existing);
+
+    edits.edits_name = edits_name;
+    map.must_apply_edits(edits, &mut timer);
+    map.save_edits();
+    println!(
+        "Wrote {}",
+        abstio::path_edits(map.get_name(), &map.get_edits().edits_name)
+    );
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use geom::{Duration, LonLat};
+use map_model::Map;
+use synthpop::{TmcLayout, TmcLocation, TrafficCounts};
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    map: String,
+    input: String,
+    layout: String,
+    osm_node_id: Option<i64>,
+    lon: Option<f64>,
+    lat: Option<f64>,
+    bin_minutes: usize,
+    output: String,
+) -> Result<()> {
+    let mut timer = Timer::new("import turning movement counts");
+    let map = Map::load_synchronously(map, &mut timer);
+    let layout = match layout.as_ref() {
+        "long" => TmcLayout::Long,
+        "wide" => TmcLayout::Wide(match (osm_node_id, lon, lat) {
+            (Some(id), _, _) => TmcLocation::OsmNode(id),
+            (None, Some(lon), Some(lat)) => TmcLocation::Position(LonLat::new(lon, lat)),
+            _ => bail!("--layout=wide needs --osm-node-id or --lon and --lat"),
+        }),
+        x => bail!("Unknown layout {}", x),
+    };
+
+    let (counts, problems) =
+        synthpop::import_tmc(&map, &input, layout, Duration::minutes(bin_minutes))?;
+    for problem in problems {
+        println!("Skipped {}", problem);
+    }
+    println!(
+        "Imported counts for {} movements at {} intersections",
+        counts.per_movement.totals().borrow().len(),
+        counts.per_intersection.borrow().len()
+    );
+
+    // Counts for different intersections often come in separate files, so accumulate them
+    let result = if abstio::file_exists(&output) {
+        let mut existing: TrafficCounts = abstio::read_json(output.clone(), &mut timer);
+        if existing.per_movement.bin_size != counts.per_movement.bin_size
+            && !existing.per_movement.is_empty()
+        {
+            bail!("{} uses a different bin size", output);
+        }
+        existing.per_movement.bin_size = counts.per_movement.bin_size;
+        for (key, cnt) in counts.per_movement.counts {
+            *existing.per_movement.counts.entry(key).or_insert(0) += cnt;
+        }
+        for (i, cnt) in counts.per_intersection.consume() {
+            existing.per_intersection.add(i, cnt);
+        }
+        println!("Added to the existing counts in {}", output);
+        existing
+    } else {
+        counts
+    };
+    abstio::write_json(output.clone(), &result);
+    println!("Wrote {}", output);
+    Ok(())
+}
@@ -4,13 +4,24 @@
 #[macro_use]
 extern crate log;
 
+mod accessibility;
+mod analyze_ltn;
 mod augment_scenario;
+mod calibrate;
 mod clip_osm;
+mod collision_data;
+mod export_trajectories;
 mod generate_houses;
 mod geojson_to_osmosis;
+mod gmns;
 mod import_grid2demand;
 mod import_scenario;
+mod import_tmc;
+mod matsim;
+mod migrate_ids;
 mod one_step_import;
+mod optimize_ltn;
+mod optimize_signals;
 mod osm2lanes;
 
 use std::io::Write;
@@ -22,6 +33,7 @@ use importer::Job;
 use structopt::StructOpt;
 
 use abstutil::Timer;
+use geom::Time;
 
 #[derive(StructOpt)]
 #[structopt(name = "abcli", about = "The A/B Street multi-tool")]
@@ -107,6 +119,94 @@ enum Command {
         #[structopt(long)]
         skip_problems: bool,
     },
+    /// Import a scenario from a MATSim plans.xml file. Each person's selected plan becomes a
+    /// schedule of trips between activities, which are snapped to the nearest building or border.
+    /// Coordinates must be WGS84 longitude and latitude.
+    #[structopt(name = "import-matsim")]
+    ImportMATSim {
+        /// The path to a MATSim plans.xml file
+        #[structopt(long)]
+        input: String,
+        /// The path to a map matching the plans
+        #[structopt(long)]
+        map: String,
+        /// The name of the scenario to create
+        #[structopt(long)]
+        scenario_name: String,
+        /// Problems occur when a position is within the map boundary, but not close enough to
+        /// buildings. Skip people with problematic positions if true, abort otherwise.
+        #[structopt(long)]
+        skip_problems: bool,
+    },
+    /// Exports a map as a MATSim network.xml file, and optionally a scenario as plans.xml.
+    /// Coordinates are written as WGS84 longitude and latitude.
+    #[structopt(name = "export-matsim")]
+    ExportMATSim {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to a scenario to export
+        #[structopt(long)]
+        scenario: Option<String>,
+        /// The directory to write the XML files
+        #[structopt(long)]
+        output: String,
+    },
+    /// Import real-world turning movement counts from a CSV file, matching them to the movements
+    /// of a map. The result is in the `synthpop::TrafficCounts` format, and can be compared
+    /// against simulated counts.
+    #[structopt(name = "import-tmc")]
+    ImportTMC {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to a CSV file with turning movement counts
+        #[structopt(long)]
+        input: String,
+        /// "long" has one row per movement and time bin, with `start_time`, `movement`, `count`,
+        /// and `osm_node_id` or `longitude` and `latitude` columns. "wide" has one row per time
+        /// bin for a single intersection, with a `start_time` column and one column per movement,
+        /// like "NBL" or "Northbound Left".
+        #[structopt(long, possible_values = &["long", "wide"])]
+        layout: String,
+        /// For --layout=wide, the OSM node ID of the intersection
+        #[structopt(long)]
+        osm_node_id: Option<i64>,
+        /// For --layout=wide, the longitude of the intersection
+        #[structopt(long)]
+        lon: Option<f64>,
+        /// For --layout=wide, the latitude of the intersection
+        #[structopt(long)]
+        lat: Option<f64>,
+        /// The length of each time bin in minutes
+        #[structopt(long, default_value = "15")]
+        bin_minutes: usize,
+        /// The JSON file to write. If it already exists, the new counts are added to it.
+        #[structopt(long)]
+        output: String,
+    },
+    /// Import real-world collision data for the city containing a map. The result is written to
+    /// the city's input directory.
+    ImportCollisions {
+        /// The path to the collision data
+        #[structopt(long)]
+        input: String,
+        /// The format of the input. "stats19" is the UK accidents table as CSV, "seattle" is
+        /// SDOT's KML, "fars" is the US FARS accident table as CSV, and "csv" is any CSV file
+        /// described by --mapping.
+        #[structopt(long, possible_values = &["stats19", "seattle", "fars", "csv"])]
+        format: String,
+        /// For --format=csv, the path to a JSON file describing the columns. See
+        /// `collisions::CsvMapping`.
+        #[structopt(long)]
+        mapping: Option<String>,
+        /// Where the data came from, to record in the output
+        #[structopt(long)]
+        source_url: String,
+        /// The path to a map in the city covered by the data
+        #[structopt(long)]
+        map: String,
+    },
     /// Transform a JSON map that's been manually edited into the binary format suitable for
     /// simulation.
     ImportJSONMap {
@@ -117,6 +217,216 @@ enum Command {
         #[structopt(long)]
         output: String,
     },
+    /// After re-importing a map from newer OSM data, IDs like `RoadID` and `BuildingID` shift.
+    /// This matches the old and new map by OSM IDs and geometry, then translates all edits,
+    /// scenarios, prebaked results, and LTN proposals for the map to the new IDs. Files are
+    /// overwritten in-place, and anything that couldn't be carried over is reported.
+    MigrateIds {
+        /// The path to a copy of the map before it was re-imported
+        #[structopt(long)]
+        old_map: String,
+        /// The path to the newly imported map
+        #[structopt(long)]
+        new_map: String,
+        /// List every object that couldn't be matched, not just a summary
+        #[structopt(long)]
+        verbose: bool,
+    },
+    /// Analyzes a low-traffic neighborhood proposal without opening the LTN tool. Reports
+    /// rat-runs and cell connectivity per neighborhood, and optionally predicts how traffic on
+    /// boundary roads changes.
+    Ltn {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to a proposal saved by the LTN tool
+        #[structopt(long)]
+        proposal: String,
+        /// The path to a scenario. If specified, every driving trip is routed before and after
+        /// the proposal's filters, to predict traffic on boundary roads.
+        #[structopt(long)]
+        scenario: Option<String>,
+        /// "json" has all results, and "geojson" has neighborhoods, cells, and roads with the
+        /// results as properties.
+        #[structopt(long, possible_values = &["json", "geojson"])]
+        format: String,
+        /// The file to write
+        #[structopt(long)]
+        output: String,
+    },
+    /// Searches for the fewest modal filters that stop all rat-runs through the neighborhoods of a
+    /// low-traffic neighborhood proposal, without disconnecting any area or filtering a road used
+    /// by transit. Each alternative is saved as a new proposal, loadable in the LTN tool.
+    LtnOptimize {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to a proposal saved by the LTN tool. Existing filters are kept.
+        #[structopt(long)]
+        proposal: String,
+        /// How many alternative layouts to save, best first
+        #[structopt(long, default_value = "3")]
+        alternatives: usize,
+    },
+    /// Calculates a 15-minute accessibility index for every residential building. For each type
+    /// of amenity, this finds the travel time to the closest few by walking, biking, and transit,
+    /// and a gravity-based score counting every reachable amenity.
+    Accessibility {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to map edits. If specified, the index is calculated before and after the
+        /// edits, and the output has every household that gained or lost 15-minute access to some
+        /// type of amenity.
+        #[structopt(long)]
+        edits: Option<String>,
+        /// The path to a JSON list of extra datasets of points of interest to add as amenities
+        /// first, in the same format as the importer's `extra_pois.json`
+        #[structopt(long)]
+        pois: Option<String>,
+        /// Find the travel time to this many of the closest amenities of each type
+        #[structopt(long, default_value = "3")]
+        nearest: usize,
+        /// "csv" has one row per household, and "geojson" has buildings with the results as
+        /// properties. Changes from edits can only be written as GeoJSON.
+        #[structopt(long, possible_values = &["csv", "geojson"])]
+        format: String,
+        /// The file to write
+        #[structopt(long)]
+        output: String,
+    },
+    /// Coordinates the traffic signals along a route into a green wave. Every signal gets a common
+    /// cycle length, and offsets are chosen to maximize the bandwidth in both directions. The new
+    /// timing is saved as map edits.
+    OptimizeSignals {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The ID of the intersection where the corridor starts
+        #[structopt(long)]
+        from: usize,
+        /// The ID of the intersection where the corridor ends
+        #[structopt(long)]
+        to: usize,
+        /// The common cycle length in seconds. Defaults to the longest existing cycle along the
+        /// corridor.
+        #[structopt(long)]
+        cycle_length: Option<usize>,
+        /// The path to a scenario. If specified, offsets are further refined by simulating this
+        /// scenario headlessly for each candidate and minimizing the delay at the corridor's
+        /// signals. This is much slower.
+        #[structopt(long)]
+        scenario: Option<String>,
+        /// When refining with a scenario, how long to simulate each candidate
+        #[structopt(long, parse(try_from_str = Time::parse), default_value = "12:00:00")]
+        until: Time,
+        /// The name of the edits to write
+        #[structopt(long)]
+        edits_name: String,
+    },
+    /// Calibrates a scenario against observed traffic counts, by duplicating or removing people
+    /// until the GEH statistic meets a target at enough count locations. Prints the GEH per count
+    /// location and saves the calibrated scenario.
+    Calibrate {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to the scenario to calibrate
+        #[structopt(long)]
+        scenario: String,
+        /// The path to a JSON file with observed counts, in the `synthpop::TrafficCounts` format
+        #[structopt(long)]
+        counts: String,
+        /// How many hours the counts cover
+        #[structopt(long, default_value = "24")]
+        hours: f64,
+        /// Count locations with a GEH below this match
+        #[structopt(long, default_value = "5")]
+        target_geh: f64,
+        /// Stop once this fraction of count locations match
+        #[structopt(long, default_value = "0.85")]
+        target_fraction: f64,
+        /// Give up after this many iterations
+        #[structopt(long, default_value = "10")]
+        max_iterations: usize,
+        /// Measure counts by simulating the full day for every iteration, instead of just routing
+        /// trips on the empty map. This is much slower, but accounts for congestion.
+        #[structopt(long)]
+        simulate: bool,
+        /// The name of the calibrated scenario to write
+        #[structopt(long)]
+        output_name: String,
+    },
+    /// Calculates crash rates per road and intersection, using the city's imported collision
+    /// data and vehicle counts from a scenario's prebaked results. Writes CSV files to the current
+    /// directory.
+    CrashRates {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The scenario whose prebaked results measure how many vehicles use each road
+        #[structopt(long, default_value = "weekday")]
+        scenario: String,
+        /// How many days the collision data covers. Defaults to the span between the first and
+        /// last collision.
+        #[structopt(long)]
+        days: Option<usize>,
+        /// Only count collisions where somebody was killed or seriously injured
+        #[structopt(long)]
+        ksi: bool,
+    },
+    /// Simulates a scenario and records the position of every agent over a window of time, for
+    /// animating in deck.gl, SUMO tools, or Kepler.gl.
+    ExportTrajectories {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to a scenario
+        #[structopt(long)]
+        scenario: String,
+        /// When to start recording
+        #[structopt(long, parse(try_from_str = Time::parse))]
+        start: Time,
+        /// When to stop recording
+        #[structopt(long, parse(try_from_str = Time::parse))]
+        end: Time,
+        /// How often to sample positions, in seconds
+        #[structopt(long, default_value = "1")]
+        interval: f64,
+        /// "deckgl" is JSON for a TripsLayer, "fcd" is SUMO's floating car data XML, and "kepler"
+        /// is CSV with one row per sample.
+        #[structopt(long, possible_values = &["deckgl", "fcd", "kepler"])]
+        format: String,
+        /// The file to write
+        #[structopt(long)]
+        output: String,
+    },
+    /// Exports a map's nodes, links, lanes, movements, and traffic signal timing as CSV files in
+    /// the https://github.com/zephyr-data-specs/GMNS format.
+    #[structopt(name = "export-gmns")]
+    ExportGMNS {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The directory to write the CSV files
+        #[structopt(long)]
+        output: String,
+    },
+    /// Reads the number of lanes and speed limits from GMNS link.csv and node.csv files, and saves
+    /// the changes as map edits. The links should come from an earlier `export-gmns` of the same
+    /// area; they're matched to roads by OSM IDs.
+    #[structopt(name = "import-gmns")]
+    ImportGMNS {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The directory containing the GMNS files
+        #[structopt(long)]
+        input: String,
+        /// The name of the edits to write
+        #[structopt(long)]
+        edits_name: String,
+    },
     /// Removes nonessential parts of a Map, for the bike network tool.
     MinifyMap {
         /// The path to a map to shrink. The map is modified in-place.
@@ -167,6 +477,10 @@ enum Command {
         /// Downgrade crosswalks not matching a `highway=crossing` OSM node into unmarked crossings.
         #[structopt(long)]
         filter_crosswalks: bool,
+        /// Snap sidewalks mapped as separate `footway=sidewalk` ways to their road, and create
+        /// mid-block crossings from `highway=crossing` nodes.
+        #[structopt(long)]
+        separate_sidewalks: bool,
         /// Generate a simple travel demand model based on 2011 UK commuting data. This will only
         /// work if the boundary is in the UK.
         #[structopt(long)]
@@ -186,6 +500,10 @@ enum Command {
         /// Downgrade crosswalks not matching a `highway=crossing` OSM node into unmarked crossings.
         #[structopt(long)]
         filter_crosswalks: bool,
+        /// Snap sidewalks mapped as separate `footway=sidewalk` ways to their road, and create
+        /// mid-block crossings from `highway=crossing` nodes.
+        #[structopt(long)]
+        separate_sidewalks: bool,
         /// Generate a simple travel demand model based on 2011 UK commuting data. This will only
         /// work if the boundary is in the UK.
         #[structopt(long)]
@@ -259,7 +577,123 @@ async fn main() -> Result<()> {
             map,
             skip_problems,
         } => import_scenario::run(input, map, skip_problems),
+        Command::ImportMATSim {
+            input,
+            map,
+            scenario_name,
+            skip_problems,
+        } => matsim::import(input, map, scenario_name, skip_problems)?,
+        Command::ExportMATSim {
+            map,
+            scenario,
+            output,
+        } => matsim::export(map, scenario, output)?,
+        Command::ImportTMC {
+            map,
+            input,
+            layout,
+            osm_node_id,
+            lon,
+            lat,
+            bin_minutes,
+            output,
+        } => import_tmc::run(
+            map,
+            input,
+            layout,
+            osm_node_id,
+            lon,
+            lat,
+            bin_minutes,
+            output,
+        )?,
+        Command::ImportCollisions {
+            input,
+            format,
+            mapping,
+            source_url,
+            map,
+        } => collision_data::import(input, format, mapping, source_url, map)?,
         Command::ImportJSONMap { input, output } => import_json_map(input, output),
+        Command::MigrateIds {
+            old_map,
+            new_map,
+            verbose,
+        } => migrate_ids::run(old_map, new_map, verbose)?,
+        Command::Ltn {
+            map,
+            proposal,
+            scenario,
+            format,
+            output,
+        } => analyze_ltn::run(map, proposal, scenario, format, output)?,
+        Command::LtnOptimize {
+            map,
+            proposal,
+            alternatives,
+        } => optimize_ltn::run(map, proposal, alternatives)?,
+        Command::Accessibility {
+            map,
+            edits,
+            pois,
+            nearest,
+            format,
+            output,
+        } => accessibility::run(map, edits, pois, nearest, format, output)?,
+        Command::OptimizeSignals {
+            map,
+            from,
+            to,
+            cycle_length,
+            scenario,
+            until,
+            edits_name,
+        } => optimize_signals::run(map, from, to, cycle_length, scenario, until, edits_name)?,
+        Command::Calibrate {
+            map,
+            scenario,
+            counts,
+            hours,
+            target_geh,
+            target_fraction,
+            max_iterations,
+            simulate,
+            output_name,
+        } => calibrate::run(
+            map,
+            scenario,
+            counts,
+            synthpop::CalibrationConfig {
+                hours,
+                target_geh,
+                target_fraction,
+                max_iterations,
+                ..Default::default()
+            },
+            simulate,
+            output_name,
+        )?,
+        Command::CrashRates {
+            map,
+            scenario,
+            days,
+            ksi,
+        } => collision_data::crash_rates(map, scenario, days, ksi)?,
+        Command::ExportTrajectories {
+            map,
+            scenario,
+            start,
+            end,
+            interval,
+            format,
+            output,
+        } => export_trajectories::run(map, scenario, start, end, interval, format, output)?,
+        Command::ExportGMNS { map, output } => gmns::export(map, output)?,
+        Command::ImportGMNS {
+            map,
+            input,
+            edits_name,
+        } => gmns::import(map, input, edits_name)?,
         Command::MinifyMap { map } => minify_map(map),
         Command::GenerateHouses {
             map,
@@ -276,6 +710,7 @@ async fn main() -> Result<()> {
             drive_on_left,
             use_geofabrik,
             filter_crosswalks,
+            separate_sidewalks,
             create_uk_travel_demand_model,
         } => {
             one_step_import::run(
@@ -284,6 +719,7 @@ async fn main() -> Result<()> {
                 drive_on_left,
                 use_geofabrik,
                 filter_crosswalks,
+                separate_sidewalks,
                 create_uk_travel_demand_model,
             )
             .await?
@@ -293,6 +729,7 @@ async fn main() -> Result<()> {
             clip_path,
             drive_on_left,
             filter_crosswalks,
+            separate_sidewalks,
             create_uk_travel_demand_model,
             opts,
         } => {
@@ -301,6 +738,7 @@ async fn main() -> Result<()> {
                 clip_path,
                 drive_on_left,
                 filter_crosswalks,
+                separate_sidewalks,
                 create_uk_travel_demand_model,
                 opts,
             )
@@ -0,0 +1,47 @@
+use anyhow::Result;
+
+use abstutil::{prettyprint_usize, Timer};
+use map_model::Map;
+use synthpop::Scenario;
+
+pub fn import(
+    input: String,
+    map: String,
+    scenario_name: String,
+    skip_problems: bool,
+) -> Result<()> {
+    let mut timer = Timer::new("import MATSim plans");
+    let map = Map::load_synchronously(map, &mut timer);
+
+    let mut s = Scenario::empty(&map, &scenario_name);
+    // Include all buses/trains
+    s.only_seed_buses = None;
+    s.people = synthpop::matsim::import_plans(&map, &input, skip_problems)?;
+    let orig_num = s.people.len();
+    s = s.remove_weird_schedules(true);
+    println!(
+        "Imported {}/{} people",
+        prettyprint_usize(s.people.len()),
+        prettyprint_usize(orig_num)
+    );
+    s.save();
+    Ok(())
+}
+
+pub fn export(map: String, scenario: Option<String>, output: String) -> Result<()> {
+    let mut timer = Timer::new("export to MATSim");
+    let map = Map::load_synchronously(map, &mut timer);
+    fs_err::create_dir_all(&output)?;
+
+    let path = format!("{}/network.xml", output);
+    fs_err::write(&path, synthpop::matsim::export_network(&map))?;
+    println!("Wrote {}", path);
+
+    if let Some(scenario) = scenario {
+        let scenario: Scenario = abstio::read_object(scenario, &mut timer)?;
+        let path = format!("{}/plans.xml", output);
+        fs_err::write(&path, synthpop::matsim::export_plans(&scenario, &map))?;
+        println!("Wrote {}", path);
+    }
+    Ok(())
+}
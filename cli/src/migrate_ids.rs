@@ -0,0 +1,96 @@
+use anyhow::Result;
+
+use abstutil::Timer;
+use ltn_model::{PermanentProposal, Proposal};
+use map_model::{IdMigration, Map, MapEdits};
+use sim::Analytics;
+use synthpop::Scenario;
+
+pub fn run(old_map: String, new_map: String, verbose: bool) -> Result<()> {
+    let mut timer = Timer::new("migrate IDs");
+    let old_map = Map::load_synchronously(old_map, &mut timer);
+    let new_map = Map::load_synchronously(new_map, &mut timer);
+    let migration = IdMigration::new(&old_map, &new_map, &mut timer);
+
+    println!("Matching {} to the new map:", old_map.get_name().describe());
+    for line in migration.describe() {
+        println!("  {}", line);
+    }
+    if verbose {
+        for obj in &migration.unmatched {
+            println!("  - no match for {}", obj);
+        }
+    }
+
+    for name in abstio::list_all_objects(abstio::path_all_edits(old_map.get_name())) {
+        let path = abstio::path_edits(old_map.get_name(), &name);
+        let edits = match MapEdits::load_from_file(&old_map, path, &mut timer) {
+            Ok(edits) => edits,
+            Err(err) => {
+                println!(
+                    "Skipping edits {}, which don't match the old map: {}",
+                    name, err
+                );
+                continue;
+            }
+        };
+        let (edits, problems) = edits.migrate(&migration, &new_map);
+        report("edits", &name, problems);
+        abstio::write_json(
+            abstio::path_edits(new_map.get_name(), &name),
+            &edits.to_permanent(&new_map),
+        );
+    }
+
+    for name in abstio::list_all_objects(abstio::path_all_scenarios(old_map.get_name())) {
+        let mut scenario: Scenario =
+            abstio::read_object(abstio::path_scenario(old_map.get_name(), &name), &mut timer)?;
+        let problems = scenario.migrate(&migration, &new_map);
+        report("scenario", &name, problems);
+        scenario.save();
+
+        let prebaked_path = abstio::path_prebaked_results(old_map.get_name(), &name);
+        if abstio::file_exists(&prebaked_path) {
+            let prebaked: Analytics = abstio::maybe_read_binary(prebaked_path, &mut timer)?;
+            let (prebaked, problems) = prebaked.migrate(&migration, &old_map, &new_map);
+            report("prebaked results", &name, problems);
+            abstio::write_binary(
+                abstio::path_prebaked_results(new_map.get_name(), &name),
+                &prebaked,
+            );
+        }
+    }
+
+    for name in Proposal::list_all(old_map.get_name()) {
+        let path = abstio::path_ltn_proposals(old_map.get_name(), &name);
+        let (proposal, mut problems) = if abstio::file_exists(&path) {
+            // These refer to OSM IDs already, so just match them against the new map
+            let mut proposal: PermanentProposal = abstio::maybe_read_json(path, &mut timer)?;
+            proposal.map = new_map.get_name().clone();
+            proposal.resolve(&new_map, &mut timer)?
+        } else {
+            // Proposals in the old binary format refer to RoadIDs
+            Proposal::load_by_name(&old_map, &name, &mut timer)?.migrate(&migration, &new_map)
+        };
+        problems.extend(proposal.save(&new_map));
+        report("LTN proposal", &name, problems);
+    }
+
+    Ok(())
+}
+
+fn report(kind: &str, name: &str, problems: Vec<String>) {
+    if problems.is_empty() {
+        println!("Migrated {} {} completely", kind, name);
+        return;
+    }
+    println!(
+        "Migrated {} {}, but {} things couldn't be carried over:",
+        kind,
+        name,
+        problems.len()
+    );
+    for problem in problems {
+        println!("  - {}", problem);
+    }
+}
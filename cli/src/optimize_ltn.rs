@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use ltn_model::Proposal;
+use map_model::Map;
+
+pub fn run(map: String, proposal: String, alternatives: usize) -> Result<()> {
+    let mut timer = Timer::new("optimize LTN filters");
+    let map = Map::load_synchronously(map, &mut timer);
+    let proposal = Proposal::load_from_file(&map, proposal, &mut timer)?;
+
+    let neighborhoods: Vec<_> = proposal
+        .partitioning
+        .all_neighborhoods()
+        .keys()
+        .cloned()
+        .collect();
+    let layouts = ltn_model::optimize_filters(
+        &map,
+        &proposal.partitioning,
+        &proposal.modal_filters,
+        &neighborhoods,
+        alternatives,
+        &mut timer,
+    );
+    if layouts.is_empty() {
+        bail!("Every neighborhood already has a disconnected cell; nothing to optimize");
+    }
+
+    for (idx, layout) in layouts.into_iter().enumerate() {
+        let name = format!("{} optimized {}", proposal.name, idx + 1);
+        println!(
+            "{}: {} new filters, {} rat-runs remaining",
+            name, layout.new_filters, layout.remaining_rat_runs
+        );
+        let problems = Proposal {
+            map: map.get_name().clone(),
+            name,
+            abst_version: proposal.abst_version.clone(),
+
+            partitioning: proposal.partitioning.clone(),
+            modal_filters: layout.modal_filters,
+        }
+        .save(&map);
+        for problem in problems {
+            println!("  - {}", problem);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{ControlTrafficSignal, CorridorTiming, IntersectionID, Map, SignalCorridor};
+use sim::{Sim, SimFlags, SimOptions};
+use synthpop::Scenario;
+
+/// Headless runs are slow, so when refining by delay, only try shifting each offset by this many
+/// evenly spaced steps through the cycle.
+const DELAY_SEARCH_STEPS: usize = 8;
+
+pub fn run(
+    map: String,
+    from: usize,
+    to: usize,
+    cycle_length: Option<usize>,
+    scenario: Option<String>,
+    until: Time,
+    edits_name: String,
+) -> Result<()> {
+    let mut timer = Timer::new("optimize signals");
+    let mut map = Map::load_synchronously(map, &mut timer);
+    let corridor = SignalCorridor::new(&map, IntersectionID(from), IntersectionID(to))?;
+    let mut timing = corridor.optimize_bandwidth(
+        &map,
+        cycle_length.map(|secs| Duration::seconds(secs as f64)),
+    )?;
+    println!(
+        "Retimed {} signals to a {} cycle",
+        timing.signals.len(),
+        timing.cycle_length
+    );
+    print_bandwidth(&timing);
+
+    if let Some(path) = scenario {
+        let scenario: Scenario = abstio::read_object(path, &mut timer)?;
+        refine_by_delay(&map, &corridor, &mut timing, &scenario, until, &mut timer);
+        print_bandwidth(&timing);
+    }
+
+    let mut edits = timing.to_edits(&map);
+    edits.edits_name = edits_name;
+    map.must_apply_edits(edits, &mut timer);
+    map.save_edits();
+    println!(
+        "Wrote {}",
+        abstio::path_edits(map.get_name(), &map.get_edits().edits_name)
+    );
+    Ok(())
+}
+
+fn print_bandwidth(timing: &CorridorTiming) {
+    println!("  Outbound bandwidth: {}", timing.outbound_bandwidth);
+    println!("  Inbound bandwidth: {}", timing.inbound_bandwidth);
+}
+
+/// Starting from the best bandwidth, adjust one offset at a time to minimize the delay measured at
+/// the corridor's signals by simulating the scenario.
+fn refine_by_delay(
+    map: &Map,
+    corridor: &SignalCorridor,
+    timing: &mut CorridorTiming,
+    scenario: &Scenario,
+    until: Time,
+    timer: &mut Timer,
+) {
+    let step = timing.cycle_length / (DELAY_SEARCH_STEPS as f64);
+    let mut best = total_delay(map, &timing.signals, scenario, until, timer);
+    println!(
+        "Delay at the corridor's signals with the best bandwidth: {}",
+        best
+    );
+
+    for idx in 1..timing.signals.len() {
+        let orig = timing.signals[idx].offset;
+        let mut best_offset = orig;
+        for num_steps in 1..DELAY_SEARCH_STEPS {
+            // Offsets are stored in whole seconds
+            let offset =
+                Duration::seconds((orig + step * (num_steps as f64)).inner_seconds().round())
+                    % timing.cycle_length;
+            timing.signals[idx].offset = offset;
+            let delay = total_delay(map, &timing.signals, scenario, until, timer);
+            if delay < best {
+                println!(
+                    "  Offset {} for {} reduces delay to {}",
+                    offset, timing.signals[idx].id, delay
+                );
+                best = delay;
+                best_offset = offset;
+            }
+        }
+        timing.signals[idx].offset = best_offset;
+    }
+
+    let (outbound, inbound) = corridor.bandwidth(&timing.signals);
+    timing.outbound_bandwidth = outbound;
+    timing.inbound_bandwidth = inbound;
+    println!("Delay at the corridor's signals after refining: {}", best);
+}
+
+fn total_delay(
+    map: &Map,
+    signals: &[ControlTrafficSignal],
+    scenario: &Scenario,
+    until: Time,
+    timer: &mut Timer,
+) -> Duration {
+    // Only the signal timing changes, so skip the full edit flow
+    let mut map = map.clone();
+    for ts in signals {
+        map.incremental_edit_traffic_signal(ts.clone());
+    }
+
+    let mut sim = Sim::new(&map, SimOptions::new("optimize_signals"));
+    let mut rng = XorShiftRng::seed_from_u64(SimFlags::RNG_SEED);
+    sim.instantiate(scenario, &map, &mut rng, timer);
+    sim.timed_step(&map, until - Time::START_OF_DAY, &mut None, timer);
+
+    signals
+        .iter()
+        .filter_map(|ts| sim.get_analytics().intersection_delays.get(&ts.id))
+        .flatten()
+        .map(|(_, _, delay, _)| *delay)
+        .sum()
+}
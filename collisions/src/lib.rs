@@ -4,8 +4,10 @@
 #[macro_use]
 extern crate log;
 
+use std::collections::{BTreeMap, BTreeSet};
+
 use geom::{Duration, LonLat};
-use kml::ExtraShapes;
+use kml::{ExtraShape, ExtraShapes};
 use serde::{Deserialize, Serialize};
 
 /// A single dataset describing some collisions that happened.
@@ -25,23 +27,97 @@ pub struct Collision {
     /// The local time the collision occurred.
     // TODO Wait, why isn't this Time?
     pub time: Duration,
+    /// The local date the collision occurred, if the data source has it.
+    pub date: Option<Date>,
     /// The severity reported in the original data source.
     pub severity: Severity,
-    /* TODO Many more interesting and common things: the date, the number of
-     * people/vehicles/bikes/casualties, road/weather/alcohol/speeding conditions possibly
-     * influencing the event, etc. */
+    /// The modes of everyone involved. Empty if the data source doesn't say.
+    pub modes: BTreeSet<Mode>,
+    /// The number of people killed or injured, if the data source has it.
+    pub casualties: Option<usize>,
+    /// Conditions possibly influencing the collision. Only conditions that the data source
+    /// reports are listed, so an absent condition doesn't mean it definitely didn't apply.
+    pub conditions: BTreeSet<Condition>,
 }
 
 /// A simple ranking for how severe the collision was. Different agencies use different
 /// classification systems, each of which likely has their own nuance and bias. This is
 /// deliberately simplified.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Severity {
     Slight,
     Serious,
     Fatal,
 }
 
+/// How somebody involved in a collision was traveling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Mode {
+    Pedestrian,
+    Bicycle,
+    Motorcycle,
+    /// Cars, buses, trucks, and anything else motorized
+    MotorVehicle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Condition {
+    /// Dark, whether or not there was street lighting
+    Dark,
+    Rain,
+    Snow,
+    Fog,
+    WetSurface,
+    IcySurface,
+    Speeding,
+    /// Somebody was under the influence of alcohol or drugs
+    Impaired,
+}
+
+/// A calendar date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Returns None if the month or day is out of range.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Date> {
+        if (1..=12).contains(&month) && (1..=31).contains(&day) {
+            Some(Date { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    /// The number of days since 1970-01-01.
+    pub fn days_since_epoch(self) -> i64 {
+        // From http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let month = self.month as i64;
+        let year = self.year as i64 - if month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+}
+
+impl CollisionDataset {
+    /// How many days does the dataset cover? This is measured between the first and last
+    /// collision, so it's only accurate for datasets with lots of collisions. None if no
+    /// collisions have a date.
+    pub fn days_covered(&self) -> Option<usize> {
+        let dates: Vec<Date> = self.collisions.iter().filter_map(|c| c.date).collect();
+        let first = dates.iter().min()?.days_since_epoch();
+        let last = dates.iter().max()?.days_since_epoch();
+        Some((last - first + 1) as usize)
+    }
+}
+
 /// Import data from the UK STATS19 dataset. See https://github.com/ropensci/stats19. Any parsing
 /// errors will skip the row and log a warning.
 pub fn import_stats19(input: ExtraShapes, source_url: &str) -> CollisionDataset {
@@ -54,10 +130,10 @@ pub fn import_stats19(input: ExtraShapes, source_url: &str) -> CollisionDataset
             warn!("One row had >1 point: {:?}", shape);
             continue;
         }
-        let time = match Duration::parse(&format!("{}:00", shape.attributes["Time"])) {
-            Ok(time) => time,
-            Err(err) => {
-                warn!("Couldn't parse time: {}", err);
+        let time = match parse_time(&shape.attributes["Time"]) {
+            Some(time) => time,
+            None => {
+                warn!("Couldn't parse time {}", shape.attributes["Time"]);
                 continue;
             }
         };
@@ -71,10 +147,42 @@ pub fn import_stats19(input: ExtraShapes, source_url: &str) -> CollisionDataset
                 continue;
             }
         };
+
+        let mut conditions = BTreeSet::new();
+        if matches!(attribute(&shape, "Light_Conditions"), "4" | "5" | "6") {
+            conditions.insert(Condition::Dark);
+        }
+        match attribute(&shape, "Weather_Conditions") {
+            "2" | "5" => {
+                conditions.insert(Condition::Rain);
+            }
+            "3" | "6" => {
+                conditions.insert(Condition::Snow);
+            }
+            "7" => {
+                conditions.insert(Condition::Fog);
+            }
+            _ => {}
+        }
+        match attribute(&shape, "Road_Surface_Conditions") {
+            "2" | "5" => {
+                conditions.insert(Condition::WetSurface);
+            }
+            "3" | "4" => {
+                conditions.insert(Condition::IcySurface);
+            }
+            _ => {}
+        }
+
         data.collisions.push(Collision {
             location: shape.points[0],
             time,
+            date: parse_date(attribute(&shape, "Date"), DateFormat::DayMonthYear),
             severity,
+            // The vehicles involved are in a separate table
+            modes: BTreeSet::new(),
+            casualties: count(&shape, "Number_of_Casualties"),
+            conditions,
         });
     }
     data
@@ -93,20 +201,14 @@ pub fn import_seattle(input: ExtraShapes, source_url: &str) -> CollisionDataset
             warn!("One row had >1 point: {:?}", shape);
             continue;
         }
-        let time = match parse_incdttm(&shape.attributes["INCDTTM"]) {
-            Some(time) => time,
+        let (date, time) = match parse_incdttm(&shape.attributes["INCDTTM"]) {
+            Some(pair) => pair,
             None => {
                 warn!("Couldn't parse time {}", shape.attributes["INCDTTM"]);
                 continue;
             }
         };
-        let severity = match shape
-            .attributes
-            .get("SEVERITYCODE")
-            .cloned()
-            .unwrap_or_else(String::new)
-            .as_ref()
-        {
+        let severity = match attribute(&shape, "SEVERITYCODE") {
             "1" | "0" => Severity::Slight,
             "2b" | "2" => Severity::Serious,
             "3" => Severity::Fatal,
@@ -115,27 +217,376 @@ pub fn import_seattle(input: ExtraShapes, source_url: &str) -> CollisionDataset
                 continue;
             }
         };
+
+        let mut modes = BTreeSet::new();
+        for (key, mode) in [
+            ("PEDCOUNT", Mode::Pedestrian),
+            ("PEDCYLCOUNT", Mode::Bicycle),
+            ("VEHCOUNT", Mode::MotorVehicle),
+        ] {
+            if count(&shape, key).unwrap_or(0) > 0 {
+                modes.insert(mode);
+            }
+        }
+
+        let casualties = ["INJURIES", "SERIOUSINJURIES", "FATALITIES"]
+            .into_iter()
+            .map(|key| count(&shape, key))
+            .sum::<Option<usize>>();
+
+        let mut conditions = BTreeSet::new();
+        if attribute(&shape, "LIGHTCOND").starts_with("Dark") {
+            conditions.insert(Condition::Dark);
+        }
+        match attribute(&shape, "WEATHER") {
+            "Raining" | "Sleet/Hail/Freezing Rain" => {
+                conditions.insert(Condition::Rain);
+            }
+            "Snowing" | "Blowing Snow" => {
+                conditions.insert(Condition::Snow);
+            }
+            "Fog/Smog/Smoke" => {
+                conditions.insert(Condition::Fog);
+            }
+            _ => {}
+        }
+        match attribute(&shape, "ROADCOND") {
+            "Wet" | "Standing Water" => {
+                conditions.insert(Condition::WetSurface);
+            }
+            "Ice" | "Snow/Slush" => {
+                conditions.insert(Condition::IcySurface);
+            }
+            _ => {}
+        }
+        if attribute(&shape, "SPEEDING") == "Y" {
+            conditions.insert(Condition::Speeding);
+        }
+        if matches!(attribute(&shape, "UNDERINFL"), "Y" | "1") {
+            conditions.insert(Condition::Impaired);
+        }
+
+        data.collisions.push(Collision {
+            location: shape.points[0],
+            time,
+            date: Some(date),
+            severity,
+            modes,
+            casualties,
+            conditions,
+        });
+    }
+    data
+}
+
+/// Import data from the US Fatality Analysis Reporting System
+/// (https://www.nhtsa.gov/research-data/fatality-analysis-reporting-system-fars). This expects
+/// the accident table, loaded with LONGITUD and LATITUDE as the coordinate columns. Every
+/// collision in FARS is fatal, and only fatalities are counted as casualties. Any parsing errors
+/// will skip the row and log a warning.
+pub fn import_fars(input: ExtraShapes, source_url: &str) -> CollisionDataset {
+    let mut data = CollisionDataset {
+        source_url: source_url.to_string(),
+        collisions: Vec::new(),
+    };
+    for shape in input.shapes {
+        if shape.points.len() != 1 {
+            warn!("One row had >1 point: {:?}", shape);
+            continue;
+        }
+        // 99 means unknown
+        let time = match (count(&shape, "HOUR"), count(&shape, "MINUTE")) {
+            (Some(hour), Some(minute)) if hour < 24 && minute < 60 => {
+                Duration::hours(hour) + Duration::minutes(minute)
+            }
+            _ => {
+                warn!(
+                    "Couldn't parse time {}:{}",
+                    attribute(&shape, "HOUR"),
+                    attribute(&shape, "MINUTE")
+                );
+                continue;
+            }
+        };
+        let date = match (
+            attribute(&shape, "YEAR").parse::<u16>(),
+            attribute(&shape, "MONTH").parse::<u8>(),
+            attribute(&shape, "DAY").parse::<u8>(),
+        ) {
+            (Ok(year), Ok(month), Ok(day)) => Date::new(year, month, day),
+            _ => None,
+        };
+
+        // PEDS counts everybody not in a motor vehicle, including cyclists. The accident table
+        // doesn't distinguish them.
+        let mut modes = BTreeSet::new();
+        if count(&shape, "PEDS").unwrap_or(0) > 0 {
+            modes.insert(Mode::Pedestrian);
+        }
+        if count(&shape, "VE_TOTAL").unwrap_or(0) > 0 {
+            modes.insert(Mode::MotorVehicle);
+        }
+
+        let mut conditions = BTreeSet::new();
+        if matches!(attribute(&shape, "LGT_COND"), "2" | "3" | "6") {
+            conditions.insert(Condition::Dark);
+        }
+        match attribute(&shape, "WEATHER") {
+            "2" | "3" | "12" => {
+                conditions.insert(Condition::Rain);
+            }
+            "4" | "11" => {
+                conditions.insert(Condition::Snow);
+            }
+            "5" => {
+                conditions.insert(Condition::Fog);
+            }
+            _ => {}
+        }
+        if count(&shape, "DRUNK_DR").unwrap_or(0) > 0 {
+            conditions.insert(Condition::Impaired);
+        }
+
+        data.collisions.push(Collision {
+            location: shape.points[0],
+            time,
+            date,
+            severity: Severity::Fatal,
+            modes,
+            casualties: count(&shape, "FATALS"),
+            conditions,
+        });
+    }
+    data
+}
+
+/// Describes how to read collisions from a CSV file with arbitrary columns, like the ones many US
+/// states publish. This is usually written as JSON.
+#[derive(Serialize, Deserialize)]
+pub struct CsvMapping {
+    /// The column with the longitude. This is consumed when loading the CSV file.
+    pub longitude: String,
+    /// The column with the latitude. This is consumed when loading the CSV file.
+    pub latitude: String,
+    /// The column with the local time, like "17:45", "17:45:00", "5:45 PM", or "1745"
+    pub time: String,
+    pub date: Option<String>,
+    pub date_format: DateFormat,
+    pub severity: String,
+    /// Maps every value of the severity column to a severity. Rows with other values are
+    /// skipped.
+    pub severity_values: BTreeMap<String, Severity>,
+    /// A column counting people killed or injured
+    pub casualties: Option<String>,
+    /// Columns counting people or vehicles of some mode. A non-zero count means that mode was
+    /// involved.
+    pub mode_counts: BTreeMap<String, Mode>,
+    pub conditions: Vec<ConditionMapping>,
+}
+
+/// If a column has one of some values, then a condition applies.
+#[derive(Serialize, Deserialize)]
+pub struct ConditionMapping {
+    pub column: String,
+    pub values: Vec<String>,
+    pub condition: Condition,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DateFormat {
+    /// 2019-11-12 or 2019/11/12
+    YearMonthDay,
+    /// 11/12/2019
+    MonthDayYear,
+    /// 12/11/2019
+    DayMonthYear,
+}
+
+/// Import data from a CSV file, interpreting the columns with a mapping. Any parsing errors will
+/// skip the row and log a warning.
+pub fn import_csv(input: ExtraShapes, mapping: &CsvMapping, source_url: &str) -> CollisionDataset {
+    let mut data = CollisionDataset {
+        source_url: source_url.to_string(),
+        collisions: Vec::new(),
+    };
+    for shape in input.shapes {
+        if shape.points.len() != 1 {
+            warn!("One row had >1 point: {:?}", shape);
+            continue;
+        }
+        let time = match parse_time(attribute(&shape, &mapping.time)) {
+            Some(time) => time,
+            None => {
+                warn!("Couldn't parse time {}", attribute(&shape, &mapping.time));
+                continue;
+            }
+        };
+        let severity = match mapping
+            .severity_values
+            .get(attribute(&shape, &mapping.severity))
+        {
+            Some(severity) => *severity,
+            None => {
+                warn!("Unknown severity {}", attribute(&shape, &mapping.severity));
+                continue;
+            }
+        };
+
+        let mut modes = BTreeSet::new();
+        for (key, mode) in &mapping.mode_counts {
+            if count(&shape, key).unwrap_or(0) > 0 {
+                modes.insert(*mode);
+            }
+        }
+        let mut conditions = BTreeSet::new();
+        for c in &mapping.conditions {
+            let value = attribute(&shape, &c.column);
+            if c.values.iter().any(|x| x == value) {
+                conditions.insert(c.condition);
+            }
+        }
+
         data.collisions.push(Collision {
             location: shape.points[0],
             time,
+            date: mapping
+                .date
+                .as_ref()
+                .and_then(|key| parse_date(attribute(&shape, key), mapping.date_format)),
             severity,
+            modes,
+            casualties: mapping
+                .casualties
+                .as_ref()
+                .and_then(|key| count(&shape, key)),
+            conditions,
         });
     }
     data
 }
 
+/// Missing attributes are treated as empty
+fn attribute<'a>(shape: &'a ExtraShape, key: &str) -> &'a str {
+    shape.attributes.get(key).map(|x| x.trim()).unwrap_or("")
+}
+
+fn count(shape: &ExtraShape, key: &str) -> Option<usize> {
+    attribute(shape, key)
+        .parse::<f64>()
+        .ok()
+        .map(|x| x as usize)
+}
+
 // INCDTTM is something like "11/12/2019 7:30:00 AM"
-fn parse_incdttm(x: &str) -> Option<Duration> {
-    let parts = x.split(' ').collect::<Vec<_>>();
+fn parse_incdttm(x: &str) -> Option<(Date, Duration)> {
+    let (date, time) = x.split_once(' ')?;
+    Some((
+        parse_date(date, DateFormat::MonthDayYear)?,
+        parse_time(time)?,
+    ))
+}
+
+/// Ignores anything after the date, like a time
+fn parse_date(x: &str, format: DateFormat) -> Option<Date> {
+    let x = x.split(|c| c == ' ' || c == 'T').next()?;
+    let parts: Vec<&str> = x.split(|c| c == '/' || c == '-').collect();
     if parts.len() != 3 {
         return None;
     }
-    let time = Duration::parse(parts[1]).ok()?;
-    if parts[2] == "AM" {
-        Some(time)
-    } else if parts[2] == "PM" {
-        Some(time + Duration::hours(12))
+    let (year, month, day) = match format {
+        DateFormat::YearMonthDay => (parts[0], parts[1], parts[2]),
+        DateFormat::MonthDayYear => (parts[2], parts[0], parts[1]),
+        DateFormat::DayMonthYear => (parts[2], parts[1], parts[0]),
+    };
+    Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+}
+
+/// Handles "17:45", "17:45:00", "5:45 PM", "5:45:00 PM", and "1745"
+fn parse_time(x: &str) -> Option<Duration> {
+    let x = x.trim();
+    let (x, pm) = if let Some(x) = x.strip_suffix("AM") {
+        (x.trim(), Some(false))
+    } else if let Some(x) = x.strip_suffix("PM") {
+        (x.trim(), Some(true))
     } else {
-        None
+        (x, None)
+    };
+
+    let (mut hours, minutes, seconds) = if x.contains(':') {
+        let parts: Vec<&str> = x.split(':').collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return None;
+        }
+        let seconds = if parts.len() == 3 {
+            parts[2].parse::<f64>().ok()?
+        } else {
+            0.0
+        };
+        (
+            parts[0].parse::<usize>().ok()?,
+            parts[1].parse::<usize>().ok()?,
+            seconds,
+        )
+    } else {
+        let hhmm = x.parse::<usize>().ok()?;
+        (hhmm / 100, hhmm % 100, 0.0)
+    };
+
+    match pm {
+        Some(true) if hours < 12 => {
+            hours += 12;
+        }
+        Some(false) if hours == 12 => {
+            hours = 0;
+        }
+        _ => {}
+    }
+    if hours >= 24 || minutes >= 60 || seconds >= 60.0 {
+        return None;
+    }
+    Some(Duration::hours(hours) + Duration::minutes(minutes) + Duration::seconds(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("17:45"), Some(Duration::minutes(17 * 60 + 45)));
+        assert_eq!(
+            parse_time("7:30:00 AM"),
+            Some(Duration::minutes(7 * 60 + 30))
+        );
+        assert_eq!(parse_time("12:15:00 AM"), Some(Duration::minutes(15)));
+        assert_eq!(
+            parse_time("12:15:00 PM"),
+            Some(Duration::minutes(12 * 60 + 15))
+        );
+        assert_eq!(parse_time("5:45 PM"), Some(Duration::minutes(17 * 60 + 45)));
+        assert_eq!(parse_time("1745"), Some(Duration::minutes(17 * 60 + 45)));
+        assert_eq!(parse_time("25:00"), None);
+    }
+
+    #[test]
+    fn test_dates() {
+        let date = parse_date("11/12/2019 7:30:00 AM", DateFormat::MonthDayYear).unwrap();
+        assert_eq!(date, Date::new(2019, 11, 12).unwrap());
+        assert_eq!(
+            parse_date("12/11/2019", DateFormat::DayMonthYear),
+            Some(date)
+        );
+        assert_eq!(
+            parse_date("2019-11-12T00:00:00", DateFormat::YearMonthDay),
+            Some(date)
+        );
+
+        assert_eq!(Date::new(1970, 1, 1).unwrap().days_since_epoch(), 0);
+        assert_eq!(Date::new(2000, 3, 1).unwrap().days_since_epoch(), 11017);
+        assert_eq!(
+            Date::new(2020, 1, 1).unwrap().days_since_epoch()
+                - Date::new(2019, 1, 1).unwrap().days_since_epoch(),
+            365
+        );
     }
 }
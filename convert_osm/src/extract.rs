@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use osm::{NodeID, OsmID, RelationID, WayID};
 
@@ -6,8 +6,10 @@ use abstio::MapName;
 use abstutil::{Tags, Timer};
 use geom::{Distance, FindClosest, HashablePt2D, Polygon, Pt2D, Ring};
 use kml::{ExtraShape, ExtraShapes};
-use map_model::raw::{RawArea, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionType};
-use map_model::{osm, Amenity, AreaType, Direction, DrivingSide, NamePerLanguage};
+use map_model::raw::{
+    RawArea, RawBikeshareDock, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionType,
+};
+use map_model::{osm, Amenity, AreaType, CrossingType, Direction, DrivingSide, NamePerLanguage};
 
 use crate::osm_geom::{get_multipolygon_members, glue_multipolygon, multipoly_geometry};
 use crate::Options;
@@ -25,7 +27,19 @@ pub struct OsmExtract {
     /// (location, amenity)
     pub amenities: Vec<(Pt2D, Amenity)>,
     /// Crosswalks located at these points, which should be on a RawRoad's center line
-    pub crosswalks: HashSet<HashablePt2D>,
+    pub crosswalks: HashMap<HashablePt2D, Crossing>,
+    /// Separately mapped sidewalks (`footway=sidewalk`), only kept if
+    /// `Options::separate_sidewalks`
+    pub sidewalks: Vec<(WayID, Vec<Pt2D>)>,
+}
+
+/// A `highway=crossing` node
+#[derive(Clone, Copy)]
+pub struct Crossing {
+    pub node: NodeID,
+    pub crossing_type: CrossingType,
+    /// Are the curbs lowered or flush, according to `kerb=*`? `None` if unknown.
+    pub curb_ramps: Option<bool>,
 }
 
 pub fn extract_osm(
@@ -63,7 +77,8 @@ pub fn extract_osm(
         simple_turn_restrictions: Vec::new(),
         complicated_turn_restrictions: Vec::new(),
         amenities: Vec::new(),
-        crosswalks: HashSet::new(),
+        crosswalks: HashMap::new(),
+        sidewalks: Vec::new(),
     };
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -80,7 +95,23 @@ pub fn extract_osm(
             out.traffic_signals.insert(node.pt.to_hashable(), dir);
         }
         if node.tags.is(osm::HIGHWAY, "crossing") {
-            out.crosswalks.insert(node.pt.to_hashable());
+            if let Some(crossing_type) = CrossingType::from_osm(&node.tags) {
+                out.crosswalks.insert(
+                    node.pt.to_hashable(),
+                    Crossing {
+                        node: *id,
+                        crossing_type,
+                        curb_ramps: get_curb_ramps(&node.tags),
+                    },
+                );
+            }
+        }
+        if is_bikeshare_dock(&node.tags) {
+            map.bikeshare_docks.push(RawBikeshareDock {
+                osm_id: OsmID::Node(*id),
+                pt: node.pt,
+                osm_tags: node.tags.clone(),
+            });
         }
         for amenity in get_bldg_amenities(&node.tags) {
             out.amenities.push((node.pt, amenity));
@@ -99,6 +130,21 @@ pub fn extract_osm(
 
         way.tags.insert(osm::OSM_WAY_ID, id.0.to_string());
 
+        if opts.separate_sidewalks && way.tags.is(osm::HIGHWAY, "footway") {
+            if way.tags.is("footway", "sidewalk") {
+                out.sidewalks.push((id, way.pts.clone()));
+            }
+            // Crossing ways are represented by the crossing node on the road instead. Just keep
+            // both kinds around as extra shapes.
+            if way.tags.is_any("footway", vec!["sidewalk", "crossing"]) {
+                extra_footways.shapes.push(ExtraShape {
+                    points: map.gps_bounds.convert_back(&way.pts),
+                    attributes: way.tags.inner().clone(),
+                });
+                continue;
+            }
+        }
+
         if is_road(&mut way.tags, opts) {
             // TODO Hardcoding these overrides. OSM is correct, these don't have
             // sidewalks; there's a crosswalk mapped. But until we can snap sidewalks properly, do
@@ -513,6 +559,23 @@ fn is_road(tags: &mut Tags, opts: &Options) -> bool {
     true
 }
 
+fn get_curb_ramps(tags: &Tags) -> Option<bool> {
+    if tags.is("wheelchair", "no") || tags.is("kerb", "raised") {
+        return Some(false);
+    }
+    if tags.is("wheelchair", "yes") || tags.is_any("kerb", vec!["lowered", "flush", "no"]) {
+        return Some(true);
+    }
+    // kerb=rolled is passable with some difficulty, so leave it unknown
+    None
+}
+
+fn is_bikeshare_dock(tags: &Tags) -> bool {
+    // Shops renting out bikes by the day don't count
+    tags.is("amenity", "bicycle_rental")
+        && !tags.is_any("bicycle_rental", vec!["shop", "dropoff_point"])
+}
+
 fn is_bldg(tags: &Tags) -> bool {
     // Sorry, the towers at Gasworks don't count. :)
     tags.contains_key("building") && !tags.contains_key("abandoned:man_made")
@@ -3,17 +3,20 @@ extern crate anyhow;
 #[macro_use]
 extern crate log;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use anyhow::Result;
 
 use abstio::MapName;
 use abstutil::{Tags, Timer};
 use geom::{Distance, FindClosest, GPSBounds, HashablePt2D, LonLat, PolyLine, Polygon, Pt2D, Ring};
-use map_model::raw::{OriginalRoad, RawMap};
-use map_model::{osm, raw, Amenity, MapConfig};
+use map_model::pois::PoiSource;
+use map_model::raw::{OriginalRoad, RawMap, RawRoad};
+use map_model::{osm, raw, Amenity, CrossingType, IntersectionType, MapConfig};
 use serde::{Deserialize, Serialize};
 
+use crate::extract::Crossing;
+
 mod clip;
 mod elevation;
 mod extract;
@@ -21,6 +24,7 @@ mod gtfs;
 pub mod osm_geom;
 mod parking;
 pub mod reader;
+mod sidewalks;
 mod split_ways;
 
 /// Configures the creation of a RawMap from OSM and other input data.
@@ -34,11 +38,17 @@ pub struct Options {
     pub include_railroads: bool,
     /// If provided, read polygons from this GeoJSON file and add them to the RawMap as buildings.
     pub extra_buildings: Option<String>,
+    /// Points of interest from other datasets to add as building amenities
+    pub extra_pois: Vec<PoiSource>,
     /// Only include highways and arterials. This may make sense for some region-wide maps for
     /// particular use cases.
     pub skip_local_roads: bool,
     /// Only include crosswalks that match a `highway=crossing` OSM node.
     pub filter_crosswalks: bool,
+    /// Use sidewalks mapped as separate `footway=sidewalk` ways, snapping them to their parent
+    /// road, instead of importing them as footways. Crossing nodes far from an intersection
+    /// become mid-block crossings. This implies `filter_crosswalks`.
+    pub separate_sidewalks: bool,
     /// Configure public transit using this URL to a static GTFS feed in .zip format.
     pub gtfs_url: Option<String>,
 }
@@ -106,6 +116,21 @@ pub fn convert(
 
     parking::apply_parking(&mut map, &opts, timer);
 
+    if opts.separate_sidewalks {
+        sidewalks::snap_sidewalks(&mut map, split_output.sidewalks, timer);
+    }
+    // This may create new intersections for mid-block crossings, so do it before looking up
+    // elevation.
+    if opts.filter_crosswalks || opts.separate_sidewalks {
+        filter_crosswalks(
+            &mut map,
+            split_output.crosswalks,
+            split_output.pt_to_road,
+            opts.separate_sidewalks,
+            timer,
+        );
+    }
+
     // TODO Make this bail out on failure, after the new dependencies are clearly explained.
     timer.start("add elevation data");
     if let Err(err) = elevation::add_data(&mut map) {
@@ -115,14 +140,8 @@ pub fn convert(
     if let Some(ref path) = opts.extra_buildings {
         add_extra_buildings(&mut map, path).unwrap();
     }
-
-    if opts.filter_crosswalks {
-        filter_crosswalks(
-            &mut map,
-            split_output.crosswalks,
-            split_output.pt_to_road,
-            timer,
-        );
+    for source in &opts.extra_pois {
+        add_extra_pois(&mut map, source, timer);
     }
 
     if opts.gtfs_url.is_some() {
@@ -177,10 +196,36 @@ fn add_extra_buildings(map: &mut RawMap, path: &str) -> Result<()> {
     Ok(())
 }
 
+fn add_extra_pois(map: &mut RawMap, source: &PoiSource, timer: &mut Timer) {
+    timer.start(format!("add POIs from {}", source.path));
+    match source.read() {
+        Ok((pois, mut problems)) => {
+            let results = map_model::pois::import_pois_to_raw(map, source, pois);
+            problems.extend(results.problems);
+            info!(
+                "Added {} amenities from {}, skipping {} duplicates",
+                results.added, source.path, results.duplicates
+            );
+            for problem in problems {
+                warn!("{}", problem);
+            }
+        }
+        Err(err) => {
+            error!("Couldn't add POIs from {}: {}", source.path, err);
+        }
+    }
+    timer.stop(format!("add POIs from {}", source.path));
+}
+
+/// Crossing nodes at least this far from both ends of a road become their own intersection, if
+/// `Options::separate_sidewalks` is set.
+const MID_BLOCK_CROSSING_THRESHOLD: Distance = Distance::const_meters(15.0);
+
 fn filter_crosswalks(
     map: &mut RawMap,
-    crosswalks: HashSet<HashablePt2D>,
+    crosswalks: HashMap<HashablePt2D, Crossing>,
     pt_to_road: HashMap<HashablePt2D, OriginalRoad>,
+    split_mid_block: bool,
     timer: &mut Timer,
 ) {
     // Normally we assume every road has a crosswalk, but since this map is configured to use OSM
@@ -190,28 +235,37 @@ fn filter_crosswalks(
         road.crosswalk_backward = false;
     }
 
+    let mut mid_block = Vec::new();
     // Match each crosswalk node to a road
     timer.start_iter("filter crosswalks", crosswalks.len());
-    for pt in crosswalks {
+    for (pt, crossing) in crosswalks {
         timer.next();
         // Some crossing nodes are outside the map boundary or otherwise not on a road that we
         // retained
-        if let Some(road) = pt_to_road.get(&pt).and_then(|r| map.roads.get_mut(r)) {
+        let id = match pt_to_road.get(&pt) {
+            Some(id) => *id,
+            None => continue,
+        };
+        if let Some(road) = map.roads.get_mut(&id) {
             // TODO Support cul-de-sacs and other loop roads
             if let Ok(pl) = PolyLine::new(road.center_points.clone()) {
                 // Crossings aren't right at an intersection. Where is this point along the center
                 // line?
                 if let Some((dist, _)) = pl.dist_along_of_point(pt.to_pt2d()) {
-                    let pct = dist / pl.length();
+                    if split_mid_block
+                        && dist >= MID_BLOCK_CROSSING_THRESHOLD
+                        && pl.length() - dist >= MID_BLOCK_CROSSING_THRESHOLD
+                    {
+                        mid_block.push((id, dist, crossing));
+                        continue;
+                    }
+
                     // Don't throw away any crossings. If it occurs in the first half of the road,
                     // snap to the first intersection. If there's a mid-block crossing mapped,
                     // that'll likely not be correctly interpreted, unless an intersection is there
                     // anyway.
-                    if pct <= 0.5 {
-                        road.crosswalk_backward = true;
-                    } else {
-                        road.crosswalk_forward = true;
-                    }
+                    let pct = dist / pl.length();
+                    snap_crossing(road, pct > 0.5, crossing);
 
                     // TODO Some crosswalks incorrectly snap to the intersection near a short
                     // service road, which later gets trimmed. So the crosswalk effectively
@@ -220,4 +274,134 @@ fn filter_crosswalks(
             }
         }
     }
+
+    if !mid_block.is_empty() {
+        split_at_crossings(map, mid_block, timer);
+    }
+}
+
+/// Mark a crossing at one end of the road. If multiple crossings snap to the same end, keep the
+/// most protected type, and assume curb ramps if any of them have them.
+fn snap_crossing(road: &mut RawRoad, fwd: bool, crossing: Crossing) {
+    let (key, curb_key) = if fwd {
+        road.crosswalk_forward = true;
+        (osm::CROSSING_FWD, osm::CURB_RAMP_FWD)
+    } else {
+        road.crosswalk_backward = true;
+        (osm::CROSSING_BACK, osm::CURB_RAMP_BACK)
+    };
+    let existing = road
+        .osm_tags
+        .get(key)
+        .and_then(|value| CrossingType::from_tag_value(value));
+    if existing.map(|x| crossing.crossing_type < x).unwrap_or(true) {
+        road.osm_tags
+            .insert(key, crossing.crossing_type.to_tag_value());
+    }
+    if let Some(curb_ramps) = crossing.curb_ramps {
+        if curb_ramps || !road.osm_tags.is(curb_key, "yes") {
+            road.osm_tags
+                .insert(curb_key, if curb_ramps { "yes" } else { "no" });
+        }
+    }
+}
+
+/// Split roads at mid-block crossings, creating a new degenerate intersection for each one.
+fn split_at_crossings(
+    map: &mut RawMap,
+    crossings: Vec<(OriginalRoad, Distance, Crossing)>,
+    timer: &mut Timer,
+) {
+    // Splitting roads involved in turn restrictions would mean fixing up the references, so just
+    // snap crossings on them to the nearest end, like usual.
+    let mut restricted: HashSet<OriginalRoad> = HashSet::new();
+    for (id, road) in &map.roads {
+        for (_, to) in &road.turn_restrictions {
+            restricted.insert(*id);
+            restricted.insert(*to);
+        }
+        for (via, to) in &road.complicated_turn_restrictions {
+            restricted.insert(*id);
+            restricted.insert(*via);
+            restricted.insert(*to);
+        }
+    }
+
+    let mut per_road: BTreeMap<OriginalRoad, Vec<(Distance, Crossing)>> = BTreeMap::new();
+    for (id, dist, crossing) in crossings {
+        per_road
+            .entry(id)
+            .or_insert_with(Vec::new)
+            .push((dist, crossing));
+    }
+
+    timer.start_iter("split roads at mid-block crossings", per_road.len());
+    for (orig_id, mut crossings) in per_road {
+        timer.next();
+        crossings.sort_by_key(|(dist, _)| *dist);
+
+        // Walk along the road, chopping off the first piece at each crossing.
+        let mut id = orig_id;
+        let mut offset = Distance::ZERO;
+        for (dist, crossing) in crossings {
+            let road = map.roads.get_mut(&id).unwrap();
+            let pl = PolyLine::must_new(road.center_points.clone());
+            let dist = dist - offset;
+            // Crossings close together, or on a restricted road, just snap to the nearest end.
+            if restricted.contains(&orig_id)
+                || dist < MID_BLOCK_CROSSING_THRESHOLD
+                || pl.length() - dist < MID_BLOCK_CROSSING_THRESHOLD
+            {
+                snap_crossing(road, dist / pl.length() > 0.5, crossing);
+                continue;
+            }
+
+            let mut second = map.roads.remove(&id).unwrap();
+            let mut first = second.clone();
+
+            first.center_points = pl.exact_slice(Distance::ZERO, dist).into_points();
+            first.osm_tags.remove(osm::ENDPT_FWD);
+            first.osm_tags.remove(osm::CROSSING_FWD);
+            first.osm_tags.remove(osm::CURB_RAMP_FWD);
+            first.crosswalk_forward = false;
+            snap_crossing(&mut first, true, crossing);
+
+            second.center_points = pl.exact_slice(dist, pl.length()).into_points();
+            second.osm_tags.remove(osm::ENDPT_BACK);
+            second.osm_tags.remove(osm::CROSSING_BACK);
+            second.osm_tags.remove(osm::CURB_RAMP_BACK);
+            second.crosswalk_backward = false;
+            snap_crossing(&mut second, false, crossing);
+
+            map.intersections.insert(
+                crossing.node,
+                raw::RawIntersection {
+                    point: pl.must_dist_along(dist).0,
+                    intersection_type: if crossing.crossing_type == CrossingType::Signalized {
+                        IntersectionType::TrafficSignal
+                    } else {
+                        IntersectionType::StopSign
+                    },
+                    // Filled out later
+                    elevation: Distance::ZERO,
+                    trim_roads_for_merging: BTreeMap::new(),
+                },
+            );
+            map.roads.insert(
+                OriginalRoad {
+                    osm_way_id: id.osm_way_id,
+                    i1: id.i1,
+                    i2: crossing.node,
+                },
+                first,
+            );
+            id = OriginalRoad {
+                osm_way_id: id.osm_way_id,
+                i1: crossing.node,
+                i2: id.i2,
+            };
+            map.roads.insert(id, second);
+            offset += dist;
+        }
+    }
 }
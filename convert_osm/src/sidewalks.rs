@@ -0,0 +1,129 @@
+//! Some places map sidewalks as separate `highway=footway` + `footway=sidewalk` ways, instead of
+//! tagging them on the road. Snap these back to their parent road, so that we wind up with
+//! `LaneType::Sidewalk` lanes like everywhere else.
+
+use std::collections::BTreeMap;
+
+use abstutil::{Tags, Timer};
+use geom::{Distance, FindClosest, PolyLine, Pt2D};
+use map_model::osm;
+use map_model::raw::{OriginalRoad, RawMap};
+
+/// Sample points along each sidewalk this far apart
+const SAMPLE_STEP: Distance = Distance::const_meters(5.0);
+/// Sidewalks further than this from a road's center line aren't matched to it
+const MAX_DIST_FROM_ROAD: Distance = Distance::const_meters(20.0);
+/// A side of the road needs to be covered by this fraction of its length to have a sidewalk
+const MIN_COVERAGE: f64 = 0.5;
+
+struct CenterLine {
+    pl: PolyLine,
+    left: PolyLine,
+    right: PolyLine,
+}
+
+pub fn snap_sidewalks(
+    map: &mut RawMap,
+    sidewalks: Vec<(osm::WayID, Vec<Pt2D>)>,
+    timer: &mut Timer,
+) {
+    let mut closest: FindClosest<OriginalRoad> = FindClosest::new(&map.gps_bounds.to_bounds());
+    let mut center_lines: BTreeMap<OriginalRoad, CenterLine> = BTreeMap::new();
+    for (id, road) in &map.roads {
+        if road.is_footway()
+            || road.is_light_rail()
+            || road
+                .osm_tags
+                .is_any(osm::HIGHWAY, vec!["motorway", "motorway_link"])
+        {
+            continue;
+        }
+        // Which side of the road is a sidewalk on? Compare against the center line shifted a bit
+        // each way.
+        if let Ok(pl) = PolyLine::new(road.center_points.clone()) {
+            if let (Ok(left), Ok(right)) = (
+                pl.shift_left(Distance::meters(1.0)),
+                pl.shift_right(Distance::meters(1.0)),
+            ) {
+                closest.add(*id, pl.points());
+                center_lines.insert(*id, CenterLine { pl, left, right });
+            }
+        }
+    }
+
+    // For every (road, is the left side), how much of the road is covered by a sidewalk?
+    let mut coverage: BTreeMap<(OriginalRoad, bool), Distance> = BTreeMap::new();
+    timer.start_iter("snap separate sidewalks", sidewalks.len());
+    for (id, pts) in sidewalks {
+        timer.next();
+        let sidewalk = match PolyLine::deduping_new(pts) {
+            Ok(pl) => pl,
+            Err(err) => {
+                warn!("Skipping sidewalk {}: {}", id, err);
+                continue;
+            }
+        };
+
+        let mut dist = SAMPLE_STEP / 2.0;
+        while dist < sidewalk.length() {
+            let (pt, angle) = sidewalk.must_dist_along(dist);
+            dist += SAMPLE_STEP;
+
+            if let Some((road, _)) = closest.closest_pt(pt, MAX_DIST_FROM_ROAD) {
+                let center = &center_lines[&road];
+                let road_angle = match center.pl.dist_along_of_point(center.pl.project_pt(pt)) {
+                    Some((_, angle)) => angle,
+                    None => continue,
+                };
+                // Skip the parts of the footway that turn a corner or cross the road
+                if !angle.approx_parallel(road_angle, 30.0) {
+                    continue;
+                }
+                let left = center.left.project_pt(pt).dist_to(pt)
+                    < center.right.project_pt(pt).dist_to(pt);
+                *coverage.entry((road, left)).or_insert(Distance::ZERO) += SAMPLE_STEP;
+            }
+        }
+    }
+
+    for (id, road) in &mut map.roads {
+        let length = match center_lines.get(id) {
+            Some(center) => center.pl.length(),
+            None => continue,
+        };
+        let snapped = |left| {
+            coverage
+                .get(&(*id, left))
+                .map(|covered| *covered / length >= MIN_COVERAGE)
+                .unwrap_or(false)
+        };
+        let (snapped_left, snapped_right) = (snapped(true), snapped(false));
+        let tagged_left = tagged_separate(&road.osm_tags, "left");
+        let tagged_right = tagged_separate(&road.osm_tags, "right");
+
+        // If the road says the sidewalks are mapped separately, trust that even if we didn't
+        // manage to snap something. Otherwise only override what we inferred (or what's missing)
+        // if we found something.
+        let guessed = road.osm_tags.is(osm::INFERRED_SIDEWALKS, "true")
+            || !road.osm_tags.contains_key(osm::SIDEWALK);
+        if !(tagged_left || tagged_right || (guessed && (snapped_left || snapped_right))) {
+            continue;
+        }
+
+        let value = match (snapped_left || tagged_left, snapped_right || tagged_right) {
+            (true, true) => "both",
+            (true, false) => "left",
+            (false, true) => "right",
+            (false, false) => "none",
+        };
+        road.osm_tags.insert(osm::SIDEWALK, value);
+        road.osm_tags.remove(osm::INFERRED_SIDEWALKS);
+    }
+}
+
+/// Does the road say the sidewalk on one side is mapped separately?
+fn tagged_separate(tags: &Tags, side: &str) -> bool {
+    tags.is(osm::SIDEWALK, "separate")
+        || tags.is("sidewalk:both", "separate")
+        || tags.is(&format!("sidewalk:{}", side), "separate")
+}
@@ -1,15 +1,16 @@
-use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
 
 use abstutil::{Counter, Timer};
 use geom::{Distance, HashablePt2D, Pt2D};
 use map_model::raw::{OriginalRoad, RawIntersection, RawMap, RawRoad};
 use map_model::{osm, Amenity, Direction, IntersectionType};
 
-use crate::extract::OsmExtract;
+use crate::extract::{Crossing, OsmExtract};
 
 pub struct Output {
     pub amenities: Vec<(Pt2D, Amenity)>,
-    pub crosswalks: HashSet<HashablePt2D>,
+    pub crosswalks: HashMap<HashablePt2D, Crossing>,
+    pub sidewalks: Vec<(osm::WayID, Vec<Pt2D>)>,
     /// A mapping of all points to the split road. Some internal points on roads get removed in
     /// `split_up_roads`, so this mapping isn't redundant.
     pub pt_to_road: HashMap<HashablePt2D, OriginalRoad>,
@@ -229,6 +230,7 @@ pub fn split_up_roads(map: &mut RawMap, mut input: OsmExtract, timer: &mut Timer
     Output {
         amenities: input.amenities,
         crosswalks: input.crosswalks,
+        sidewalks: input.sidewalks,
         pt_to_road,
     }
 }
@@ -28,8 +28,11 @@ impl FindHome {
                     .into_widget(ctx),
                 ctx.style().btn_close_widget(ctx),
             ]),
-            // TODO Adjust text to say bikeshed, or otherwise reflect the options chosen
-            "Select the types of businesses you want within a 15 minute walkshed.".text_widget(ctx),
+            format!(
+                "Select the types of businesses you want within a 15 minute {}.",
+                options.describe_trip()
+            )
+            .text_widget(ctx),
             Widget::custom_row(
                 AmenityType::all()
                     .into_iter()
@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+use abstutil::Timer;
+use map_gui::tools::{FilePicker, PopupMsg};
+use map_model::pois::PoiSource;
+use map_model::BuildingID;
+use widgetry::{EventCtx, State, Transition};
+
+use crate::viewer::Viewer;
+use crate::App;
+
+/// Asks for a JSON file with a list of `PoiSource`s, in the same format as the importer's
+/// `extra_pois.json`, then adds their points of interest to the current map as amenities.
+/// Afterwards, returns to exploring from `start`.
+pub fn import_amenities(ctx: &mut EventCtx, start: BuildingID) -> Box<dyn State<App>> {
+    FilePicker::new_state(
+        ctx,
+        None,
+        Box::new(move |ctx, app, maybe_path| {
+            if let Ok(Some(path)) = maybe_path {
+                let result =
+                    ctx.loading_screen("import amenities", |_, timer| import(app, path, timer));
+                match result {
+                    Ok(lines) => Transition::Multi(vec![
+                        Transition::Pop,
+                        Transition::Replace(Viewer::new_state(ctx, app, start)),
+                        Transition::Push(PopupMsg::new_state(ctx, "Amenities imported", lines)),
+                    ]),
+                    Err(err) => Transition::Replace(PopupMsg::new_state(
+                        ctx,
+                        "Import failed",
+                        vec![err.to_string()],
+                    )),
+                }
+            } else {
+                Transition::Pop
+            }
+        }),
+    )
+}
+
+fn import(app: &mut App, path: String, timer: &mut Timer) -> Result<Vec<String>> {
+    let sources: Vec<PoiSource> = abstio::maybe_read_json(path, timer)?;
+    let mut added = 0;
+    let mut duplicates = 0;
+    let mut problems = Vec::new();
+    for source in sources {
+        let (pois, source_problems) = source.read()?;
+        problems.extend(source_problems);
+        let results = map_model::pois::import_pois(&mut app.map, &source, pois);
+        added += results.added;
+        duplicates += results.duplicates;
+        problems.extend(results.problems);
+    }
+
+    let mut lines = vec![
+        format!("{} new amenities added", added),
+        format!(
+            "{} were skipped, because they match existing amenities",
+            duplicates
+        ),
+    ];
+    if !problems.is_empty() {
+        lines.push(format!("{} couldn't be used:", problems.len()));
+        // Don't make a huge popup
+        lines.extend(problems.into_iter().take(10));
+    }
+    Ok(lines)
+}
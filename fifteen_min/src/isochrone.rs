@@ -2,12 +2,13 @@ use std::collections::{HashMap, HashSet};
 
 use abstutil::MultiMap;
 use connectivity::Spot;
-use geom::Duration;
+use geom::{Duration, Time};
 use map_gui::tools::draw_isochrone;
 use map_model::{
     connectivity, AmenityType, BuildingID, BuildingType, IntersectionID, LaneType, Map, Path,
     PathConstraints, PathRequest,
 };
+use sim::{AgentType, Analytics};
 use widgetry::{Color, Drawable, EventCtx};
 
 use crate::App;
@@ -40,11 +41,14 @@ pub struct Isochrone {
 pub enum Options {
     Walking(connectivity::WalkingOptions),
     Biking,
+    /// Walking and riding public transit
+    Transit(connectivity::WalkingOptions, connectivity::TransitOptions),
+    Driving(Congestion),
 }
 
 impl Options {
     /// Calculate the quickest time to reach buildings across the map from any of the starting
-    /// points, subject to the walking/biking settings configured in these Options.
+    /// points, subject to the settings configured in these Options.
     pub fn times_from(self, map: &Map, starts: Vec<Spot>) -> HashMap<BuildingID, Duration> {
         match self {
             Options::Walking(opts) => {
@@ -56,8 +60,94 @@ impl Options {
                 Duration::minutes(15),
                 PathConstraints::Bike,
             ),
+            Options::Transit(walking, transit) => connectivity::all_walking_and_transit_costs_from(
+                map,
+                starts,
+                Duration::minutes(15),
+                walking,
+                &mut connectivity::TransitCosts::new(map, transit),
+            ),
+            Options::Driving(congestion) => connectivity::all_vehicle_costs_with_delays_from(
+                map,
+                starts,
+                Duration::minutes(15),
+                PathConstraints::Car,
+                congestion.delays().unwrap_or(&HashMap::new()),
+            ),
+        }
+    }
+
+    /// Describes a trip using these options, like "15 minute walk"
+    pub fn describe_trip(&self) -> &'static str {
+        match self {
+            Options::Walking(_) => "walk",
+            Options::Biking => "bike ride",
+            Options::Transit(_, _) => "walk or transit ride",
+            Options::Driving(_) => "drive",
+        }
+    }
+}
+
+/// The average delay for cars through every traffic signal, per hour of the day, observed in a
+/// prebaked simulation. Other intersections and congestion along roads aren't captured.
+#[derive(Clone)]
+pub struct Congestion {
+    /// Use the delays observed during the clock hour containing this, so departing at 8:30 uses
+    /// the delays between 8 and 9
+    pub departure: Time,
+    /// The scenario the delays come from. If None, there aren't any delays.
+    pub scenario: Option<String>,
+    delays_per_hour: Vec<HashMap<IntersectionID, Duration>>,
+}
+
+impl Congestion {
+    /// No delays anywhere
+    pub fn free_flow(departure: Time) -> Congestion {
+        Congestion {
+            departure,
+            scenario: None,
+            delays_per_hour: Vec::new(),
+        }
+    }
+
+    pub fn from_prebaked(analytics: &Analytics, scenario: String, departure: Time) -> Congestion {
+        // Per hour and intersection, the total delay and number of cars
+        let mut totals: Vec<HashMap<IntersectionID, (Duration, usize)>> = Vec::new();
+        for (i, delays) in &analytics.intersection_delays {
+            for (_, t, delay, agent_type) in delays {
+                if *agent_type != AgentType::Car {
+                    continue;
+                }
+                let hour = t.get_hours();
+                if totals.len() <= hour {
+                    totals.resize_with(hour + 1, HashMap::new);
+                }
+                let entry = totals[hour].entry(*i).or_insert((Duration::ZERO, 0));
+                entry.0 += *delay;
+                entry.1 += 1;
+            }
+        }
+
+        Congestion {
+            departure,
+            scenario: Some(scenario),
+            delays_per_hour: totals
+                .into_iter()
+                .map(|per_intersection| {
+                    per_intersection
+                        .into_iter()
+                        .map(|(i, (total, count))| (i, total / (count as f64)))
+                        .collect()
+                })
+                .collect(),
         }
     }
+
+    /// The average delay through each intersection during the clock hour of departure. None if
+    /// there weren't any delays.
+    pub fn delays(&self) -> Option<&HashMap<IntersectionID, Duration>> {
+        self.delays_per_hour.get(self.departure.get_hours())
+    }
 }
 
 impl Isochrone {
@@ -144,9 +234,11 @@ impl Isochrone {
             return None;
         }
 
+        // TODO Show the transit part of the trip
         let constraints = match self.options {
-            Options::Walking(_) => PathConstraints::Pedestrian,
+            Options::Walking(_) | Options::Transit(_, _) => PathConstraints::Pedestrian,
             Options::Biking => PathConstraints::Bike,
+            Options::Driving(_) => PathConstraints::Car,
         };
 
         let all_paths = self.start.iter().filter_map(|b_id| {
@@ -209,3 +301,44 @@ impl BorderIsochrone {
         i
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_congestion_hour_boundary() {
+        let i = IntersectionID(0);
+        let at = |hours: usize, seconds: f64| {
+            Time::START_OF_DAY + Duration::hours(hours) + Duration::seconds(seconds)
+        };
+        let mut analytics = Analytics::new(true);
+        analytics.intersection_delays.insert(
+            i,
+            vec![
+                (0, at(8, 0.0), Duration::seconds(10.0), AgentType::Car),
+                (0, at(8, 3599.0), Duration::seconds(20.0), AgentType::Car),
+                (0, at(9, 0.0), Duration::seconds(60.0), AgentType::Car),
+                (0, at(9, 0.0), Duration::seconds(500.0), AgentType::Bus),
+            ],
+        );
+
+        for (departure, expected) in [
+            (at(8, 0.0), Some(15.0)),
+            (at(8, 1800.0), Some(15.0)),
+            (at(8, 3599.0), Some(15.0)),
+            (at(9, 0.0), Some(60.0)),
+            (at(10, 0.0), None),
+        ] {
+            let congestion = Congestion::from_prebaked(&analytics, "test".to_string(), departure);
+            assert_eq!(
+                congestion
+                    .delays()
+                    .map(|delays| delays[&i].inner_seconds()),
+                expected,
+                "departing at {}",
+                departure
+            );
+        }
+    }
+}
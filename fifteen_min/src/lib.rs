@@ -9,6 +9,7 @@ extern crate log;
 
 mod find_amenities;
 mod find_home;
+mod import_amenities;
 mod isochrone;
 mod viewer;
 
@@ -5,23 +5,28 @@
 //! See https://github.com/a-b-street/abstreet/issues/393 for more context.
 
 use abstutil::prettyprint_usize;
-use geom::{Distance, Duration};
+use geom::{Distance, Duration, Time};
+use map_gui::load::FileLoader;
 use map_gui::tools::{
     draw_isochrone, open_browser, CityPicker, ColorLegend, Navigator, PopupMsg, URLManager,
 };
 use map_gui::ID;
-use map_model::connectivity::WalkingOptions;
-use map_model::{AmenityType, Building, BuildingID, LaneType};
+use map_model::connectivity::{TransitOptions, WalkingOptions};
+use map_model::{AmenityType, Building, BuildingID, LaneType, PedestrianProfile};
+use sim::Analytics;
 use std::str::FromStr;
+use synthpop::Scenario;
 use widgetry::table::{Col, Filter, Table};
 use widgetry::{
     lctrl, Cached, Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key,
-    Line, Outcome, Panel, RewriteColor, State, Text, Toggle, Transition, VerticalAlignment, Widget,
+    Line, Outcome, Panel, RewriteColor, State, Text, TextExt, Toggle, Transition,
+    VerticalAlignment, Widget,
 };
 
 use crate::find_amenities::FindAmenity;
 use crate::find_home::FindHome;
-use crate::isochrone::{Isochrone, Options};
+use crate::import_amenities::import_amenities;
+use crate::isochrone::{Congestion, Isochrone, Options};
 use crate::App;
 
 /// This is the UI state for exploring the isochrone/walkshed from a single building.
@@ -65,6 +70,47 @@ impl Viewer {
     }
 }
 
+impl Viewer {
+    fn change_options(&mut self, ctx: &mut EventCtx, app: &App, options: Options) {
+        self.draw_unwalkable_roads = draw_unwalkable_roads(ctx, app, &options);
+        self.isochrone = Isochrone::new(ctx, app, vec![self.isochrone.start[0]], options);
+        self.panel = build_panel(
+            ctx,
+            app,
+            app.map.get_b(self.isochrone.start[0]),
+            &self.isochrone,
+        );
+    }
+}
+
+/// Switches to driving, with delays from the map's prebaked simulation results if they exist.
+fn load_congestion(ctx: &mut EventCtx, app: &App, departure: Time) -> Transition<App> {
+    let scenario = Scenario::default_scenario_for_map(app.map.get_name());
+    Transition::Push(FileLoader::<App, Analytics>::new_state(
+        ctx,
+        abstio::path_prebaked_results(app.map.get_name(), &scenario),
+        Box::new(move |_, _, _, prebaked| {
+            let congestion = match prebaked {
+                Ok(prebaked) => Congestion::from_prebaked(&prebaked, scenario, departure),
+                Err(err) => {
+                    warn!(
+                        "No prebaked simulation results, so assuming no traffic: {}",
+                        err
+                    );
+                    Congestion::free_flow(departure)
+                }
+            };
+            Transition::Multi(vec![
+                Transition::Pop,
+                Transition::ModifyState(Box::new(move |state, ctx, app| {
+                    let viewer = state.downcast_mut::<Viewer>().unwrap();
+                    viewer.change_options(ctx, app, Options::Driving(congestion));
+                })),
+            ])
+        }),
+    ))
+}
+
 impl State<App> for Viewer {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition<App> {
         // Allow panning and zooming
@@ -175,6 +221,9 @@ impl State<App> for Viewer {
                         self.isochrone.options.clone(),
                     ));
                 }
+                "Import amenities" => {
+                    return Transition::Push(import_amenities(ctx, self.isochrone.start[0]));
+                }
                 x => {
                     if let Some(category) = x.strip_prefix("businesses: ") {
                         return Transition::Push(ExploreAmenities::new_state(
@@ -189,15 +238,13 @@ impl State<App> for Viewer {
                 }
             },
             Outcome::Changed(_) => {
-                let options = options_from_controls(&self.panel);
-                self.draw_unwalkable_roads = draw_unwalkable_roads(ctx, app, &options);
-                self.isochrone = Isochrone::new(ctx, app, vec![self.isochrone.start[0]], options);
-                self.panel = build_panel(
-                    ctx,
-                    app,
-                    app.map.get_b(self.isochrone.start[0]),
-                    &self.isochrone,
-                );
+                let options = options_from_controls(&self.panel, &self.isochrone.options);
+                if let Options::Driving(ref congestion) = options {
+                    if !matches!(self.isochrone.options, Options::Driving(_)) {
+                        return load_congestion(ctx, app, congestion.departure);
+                    }
+                }
+                self.change_options(ctx, app, options);
             }
             _ => {}
         }
@@ -221,19 +268,27 @@ impl State<App> for Viewer {
 }
 
 fn options_to_controls(ctx: &mut EventCtx, opts: &Options) -> Widget {
-    let mut rows = vec![Toggle::choice(
-        ctx,
-        "walking / biking",
-        "walking",
-        "biking",
-        None,
-        match opts {
-            Options::Walking(_) => true,
-            Options::Biking => false,
-        },
-    )];
+    let mut rows = vec![Widget::row(vec![
+        "Travel by:".text_widget(ctx).centered_vert(),
+        Widget::dropdown(
+            ctx,
+            "mode",
+            match opts {
+                Options::Walking(_) => "walking",
+                Options::Biking => "biking",
+                Options::Transit(_, _) => "transit",
+                Options::Driving(_) => "driving",
+            },
+            vec![
+                Choice::new("walking", "walking"),
+                Choice::new("biking", "biking"),
+                Choice::new("walking and transit", "transit"),
+                Choice::new("driving", "driving"),
+            ],
+        ),
+    ])];
     match opts {
-        Options::Walking(ref opts) => {
+        Options::Walking(ref opts) | Options::Transit(ref opts, _) => {
             rows.push(Toggle::switch(
                 ctx,
                 "Allow walking on the shoulder of the road without a sidewalk",
@@ -249,26 +304,110 @@ fn options_to_controls(ctx: &mut EventCtx, opts: &Options) -> Widget {
                     .map(|(label, speed)| Choice::new(label, speed))
                     .collect(),
             ));
+            rows.push(Widget::row(vec![
+                "Mobility needs:".text_widget(ctx).centered_vert(),
+                Widget::dropdown(
+                    ctx,
+                    "profile",
+                    opts.profile,
+                    PedestrianProfile::all()
+                        .into_iter()
+                        .map(|p| Choice::new(p.describe(), p))
+                        .collect(),
+                ),
+            ]));
 
             rows.push(ColorLegend::row(ctx, Color::BLUE, "unwalkable roads"));
         }
-        Options::Biking => {}
+        Options::Biking | Options::Driving(_) => {}
+    }
+    match opts {
+        Options::Transit(_, ref transit) => {
+            rows.push(departure_dropdown(ctx, transit.departure));
+            rows.push(Toggle::switch(
+                ctx,
+                "Wait for scheduled vehicles, instead of the average wait",
+                None,
+                transit.exact_waits,
+            ));
+        }
+        Options::Driving(ref congestion) => {
+            rows.push(departure_dropdown(ctx, congestion.departure));
+            rows.push(
+                if let Some(ref scenario) = congestion.scenario {
+                    format!(
+                        "Delays at traffic signals come from simulating the {} scenario",
+                        scenario
+                    )
+                } else {
+                    "There's no simulation of this map, so traffic isn't included".to_string()
+                }
+                .text_widget(ctx),
+            );
+        }
+        Options::Walking(_) | Options::Biking => {}
     }
     Widget::col(rows)
 }
 
-fn options_from_controls(panel: &Panel) -> Options {
-    if panel.is_checked("walking / biking") {
-        Options::Walking(WalkingOptions {
-            allow_shoulders: panel
-                .maybe_is_checked("Allow walking on the shoulder of the road without a sidewalk")
-                .unwrap_or(true),
-            walking_speed: panel
-                .maybe_dropdown_value("speed")
-                .unwrap_or_else(WalkingOptions::default_speed),
-        })
-    } else {
-        Options::Biking
+fn departure_dropdown(ctx: &mut EventCtx, departure: Time) -> Widget {
+    Widget::row(vec![
+        "Departing at:".text_widget(ctx).centered_vert(),
+        Widget::dropdown(
+            ctx,
+            "departure",
+            departure,
+            (5..24)
+                .map(|hour| {
+                    let time = Time::START_OF_DAY + Duration::hours(hour);
+                    Choice::new(time.ampm_tostring(), time)
+                })
+                .collect(),
+        ),
+    ])
+}
+
+fn default_departure() -> Time {
+    Time::START_OF_DAY + Duration::hours(8)
+}
+
+fn options_from_controls(panel: &Panel, prev: &Options) -> Options {
+    let walking = WalkingOptions {
+        allow_shoulders: panel
+            .maybe_is_checked("Allow walking on the shoulder of the road without a sidewalk")
+            .unwrap_or(true),
+        walking_speed: panel
+            .maybe_dropdown_value("speed")
+            .unwrap_or_else(WalkingOptions::default_speed),
+        profile: panel
+            .maybe_dropdown_value("profile")
+            .unwrap_or(PedestrianProfile::Default),
+    };
+    let departure = panel
+        .maybe_dropdown_value("departure")
+        .unwrap_or_else(default_departure);
+    match panel.dropdown_value::<&str, _>("mode") {
+        "walking" => Options::Walking(walking),
+        "biking" => Options::Biking,
+        "transit" => Options::Transit(
+            walking,
+            TransitOptions {
+                departure,
+                exact_waits: panel
+                    .maybe_is_checked("Wait for scheduled vehicles, instead of the average wait")
+                    .unwrap_or(false),
+            },
+        ),
+        "driving" => {
+            // Keep any delays that were already loaded
+            let mut congestion = match prev {
+                Options::Driving(congestion) => congestion.clone(),
+                _ => Congestion::free_flow(departure),
+            };
+            congestion.departure = departure;
+            Options::Driving(congestion)
+        }
+        _ => unreachable!(),
     }
 }
 
@@ -331,6 +470,15 @@ fn build_panel(ctx: &mut EventCtx, app: &App, start: &Building, isochrone: &Isoc
             .text("Search by amenity")
             .build_def(ctx),
     );
+    // Reading the dataset isn't supported on the web yet
+    if !cfg!(target_arch = "wasm32") {
+        rows.push(
+            ctx.style()
+                .btn_outline
+                .text("Import amenities")
+                .build_def(ctx),
+        );
+    }
     rows.push(Widget::row(vec![
         ctx.style().btn_plain.text("About").build_def(ctx),
         ctx.style()
@@ -534,9 +682,11 @@ impl State<App> for ExploreAmenities {
 }
 
 pub fn draw_unwalkable_roads(ctx: &mut EventCtx, app: &App, opts: &Options) -> Drawable {
-    let allow_shoulders = match opts {
-        Options::Walking(ref opts) => opts.allow_shoulders,
-        Options::Biking => {
+    let (allow_shoulders, profile) = match opts {
+        Options::Walking(ref opts) | Options::Transit(ref opts, _) => {
+            (opts.allow_shoulders, opts.profile)
+        }
+        Options::Biking | Options::Driving(_) => {
             return Drawable::empty(ctx);
         }
     };
@@ -546,6 +696,11 @@ pub fn draw_unwalkable_roads(ctx: &mut EventCtx, app: &App, opts: &Options) -> D
         if road.is_light_rail() {
             continue;
         }
+        // Steps, steep hills, and rough surfaces may be impassable for some people
+        if profile.road_cost(road).is_none() {
+            batch.push(Color::BLUE.alpha(0.5), road.get_thick_polygon());
+            continue;
+        }
         for l in &road.lanes {
             if l.lane_type == LaneType::Sidewalk
                 || (l.lane_type == LaneType::Shoulder && allow_shoulders)
@@ -6,14 +6,14 @@ use map_gui::colors::ColorSchemeChoice;
 use map_gui::tools::{cmp_count, ColorNetwork};
 use map_gui::{AppLike, ID};
 use map_model::{
-    DirectedRoadID, Direction, PathRequest, PathfinderCaching, RoadID, RoutingParams, Traversable,
-    NORMAL_LANE_THICKNESS,
+    DirectedRoadID, Direction, PathRequest, PathfinderCaching, PedestrianProfile, RoadID,
+    RoutingParams, Traversable, NORMAL_LANE_THICKNESS,
 };
 use synthpop::{TripEndpoint, TripMode};
 use widgetry::mapspace::ToggleZoomed;
 use widgetry::{
-    Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
-    RoundedF64, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
+    Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome,
+    Panel, RoundedF64, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
 };
 
 use crate::app::{App, Transition};
@@ -220,6 +220,44 @@ fn params_to_controls(ctx: &mut EventCtx, mode: TripMode, params: &RoutingParams
             ),
         ]));
     }
+    if mode == TripMode::Walk {
+        rows.push(Widget::row(vec![
+            "Mobility needs:".text_widget(ctx).margin_right(20),
+            Widget::dropdown(
+                ctx,
+                "pedestrian_profile",
+                params.pedestrian_profile,
+                PedestrianProfile::all()
+                    .into_iter()
+                    .map(|p| Choice::new(p.describe(), p))
+                    .collect(),
+            ),
+        ]));
+        rows.push(Widget::row(vec![
+            "Unmarked crossing penalty:"
+                .text_widget(ctx)
+                .margin_right(20),
+            Spinner::f64_widget(
+                ctx,
+                "unmarked_crossing_penalty",
+                (1.0, 5.0),
+                params.unmarked_crossing_penalty,
+                0.1,
+            ),
+        ]));
+        rows.push(Widget::row(vec![
+            "Signalized crossing delay:"
+                .text_widget(ctx)
+                .margin_right(20),
+            Spinner::widget(
+                ctx,
+                "signalized_crossing_delay",
+                (Duration::ZERO, Duration::seconds(60.0)),
+                params.signalized_crossing_delay,
+                Duration::seconds(1.0),
+            ),
+        ]));
+    }
     if mode == TripMode::Bike {
         rows.push(Widget::row(vec![
             "Bike lane penalty:".text_widget(ctx).margin_right(20),
@@ -284,6 +322,10 @@ fn controls_to_params(panel: &Panel) -> (TripMode, RoutingParams) {
         return (TripMode::Drive, params);
     }
     if !panel.is_button_enabled("pedestrians") {
+        params.pedestrian_profile = panel.dropdown_value("pedestrian_profile");
+        params.unmarked_crossing_penalty =
+            panel.spinner::<RoundedF64>("unmarked_crossing_penalty").0;
+        params.signalized_crossing_delay = panel.spinner("signalized_crossing_delay");
         return (TripMode::Walk, params);
     }
     params.unprotected_turn_penalty = panel.spinner("unprotected_turn_penalty");
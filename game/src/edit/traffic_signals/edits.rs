@@ -343,8 +343,7 @@ pub fn edit_entire_signal(
                 Box::new(move |ctx, app, maybe_path| {
                     if let Ok(Some(path)) = maybe_path {
                         app.session.last_gmns_timing_csv = Some(path.clone());
-                        match crate::edit::traffic_signals::gmns::import(&app.primary.map, i, &path)
-                        {
+                        match map_model::gmns::import_signal_timing(&app.primary.map, i, &path) {
                             Ok(new_signal) => Transition::Multi(vec![
                                 Transition::Pop,
                                 Transition::ModifyState(Box::new(move |state, ctx, app| {
@@ -367,7 +366,7 @@ pub fn edit_entire_signal(
                 }),
             )),
             x if Some(x.to_string()) == gmns_existing => {
-                match crate::edit::traffic_signals::gmns::import(
+                match map_model::gmns::import_signal_timing(
                     &app.primary.map,
                     i,
                     app.session.last_gmns_timing_csv.as_ref().unwrap(),
@@ -3,7 +3,8 @@ use std::collections::BTreeSet;
 use maplit::btreeset;
 
 use geom::{Distance, Duration};
-use map_model::IntersectionID;
+use map_gui::tools::PopupMsg;
+use map_model::{IntersectionID, SignalCorridor};
 use widgetry::{
     Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Panel, RewriteColor,
     SimpleState, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
@@ -271,6 +272,10 @@ impl TuneRelative {
                 .text("Update offset")
                 .hotkey(Key::Enter)
                 .build_def(ctx),
+            ctx.style()
+                .btn_outline
+                .text("Optimize green wave")
+                .build_def(ctx),
         ]))
         .build(ctx);
         <dyn SimpleState<_>>::new_state(
@@ -311,6 +316,54 @@ impl SimpleState<App> for TuneRelative {
                     )),
                 ])
             }
+            "Optimize green wave" => {
+                let map = &app.primary.map;
+                let result = SignalCorridor::new(map, self.i1, self.i2).and_then(|corridor| {
+                    if let Some(i) = corridor.signals.iter().find(|i| !self.members.contains(*i)) {
+                        bail!(
+                            "The route passes through {}, which isn't being edited. Edit all of \
+                             the signals along the corridor together.",
+                            i
+                        );
+                    }
+                    corridor.optimize_bandwidth(map, None)
+                });
+                match result {
+                    Ok(timing) => {
+                        let msg = vec![
+                            format!(
+                                "Retimed {} signals to a {} cycle",
+                                timing.signals.len(),
+                                timing.cycle_length
+                            ),
+                            format!(
+                                "Green band from {} to {}: {}",
+                                self.i1, self.i2, timing.outbound_bandwidth
+                            ),
+                            format!(
+                                "Green band from {} to {}: {}",
+                                self.i2, self.i1, timing.inbound_bandwidth
+                            ),
+                        ];
+                        for ts in timing.signals {
+                            app.primary.map.incremental_edit_traffic_signal(ts);
+                        }
+                        Transition::Multi(vec![
+                            Transition::Pop,
+                            Transition::Replace(ShowRelative::new_state(
+                                ctx,
+                                app,
+                                self.i1,
+                                self.members.clone(),
+                            )),
+                            Transition::Push(PopupMsg::new_state(ctx, "Green wave", msg)),
+                        ])
+                    }
+                    Err(err) => {
+                        Transition::Push(PopupMsg::new_state(ctx, "Error", vec![err.to_string()]))
+                    }
+                }
+            }
             _ => unreachable!(),
         }
     }
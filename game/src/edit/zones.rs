@@ -35,12 +35,16 @@ impl ZoneEditor {
             // Starting a new zone
             btreeset! { start.id }
         };
-        let allow_through_traffic = start
+        let mut allow_through_traffic: BTreeSet<TripMode> = start
             .access_restrictions
             .allow_through_traffic
             .into_iter()
             .map(TripMode::from_constraints)
             .collect();
+        // Shared bikes and scooters follow the same rules as bikes
+        if allow_through_traffic.contains(&TripMode::Bike) {
+            allow_through_traffic.insert(TripMode::Micromobility);
+        }
 
         let (draw, legend) = draw_zone(ctx, app, &members);
         let orig_members = members.clone();
@@ -426,7 +426,7 @@ fn describe_problems(
                 txt.into_widget(ctx),
             ])
         }
-        TripMode::Bike => {
+        TripMode::Bike | TripMode::Micromobility => {
             let mut count_complex_intersections = 0;
             let mut count_overtakes = 0;
             let empty = Vec::new();
@@ -0,0 +1,117 @@
+use abstutil::prettyprint_usize;
+use geom::{Circle, Distance, Duration, Time};
+use map_gui::tools::ColorLegend;
+use widgetry::mapspace::ToggleZoomed;
+use widgetry::{Color, EventCtx, GfxCtx, Line, Outcome, Panel, Text, Widget};
+
+use crate::app::App;
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+
+pub struct DockOccupancy {
+    time: Time,
+    draw: ToggleZoomed,
+    panel: Panel,
+}
+
+impl Layer for DockOccupancy {
+    fn name(&self) -> Option<&'static str> {
+        Some("bikeshare docks")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        if app.primary.sim.time() != self.time {
+            *self = DockOccupancy::new(ctx, app);
+        }
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            }
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+        self.draw.draw(g);
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw.unzoomed);
+    }
+}
+
+impl DockOccupancy {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> DockOccupancy {
+        let map = &app.primary.map;
+        let now = app.primary.sim.time();
+        let empty_color = Color::RED;
+        let some_color = Color::GREEN;
+        let full_color = Color::PURPLE;
+
+        let mut num_empty = 0;
+        let mut num_full = 0;
+        let mut time_empty = Duration::ZERO;
+        let mut draw = ToggleZoomed::builder();
+        for dock in map.all_bikeshare_docks() {
+            let (bikes, capacity) = app.primary.sim.get_bikeshare_dock_state(dock.id);
+            let color = if bikes == 0 {
+                num_empty += 1;
+                empty_color
+            } else if bikes == capacity {
+                num_full += 1;
+                full_color
+            } else {
+                some_color
+            };
+            draw.unzoomed.push(
+                color.alpha(0.8),
+                Circle::new(dock.pt, Distance::meters(15.0)).to_polygon(),
+            );
+            draw.zoomed.push(
+                color.alpha(0.5),
+                Circle::new(dock.pt, Distance::meters(3.0)).to_polygon(),
+            );
+
+            // Nobody can start a ride while the dock is empty, so sum how long that's been
+            let pts = app
+                .primary
+                .sim
+                .get_analytics()
+                .bikeshare_dock_occupancy(now, dock.id, capacity);
+            for pair in pts.windows(2) {
+                if pair[0].1 == 0 {
+                    time_empty += pair[1].0 - pair[0].0;
+                }
+            }
+        }
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            header(ctx, "Bikeshare docks"),
+            Text::from_multiline(vec![
+                Line(format!(
+                    "{} docks",
+                    prettyprint_usize(map.all_bikeshare_docks().len())
+                )),
+                Line(format!("{} empty now", prettyprint_usize(num_empty))),
+                Line(format!("{} full now", prettyprint_usize(num_full))),
+                Line(format!(
+                    "Docks have spent {} empty so far today",
+                    time_empty
+                )),
+            ])
+            .into_widget(ctx),
+            ColorLegend::row(ctx, empty_color, "empty"),
+            ColorLegend::row(ctx, some_color, "some bikes and free slots"),
+            ColorLegend::row(ctx, full_color, "full"),
+        ]))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx);
+
+        DockOccupancy {
+            time: now,
+            draw: draw.build(ctx),
+            panel,
+        }
+    }
+}
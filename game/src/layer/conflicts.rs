@@ -0,0 +1,308 @@
+use std::collections::BTreeSet;
+
+use abstutil::{prettyprint_usize, Counter};
+use collisions::CollisionDataset;
+use geom::{Circle, Distance, FindClosest, Pt2D, Time};
+use map_gui::tools::{make_heatmap, HeatmapOptions};
+use map_model::IntersectionID;
+use sim::{Conflict, ConflictKind, ConflictSeverity};
+use widgetry::mapspace::ToggleZoomed;
+use widgetry::{
+    Choice, Color, EventCtx, GfxCtx, Line, Outcome, Panel, Text, TextExt, Toggle, Widget,
+};
+
+use crate::app::App;
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+
+/// Real collisions further than this from an intersection aren't compared
+const MAX_COLLISION_DIST: Distance = Distance::const_meters(30.0);
+/// How many of the worst intersections to compare against real collisions
+const NUM_HOTSPOTS: usize = 10;
+
+pub struct ConflictMap {
+    time: Time,
+    opts: Options,
+    draw: ToggleZoomed,
+    panel: Panel,
+    /// Real collisions within the map, if the city has any data
+    collisions: Option<Vec<Pt2D>>,
+}
+
+impl Layer for ConflictMap {
+    fn name(&self) -> Option<&'static str> {
+        Some("conflicts")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        if app.primary.sim.time() != self.time {
+            let mut new = ConflictMap::new(ctx, app, self.opts.clone(), self.collisions.take());
+            new.panel.restore(ctx, &self.panel);
+            *self = new;
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            },
+            _ => {
+                let new_opts = self.options();
+                if self.opts != new_opts {
+                    *self = ConflictMap::new(ctx, app, new_opts, self.collisions.take());
+                }
+            }
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+        self.draw.draw(g);
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw.unzoomed);
+    }
+}
+
+impl ConflictMap {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        opts: Options,
+        collisions: Option<Vec<Pt2D>>,
+    ) -> ConflictMap {
+        let conflicts: Vec<&Conflict> = app
+            .primary
+            .sim
+            .get_analytics()
+            .surrogate_safety
+            .conflicts
+            .iter()
+            .filter(|c| opts.show(c))
+            .collect();
+
+        let mut draw = ToggleZoomed::builder();
+        let pts: Vec<Pt2D> = conflicts.iter().map(|c| c.pt).collect();
+        let legend = if let Some(ref o) = opts.heatmap {
+            Some(make_heatmap(
+                ctx,
+                &mut draw.unzoomed,
+                app.primary.map.get_bounds(),
+                pts,
+                o,
+            ))
+        } else {
+            let circle = Circle::new(Pt2D::new(0.0, 0.0), Distance::meters(5.0)).to_polygon();
+            for c in &conflicts {
+                let color = match c.severity {
+                    ConflictSeverity::Critical => Color::RED,
+                    ConflictSeverity::Serious => Color::ORANGE,
+                    ConflictSeverity::Minor => Color::YELLOW,
+                };
+                draw.unzoomed
+                    .push(color.alpha(0.8), circle.translate(c.pt.x(), c.pt.y()));
+            }
+            None
+        };
+
+        let comparison = if let Some(ref pts) = collisions {
+            if opts.show_collisions {
+                if let Ok(circle) = Circle::new(Pt2D::new(0.0, 0.0), Distance::meters(8.0))
+                    .to_outline(Distance::meters(2.0))
+                {
+                    for pt in pts {
+                        draw.unzoomed
+                            .push(Color::BLACK, circle.translate(pt.x(), pt.y()));
+                    }
+                }
+            }
+            Some(compare_with_collisions(app, &conflicts, pts))
+        } else {
+            None
+        };
+
+        let panel = make_controls(ctx, &opts, legend, conflicts.len(), comparison);
+        ConflictMap {
+            time: app.primary.sim.time(),
+            opts,
+            draw: draw.build(ctx),
+            panel,
+            collisions,
+        }
+    }
+
+    fn options(&self) -> Options {
+        let heatmap = if self.panel.is_checked("Show heatmap") {
+            Some(HeatmapOptions::from_controls(&self.panel))
+        } else {
+            None
+        };
+        let mut kinds = BTreeSet::new();
+        for kind in ConflictKind::all() {
+            if self.panel.is_checked(kind.describe()) {
+                kinds.insert(kind);
+            }
+        }
+        Options {
+            heatmap,
+            kinds,
+            min_severity: self.panel.dropdown_value("min severity"),
+            show_collisions: self.panel.maybe_is_checked("show real collisions") == Some(true),
+        }
+    }
+}
+
+/// Load real collisions within the map, if the city has any
+pub fn load_collisions(ctx: &mut EventCtx, app: &App) -> Option<Vec<Pt2D>> {
+    let map = &app.primary.map;
+    let path = map.get_city_name().input_path("collisions.bin");
+    if !abstio::file_exists(&path) {
+        return None;
+    }
+    Some(ctx.loading_screen("load collision data", |_, timer| {
+        let data: CollisionDataset = abstio::read_binary(path, timer);
+        data.collisions
+            .into_iter()
+            .map(|c| c.location.to_pt(map.get_gps_bounds()))
+            .filter(|pt| map.get_boundary_polygon().contains_pt(*pt))
+            .collect()
+    }))
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Options {
+    // If None, just a dot map
+    heatmap: Option<HeatmapOptions>,
+    kinds: BTreeSet<ConflictKind>,
+    min_severity: ConflictSeverity,
+    show_collisions: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            heatmap: Some(HeatmapOptions::new()),
+            kinds: ConflictKind::all().into_iter().collect(),
+            min_severity: ConflictSeverity::Minor,
+            show_collisions: false,
+        }
+    }
+}
+
+impl Options {
+    fn show(&self, conflict: &Conflict) -> bool {
+        // Critical sorts first
+        self.kinds.contains(&conflict.kind) && conflict.severity <= self.min_severity
+    }
+}
+
+/// Do the places with the most simulated conflicts match where real collisions happen?
+fn compare_with_collisions(app: &App, conflicts: &[&Conflict], collisions: &[Pt2D]) -> Text {
+    let map = &app.primary.map;
+    let mut closest: FindClosest<IntersectionID> = FindClosest::new(map.get_bounds());
+    for i in map.all_intersections() {
+        closest.add(i.id, i.polygon.points());
+    }
+    let mut real: Counter<IntersectionID> = Counter::new();
+    for pt in collisions {
+        if let Some((i, _)) = closest.closest_pt(*pt, MAX_COLLISION_DIST) {
+            real.inc(i);
+        }
+    }
+    let mut simulated: Counter<IntersectionID> = Counter::new();
+    for c in conflicts {
+        simulated.inc(c.intersection);
+    }
+
+    let hotspots = simulated.highest_n(NUM_HOTSPOTS);
+    let hits = hotspots.iter().filter(|(i, _)| real.get(*i) > 0).count();
+    let collisions_near_conflicts: usize = real
+        .borrow()
+        .iter()
+        .filter(|(i, _)| simulated.get(**i) > 0)
+        .map(|(_, cnt)| *cnt)
+        .sum();
+
+    let mut txt = Text::new();
+    txt.add_line(Line(format!(
+        "{} real collisions near intersections",
+        prettyprint_usize(real.sum())
+    )));
+    txt.add_line(Line(format!(
+        "{} of the {} intersections with the most conflicts also had a real collision",
+        hits,
+        hotspots.len()
+    )));
+    txt.add_line(Line(format!(
+        "{} real collisions happened where there's a simulated conflict",
+        prettyprint_usize(collisions_near_conflicts)
+    )));
+    txt
+}
+
+fn make_controls(
+    ctx: &mut EventCtx,
+    opts: &Options,
+    legend: Option<Widget>,
+    num_conflicts: usize,
+    comparison: Option<Text>,
+) -> Panel {
+    let mut col = vec![
+        header(ctx, "Conflicts between agents"),
+        Text::from_all(vec![
+            Line("Matching conflicts: ").secondary(),
+            Line(prettyprint_usize(num_conflicts)),
+        ])
+        .into_widget(ctx),
+        "Agents crossing the same point within a few seconds of each other".text_widget(ctx),
+    ];
+
+    for kind in ConflictKind::all() {
+        col.push(Toggle::checkbox(
+            ctx,
+            kind.describe(),
+            None,
+            opts.kinds.contains(&kind),
+        ));
+    }
+    col.push(Widget::row(vec![
+        "Minimum severity:".text_widget(ctx).centered_vert(),
+        Widget::dropdown(
+            ctx,
+            "min severity",
+            opts.min_severity,
+            vec![
+                Choice::new("critical", ConflictSeverity::Critical),
+                Choice::new("serious", ConflictSeverity::Serious),
+                Choice::new("minor", ConflictSeverity::Minor),
+            ],
+        ),
+    ]));
+
+    if let Some(txt) = comparison {
+        col.push(Toggle::checkbox(
+            ctx,
+            "show real collisions",
+            None,
+            opts.show_collisions,
+        ));
+        col.push(txt.into_widget(ctx));
+    }
+
+    col.push(Toggle::choice(
+        ctx,
+        "Show heatmap",
+        "Heatmap",
+        "Points",
+        None,
+        opts.heatmap.is_some(),
+    ));
+    if let Some(ref o) = opts.heatmap {
+        col.push(Line("Heatmap Options").small_heading().into_widget(ctx));
+        col.extend(o.to_controls(ctx, legend.unwrap()));
+    }
+
+    Panel::new_builder(Widget::col(col))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx)
+}
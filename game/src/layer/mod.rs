@@ -8,6 +8,8 @@ use widgetry::{
 use crate::app::{App, Transition};
 use crate::sandbox::dashboards;
 
+mod bikeshare;
+mod conflicts;
 pub mod elevation;
 pub mod favorites;
 pub mod map;
@@ -117,6 +119,7 @@ impl PickLayer {
                     btn("population map", Key::X),
                     btn("no sidewalks", Key::S),
                     btn("favorite buildings", Key::F),
+                    btn("bikeshare docks", Key::I),
                 ]),
             ])
             .evenly_spaced(),
@@ -133,6 +136,7 @@ impl PickLayer {
                     btn("parking efficiency", Key::O),
                     btn("blackholes", Key::L),
                     btn("problem map", Key::K),
+                    btn("conflicts", Key::C),
                     btn("high stress", Key::H),
                     if app.primary.sim.get_pandemic_model().is_some() {
                         btn("pandemic model", Key::Y)
@@ -213,6 +217,9 @@ impl State<App> for PickLayer {
                         ctx, app, true, true, true, false, true,
                     )));
                 }
+                "bikeshare docks" => {
+                    app.primary.layer = Some(Box::new(bikeshare::DockOccupancy::new(ctx, app)));
+                }
                 "parking efficiency" => {
                     app.primary.layer = Some(Box::new(parking::Efficiency::new(ctx, app)));
                 }
@@ -225,6 +232,15 @@ impl State<App> for PickLayer {
                         },
                     )));
                 }
+                "conflicts" => {
+                    let collisions = conflicts::load_collisions(ctx, app);
+                    app.primary.layer = Some(Box::new(conflicts::ConflictMap::new(
+                        ctx,
+                        app,
+                        conflicts::Options::default(),
+                        collisions,
+                    )));
+                }
                 "problem map" => {
                     app.primary.layer = Some(Box::new(problems::ProblemMap::new(
                         ctx,
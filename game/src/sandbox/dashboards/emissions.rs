@@ -0,0 +1,206 @@
+use std::io::Write;
+
+use anyhow::Result;
+use fs_err::File;
+
+use abstutil::prettyprint_usize;
+use geom::{Duration, Time};
+use map_gui::tools::PopupMsg;
+use sim::Pollutants;
+use widgetry::{
+    EventCtx, GfxCtx, Line, LinePlot, Outcome, Panel, PlotOptions, Series, State, Text, TextExt,
+    Widget,
+};
+
+use crate::app::{App, Transition};
+use crate::sandbox::dashboards::DashTab;
+
+pub struct EmissionsSummary {
+    panel: Panel,
+}
+
+impl EmissionsSummary {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let now = app.primary.sim.time();
+        let after = app.primary.sim.get_analytics().emissions.total_by(now);
+        let before = if app.has_prebaked().is_some() {
+            Some(app.prebaked().emissions.total_by(now))
+        } else {
+            None
+        };
+
+        let mut txt = Text::from(Line("Estimated vehicle emissions so far").small_heading());
+        txt.add_line(Line(
+            "Based on the average speed of cars and buses along every road, and the road's grade. \
+             Use this to compare proposals, not as an absolute inventory.",
+        ));
+        txt.add_line(Line(""));
+        for (label, unit, get) in pollutants() {
+            let mut line = format!("{}: {} {}", label, format_amount(get(&after)), unit);
+            if let Some(ref before) = before {
+                let (b, a) = (get(before), get(&after));
+                if b > 0.0 {
+                    line = format!("{} ({:+.1}% from before)", line, 100.0 * (a - b) / b);
+                }
+            }
+            txt.add_line(Line(line));
+        }
+
+        let mut col = vec![
+            DashTab::Emissions.picker(ctx, app),
+            txt.into_widget(ctx).section(ctx),
+            Widget::col(vec![
+                Line("CO2 per hour (kg)").small_heading().into_widget(ctx),
+                co2_per_hour(ctx, app),
+            ])
+            .section(ctx),
+            Widget::col(vec![
+                Line("Roads with the most CO2")
+                    .small_heading()
+                    .into_widget(ctx),
+                worst_roads(app).into_widget(ctx),
+            ])
+            .section(ctx),
+        ];
+        // TODO We can make file downloads of dynamically generated data work on the browser too...
+        if cfg!(not(target_arch = "wasm32")) {
+            col.push(ctx.style().btn_plain.text("Export to CSV").build_def(ctx));
+        }
+
+        Box::new(EmissionsSummary {
+            panel: Panel::new_builder(Widget::col(col))
+                .exact_size_percent(90, 90)
+                .build(ctx),
+        })
+    }
+}
+
+impl State<App> for EmissionsSummary {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "Export to CSV" => Transition::Push(match export_emissions(app) {
+                    Ok(paths) => PopupMsg::new_state(
+                        ctx,
+                        "Data exported",
+                        paths
+                            .into_iter()
+                            .map(|p| format!("Data exported to {}", p))
+                            .collect(),
+                    ),
+                    Err(err) => PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()]),
+                }),
+                "close" => Transition::Pop,
+                _ => unreachable!(),
+            },
+            Outcome::Changed(_) => DashTab::Emissions
+                .transition(ctx, app, &self.panel)
+                .unwrap(),
+            _ => Transition::Keep,
+        }
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _app: &App) {
+        self.panel.draw(g);
+    }
+}
+
+type Getter = fn(&Pollutants) -> f64;
+
+fn pollutants() -> [(&'static str, &'static str, Getter); 4] {
+    [
+        ("CO2", "g", |p| p.co2),
+        ("NOx", "g", |p| p.nox),
+        ("PM2.5", "g", |p| p.pm25),
+        ("Energy", "MJ", |p| p.energy),
+    ]
+}
+
+fn format_amount(x: f64) -> String {
+    if x >= 100.0 {
+        prettyprint_usize(x.round() as usize)
+    } else {
+        format!("{:.2}", x)
+    }
+}
+
+fn co2_per_hour(ctx: &mut EventCtx, app: &App) -> Widget {
+    let to_pts = |per_hour: &[Pollutants]| -> Vec<(Time, usize)> {
+        per_hour
+            .iter()
+            .enumerate()
+            .map(|(hour, p)| {
+                (
+                    Time::START_OF_DAY + Duration::hours(hour),
+                    (p.co2 / 1000.0).round() as usize,
+                )
+            })
+            .collect()
+    };
+
+    let mut series = vec![Series {
+        label: format!("After \"{}\"", app.primary.map.get_edits().edits_name),
+        color: app.cs.after_changes,
+        pts: to_pts(&app.primary.sim.get_analytics().emissions.per_hour),
+    }];
+    if app.has_prebaked().is_some() {
+        series.push(Series {
+            label: format!("Before \"{}\"", app.primary.map.get_edits().edits_name),
+            color: app.cs.before_changes.alpha(0.5),
+            pts: to_pts(&app.prebaked().emissions.per_hour),
+        });
+    }
+    LinePlot::new_widget(ctx, "co2", series, PlotOptions::fixed(), app.opts.units)
+}
+
+fn worst_roads(app: &App) -> Text {
+    let map = &app.primary.map;
+    let mut roads: Vec<_> = app
+        .primary
+        .sim
+        .get_analytics()
+        .emissions
+        .per_road
+        .iter()
+        .collect();
+    roads.sort_by(|(_, a), (_, b)| b.co2.partial_cmp(&a.co2).unwrap());
+
+    let mut txt = Text::new();
+    for (r, amount) in roads.into_iter().take(10) {
+        txt.add_line(Line(format!(
+            "{} ({}): {} kg",
+            map.get_r(*r).get_name(app.opts.language.as_ref()),
+            r,
+            format_amount(amount.co2 / 1000.0)
+        )));
+    }
+    if txt.is_empty() {
+        txt.add_line(Line("No vehicles have finished crossing a road yet"));
+    }
+    txt
+}
+
+fn export_emissions(app: &App) -> Result<Vec<String>> {
+    let emissions = &app.primary.sim.get_analytics().emissions;
+    let suffix = format!(
+        "{}_{}",
+        app.primary.map.get_name().as_filename(),
+        app.primary.sim.time().as_filename()
+    );
+
+    let roads_path = format!("road_emissions_{}.csv", suffix);
+    let mut f = File::create(&roads_path)?;
+    writeln!(f, "road,co2_grams,nox_grams,pm25_grams,energy_megajoules")?;
+    for (r, p) in &emissions.per_road {
+        writeln!(f, "{},{},{},{},{}", r.0, p.co2, p.nox, p.pm25, p.energy)?;
+    }
+
+    let trips_path = format!("trip_emissions_{}.csv", suffix);
+    let mut f = File::create(&trips_path)?;
+    writeln!(f, "trip,co2_grams,nox_grams,pm25_grams,energy_megajoules")?;
+    for (t, p) in &emissions.per_trip {
+        writeln!(f, "{},{},{},{},{}", t.0, p.co2, p.nox, p.pm25, p.energy)?;
+    }
+
+    Ok(vec![roads_path, trips_path])
+}
@@ -7,9 +7,11 @@ use crate::app::App;
 use crate::app::Transition;
 
 mod commuter;
+mod emissions;
 mod generic_trip_table;
 mod misc;
 mod mode_shift;
+mod noise;
 mod parking_overhead;
 mod risks;
 mod selector;
@@ -24,6 +26,8 @@ pub enum DashTab {
     TripTable,
     TravelTimes,
     RiskSummaries,
+    Emissions,
+    Noise,
     ParkingOverhead,
     ActiveTraffic,
     TransitRoutes,
@@ -38,6 +42,8 @@ impl DashTab {
             Choice::new("Trip Table", DashTab::TripTable),
             Choice::new("Travel Times", DashTab::TravelTimes),
             Choice::new("Risk Exposure", DashTab::RiskSummaries),
+            Choice::new("Emissions", DashTab::Emissions),
+            Choice::new("Noise Exposure", DashTab::Noise),
             Choice::new("Parking Overhead", DashTab::ParkingOverhead),
             Choice::new("Active Traffic", DashTab::ActiveTraffic),
             Choice::new("Transit Routes", DashTab::TransitRoutes),
@@ -67,6 +73,8 @@ impl DashTab {
                 travel_times::TravelTimes::new_state(ctx, app, travel_times::Filter::new())
             }
             DashTab::RiskSummaries => risks::RiskSummaries::new_state(ctx, app, false),
+            DashTab::Emissions => emissions::EmissionsSummary::new_state(ctx, app),
+            DashTab::Noise => noise::NoiseSummary::new_state(ctx, app),
             DashTab::ParkingOverhead => parking_overhead::ParkingOverhead::new_state(ctx, app),
             DashTab::ActiveTraffic => misc::ActiveTraffic::new_state(ctx, app),
             DashTab::TransitRoutes => misc::TransitRoutes::new_state(ctx, app),
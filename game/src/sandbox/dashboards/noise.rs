@@ -0,0 +1,158 @@
+use std::io::Write;
+
+use anyhow::Result;
+use fs_err::File;
+
+use abstutil::prettyprint_usize;
+use map_gui::tools::PopupMsg;
+use sim::{NoiseEstimate, LDEN_THRESHOLDS};
+use widgetry::{EventCtx, GfxCtx, Line, Outcome, Panel, State, Text, Widget};
+
+use crate::app::{App, Transition};
+use crate::sandbox::dashboards::DashTab;
+
+pub struct NoiseSummary {
+    panel: Panel,
+    after: NoiseEstimate,
+}
+
+impl NoiseSummary {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let map = &app.primary.map;
+        let (after, before) = ctx.loading_screen("estimate traffic noise", |_, timer| {
+            let after = NoiseEstimate::new(map, app.primary.sim.get_analytics(), timer);
+            let before = if app.has_prebaked().is_some() {
+                Some(NoiseEstimate::new(map, app.prebaked(), timer))
+            } else {
+                None
+            };
+            (after, before)
+        });
+
+        let mut txt = Text::from(Line("Residents exposed to road traffic noise").small_heading());
+        txt.add_line(Line(
+            "Based on the volume and speed of cars and buses along every road, and the distance \
+             to each building. Only meaningful after simulating the full day. Use this to compare \
+             proposals, not as a strategic noise map.",
+        ));
+        txt.add_line(Line(""));
+        let after_exposure = after.exposure(map, &LDEN_THRESHOLDS);
+        let before_exposure = before
+            .as_ref()
+            .map(|before| before.exposure(map, &LDEN_THRESHOLDS));
+        for (idx, (threshold, people)) in after_exposure.into_iter().enumerate() {
+            let mut line = format!(
+                "Lden {} dB or more: {} people",
+                threshold,
+                prettyprint_usize(people)
+            );
+            if let Some(ref before) = before_exposure {
+                let delta = people as isize - before[idx].1 as isize;
+                line = format!("{} ({:+} from before)", line, delta);
+            }
+            txt.add_line(Line(line));
+        }
+
+        let mut col = vec![
+            DashTab::Noise.picker(ctx, app),
+            txt.into_widget(ctx).section(ctx),
+            Widget::col(vec![
+                Line("Loudest roads").small_heading().into_widget(ctx),
+                loudest_roads(app, &after).into_widget(ctx),
+            ])
+            .section(ctx),
+        ];
+        // TODO We can make file downloads of dynamically generated data work on the browser too...
+        if cfg!(not(target_arch = "wasm32")) {
+            col.push(ctx.style().btn_plain.text("Export to CSV").build_def(ctx));
+        }
+
+        Box::new(NoiseSummary {
+            panel: Panel::new_builder(Widget::col(col))
+                .exact_size_percent(90, 90)
+                .build(ctx),
+            after,
+        })
+    }
+}
+
+impl State<App> for NoiseSummary {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "Export to CSV" => Transition::Push(match export_noise(app, &self.after) {
+                    Ok(paths) => PopupMsg::new_state(
+                        ctx,
+                        "Data exported",
+                        paths
+                            .into_iter()
+                            .map(|p| format!("Data exported to {}", p))
+                            .collect(),
+                    ),
+                    Err(err) => PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()]),
+                }),
+                "close" => Transition::Pop,
+                _ => unreachable!(),
+            },
+            Outcome::Changed(_) => DashTab::Noise.transition(ctx, app, &self.panel).unwrap(),
+            _ => Transition::Keep,
+        }
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _app: &App) {
+        self.panel.draw(g);
+    }
+}
+
+fn loudest_roads(app: &App, noise: &NoiseEstimate) -> Text {
+    let map = &app.primary.map;
+    let mut roads: Vec<_> = noise.per_road.iter().collect();
+    roads.sort_by(|(_, a), (_, b)| b.lden.partial_cmp(&a.lden).unwrap());
+
+    let mut txt = Text::new();
+    for (r, levels) in roads.into_iter().take(10) {
+        txt.add_line(Line(format!(
+            "{} ({}): Lden {:.1} dB",
+            map.get_r(*r).get_name(app.opts.language.as_ref()),
+            r,
+            levels.lden
+        )));
+    }
+    if txt.is_empty() {
+        txt.add_line(Line("No vehicles have crossed a road yet"));
+    }
+    txt
+}
+
+fn export_noise(app: &App, noise: &NoiseEstimate) -> Result<Vec<String>> {
+    let suffix = format!(
+        "{}_{}",
+        app.primary.map.get_name().as_filename(),
+        app.primary.sim.time().as_filename()
+    );
+
+    let roads_path = format!("road_noise_{}.csv", suffix);
+    let mut f = File::create(&roads_path)?;
+    writeln!(f, "road,lday,levening,lnight,lden")?;
+    for (r, l) in &noise.per_road {
+        writeln!(f, "{},{},{},{},{}", r.0, l.day, l.evening, l.night, l.lden)?;
+    }
+
+    let buildings_path = format!("building_noise_{}.csv", suffix);
+    let mut f = File::create(&buildings_path)?;
+    writeln!(f, "building,osm_id,lday,levening,lnight,lden")?;
+    for (b, l) in &noise.per_building {
+        writeln!(
+            f,
+            "{},{},{},{},{},{}",
+            b.0,
+            app.primary.map.get_b(*b).orig_id.inner(),
+            l.day,
+            l.evening,
+            l.night,
+            l.lden
+        )?;
+    }
+
+    Ok(vec![roads_path, buildings_path])
+}
@@ -32,7 +32,8 @@ use map_model::{
     MovementID, PermanentMapEdits, RoadID, TurnID,
 };
 use sim::{
-    AgentID, AgentType, DelayCause, PersonID, Sim, SimFlags, SimOptions, TripID, VehicleType,
+    AgentID, AgentType, DelayCause, LegRecord, PersonID, Pollutants, Sim, SimFlags, SimOptions,
+    TripExport, TripID, TripRecord, VehicleType,
 };
 use synthpop::{ExternalPerson, Scenario, ScenarioModifier, TripMode};
 
@@ -318,6 +319,18 @@ fn handle_command(
             }
             Ok(abstutil::to_json(&trips))
         }
+        "/data/get-trips" => {
+            let export = TripExport::new(sim, map, false);
+            Ok(abstutil::to_json(&Trips {
+                trips: export.trips,
+                legs: export.legs,
+            }))
+        }
+        "/data/get-trips-csv" => TripExport::new(sim, map, false).trips_csv(),
+        "/data/get-trip-legs-csv" => TripExport::new(sim, map, false).legs_csv(),
+        "/data/get-trip-legs-geojson" => Ok(abstutil::to_json(
+            &TripExport::new(sim, map, true).legs_geojson(map)?,
+        )),
         "/data/get-agent-positions" => Ok(abstutil::to_json(&AgentPositions {
             agents: sim
                 .get_unzoomed_agents(map)
@@ -342,6 +355,15 @@ fn handle_command(
                 .map(|((r, a, hr), cnt)| (*r, *a, *hr, *cnt))
                 .collect(),
         })),
+        "/data/get-emissions" => {
+            let emissions = &sim.get_analytics().emissions;
+            Ok(abstutil::to_json(&EmissionsData {
+                total: emissions.total_by(sim.time()),
+                per_road: emissions.per_road.iter().map(|(r, p)| (*r, *p)).collect(),
+                per_trip: emissions.per_trip.iter().map(|(t, p)| (*t, *p)).collect(),
+                per_hour: emissions.per_hour.clone(),
+            }))
+        }
         "/data/get-blocked-by-graph" => Ok(abstutil::to_json(&BlockedByGraph {
             blocked_by: sim
                 .get_blocked_by_graph(map)
@@ -420,6 +442,12 @@ struct FinishedTrip {
     mode: TripMode,
 }
 
+#[derive(Serialize)]
+struct Trips {
+    trips: Vec<TripRecord>,
+    legs: Vec<LegRecord>,
+}
+
 #[derive(Serialize)]
 struct Delays {
     #[serde(serialize_with = "serialize_btreemap")]
@@ -469,6 +497,16 @@ struct RoadThroughput {
     counts: Vec<(RoadID, AgentType, usize, usize)>,
 }
 
+#[derive(Serialize)]
+struct EmissionsData {
+    // Everything emitted so far
+    total: Pollutants,
+    per_road: Vec<(RoadID, Pollutants)>,
+    per_trip: Vec<(TripID, Pollutants)>,
+    // Indexed by the hour of the day
+    per_hour: Vec<Pollutants>,
+}
+
 #[derive(Serialize)]
 struct TrafficSignalState {
     current_stage_idx: usize,
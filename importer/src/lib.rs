@@ -51,6 +51,7 @@ pub async fn oneshot(
     clip: Option<String>,
     drive_on_right: bool,
     filter_crosswalks: bool,
+    separate_sidewalks: bool,
     create_uk_travel_demand_model: bool,
     opts: RawToMapOptions,
 ) {
@@ -79,8 +80,10 @@ pub async fn oneshot(
             private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(1),
             include_railroads: true,
             extra_buildings: None,
+            extra_pois: Vec::new(),
             skip_local_roads: false,
             filter_crosswalks,
+            separate_sidewalks,
             gtfs_url: None,
         },
         &mut timer,
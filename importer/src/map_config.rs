@@ -1,4 +1,5 @@
 use abstio::{CityName, MapName};
+use abstutil::Timer;
 use geom::Distance;
 use map_model::DrivingSide;
 
@@ -17,6 +18,14 @@ pub fn config_for_map(name: &MapName) -> convert_osm::Options {
     } else {
         None
     };
+    // Points of interest from other datasets are also configured in a canonical location, as a
+    // list of `PoiSource`s.
+    let extra_pois_path = name.city.input_path("extra_pois.json");
+    let extra_pois = if abstio::file_exists(&extra_pois_path) {
+        abstio::read_json(extra_pois_path, &mut Timer::throwaway())
+    } else {
+        Vec::new()
+    };
 
     convert_osm::Options {
         map_config: map_model::MapConfig {
@@ -70,8 +79,10 @@ pub fn config_for_map(name: &MapName) -> convert_osm::Options {
             _ => true,
         },
         extra_buildings,
+        extra_pois,
         skip_local_roads: name == &MapName::new("us", "phoenix", "loop101"),
         filter_crosswalks: false,
+        separate_sidewalks: false,
         // https://www.transit.land is a great place to find the static GTFS URLs
         gtfs_url: if name == &MapName::new("us", "seattle", "arboretum") {
             Some("http://metro.kingcounty.gov/GTFS/google_transit.zip".to_string())
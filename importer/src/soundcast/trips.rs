@@ -201,7 +201,9 @@ fn clip_trips(
                 &osm_id_to_bldg,
                 borders.for_mode(orig.mode),
                 match orig.mode {
-                    TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
+                    TripMode::Walk | TripMode::Transit | TripMode::Micromobility => {
+                        PathConstraints::Pedestrian
+                    }
                     TripMode::Drive => PathConstraints::Car,
                     TripMode::Bike => PathConstraints::Bike,
                 },
@@ -149,14 +149,25 @@ impl ExtraShapes {
         path: String,
         gps_bounds: &GPSBounds,
         timer: &mut Timer,
+    ) -> Result<ExtraShapes> {
+        ExtraShapes::load_csv_with_coordinates(path, "Longitude", "Latitude", gps_bounds, timer)
+    }
+
+    /// Like `load_csv`, but for files naming the columns with point coordinates differently.
+    pub fn load_csv_with_coordinates(
+        path: String,
+        longitude_column: &str,
+        latitude_column: &str,
+        gps_bounds: &GPSBounds,
+        timer: &mut Timer,
     ) -> Result<ExtraShapes> {
         timer.start(format!("read {}", path));
         let mut shapes = Vec::new();
         for rec in csv::Reader::from_path(&path)?.deserialize() {
             let mut rec: BTreeMap<String, String> = rec?;
             match (
-                rec.remove("Longitude"),
-                rec.remove("Latitude"),
+                rec.remove(longitude_column),
+                rec.remove(latitude_column),
                 rec.remove("geometry"),
             ) {
                 (Some(lon), Some(lat), _) => {
@@ -183,8 +194,10 @@ impl ExtraShapes {
                 _ => {
                     timer.stop(format!("read {}", path));
                     bail!(
-                        "{} doesn't have a column called Longitude, Latitude, or geometry",
-                        path
+                        "{} doesn't have a column called {}, {}, or geometry",
+                        path,
+                        longitude_column,
+                        latitude_column
                     )
                 }
             }
@@ -1,11 +1,9 @@
 //! Experiments to make a neighborhood be low-traffic by automatically placing filters to prevent all rat runs.
 
 use abstutil::Timer;
-use map_model::RoadID;
-use widgetry::{Choice, EventCtx};
-
-use crate::rat_runs::find_rat_runs;
-use crate::{after_edit, App, Neighborhood};
+use ltn_model::{find_rat_runs, ModalFilters, Neighborhood, Partitioning};
+use map_model::{Map, RoadID};
+use widgetry::Choice;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Heuristic {
@@ -21,6 +19,9 @@ pub enum Heuristic {
     /// Per cell, close all borders except for one. This doesn't affect connectivity, but prevents
     /// all rat-runs.
     OnlyOneBorder,
+    /// Search for the fewest filters that stop all rat-runs, without disconnecting any cells or
+    /// filtering a bus route.
+    FewestFilters,
 }
 
 impl Heuristic {
@@ -29,13 +30,16 @@ impl Heuristic {
             Choice::new("greedy", Heuristic::Greedy),
             Choice::new("brute-force", Heuristic::BruteForce),
             Choice::new("only one border", Heuristic::OnlyOneBorder),
+            Choice::new("fewest filters", Heuristic::FewestFilters),
         ]
     }
 
+    /// Adds filters to one neighborhood. The caller is responsible for redrawing filters after.
     pub fn apply(
         self,
-        ctx: &EventCtx,
-        app: &mut App,
+        map: &Map,
+        partitioning: &Partitioning,
+        modal_filters: &mut ModalFilters,
         neighborhood: &Neighborhood,
         timer: &mut Timer,
     ) {
@@ -54,21 +58,42 @@ impl Heuristic {
 
         // TODO If we already have no rat-runs, stop
 
-        app.session.modal_filters.before_edit();
+        modal_filters.before_edit();
 
         match self {
-            Heuristic::Greedy => greedy(ctx, app, neighborhood, timer),
-            Heuristic::BruteForce => brute_force(ctx, app, neighborhood, timer),
-            Heuristic::OnlyOneBorder => only_one_border(app, neighborhood),
+            Heuristic::Greedy => greedy(map, partitioning, modal_filters, neighborhood, timer),
+            Heuristic::BruteForce => {
+                brute_force(map, partitioning, modal_filters, neighborhood, timer)
+            }
+            Heuristic::OnlyOneBorder => only_one_border(map, modal_filters, neighborhood),
+            Heuristic::FewestFilters => {
+                if let Some(layout) = ltn_model::optimize_filters(
+                    map,
+                    partitioning,
+                    modal_filters,
+                    &[neighborhood.id],
+                    1,
+                    timer,
+                )
+                .pop()
+                {
+                    modal_filters.roads = layout.modal_filters.roads;
+                }
+            }
         }
 
-        app.session.modal_filters.cancel_empty_edit();
-        after_edit(ctx, app);
+        modal_filters.cancel_empty_edit();
     }
 }
 
-fn greedy(ctx: &EventCtx, app: &mut App, neighborhood: &Neighborhood, timer: &mut Timer) {
-    let rat_runs = find_rat_runs(app, &neighborhood, timer);
+fn greedy(
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &mut ModalFilters,
+    neighborhood: &Neighborhood,
+    timer: &mut Timer,
+) {
+    let rat_runs = find_rat_runs(map, neighborhood, modal_filters, timer);
     // TODO How should we break ties? Some rat-runs are worse than others; use that weight?
     // TODO Should this operation be per cell instead? We could hover on a road belonging to that
     // cell to select it
@@ -78,31 +103,37 @@ fn greedy(ctx: &EventCtx, app: &mut App, neighborhood: &Neighborhood, timer: &mu
         .iter()
         .max_by_key(|pair| pair.1)
     {
-        if try_to_filter_road(ctx, app, neighborhood, *r).is_none() {
+        if try_to_filter_road(map, partitioning, modal_filters, neighborhood, *r).is_none() {
             warn!("Filtering {} disconnects a cell, never mind", r);
             // TODO Try the next choice
         }
     }
 }
 
-fn brute_force(ctx: &EventCtx, app: &mut App, neighborhood: &Neighborhood, timer: &mut Timer) {
+fn brute_force(
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &mut ModalFilters,
+    neighborhood: &Neighborhood,
+    timer: &mut Timer,
+) {
     // Which road leads to the fewest rat-runs?
     let mut best: Option<(RoadID, usize)> = None;
 
-    let orig_filters = app.session.modal_filters.roads.len();
+    let orig_filters = modal_filters.roads.len();
     timer.start_iter(
         "evaluate candidate filters",
         neighborhood.orig_perimeter.interior.len(),
     );
     for r in &neighborhood.orig_perimeter.interior {
         timer.next();
-        if app.session.modal_filters.roads.contains_key(r) {
+        if modal_filters.roads.contains_key(r) {
             continue;
         }
-        if let Some(new) = try_to_filter_road(ctx, app, neighborhood, *r) {
+        if let Some(new) = try_to_filter_road(map, partitioning, modal_filters, neighborhood, *r) {
             let num_rat_runs =
                 // This spams too many logs, and can't be used within a start_iter anyway
-                find_rat_runs(app, &new, &mut Timer::throwaway())
+                find_rat_runs(map, &new, modal_filters, &mut Timer::throwaway())
                     .paths
                     .len();
             // TODO Again, break ties. Just the number of paths is kind of a weak metric.
@@ -110,36 +141,30 @@ fn brute_force(ctx: &EventCtx, app: &mut App, neighborhood: &Neighborhood, timer
                 best = Some((*r, num_rat_runs));
             }
             // Always undo the new filter between each test
-            app.session.modal_filters.roads.remove(r).unwrap();
+            modal_filters.roads.remove(r).unwrap();
         }
 
-        assert_eq!(orig_filters, app.session.modal_filters.roads.len());
+        assert_eq!(orig_filters, modal_filters.roads.len());
     }
 
     if let Some((r, _)) = best {
-        try_to_filter_road(ctx, app, neighborhood, r).unwrap();
+        try_to_filter_road(map, partitioning, modal_filters, neighborhood, r).unwrap();
     }
 }
 
-fn only_one_border(app: &mut App, neighborhood: &Neighborhood) {
+fn only_one_border(map: &Map, modal_filters: &mut ModalFilters, neighborhood: &Neighborhood) {
     for cell in &neighborhood.cells {
         if cell.borders.len() > 1 {
             // TODO How to pick which one to leave open?
             for i in cell.borders.iter().skip(1) {
                 // Find the road in this cell connected to this border
                 for r in cell.roads.keys() {
-                    let road = app.map.get_r(*r);
+                    let road = map.get_r(*r);
                     if road.src_i == *i {
-                        app.session
-                            .modal_filters
-                            .roads
-                            .insert(road.id, 0.1 * road.length());
+                        modal_filters.roads.insert(road.id, 0.1 * road.length());
                         break;
                     } else if road.dst_i == *i {
-                        app.session
-                            .modal_filters
-                            .roads
-                            .insert(road.id, 0.9 * road.length());
+                        modal_filters.roads.insert(road.id, 0.9 * road.length());
                         break;
                     }
                 }
@@ -151,20 +176,17 @@ fn only_one_border(app: &mut App, neighborhood: &Neighborhood) {
 // If successful, returns a Neighborhood and leaves the new filter in place. If it disconncts a
 // cell, reverts the change and returns None
 fn try_to_filter_road(
-    ctx: &EventCtx,
-    app: &mut App,
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &mut ModalFilters,
     neighborhood: &Neighborhood,
     r: RoadID,
 ) -> Option<Neighborhood> {
-    let road = app.map.get_r(r);
-    app.session
-        .modal_filters
-        .roads
-        .insert(r, road.length() / 2.0);
-    // TODO This is expensive; can we just do the connectivity work and not drawing?
-    let new_neighborhood = Neighborhood::new(ctx, app, neighborhood.id);
+    let road = map.get_r(r);
+    modal_filters.roads.insert(r, road.length() / 2.0);
+    let new_neighborhood = Neighborhood::new(map, partitioning, modal_filters, neighborhood.id);
     if new_neighborhood.cells.iter().any(|c| c.is_disconnected()) {
-        app.session.modal_filters.roads.remove(&r).unwrap();
+        modal_filters.roads.remove(&r).unwrap();
         None
     } else {
         Some(new_neighborhood)
@@ -2,26 +2,30 @@ use std::collections::HashSet;
 
 use abstutil::{Counter, Timer};
 use geom::Distance;
+use ltn_model::{find_rat_runs, ModalFilters, NeighborhoodID, Partitioning, RenderCells};
 use map_gui::tools::{ColorNetwork, DrawRoadLabels, PopupMsg, URLManager};
 use synthpop::Scenario;
-use widgetry::mapspace::{ToggleZoomed, World, WorldOutcome};
+use widgetry::mapspace::{ObjectID, ToggleZoomed, World, WorldOutcome};
 use widgetry::{
     Choice, Color, DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
     State, Text, TextExt, Toggle, VerticalAlignment, Widget,
 };
 
 use super::auto::Heuristic;
-use super::{Neighborhood, NeighborhoodID, Partitioning};
-use crate::{App, ModalFilters, Transition};
+use crate::{App, Transition};
 
 pub struct BrowseNeighborhoods {
     panel: Panel,
-    world: World<NeighborhoodID>,
+    world: World<Obj>,
     draw_over_roads: ToggleZoomed,
     labels: DrawRoadLabels,
     draw_boundary_roads: ToggleZoomed,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Obj(NeighborhoodID);
+impl ObjectID for Obj {}
+
 impl BrowseNeighborhoods {
     pub fn new_state(ctx: &mut EventCtx, app: &mut App) -> Box<dyn State<App>> {
         URLManager::update_url_map_name(app);
@@ -32,8 +36,8 @@ impl BrowseNeighborhoods {
                     // Reset this first. transform_existing_filters will fill some out.
                     app.session.modal_filters = ModalFilters::default();
                     crate::filters::transform_existing_filters(ctx, app, timer);
-                    app.session.partitioning = Partitioning::seed_using_heuristics(app, timer);
-                    app.session.draw_all_filters = app.session.modal_filters.draw(ctx, &app.map);
+                    app.session.partitioning = Partitioning::seed_using_heuristics(&app.map, timer);
+                    crate::after_edit(ctx, app);
                 }
                 (
                     make_world(ctx, app, timer),
@@ -73,10 +77,25 @@ impl BrowseNeighborhoods {
                     ctx.style().btn_outline.text("Load proposal").build_def(ctx),
                     ctx.style().btn_outline.text("Save proposal").build_def(ctx),
                 ]),
-                ctx.style()
-                    .btn_outline
-                    .text("Export to GeoJSON")
-                    .build_def(ctx),
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_outline
+                        .text("Export to GeoJSON")
+                        .build_def(ctx),
+                    ctx.style()
+                        .btn_outline
+                        .text("Export proposal")
+                        .build_def(ctx),
+                    // TODO The file picker doesn't work on web yet
+                    if cfg!(target_arch = "wasm32") {
+                        Widget::nothing()
+                    } else {
+                        ctx.style()
+                            .btn_outline
+                            .text("Import proposal")
+                            .build_def(ctx)
+                    },
+                ]),
             ])
             .section(ctx),
             Widget::col(vec![
@@ -120,13 +139,19 @@ impl State<App> for BrowseNeighborhoods {
                     return Transition::Replace(BrowseNeighborhoods::new_state(ctx, app));
                 }
                 "Load proposal" => {
-                    return Transition::Push(crate::save::Proposal::load_picker_ui(ctx, app));
+                    return Transition::Push(crate::save::load_picker_ui(ctx, app));
                 }
                 "Save proposal" => {
-                    return Transition::Push(crate::save::Proposal::save_ui(ctx));
+                    return Transition::Push(crate::save::save_ui(ctx));
+                }
+                "Export proposal" => {
+                    return Transition::Push(crate::save::export_ui(ctx));
+                }
+                "Import proposal" => {
+                    return Transition::Push(crate::save::import_ui(ctx));
                 }
                 "Export to GeoJSON" => {
-                    let result = super::export::write_geojson_file(ctx, app);
+                    let result = super::export::write_geojson_file(app);
                     return Transition::Push(match result {
                         Ok(path) => PopupMsg::new_state(
                             ctx,
@@ -142,7 +167,7 @@ impl State<App> for BrowseNeighborhoods {
                     return Transition::Push(super::impact::ShowResults::new_state(ctx, app));
                 }
                 "Automatically stop rat-runs" => {
-                    ctx.loading_screen("automatically filter all neighborhoods", |ctx, timer| {
+                    ctx.loading_screen("automatically filter all neighborhoods", |_, timer| {
                         for id in app
                             .session
                             .partitioning
@@ -151,10 +176,17 @@ impl State<App> for BrowseNeighborhoods {
                             .cloned()
                             .collect::<Vec<_>>()
                         {
-                            let neighborhood = Neighborhood::new(ctx, app, id);
-                            app.session.heuristic.apply(ctx, app, &neighborhood, timer);
+                            let neighborhood = crate::neighborhood::from_app(app, id);
+                            app.session.heuristic.apply(
+                                &app.map,
+                                &app.session.partitioning,
+                                &mut app.session.modal_filters,
+                                &neighborhood,
+                                timer,
+                            );
                         }
                     });
+                    crate::after_edit(ctx, app);
                     return Transition::Replace(BrowseNeighborhoods::new_state(ctx, app));
                 }
                 x => {
@@ -174,7 +206,7 @@ impl State<App> for BrowseNeighborhoods {
             _ => {}
         }
 
-        if let WorldOutcome::ClickedObject(id) = self.world.event(ctx) {
+        if let WorldOutcome::ClickedObject(Obj(id)) = self.world.event(ctx) {
             return Transition::Push(super::connectivity::Viewer::new_state(ctx, app, id));
         }
 
@@ -200,14 +232,15 @@ impl State<App> for BrowseNeighborhoods {
     }
 }
 
-fn make_world(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> World<NeighborhoodID> {
+fn make_world(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> World<Obj> {
     let mut world = World::bounded(app.map.get_bounds());
     let map = &app.map;
     for (id, (block, color)) in app.session.partitioning.all_neighborhoods() {
+        let color = crate::neighborhood_color(*color);
         match app.session.draw_neighborhood_style {
             Style::SimpleColoring => {
                 world
-                    .add(*id)
+                    .add(Obj(*id))
                     .hitbox(block.polygon.clone())
                     .draw_color(color.alpha(0.3))
                     .hover_outline(Color::BLACK, Distance::meters(5.0))
@@ -217,11 +250,11 @@ fn make_world(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> World<Neighbo
             Style::Cells => {
                 // TODO The cell colors are confusing alongside the other neighborhood colors. I
                 // tried greying out everything else, but then the view is too jumpy.
-                let neighborhood = Neighborhood::new(ctx, app, *id);
-                let render_cells = super::draw_cells::RenderCells::new(map, &neighborhood);
-                let hovered_batch = render_cells.draw();
+                let neighborhood = crate::neighborhood::from_app(app, *id);
+                let render_cells = RenderCells::new(map, &neighborhood);
+                let hovered_batch = crate::draw_cells::draw(&render_cells);
                 world
-                    .add(*id)
+                    .add(Obj(*id))
                     .hitbox(block.polygon.clone())
                     .draw_color(color.alpha(0.5))
                     .draw_hovered(hovered_batch)
@@ -229,8 +262,8 @@ fn make_world(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> World<Neighbo
                     .build(ctx);
             }
             Style::Quietness => {
-                let neighborhood = Neighborhood::new(ctx, app, *id);
-                let rat_runs = super::rat_runs::find_rat_runs(app, &neighborhood, timer);
+                let neighborhood = crate::neighborhood::from_app(app, *id);
+                let rat_runs = find_rat_runs(map, &neighborhood, &app.session.modal_filters, timer);
                 let (quiet_streets, total_streets) =
                     rat_runs.quiet_and_total_streets(&neighborhood);
                 let pct = if total_streets == 0 {
@@ -240,7 +273,7 @@ fn make_world(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> World<Neighbo
                 };
                 let color = app.cs.good_to_bad_red.eval(pct);
                 world
-                    .add(*id)
+                    .add(Obj(*id))
                     .hitbox(block.polygon.clone())
                     .draw_color(color.alpha(0.5))
                     .hover_outline(Color::BLACK, Distance::meters(5.0))
@@ -249,7 +282,7 @@ fn make_world(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> World<Neighbo
             }
             Style::RatRuns => {
                 world
-                    .add(*id)
+                    .add(Obj(*id))
                     .hitbox(block.polygon.clone())
                     // Slight lie, because draw_over_roads has to be drawn after the World
                     .drawn_in_master_batch()
@@ -271,8 +304,8 @@ fn draw_over_roads(ctx: &mut EventCtx, app: &App, timer: &mut Timer) -> ToggleZo
     let mut count_per_intersection = Counter::new();
 
     for id in app.session.partitioning.all_neighborhoods().keys() {
-        let neighborhood = Neighborhood::new(ctx, app, *id);
-        let rat_runs = super::rat_runs::find_rat_runs(app, &neighborhood, timer);
+        let neighborhood = crate::neighborhood::from_app(app, *id);
+        let rat_runs = find_rat_runs(&app.map, &neighborhood, &app.session.modal_filters, timer);
         count_per_road.extend(rat_runs.count_per_road);
         count_per_intersection.extend(rat_runs.count_per_intersection);
     }
@@ -1,4 +1,5 @@
 use geom::{Angle, ArrowCap, Distance, PolyLine};
+use ltn_model::{Neighborhood, NeighborhoodID, RenderCells};
 use widgetry::mapspace::{ToggleZoomed, World};
 use widgetry::{
     Color, DrawBaselayer, EventCtx, GeomBatch, GfxCtx, Key, Outcome, Panel, State, TextExt, Toggle,
@@ -7,23 +8,26 @@ use widgetry::{
 
 use super::auto::Heuristic;
 use super::per_neighborhood::{FilterableObj, Tab};
-use super::{Neighborhood, NeighborhoodID};
+use super::DrawNeighborhood;
 use crate::{App, Transition};
 
 pub struct Viewer {
     panel: Panel,
     neighborhood: Neighborhood,
+    draw_neighborhood: DrawNeighborhood,
     world: World<FilterableObj>,
     draw_top_layer: ToggleZoomed,
 }
 
 impl Viewer {
     pub fn new_state(ctx: &mut EventCtx, app: &App, id: NeighborhoodID) -> Box<dyn State<App>> {
-        let neighborhood = Neighborhood::new(ctx, app, id);
+        let neighborhood = crate::neighborhood::from_app(app, id);
+        let draw_neighborhood = DrawNeighborhood::new(ctx, app, &neighborhood);
 
         let mut viewer = Viewer {
             panel: Panel::empty(ctx),
             neighborhood,
+            draw_neighborhood,
             world: World::unbounded(),
             draw_top_layer: ToggleZoomed::empty(ctx),
         };
@@ -100,12 +104,17 @@ impl State<App> for Viewer {
         match self.panel.event(ctx) {
             Outcome::Clicked(x) => {
                 if x == "Automatically stop rat-runs" {
-                    ctx.loading_screen("automatically filter a neighborhood", |ctx, timer| {
-                        app.session
-                            .heuristic
-                            .apply(ctx, app, &self.neighborhood, timer);
+                    ctx.loading_screen("automatically filter a neighborhood", |_, timer| {
+                        app.session.heuristic.apply(
+                            &app.map,
+                            &app.session.partitioning,
+                            &mut app.session.modal_filters,
+                            &self.neighborhood,
+                            timer,
+                        );
                     });
-                    self.neighborhood = Neighborhood::new(ctx, app, self.neighborhood.id);
+                    crate::after_edit(ctx, app);
+                    self.neighborhood = crate::neighborhood::from_app(app, self.neighborhood.id);
                     self.update(ctx, app);
                     return Transition::Keep;
                 }
@@ -130,7 +139,7 @@ impl State<App> for Viewer {
 
         let world_outcome = self.world.event(ctx);
         if super::per_neighborhood::handle_world_outcome(ctx, app, world_outcome) {
-            self.neighborhood = Neighborhood::new(ctx, app, self.neighborhood.id);
+            self.neighborhood = crate::neighborhood::from_app(app, self.neighborhood.id);
             self.update(ctx, app);
         }
 
@@ -143,7 +152,7 @@ impl State<App> for Viewer {
 
     fn draw(&self, g: &mut GfxCtx, app: &App) {
         crate::draw_with_layering(g, app, |g| self.world.draw(g));
-        g.redraw(&self.neighborhood.fade_irrelevant);
+        g.redraw(&self.draw_neighborhood.fade_irrelevant);
         self.draw_top_layer.draw(g);
 
         self.panel.draw(g);
@@ -152,7 +161,7 @@ impl State<App> for Viewer {
         // same might be nice. And we should seed the quadtree with the locations of filters and
         // arrows, possibly.
         if g.canvas.is_unzoomed() {
-            self.neighborhood.labels.draw(g, app);
+            self.draw_neighborhood.labels.draw(g, app);
         }
     }
 }
@@ -171,12 +180,12 @@ fn make_world(
     // roads
     let mut draw_top_layer = GeomBatch::new();
 
-    let render_cells = super::draw_cells::RenderCells::new(map, neighborhood);
+    let render_cells = RenderCells::new(map, neighborhood);
     if app.session.draw_cells_as_areas {
-        world.draw_master_batch(ctx, render_cells.draw());
+        world.draw_master_batch(ctx, crate::draw_cells::draw(&render_cells));
     } else {
         for (idx, cell) in neighborhood.cells.iter().enumerate() {
-            let color = render_cells.colors[idx].alpha(0.9);
+            let color = crate::draw_cells::cell_color(render_cells.colors[idx]).alpha(0.9);
             for (r, interval) in &cell.roads {
                 let road = map.get_r(*r);
                 draw_top_layer.push(
@@ -196,7 +205,7 @@ fn make_world(
 
     // Draw the borders of each cell
     for (idx, cell) in neighborhood.cells.iter().enumerate() {
-        let color = render_cells.colors[idx];
+        let color = crate::draw_cells::cell_color(render_cells.colors[idx]);
         for i in &cell.borders {
             let angles: Vec<Angle> = cell
                 .roads
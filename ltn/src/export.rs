@@ -1,16 +1,26 @@
 use anyhow::Result;
+use geojson::Feature;
 
-use geom::{PolyLine, Pt2D};
-use widgetry::EventCtx;
+use geom::PolyLine;
+use ltn_model::{ModalFilters, Neighborhood, Partitioning, RenderCells};
+use map_model::Map;
 
-use super::Neighborhood;
 use crate::App;
 
 /// Returns the path where the file was written
-pub fn write_geojson_file(ctx: &EventCtx, app: &App) -> Result<String> {
-    let contents = geojson_string(ctx, app)?;
+pub fn write_geojson_file(app: &App) -> Result<String> {
+    let contents = geojson_string(
+        &app.map,
+        &app.session.partitioning,
+        &app.session.modal_filters,
+    )?;
     let path = format!("ltn_{}.geojson", app.map.get_name().map);
+    write_file(path, contents)
+}
 
+/// On native, writes a file to the current directory. On web, downloads it. Returns the path where
+/// the file was written.
+pub fn write_file(path: String, contents: String) -> Result<String> {
     // TODO Refactor into map_gui or abstio and handle errors better
     #[cfg(target_arch = "wasm32")]
     {
@@ -45,15 +55,17 @@ pub fn write_geojson_file(ctx: &EventCtx, app: &App) -> Result<String> {
     Ok(path)
 }
 
-fn geojson_string(ctx: &EventCtx, app: &App) -> Result<String> {
-    use geo::algorithm::map_coords::MapCoordsInplace;
-    use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+fn geojson_string(
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &ModalFilters,
+) -> Result<String> {
+    use geojson::{FeatureCollection, GeoJson, Geometry, Value};
 
-    let map = &app.map;
     let mut features = Vec::new();
 
     // All neighborhood boundaries
-    for (id, (block, color)) in app.session.partitioning.all_neighborhoods() {
+    for (id, (block, color)) in partitioning.all_neighborhoods() {
         let mut feature = Feature {
             bbox: None,
             geometry: Some(block.polygon.to_geojson(None)),
@@ -62,14 +74,16 @@ fn geojson_string(ctx: &EventCtx, app: &App) -> Result<String> {
             foreign_members: None,
         };
         feature.set_property("type", "neighborhood");
-        feature.set_property("fill", color.as_hex());
+        feature.set_property("fill", crate::neighborhood_color(*color).as_hex());
         // Cells should cover these up
         feature.set_property("fill-opacity", 0.0);
         features.push(feature);
 
         // Cells per neighborhood
-        let render_cells =
-            super::draw_cells::RenderCells::new(map, &Neighborhood::new(ctx, app, *id));
+        let render_cells = RenderCells::new(
+            map,
+            &Neighborhood::new(map, partitioning, modal_filters, *id),
+        );
         for (idx, multipolygon) in render_cells.to_multipolygons().into_iter().enumerate() {
             let mut feature = Feature {
                 bbox: None,
@@ -83,13 +97,16 @@ fn geojson_string(ctx: &EventCtx, app: &App) -> Result<String> {
                 foreign_members: None,
             };
             feature.set_property("type", "cell");
-            feature.set_property("fill", render_cells.colors[idx].as_hex());
+            feature.set_property(
+                "fill",
+                crate::draw_cells::cell_color(render_cells.colors[idx]).as_hex(),
+            );
             features.push(feature);
         }
     }
 
     // All modal filters
-    for (r, dist) in &app.session.modal_filters.roads {
+    for (r, dist) in &modal_filters.roads {
         let road = map.get_r(*r);
         if let Ok((pt, angle)) = road.center_pts.dist_along(*dist) {
             let road_width = road.get_width();
@@ -105,11 +122,21 @@ fn geojson_string(ctx: &EventCtx, app: &App) -> Result<String> {
                 foreign_members: None,
             };
             feature.set_property("type", "road filter");
+            // So the filter can be matched again after the map changes
+            feature.set_property("osm_way_id", road.orig_id.osm_way_id.0);
+            feature.set_property(
+                "filter type",
+                modal_filters
+                    .road_filter_types
+                    .get(r)
+                    .map(|filter_type| filter_type.describe())
+                    .unwrap_or("physical barrier"),
+            );
             feature.set_property("stroke", "red");
             features.push(feature);
         }
     }
-    for (_, filter) in &app.session.modal_filters.intersections {
+    for (_, filter) in &modal_filters.intersections {
         let pl = filter.geometry(map).to_polyline();
         let mut feature = Feature {
             bbox: None,
@@ -123,25 +150,7 @@ fn geojson_string(ctx: &EventCtx, app: &App) -> Result<String> {
         features.push(feature);
     }
 
-    // Transform to WGS84
-    let gps_bounds = map.get_gps_bounds();
-    for feature in &mut features {
-        // geojson to geo
-        // This could be a Polygon, MultiPolygon, LineString
-        let mut geom: geo::Geometry<f64> = feature.geometry.take().unwrap().value.try_into()?;
-
-        geom.map_coords_inplace(|c| {
-            let gps = Pt2D::new(c.0, c.1).to_gps(gps_bounds);
-            (gps.x(), gps.y())
-        });
-
-        // geo to geojson
-        feature.geometry = Some(Geometry {
-            bbox: None,
-            value: Value::from(&geom),
-            foreign_members: None,
-        });
-    }
+    ltn_model::to_wgs84(map, &mut features)?;
 
     let gj = GeoJson::FeatureCollection(FeatureCollection {
         features,
@@ -1,3 +1,4 @@
+mod simulation_ui;
 mod ui;
 
 use std::collections::BTreeSet;
@@ -5,11 +6,16 @@ use std::collections::BTreeSet;
 use abstio::MapName;
 use abstutil::Timer;
 use geom::{Duration, Time};
+use ltn_model::impact::{
+    counts_after_filters, counts_before_filters, filter_trips, trips_from_scenario,
+};
+use ltn_model::SimulationImpact;
 use map_gui::tools::compare_counts::CompareCounts;
-use map_model::{PathConstraints, PathRequest, PathfinderCaching};
-use synthpop::{Scenario, TrafficCounts, TripEndpoint, TripMode};
+use map_model::PathRequest;
+use synthpop::{Scenario, TripMode};
 use widgetry::EventCtx;
 
+pub use self::simulation_ui::ShowSimulation;
 pub use self::ui::ShowResults;
 use crate::App;
 
@@ -22,6 +28,7 @@ use crate::App;
 // - all_trips and everything else depends just on the map (we only have one scenario per map now)
 // - filtered_trips depend on filters
 // - the 'b' and 'relative' parts of compare_counts depend on change_key (for when the map is edited)
+// - simulation is only calculated on demand, and also depends on change_key
 pub struct Impact {
     pub map: MapName,
     pub filters: Filters,
@@ -32,6 +39,9 @@ pub struct Impact {
 
     pub compare_counts: CompareCounts,
     pub change_key: usize,
+
+    /// The full traffic simulation before and after filters, and the change_key it was run with
+    pub simulation: Option<(usize, SimulationImpact)>,
 }
 
 #[derive(PartialEq)]
@@ -58,6 +68,8 @@ impl Impact {
 
             compare_counts: CompareCounts::empty(ctx),
             change_key: 0,
+
+            simulation: None,
         }
     }
 
@@ -72,13 +84,7 @@ impl Impact {
 
         impact.map = app.map.get_name().clone();
         impact.change_key = app.session.modal_filters.change_key;
-        impact.all_trips = timer
-            .parallelize("analyze trips", scenario.all_trips().collect(), |trip| {
-                TripEndpoint::path_req(trip.origin, trip.destination, trip.mode, map)
-            })
-            .into_iter()
-            .flatten()
-            .collect();
+        impact.all_trips = trips_from_scenario(map, &scenario, timer);
         impact.trips_changed(ctx, app, timer);
         impact.compare_counts.autoselect_layer();
         impact
@@ -86,70 +92,22 @@ impl Impact {
 
     fn trips_changed(&mut self, ctx: &mut EventCtx, app: &App, timer: &mut Timer) {
         let map = &app.map;
-        let constraints: BTreeSet<PathConstraints> = self
-            .filters
-            .modes
-            .iter()
-            .map(|m| m.to_constraints())
-            .collect();
-        self.filtered_trips = PathRequest::deduplicate(
-            map,
-            self.all_trips
-                .iter()
-                .filter(|req| constraints.contains(&req.constraints))
-                .cloned()
-                .collect(),
-        );
-
-        let counts_a = TrafficCounts::from_path_requests(
-            map,
-            // Don't bother describing all the trip filtering
-            "before filters".to_string(),
-            &self.filtered_trips,
-            map.routing_params().clone(),
-            PathfinderCaching::NoCache,
-            timer,
-        );
-
-        let counts_b = {
-            let mut params = map.routing_params().clone();
-            app.session.modal_filters.update_routing_params(&mut params);
-            // Since we're making so many requests, it's worth it to rebuild a contraction
-            // hierarchy. And since we're single-threaded, no complications there.
-            TrafficCounts::from_path_requests(
-                map,
-                // Don't bother describing all the trip filtering
-                "after filters".to_string(),
-                &self.filtered_trips,
-                params,
-                PathfinderCaching::CacheCH,
-                timer,
-            )
-        };
-
+        self.filtered_trips = filter_trips(map, &self.all_trips, &self.filters.modes);
+        let counts_a = counts_before_filters(map, &self.filtered_trips, timer);
+        let counts_b =
+            counts_after_filters(map, &app.session.modal_filters, &self.filtered_trips, timer);
         self.compare_counts =
             CompareCounts::new(ctx, app, counts_a, counts_b, self.compare_counts.layer);
     }
 
     fn map_edits_changed(&mut self, ctx: &mut EventCtx, app: &App, timer: &mut Timer) {
         self.change_key = app.session.modal_filters.change_key;
-        let map = &app.map;
-
-        let counts_b = {
-            let mut params = map.routing_params().clone();
-            app.session.modal_filters.update_routing_params(&mut params);
-            // Since we're making so many requests, it's worth it to rebuild a contraction
-            // hierarchy. And since we're single-threaded, no complications there.
-            TrafficCounts::from_path_requests(
-                map,
-                // Don't bother describing all the trip filtering
-                "after filters".to_string(),
-                &self.filtered_trips,
-                params,
-                PathfinderCaching::CacheCH,
-                timer,
-            )
-        };
+        let counts_b = counts_after_filters(
+            &app.map,
+            &app.session.modal_filters,
+            &self.filtered_trips,
+            timer,
+        );
         self.compare_counts.recalculate_b(ctx, app, counts_b);
     }
 }
@@ -0,0 +1,233 @@
+use abstutil::prettyprint_usize;
+use geom::{Duration, Histogram, Statistic};
+use ltn_model::SimulationImpact;
+use map_gui::load::FileLoader;
+use map_gui::tools::compare_counts::{CompareCounts, Layer};
+use synthpop::Scenario;
+use widgetry::{
+    CompareTimes, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Panel,
+    SimpleState, State, Text, TextExt, VerticalAlignment, Widget,
+};
+
+use super::ShowResults;
+use crate::{App, Transition};
+
+pub struct ShowSimulation {
+    compare_counts: CompareCounts,
+    draw_all_neighborhoods: Drawable,
+}
+
+impl ShowSimulation {
+    pub fn new_state(ctx: &mut EventCtx, app: &mut App) -> Box<dyn State<App>> {
+        let change_key = app.session.modal_filters.change_key;
+        if app.session.impact.simulation.as_ref().map(|(key, _)| *key) != Some(change_key) {
+            let map_name = app.map.get_name().clone();
+            let scenario_name = Scenario::default_scenario_for_map(&map_name);
+            return FileLoader::<App, Scenario>::new_state(
+                ctx,
+                abstio::path_scenario(&map_name, &scenario_name),
+                Box::new(move |ctx, app, timer, maybe_scenario| {
+                    // TODO Handle corrupt files
+                    let scenario = maybe_scenario.unwrap();
+                    let impact = SimulationImpact::new(
+                        &app.map,
+                        &app.session.partitioning,
+                        &app.session.modal_filters,
+                        &scenario,
+                        timer,
+                    );
+                    app.session.impact.simulation = Some((change_key, impact));
+                    Transition::Replace(ShowSimulation::new_state(ctx, app))
+                }),
+            );
+        }
+
+        let impact = &app.session.impact.simulation.as_ref().unwrap().1;
+        let mut compare_counts = CompareCounts::new(
+            ctx,
+            app,
+            impact.counts_before.clone(),
+            impact.counts_after.clone(),
+            Layer::A,
+        );
+        compare_counts.autoselect_layer();
+
+        let mut col = vec![
+            crate::app_header(ctx, app),
+            "Simulated impact".text_widget(ctx),
+            ctx.style()
+                .btn_back("Impact prediction")
+                .hotkey(Key::Escape)
+                .build_def(ctx),
+            Text::from(Line(
+                "This tool runs the full traffic simulation for a day, before and after your \
+                 filters. Vehicles react to congestion, but nobody changes how they travel. \
+                 Time-limited filters are in effect all day.",
+            ))
+            .wrap_to_pct(ctx, 20)
+            .into_widget(ctx),
+        ];
+        if !impact.problems.is_empty() {
+            let mut txt = Text::from(Line("Problems").small_heading());
+            for problem in &impact.problems {
+                txt.add_line(Line(problem));
+            }
+            col.push(txt.wrap_to_pct(ctx, 20).into_widget(ctx).section(ctx));
+        }
+        col.push(displaced_traffic(ctx, app, impact));
+        col.push(trip_times(ctx, app, impact));
+        col.push(compare_counts.get_panel_widget(ctx).named("compare counts"));
+        let panel = Panel::new_builder(Widget::col(col))
+            .aligned(HorizontalAlignment::Left, VerticalAlignment::Top)
+            .build(ctx);
+
+        let mut batch = GeomBatch::new();
+        for (_, (block, color)) in app.session.partitioning.all_neighborhoods() {
+            batch.push(
+                crate::neighborhood_color(*color).alpha(0.2),
+                block.polygon.clone(),
+            );
+        }
+        let draw_all_neighborhoods = batch.upload(ctx);
+        <dyn SimpleState<_>>::new_state(
+            panel,
+            Box::new(ShowSimulation {
+                compare_counts,
+                draw_all_neighborhoods,
+            }),
+        )
+    }
+}
+
+impl SimpleState<App> for ShowSimulation {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        panel: &mut Panel,
+    ) -> Transition {
+        match x {
+            "Impact prediction" => Transition::Replace(ShowResults::new_state(ctx, app)),
+            x => {
+                if let Some(t) = crate::handle_app_header_click(ctx, app, x) {
+                    return t;
+                }
+
+                let widget = self
+                    .compare_counts
+                    .on_click(ctx, app, x)
+                    .expect("button click didn't belong to CompareCounts");
+                panel.replace(ctx, "compare counts", widget);
+                Transition::Keep
+            }
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, _: &mut App) -> Transition {
+        self.compare_counts.other_event(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.draw_all_neighborhoods);
+        self.compare_counts.draw(g);
+        app.session.draw_all_filters.draw(g);
+    }
+}
+
+fn displaced_traffic(ctx: &mut EventCtx, app: &App, impact: &SimulationImpact) -> Widget {
+    let mut txt = Text::from(Line("Displaced traffic").small_heading());
+    let (interior_before, interior_after) = impact.interior_vehicles;
+    txt.add_line(Line(format!(
+        "Vehicles crossing neighborhood interiors: {} before, {} after",
+        prettyprint_usize(interior_before),
+        prettyprint_usize(interior_after)
+    )));
+    let boundary_before: usize = impact
+        .boundary_roads
+        .iter()
+        .map(|x| x.vehicles_before)
+        .sum();
+    let boundary_after: usize = impact.boundary_roads.iter().map(|x| x.vehicles_after).sum();
+    txt.add_line(Line(format!(
+        "Vehicles crossing boundary roads: {} before, {} after",
+        prettyprint_usize(boundary_before),
+        prettyprint_usize(boundary_after)
+    )));
+
+    // Show the boundary roads where driving got the slowest
+    let mut delays: Vec<_> = impact
+        .boundary_roads
+        .iter()
+        .filter_map(|x| x.delay().map(|delay| (delay, x)))
+        .filter(|(delay, _)| *delay > Duration::ZERO)
+        .collect();
+    delays.sort_by_key(|(delay, _)| *delay);
+    delays.reverse();
+    if !delays.is_empty() {
+        txt.add_line(Line(""));
+        txt.add_line(Line("Most delayed boundary roads"));
+    }
+    for (delay, road) in delays.into_iter().take(10) {
+        txt.add_line(Line(format!(
+            "{}: {} longer to cross, {} → {} vehicles",
+            app.map
+                .get_r(road.road)
+                .get_name(app.opts.language.as_ref()),
+            delay.to_string(&app.opts.units),
+            prettyprint_usize(road.vehicles_before),
+            prettyprint_usize(road.vehicles_after)
+        )));
+    }
+    txt.wrap_to_pct(ctx, 20).into_widget(ctx).section(ctx)
+}
+
+fn trip_times(ctx: &mut EventCtx, app: &App, impact: &SimulationImpact) -> Widget {
+    let mut txt = Text::from(Line("Trip times").small_heading());
+    for (mode, (faster, slower)) in impact.faster_and_slower() {
+        txt.add_line(Line(format!(
+            "{}: {} trips faster, {} slower",
+            mode.noun(),
+            prettyprint_usize(faster),
+            prettyprint_usize(slower)
+        )));
+    }
+    txt.add_line(Line(format!(
+        "Cancelled trips: {} before, {} after",
+        prettyprint_usize(impact.cancelled_before),
+        prettyprint_usize(impact.cancelled_after)
+    )));
+
+    let mut before = Histogram::new();
+    let mut after = Histogram::new();
+    for (_, t1, t2, _) in &impact.trip_times {
+        before.add(*t1);
+        after.add(*t2);
+    }
+    for stat in [Statistic::P50, Statistic::P90] {
+        if let (Some(t1), Some(t2)) = (before.select(stat), after.select(stat)) {
+            txt.add_line(Line(format!(
+                "{} trip time: {} before, {} after",
+                stat,
+                t1.to_string(&app.opts.units),
+                t2.to_string(&app.opts.units)
+            )));
+        }
+    }
+
+    Widget::col(vec![
+        txt.wrap_to_pct(ctx, 20).into_widget(ctx),
+        CompareTimes::new_widget(
+            ctx,
+            "Trip time before filters",
+            "Trip time after filters",
+            impact
+                .trip_times
+                .iter()
+                .map(|(_, t1, t2, _)| (*t1, *t2))
+                .collect(),
+        ),
+    ])
+    .section(ctx)
+}
@@ -8,7 +8,7 @@ use widgetry::{
     Slider, State, Text, TextExt, Toggle, VerticalAlignment, Widget,
 };
 
-use super::{end_of_day, Filters, Impact};
+use super::{end_of_day, Filters, Impact, ShowSimulation};
 use crate::{App, BrowseNeighborhoods, Transition};
 
 // TODO Share structure or pieces with Ungap's predict mode
@@ -53,13 +53,23 @@ impl ShowResults {
             app.session.impact.filters.to_panel(ctx, app),
             app.session.impact.compare_counts.get_panel_widget(ctx).named("compare counts"),
             ctx.style().btn_outline.text("Save before/after counts to files").build_def(ctx),
+            ctx.style()
+                .btn_outline
+                .text("Run the full traffic simulation")
+                // Map edits can't express diagonal filters, so the simulation would ignore them
+                .disabled(!app.session.modal_filters.intersections.is_empty())
+                .disabled_tooltip("The simulation can't model diagonal filters yet. Remove them to compare.")
+                .build_def(ctx),
         ]))
         .aligned(HorizontalAlignment::Left, VerticalAlignment::Top)
         .build(ctx);
 
         let mut batch = GeomBatch::new();
         for (_, (block, color)) in app.session.partitioning.all_neighborhoods() {
-            batch.push(color.alpha(0.2), block.polygon.clone());
+            batch.push(
+                crate::neighborhood_color(*color).alpha(0.2),
+                block.polygon.clone(),
+            );
         }
         let draw_all_neighborhoods = batch.upload(ctx);
         <dyn SimpleState<_>>::new_state(
@@ -85,6 +95,9 @@ impl SimpleState<App> for ShowResults {
                 // loading
                 Transition::Replace(BrowseNeighborhoods::new_state(ctx, app))
             }
+            "Run the full traffic simulation" => {
+                Transition::Replace(ShowSimulation::new_state(ctx, app))
+            }
             "Save before/after counts to files" => {
                 let path1 = "counts_a.json";
                 let path2 = "counts_b.json";
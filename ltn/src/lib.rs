@@ -2,16 +2,13 @@
 
 use structopt::StructOpt;
 
-use widgetry::{lctrl, EventCtx, GfxCtx, Key, Line, Settings, Widget};
+use ltn_model::{ModalFilters, Partitioning, NUM_COLORS};
+use widgetry::{lctrl, Color, EventCtx, GfxCtx, Key, Line, Settings, Widget};
 
 pub use browse::BrowseNeighborhoods;
 use filters::Toggle3Zoomed;
-pub use filters::{DiagonalFilter, ModalFilters};
-pub use neighborhood::{Cell, DistanceInterval, Neighborhood};
-pub use partition::{NeighborhoodID, Partitioning};
+use neighborhood::DrawNeighborhood;
 
-#[macro_use]
-extern crate anyhow;
 #[macro_use]
 extern crate log;
 
@@ -23,17 +20,24 @@ mod export;
 mod filters;
 mod impact;
 mod neighborhood;
-mod partition;
 mod pathfinding;
 mod per_neighborhood;
 mod rat_run_viewer;
-mod rat_runs;
 mod save;
 mod select_boundary;
 
 type App = map_gui::SimpleApp<Session>;
 type Transition = widgetry::Transition<App>;
 
+const NEIGHBORHOOD_COLORS: [Color; NUM_COLORS] = [
+    Color::BLUE,
+    Color::YELLOW,
+    Color::GREEN,
+    Color::PURPLE,
+    Color::PINK,
+    Color::ORANGE,
+];
+
 pub fn main() {
     let settings = Settings::new("Low traffic neighborhoods");
     run(settings);
@@ -85,7 +89,7 @@ fn run(mut settings: Settings) {
                 let popup_state = args
                     .proposal
                     .as_ref()
-                    .and_then(|name| crate::save::Proposal::load(ctx, app, name));
+                    .and_then(|name| crate::save::load(ctx, app, name));
 
                 let mut states = vec![
                     map_gui::tools::TitleScreen::new_state(
@@ -218,5 +222,10 @@ fn handle_app_header_click(ctx: &mut EventCtx, app: &App, x: &str) -> Option<Tra
 }
 
 pub fn after_edit(ctx: &EventCtx, app: &mut App) {
-    app.session.draw_all_filters = app.session.modal_filters.draw(ctx, &app.map);
+    app.session.draw_all_filters = filters::draw_filters(ctx, &app.map, &app.session.modal_filters);
+}
+
+/// Neighborhoods are colored by an index into a fixed palette; see `Partitioning`.
+fn neighborhood_color(color_idx: usize) -> Color {
+    NEIGHBORHOOD_COLORS[color_idx % NUM_COLORS]
 }
@@ -1,8 +1,9 @@
 use geom::{Distance, Duration};
+use ltn_model::{Neighborhood, NeighborhoodID};
 use map_gui::tools::{
     cmp_dist, cmp_duration, InputWaypoints, TripManagement, TripManagementState, WaypointID,
 };
-use map_model::{PathfinderCaching, NORMAL_LANE_THICKNESS};
+use map_model::{PathConstraints, PathfinderCaching, NORMAL_LANE_THICKNESS};
 use synthpop::{TripEndpoint, TripMode};
 use widgetry::mapspace::{ObjectID, ToggleZoomed, World};
 use widgetry::{
@@ -10,7 +11,7 @@ use widgetry::{
 };
 
 use super::per_neighborhood::{FilterableObj, Tab};
-use super::{Neighborhood, NeighborhoodID};
+use super::DrawNeighborhood;
 use crate::{App, Transition};
 
 pub struct RoutePlanner {
@@ -21,6 +22,7 @@ pub struct RoutePlanner {
     draw_routes: ToggleZoomed,
 
     neighborhood: Neighborhood,
+    draw_neighborhood: DrawNeighborhood,
 }
 
 impl TripManagementState<App> for RoutePlanner {
@@ -48,7 +50,8 @@ impl ObjectID for Obj {}
 
 impl RoutePlanner {
     pub fn new_state(ctx: &mut EventCtx, app: &mut App, id: NeighborhoodID) -> Box<dyn State<App>> {
-        let neighborhood = Neighborhood::new(ctx, app, id);
+        let neighborhood = crate::neighborhood::from_app(app, id);
+        let draw_neighborhood = DrawNeighborhood::new(ctx, app, &neighborhood);
 
         let mut rp = RoutePlanner {
             panel: Panel::empty(ctx),
@@ -57,6 +60,7 @@ impl RoutePlanner {
             world: World::unbounded(),
             draw_routes: ToggleZoomed::empty(ctx),
             neighborhood,
+            draw_neighborhood,
         };
 
         if let Some(current_name) = &app.session.current_trip_name {
@@ -128,7 +132,9 @@ impl RoutePlanner {
         // First the route respecting the filters
         let (total_time_after, total_dist_after) = {
             let mut params = map.routing_params().clone();
-            app.session.modal_filters.update_routing_params(&mut params);
+            app.session
+                .modal_filters
+                .update_routing_params(&mut params, PathConstraints::Car);
             params.main_road_penalty = app.session.main_road_penalty;
 
             let mut total_time = Duration::ZERO;
@@ -243,7 +249,7 @@ impl State<App> for RoutePlanner {
             _ => None,
         }) {
             if super::per_neighborhood::handle_world_outcome(ctx, app, outcome) {
-                self.neighborhood = Neighborhood::new(ctx, app, self.neighborhood.id);
+                self.neighborhood = crate::neighborhood::from_app(app, self.neighborhood.id);
                 self.update_everything(ctx, app);
                 return Transition::Keep;
             }
@@ -293,11 +299,11 @@ impl State<App> for RoutePlanner {
     fn draw(&self, g: &mut GfxCtx, app: &App) {
         self.panel.draw(g);
 
-        g.redraw(&self.neighborhood.fade_irrelevant);
+        g.redraw(&self.draw_neighborhood.fade_irrelevant);
         self.draw_routes.draw(g);
         app.session.draw_all_filters.draw(g);
         if g.canvas.is_unzoomed() {
-            self.neighborhood.labels.draw(g, app);
+            self.draw_neighborhood.labels.draw(g, app);
         }
 
         self.world.draw(g);
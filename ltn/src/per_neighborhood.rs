@@ -1,4 +1,5 @@
 use geom::Distance;
+use ltn_model::{DiagonalFilter, FilterType, Neighborhood, NeighborhoodID};
 use map_gui::tools::open_browser;
 use map_model::{IntersectionID, PathConstraints, RoadID};
 use widgetry::mapspace::{ObjectID, World, WorldOutcome};
@@ -7,9 +8,7 @@ use widgetry::{
     VerticalAlignment, Widget, DEFAULT_CORNER_RADIUS,
 };
 
-use crate::{
-    after_edit, App, BrowseNeighborhoods, DiagonalFilter, Neighborhood, NeighborhoodID, Transition,
-};
+use crate::{after_edit, App, BrowseNeighborhoods, Transition};
 
 #[derive(PartialEq)]
 pub enum Tab {
@@ -42,7 +41,8 @@ impl Tab {
             Widget::col(vec![
                 Widget::row(vec![
                     Image::from_path("system/assets/tools/pencil.svg").into_widget(ctx),
-                    "Click a road or intersection to add or remove a modal filter"
+                    "Click a road or intersection to add a modal filter. Click again to change or \
+                     remove it."
                         .text_widget(ctx)
                         .centered_vert(),
                 ]),
@@ -196,7 +196,22 @@ pub fn handle_world_outcome(
             }
 
             app.session.modal_filters.before_edit();
-            if app.session.modal_filters.roads.remove(&r).is_none() {
+            let filters = &mut app.session.modal_filters;
+            if filters.roads.contains_key(&r) {
+                // Toggle through all types of filters, starting with a physical barrier
+                let next = match filters.road_filter_types.get(&r).cloned() {
+                    None => Some(FilterType::BusGate),
+                    Some(FilterType::BusGate) => Some(FilterType::Camera),
+                    Some(FilterType::Camera) => Some(FilterType::SchoolStreet),
+                    Some(FilterType::SchoolStreet) => None,
+                };
+                if let Some(filter_type) = next {
+                    filters.road_filter_types.insert(r, filter_type);
+                } else {
+                    filters.roads.remove(&r);
+                    filters.road_filter_types.remove(&r);
+                }
+            } else {
                 // Place the filter on the part of the road that was clicked
                 // These calls shouldn't fail -- since we clicked a road, the cursor must be in
                 // map-space. And project_pt returns a point that's guaranteed to be on the
@@ -205,7 +220,7 @@ pub fn handle_world_outcome(
                 let pt_on_line = road.center_pts.project_pt(cursor_pt);
                 let (distance, _) = road.center_pts.dist_along_of_point(pt_on_line).unwrap();
 
-                app.session.modal_filters.roads.insert(r, distance);
+                filters.roads.insert(r, distance);
             }
             after_edit(ctx, app);
             true
@@ -219,7 +234,7 @@ pub fn handle_world_outcome(
 
             // Toggle through all possible filters
             app.session.modal_filters.before_edit();
-            let mut all = DiagonalFilter::filters_for(app, i);
+            let mut all = DiagonalFilter::filters_for(&app.map, i);
             if let Some(current) = app.session.modal_filters.intersections.get(&i) {
                 let idx = all.iter().position(|x| x == current).unwrap();
                 if idx == all.len() - 1 {
@@ -1,4 +1,5 @@
 use geom::ArrowCap;
+use ltn_model::{find_rat_runs, Neighborhood, NeighborhoodID, RatRuns};
 use map_gui::tools::{percentage_bar, ColorNetwork};
 use map_model::NORMAL_LANE_THICKNESS;
 use widgetry::mapspace::{ToggleZoomed, World};
@@ -7,8 +8,7 @@ use widgetry::{
 };
 
 use super::per_neighborhood::{FilterableObj, Tab};
-use super::rat_runs::{find_rat_runs, RatRuns};
-use super::{Neighborhood, NeighborhoodID};
+use super::DrawNeighborhood;
 use crate::{App, Transition};
 
 pub struct BrowseRatRuns {
@@ -20,14 +20,16 @@ pub struct BrowseRatRuns {
     draw_heatmap: ToggleZoomed,
     world: World<FilterableObj>,
     neighborhood: Neighborhood,
+    draw_neighborhood: DrawNeighborhood,
 }
 
 impl BrowseRatRuns {
     pub fn new_state(ctx: &mut EventCtx, app: &App, id: NeighborhoodID) -> Box<dyn State<App>> {
-        let neighborhood = Neighborhood::new(ctx, app, id);
+        let neighborhood = crate::neighborhood::from_app(app, id);
+        let draw_neighborhood = DrawNeighborhood::new(ctx, app, &neighborhood);
 
         let rat_runs = ctx.loading_screen("find rat runs", |_, timer| {
-            find_rat_runs(app, &neighborhood, timer)
+            find_rat_runs(&app.map, &neighborhood, &app.session.modal_filters, timer)
         });
         let mut colorer = ColorNetwork::no_fading(app);
         colorer.ranked_roads(rat_runs.count_per_road.clone(), &app.cs.good_to_bad_red);
@@ -45,6 +47,7 @@ impl BrowseRatRuns {
             draw_path: ToggleZoomed::empty(ctx),
             draw_heatmap: colorer.build(ctx),
             neighborhood,
+            draw_neighborhood,
             world,
         };
         state.recalculate(ctx, app);
@@ -200,10 +203,10 @@ impl State<App> for BrowseRatRuns {
             self.draw_path.draw(g);
         }
 
-        g.redraw(&self.neighborhood.fade_irrelevant);
+        g.redraw(&self.draw_neighborhood.fade_irrelevant);
         app.session.draw_all_filters.draw(g);
         if g.canvas.is_unzoomed() {
-            self.neighborhood.labels.draw(g, app);
+            self.draw_neighborhood.labels.draw(g, app);
         }
     }
 }
@@ -1,92 +1,171 @@
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
 
-use abstio::MapName;
 use abstutil::Timer;
-use map_gui::tools::{ChooseSomething, PopupMsg, PromptInput};
+use ltn_model::{import_geojson_filters, PermanentProposal, Proposal};
+use map_gui::tools::{ChooseSomething, FilePicker, PopupMsg, PromptInput};
 use widgetry::{Choice, EventCtx, State, Transition};
 
-use crate::{App, BrowseNeighborhoods, ModalFilters, Partitioning};
+use crate::{App, BrowseNeighborhoods};
 
-/// Captures all of the edits somebody makes to a map in the LTN tool. Note this separate from
-/// `map_model::MapEdits`.
-///
-/// TODO Note this format isn't future-proof at all. Changes to the LTN blockfinding algorithm or
-/// map data (like RoadIDs) will probably break someone's edits.
-#[derive(Serialize, Deserialize)]
-pub struct Proposal {
-    pub map: MapName,
-    pub name: String,
-    pub abst_version: String,
-
-    pub partitioning: Partitioning,
-    pub modal_filters: ModalFilters,
+pub fn save_ui(ctx: &mut EventCtx) -> Box<dyn State<App>> {
+    PromptInput::new_state(
+        ctx,
+        "Name this proposal",
+        String::new(),
+        Box::new(|name, ctx, app| {
+            let problems = from_session(app, name).save(&app.map);
+            if problems.is_empty() {
+                Transition::Pop
+            } else {
+                let mut lines = vec!["Some filters couldn't be saved:".to_string()];
+                lines.extend(problems);
+                Transition::Replace(PopupMsg::new_state(ctx, "Proposal saved", lines))
+            }
+        }),
+    )
 }
 
-impl Proposal {
-    pub fn save_ui(ctx: &mut EventCtx) -> Box<dyn State<App>> {
-        PromptInput::new_state(
-            ctx,
-            "Name this proposal",
-            String::new(),
-            Box::new(|name, _, app| {
-                Self::save(app, name);
-                Transition::Pop
-            }),
-        )
+fn from_session(app: &App, name: String) -> Proposal {
+    Proposal {
+        map: app.map.get_name().clone(),
+        name,
+        abst_version: map_gui::tools::version().to_string(),
+
+        partitioning: app.session.partitioning.clone(),
+        modal_filters: app.session.modal_filters.clone(),
     }
+}
 
-    fn save(app: &App, name: String) {
-        let path = abstio::path_ltn_proposals(app.map.get_name(), &name);
-        let proposal = Proposal {
-            map: app.map.get_name().clone(),
-            name,
-            abst_version: map_gui::tools::version().to_string(),
+pub fn export_ui(ctx: &mut EventCtx) -> Box<dyn State<App>> {
+    PromptInput::new_state(
+        ctx,
+        "Name this proposal",
+        String::new(),
+        Box::new(|name, ctx, app| {
+            let (proposal, problems) = from_session(app, name).to_permanent(&app.map);
+            let path = format!(
+                "ltn_proposal_{}_{}.json",
+                app.map.get_name().map,
+                proposal.name
+            );
+            Transition::Replace(
+                match crate::export::write_file(path, abstutil::to_json(&proposal)) {
+                    Ok(path) => {
+                        let mut lines = vec![format!("Proposal exported to {}", path)];
+                        if !problems.is_empty() {
+                            lines.push("Some filters couldn't be exported:".to_string());
+                            lines.extend(problems);
+                        }
+                        PopupMsg::new_state(ctx, "Proposal exported", lines)
+                    }
+                    Err(err) => PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()]),
+                },
+            )
+        }),
+    )
+}
 
-            partitioning: app.session.partitioning.clone(),
-            modal_filters: app.session.modal_filters.clone(),
-        };
-        abstio::write_binary(path, &proposal);
-    }
+/// Imports an exported proposal, replacing the current one, or adds filters from a GeoJSON
+/// file.
+pub fn import_ui(ctx: &mut EventCtx) -> Box<dyn State<App>> {
+    FilePicker::new_state(
+        ctx,
+        None,
+        Box::new(|ctx, app, maybe_path| {
+            if let Ok(Some(path)) = maybe_path {
+                let result = ctx.loading_screen("import proposal", |ctx, timer| {
+                    import(ctx, app, path, timer)
+                });
+                match result {
+                    Ok(problems) => {
+                        let mut lines = Vec::new();
+                        if problems.is_empty() {
+                            lines.push("Everything matched the current map".to_string());
+                        } else {
+                            lines.push("Some things couldn't be matched:".to_string());
+                            lines.extend(problems);
+                        }
+                        Transition::Multi(vec![
+                            Transition::Replace(BrowseNeighborhoods::new_state(ctx, app)),
+                            Transition::Push(PopupMsg::new_state(ctx, "Proposal imported", lines)),
+                        ])
+                    }
+                    Err(err) => Transition::Replace(PopupMsg::new_state(
+                        ctx,
+                        "Import failed",
+                        vec![err.to_string()],
+                    )),
+                }
+            } else {
+                Transition::Pop
+            }
+        }),
+    )
+}
 
-    pub fn load_picker_ui(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
-        ChooseSomething::new_state(
-            ctx,
-            "Load which proposal?",
-            Choice::strings(abstio::list_all_objects(abstio::path_all_ltn_proposals(
-                app.map.get_name(),
-            ))),
-            Box::new(|name, ctx, app| {
-                Transition::Replace(match Self::load(ctx, app, &name) {
-                    Some(err_state) => err_state,
-                    None => BrowseNeighborhoods::new_state(ctx, app),
-                })
-            }),
-        )
+fn import(
+    ctx: &mut EventCtx,
+    app: &mut App,
+    path: String,
+    timer: &mut Timer,
+) -> Result<Vec<String>> {
+    if path.ends_with(".geojson") {
+        let raw = String::from_utf8(abstio::slurp_file(path)?)?;
+        let (filters, problems) = import_geojson_filters(&app.map, &raw)?;
+        app.session.modal_filters.before_edit();
+        app.session.modal_filters.roads.extend(filters.roads);
+        app.session
+            .modal_filters
+            .road_filter_types
+            .extend(filters.road_filter_types);
+        app.session
+            .modal_filters
+            .intersections
+            .extend(filters.intersections);
+        crate::after_edit(ctx, app);
+        return Ok(problems);
     }
 
-    /// Try to load a proposal. If it fails, returns a popup message state.
-    pub fn load(ctx: &mut EventCtx, app: &mut App, name: &str) -> Option<Box<dyn State<App>>> {
-        ctx.loading_screen(
-            "load existing proposal",
-            |ctx, mut timer| match Self::inner_load(ctx, app, name, &mut timer) {
-                Ok(()) => None,
-                Err(err) => Some(PopupMsg::new_state(
-                    ctx,
-                    "Error",
-                    vec![format!("Couldn't load proposal {}", name), err.to_string()],
-                )),
-            },
-        )
-    }
+    let proposal: PermanentProposal = abstio::maybe_read_json(path, timer)?;
+    let (proposal, problems) = proposal.resolve(&app.map, timer)?;
+    app.session.partitioning = proposal.partitioning;
+    app.session.modal_filters = proposal.modal_filters;
+    crate::after_edit(ctx, app);
+    Ok(problems)
+}
 
-    fn inner_load(ctx: &mut EventCtx, app: &mut App, name: &str, timer: &mut Timer) -> Result<()> {
-        let proposal: Proposal =
-            abstio::maybe_read_binary(abstio::path_ltn_proposals(app.map.get_name(), name), timer)?;
-        // TODO We could try to detect if the file still matches this version of the map or not
-        app.session.partitioning = proposal.partitioning;
-        app.session.modal_filters = proposal.modal_filters;
-        app.session.draw_all_filters = app.session.modal_filters.draw(ctx, &app.map);
-        Ok(())
-    }
+pub fn load_picker_ui(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+    ChooseSomething::new_state(
+        ctx,
+        "Load which proposal?",
+        Choice::strings(Proposal::list_all(app.map.get_name())),
+        Box::new(|name, ctx, app| {
+            Transition::Replace(match load(ctx, app, &name) {
+                Some(err_state) => err_state,
+                None => BrowseNeighborhoods::new_state(ctx, app),
+            })
+        }),
+    )
+}
+
+/// Try to load a proposal. If it fails, returns a popup message state.
+pub fn load(ctx: &mut EventCtx, app: &mut App, name: &str) -> Option<Box<dyn State<App>>> {
+    ctx.loading_screen("load existing proposal", |ctx, timer| {
+        match inner_load(ctx, app, name, timer) {
+            Ok(()) => None,
+            Err(err) => Some(PopupMsg::new_state(
+                ctx,
+                "Error",
+                vec![format!("Couldn't load proposal {}", name), err.to_string()],
+            )),
+        }
+    })
+}
+
+fn inner_load(ctx: &mut EventCtx, app: &mut App, name: &str, timer: &mut Timer) -> Result<()> {
+    let proposal = Proposal::load_by_name(&app.map, name, timer)?;
+    app.session.partitioning = proposal.partitioning;
+    app.session.modal_filters = proposal.modal_filters;
+    crate::after_edit(ctx, app);
+    Ok(())
 }
@@ -3,21 +3,21 @@ use std::collections::BTreeSet;
 use anyhow::Result;
 
 use geom::Distance;
+use ltn_model::{BlockID, NeighborhoodID, Partitioning};
 use map_model::Block;
 use widgetry::mapspace::ToggleZoomed;
-use widgetry::mapspace::{World, WorldOutcome};
+use widgetry::mapspace::{ObjectID, World, WorldOutcome};
 use widgetry::{
     Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State, Text, TextExt,
     VerticalAlignment, Widget,
 };
 
-use crate::partition::BlockID;
-use crate::{App, NeighborhoodID, Partitioning, Transition};
+use crate::{App, Transition};
 
 pub struct SelectBoundary {
     panel: Panel,
     id: NeighborhoodID,
-    world: World<BlockID>,
+    world: World<Obj>,
     draw_outline: ToggleZoomed,
     frontier: BTreeSet<BlockID>,
 
@@ -28,6 +28,10 @@ pub struct SelectBoundary {
     last_failed_change: Option<(BlockID, bool)>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Obj(BlockID);
+impl ObjectID for Obj {}
+
 impl SelectBoundary {
     pub fn new_state(ctx: &mut EventCtx, app: &App, id: NeighborhoodID) -> Box<dyn State<App>> {
         let mut state = SelectBoundary {
@@ -59,13 +63,14 @@ impl SelectBoundary {
 
     fn add_block(&mut self, ctx: &mut EventCtx, app: &App, id: BlockID) {
         let neighborhood = app.session.partitioning.block_to_neighborhood(id);
-        let color = app.session.partitioning.neighborhood_color(neighborhood);
+        let color =
+            crate::neighborhood_color(app.session.partitioning.neighborhood_color(neighborhood));
 
         if self.frontier.contains(&id) {
             let have_block = self.currently_have_block(app, id);
             let mut obj = self
                 .world
-                .add(id)
+                .add(Obj(id))
                 .hitbox(app.session.partitioning.get_block(id).polygon.clone())
                 .draw_color(color.alpha(0.5))
                 .hover_alpha(0.8)
@@ -85,7 +90,7 @@ impl SelectBoundary {
             // it
             let alpha = if self.id == neighborhood { 0.5 } else { 0.1 };
             self.world
-                .add(id)
+                .add(Obj(id))
                 .hitbox(app.session.partitioning.get_block(id).polygon.clone())
                 .draw_color(color.alpha(alpha))
                 .build(ctx);
@@ -142,7 +147,7 @@ impl SelectBoundary {
                 }
 
                 for changed in changed_blocks {
-                    self.world.delete_before_replacement(changed);
+                    self.world.delete_before_replacement(Obj(changed));
                     self.add_block(ctx, app, changed);
                 }
 
@@ -206,14 +211,15 @@ impl State<App> for SelectBoundary {
         }
 
         match self.world.event(ctx) {
-            WorldOutcome::Keypress("add" | "remove", id) | WorldOutcome::ClickedObject(id) => {
+            WorldOutcome::Keypress("add" | "remove", Obj(id))
+            | WorldOutcome::ClickedObject(Obj(id)) => {
                 return self.toggle_block(ctx, app, id);
             }
             _ => {}
         }
         // TODO Bypasses World...
         if ctx.redo_mouseover() {
-            if let Some(id) = self.world.get_hovering() {
+            if let Some(Obj(id)) = self.world.get_hovering() {
                 if ctx.is_key_down(Key::LeftControl) {
                     if !self.currently_have_block(app, id) {
                         return self.toggle_block(ctx, app, id);
@@ -0,0 +1,225 @@
+//! Analyzes a proposal without any UI, so it can be used from the command line or other tools.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use serde::Serialize;
+
+use abstutil::Timer;
+use map_model::{IntersectionID, Map, RoadID};
+use synthpop::{Scenario, TripMode};
+
+use crate::rat_runs::find_rat_runs;
+use crate::{Neighborhood, NeighborhoodID, Proposal};
+
+#[derive(Serialize)]
+pub struct ProposalAnalysis {
+    pub neighborhoods: Vec<NeighborhoodAnalysis>,
+    /// How driving traffic along the perimeter of every neighborhood changes. Only calculated when
+    /// a scenario is provided.
+    pub boundary_roads: Vec<BoundaryRoadImpact>,
+}
+
+#[derive(Serialize)]
+pub struct NeighborhoodAnalysis {
+    pub id: NeighborhoodID,
+    pub interior_roads: usize,
+    /// Interior roads that no rat-run crosses
+    pub quiet_roads: usize,
+    pub rat_runs: usize,
+    /// The number of rat-runs crossing each interior road
+    pub rat_runs_per_road: BTreeMap<RoadID, usize>,
+    pub cells: Vec<CellAnalysis>,
+    pub disconnected_cells: usize,
+}
+
+#[derive(Serialize)]
+pub struct CellAnalysis {
+    pub roads: Vec<RoadID>,
+    /// Where this cell can be entered or exited by car
+    pub borders: Vec<IntersectionID>,
+    pub car_free: bool,
+    pub disconnected: bool,
+}
+
+#[derive(Serialize)]
+pub struct BoundaryRoadImpact {
+    pub road: RoadID,
+    pub name: String,
+    /// The neighborhoods this road is part of the perimeter of
+    pub neighborhoods: Vec<NeighborhoodID>,
+    /// Trips crossing this road before and after the proposal's filters
+    pub before: usize,
+    pub after: usize,
+}
+
+impl ProposalAnalysis {
+    /// Finds rat-runs and cells in every neighborhood. If a scenario is given, also predicts how
+    /// driving trips reroute around the filters, the same way as the impact prediction tool.
+    pub fn new(
+        map: &Map,
+        proposal: &Proposal,
+        scenario: Option<&Scenario>,
+        timer: &mut Timer,
+    ) -> ProposalAnalysis {
+        let mut neighborhoods = Vec::new();
+        let mut boundary_roads: BTreeMap<RoadID, Vec<NeighborhoodID>> = BTreeMap::new();
+        for id in proposal.partitioning.all_neighborhoods().keys() {
+            let neighborhood =
+                Neighborhood::new(map, &proposal.partitioning, &proposal.modal_filters, *id);
+            let rat_runs = find_rat_runs(map, &neighborhood, &proposal.modal_filters, timer);
+            let (quiet_roads, interior_roads) = rat_runs.quiet_and_total_streets(&neighborhood);
+            for r in &neighborhood.perimeter {
+                boundary_roads.entry(*r).or_insert_with(Vec::new).push(*id);
+            }
+
+            neighborhoods.push(NeighborhoodAnalysis {
+                id: *id,
+                interior_roads,
+                quiet_roads,
+                rat_runs: rat_runs.paths.len(),
+                rat_runs_per_road: rat_runs.count_per_road.consume(),
+                disconnected_cells: neighborhood
+                    .cells
+                    .iter()
+                    .filter(|c| c.is_disconnected())
+                    .count(),
+                cells: neighborhood
+                    .cells
+                    .iter()
+                    .map(|cell| CellAnalysis {
+                        roads: cell.roads.keys().cloned().collect(),
+                        borders: cell.borders.iter().cloned().collect(),
+                        car_free: cell.car_free,
+                        disconnected: cell.is_disconnected(),
+                    })
+                    .collect(),
+            });
+        }
+
+        let boundary_roads = if let Some(scenario) = scenario {
+            let all_trips = crate::impact::trips_from_scenario(map, scenario, timer);
+            let modes: BTreeSet<TripMode> = vec![TripMode::Drive].into_iter().collect();
+            let trips = crate::impact::filter_trips(map, &all_trips, &modes);
+            let before = crate::impact::counts_before_filters(map, &trips, timer);
+            let after =
+                crate::impact::counts_after_filters(map, &proposal.modal_filters, &trips, timer);
+            boundary_roads
+                .into_iter()
+                .map(|(r, neighborhoods)| BoundaryRoadImpact {
+                    road: r,
+                    name: map.get_r(r).get_name(None),
+                    neighborhoods,
+                    before: before.per_road.get(r),
+                    after: after.per_road.get(r),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        ProposalAnalysis {
+            neighborhoods,
+            boundary_roads,
+        }
+    }
+
+    /// Neighborhood boundaries, cells, and interior and boundary roads, with the results as
+    /// properties
+    pub fn to_geojson(&self, map: &Map, proposal: &Proposal) -> Result<GeoJson> {
+        let mut features = Vec::new();
+
+        for analysis in &self.neighborhoods {
+            let block = proposal.partitioning.neighborhood_block(analysis.id);
+            let mut feature = Feature {
+                bbox: None,
+                geometry: Some(block.polygon.to_geojson(None)),
+                id: None,
+                properties: None,
+                foreign_members: None,
+            };
+            feature.set_property("type", "neighborhood");
+            feature.set_property("neighborhood", serde_json::to_value(analysis.id)?);
+            feature.set_property("interior_roads", analysis.interior_roads);
+            feature.set_property("quiet_roads", analysis.quiet_roads);
+            feature.set_property("rat_runs", analysis.rat_runs);
+            feature.set_property("cells", analysis.cells.len());
+            feature.set_property("disconnected_cells", analysis.disconnected_cells);
+            features.push(feature);
+
+            let neighborhood = Neighborhood::new(
+                map,
+                &proposal.partitioning,
+                &proposal.modal_filters,
+                analysis.id,
+            );
+            let render_cells = crate::RenderCells::new(map, &neighborhood);
+            for (cell, multipolygon) in analysis
+                .cells
+                .iter()
+                .zip(render_cells.to_multipolygons().into_iter())
+            {
+                let mut feature = Feature {
+                    bbox: None,
+                    geometry: Some(Geometry {
+                        bbox: None,
+                        value: Value::from(&multipolygon),
+                        foreign_members: None,
+                    }),
+                    id: None,
+                    properties: None,
+                    foreign_members: None,
+                };
+                feature.set_property("type", "cell");
+                feature.set_property("neighborhood", serde_json::to_value(analysis.id)?);
+                feature.set_property("car_free", cell.car_free);
+                feature.set_property("disconnected", cell.disconnected);
+                features.push(feature);
+            }
+
+            for r in &neighborhood.orig_perimeter.interior {
+                let mut feature = Feature {
+                    bbox: None,
+                    geometry: Some(map.get_r(*r).center_pts.to_geojson(None)),
+                    id: None,
+                    properties: None,
+                    foreign_members: None,
+                };
+                feature.set_property("type", "interior road");
+                feature.set_property("road", r.0);
+                feature.set_property("neighborhood", serde_json::to_value(analysis.id)?);
+                feature.set_property(
+                    "rat_runs",
+                    analysis.rat_runs_per_road.get(r).cloned().unwrap_or(0),
+                );
+                features.push(feature);
+            }
+        }
+
+        for impact in &self.boundary_roads {
+            let mut feature = Feature {
+                bbox: None,
+                geometry: Some(map.get_r(impact.road).center_pts.to_geojson(None)),
+                id: None,
+                properties: None,
+                foreign_members: None,
+            };
+            feature.set_property("type", "boundary road");
+            feature.set_property("road", impact.road.0);
+            feature.set_property("name", impact.name.clone());
+            feature.set_property("before", impact.before);
+            feature.set_property("after", impact.after);
+            feature.set_property("change", impact.after as isize - impact.before as isize);
+            features.push(feature);
+        }
+
+        crate::to_wgs84(map, &mut features)?;
+
+        Ok(GeoJson::FeatureCollection(FeatureCollection {
+            features,
+            bbox: None,
+            foreign_members: None,
+        }))
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use geo::algorithm::map_coords::MapCoordsInplace;
+use geojson::{Feature, Geometry, Value};
+
+use geom::Pt2D;
+use map_model::Map;
+
+/// Transforms features with geometry in map-space to WGS84
+pub fn to_wgs84(map: &Map, features: &mut [Feature]) -> Result<()> {
+    let gps_bounds = map.get_gps_bounds();
+    for feature in features {
+        // geojson to geo
+        // This could be a Polygon, MultiPolygon, LineString
+        let mut geom: geo::Geometry<f64> = feature.geometry.take().unwrap().value.try_into()?;
+
+        geom.map_coords_inplace(|c| {
+            let gps = Pt2D::new(c.0, c.1).to_gps(gps_bounds);
+            (gps.x(), gps.y())
+        });
+
+        // geo to geojson
+        feature.geometry = Some(Geometry {
+            bbox: None,
+            value: Value::from(&geom),
+            foreign_members: None,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Line, Time};
+use map_model::{
+    AccessRestrictions, IdMigration, IntersectionID, Map, MapEdits, PathConstraints, RoadID,
+    RoutingParams, TurnID,
+};
+/// All of the modal filters in a proposal. Before making any changes, call `before_edit`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ModalFilters {
+    /// For filters placed along a road, where is the filter located?
+    pub roads: BTreeMap<RoadID, Distance>,
+    pub intersections: BTreeMap<IntersectionID, DiagonalFilter>,
+    /// Filters along roads that only stop some vehicles, or only at some times. Road filters
+    /// without an entry here physically block all vehicles, all day.
+    ///
+    /// TODO Diagonal filters are always absolute.
+    pub road_filter_types: BTreeMap<RoadID, FilterType>,
+
+    /// Edit history is preserved recursively
+    #[serde(skip_serializing, skip_deserializing)]
+    pub previous_version: Box<Option<ModalFilters>>,
+    /// This changes every time an edit occurs
+    #[serde(skip_serializing, skip_deserializing)]
+    pub change_key: usize,
+}
+
+/// A filter that only stops some vehicles, or only at some times. The type determines both.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FilterType {
+    /// Buses can pass. Taxis usually can too, but aren't modelled.
+    BusGate,
+    /// Enforced by cameras reading number plates instead of a physical barrier. Buses and
+    /// residents are usually exempt. Residents aren't modelled, except that vehicles can still
+    /// reach the filtered road itself from either end.
+    Camera,
+    /// Closed to vehicles around the start and end of the school day
+    SchoolStreet,
+}
+
+/// A diagonal filter exists in an intersection. It's defined by two roads (the order is
+/// arbitrary). When all of the intersection's roads are sorted in clockwise order, this pair of
+/// roads splits the ordering into two groups. Turns in each group are still possible, but not
+/// across groups.
+///
+/// TODO Be careful with PartialEq! At a 4-way intersection, the same filter can be expressed as a
+/// different pair of two roads. And the (r1, r2) ordering is also arbitrary.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagonalFilter {
+    pub(crate) r1: RoadID,
+    pub(crate) r2: RoadID,
+    pub(crate) i: IntersectionID,
+
+    group1: BTreeSet<RoadID>,
+    group2: BTreeSet<RoadID>,
+}
+
+impl ModalFilters {
+    /// Call before making any changes to preserve edit history
+    pub fn before_edit(&mut self) {
+        let copy = self.clone();
+        self.previous_version = Box::new(Some(copy));
+        self.change_key += 1;
+    }
+
+    /// If it's possible no edits were made, undo the previous call to `before_edit` and collapse
+    /// the redundant piece of history.
+    pub fn cancel_empty_edit(&mut self) {
+        if let Some(prev) = self.previous_version.take() {
+            if self.roads == prev.roads
+                && self.intersections == prev.intersections
+                && self.road_filter_types == prev.road_filter_types
+            {
+                self.previous_version = prev.previous_version;
+                // Leave change_key alone for simplicity
+            } else {
+                // There was a real difference, keep
+                self.previous_version = Box::new(Some(prev));
+            }
+        }
+    }
+
+    /// Only the filters that stop one type of vehicle at some time, or at any time of the day if
+    /// `time` is None. All of the filters are absolute in the result, so connectivity and
+    /// rat-runs can be worked out the same way for every type of filter.
+    pub fn enforced_for(&self, constraints: PathConstraints, time: Option<Time>) -> ModalFilters {
+        let mut result = ModalFilters::default();
+        if constraints == PathConstraints::Pedestrian || constraints == PathConstraints::Bike {
+            return result;
+        }
+        for (r, dist) in &self.roads {
+            if let Some(filter_type) = self.road_filter_types.get(r) {
+                if !filter_type.stops(constraints) {
+                    continue;
+                }
+                if let Some(time) = time {
+                    if !filter_type.is_enforced(time) {
+                        continue;
+                    }
+                }
+            }
+            result.roads.insert(*r, *dist);
+        }
+        result.intersections = self.intersections.clone();
+        result
+    }
+
+    /// Modify RoutingParams to respect these modal filters for one type of vehicle. Time-limited
+    /// filters are treated as if they're in effect.
+    pub fn update_routing_params(&self, params: &mut RoutingParams, constraints: PathConstraints) {
+        let filters = self.enforced_for(constraints, None);
+        params.avoid_roads.extend(filters.roads.keys().cloned());
+        for filter in filters.intersections.values() {
+            params
+                .avoid_movements_between
+                .extend(filter.avoid_movements_between_roads());
+        }
+    }
+
+    pub fn allows_turn(&self, t: TurnID) -> bool {
+        if let Some(filter) = self.intersections.get(&t.parent) {
+            return filter.allows_turn(t.src.road, t.dst.road);
+        }
+        true
+    }
+
+    /// Translate filters placed on an old version of a map to a new version. Returns the new
+    /// filters and a description of every filter that couldn't be carried over.
+    pub fn migrate(&self, migration: &IdMigration, new: &Map) -> (ModalFilters, Vec<String>) {
+        let mut result = ModalFilters::default();
+        let mut problems = Vec::new();
+        for (r, dist) in &self.roads {
+            match (migration.r(*r), migration.dist_along_road(*r, *dist)) {
+                (Some(id), Some(dist)) => {
+                    result.roads.insert(id, dist);
+                    if let Some(filter_type) = self.road_filter_types.get(r) {
+                        result.road_filter_types.insert(id, *filter_type);
+                    }
+                }
+                _ => {
+                    problems.push(format!("Filter on {} doesn't exist anymore", r));
+                }
+            }
+        }
+        for (i, filter) in &self.intersections {
+            if let Some(filter) = filter.migrate(migration, new) {
+                result.intersections.insert(filter.i, filter);
+            } else {
+                problems.push(format!("Diagonal filter at {} doesn't fit anymore", i));
+            }
+        }
+        (result, problems)
+    }
+
+    /// Express the road filters as access restrictions, so the full traffic simulation can use
+    /// them. Each filtered road becomes a zone; vehicles can still reach the road itself, but
+    /// can't pass through. Map edits can't change partway through a simulation, so time-limited
+    /// filters are included only if they're enforced at `time`, or at any time of the day if
+    /// `time` is None. Returns a description of every filter that couldn't be expressed.
+    pub fn to_map_edits(&self, map: &Map, time: Option<Time>) -> (MapEdits, Vec<String>) {
+        let mut edits = map.get_edits().clone();
+        let mut problems = Vec::new();
+        for r in self.roads.keys() {
+            let filter_type = self.road_filter_types.get(r).cloned();
+            if let (Some(filter_type), Some(time)) = (filter_type, time) {
+                if !filter_type.is_enforced(time) {
+                    continue;
+                }
+            }
+            edits.commands.push(map.edit_road_cmd(*r, |new| {
+                new.access_restrictions.allow_through_traffic = AccessRestrictions::new()
+                    .allow_through_traffic
+                    .iter()
+                    .filter(|c| match filter_type {
+                        Some(filter_type) => !filter_type.stops(*c),
+                        None => *c == PathConstraints::Pedestrian || *c == PathConstraints::Bike,
+                    })
+                    .collect();
+            }));
+        }
+        for i in self.intersections.keys() {
+            problems.push(format!(
+                "Diagonal filter at {} can't be expressed as map edits yet",
+                i
+            ));
+        }
+        (edits, problems)
+    }
+}
+
+impl FilterType {
+    pub fn all() -> Vec<FilterType> {
+        vec![
+            FilterType::BusGate,
+            FilterType::Camera,
+            FilterType::SchoolStreet,
+        ]
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            FilterType::BusGate => "bus gate",
+            FilterType::Camera => "camera filter",
+            FilterType::SchoolStreet => "school street",
+        }
+    }
+
+    /// Does this filter ever stop this type of vehicle? Pedestrians and bikes can always pass.
+    pub fn stops(self, constraints: PathConstraints) -> bool {
+        match constraints {
+            PathConstraints::Pedestrian | PathConstraints::Bike => false,
+            PathConstraints::Bus => self == FilterType::SchoolStreet,
+            PathConstraints::Car | PathConstraints::Train => true,
+        }
+    }
+
+    pub fn is_enforced(self, time: Time) -> bool {
+        match self {
+            FilterType::BusGate | FilterType::Camera => true,
+            FilterType::SchoolStreet => {
+                // Around the start and end of the school day, repeating every day
+                let hour_of_day = (time.inner_seconds() / 3600.0) % 24.0;
+                (8.0..9.0).contains(&hour_of_day) || (15.0..16.0).contains(&hour_of_day)
+            }
+        }
+    }
+}
+
+impl DiagonalFilter {
+    /// Find all possible diagonal filters at an intersection
+    pub fn filters_for(map: &Map, i: IntersectionID) -> Vec<DiagonalFilter> {
+        let roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
+        // TODO Handle >4-ways
+        if roads.len() != 4 {
+            return Vec::new();
+        }
+
+        vec![
+            DiagonalFilter::new(map, i, roads[0], roads[1]),
+            DiagonalFilter::new(map, i, roads[1], roads[2]),
+        ]
+    }
+
+    /// The filter splitting off `r1` and `r2` at a 4-way intersection. None if `r2` doesn't
+    /// immediately follow `r1` in clockwise order.
+    pub fn between(map: &Map, i: IntersectionID, r1: RoadID, r2: RoadID) -> Option<DiagonalFilter> {
+        let roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
+        if roads.len() != 4 {
+            return None;
+        }
+        let idx1 = roads.iter().position(|r| *r == r1)?;
+        let idx2 = roads.iter().position(|r| *r == r2)?;
+        if (idx1 + 1) % roads.len() != idx2 {
+            return None;
+        }
+        Some(DiagonalFilter::new(map, i, r1, r2))
+    }
+
+    fn new(map: &Map, i: IntersectionID, r1: RoadID, r2: RoadID) -> DiagonalFilter {
+        let mut roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
+        // Make self.r1 be the first entry
+        while roads[0] != r1 {
+            roads.rotate_right(1);
+        }
+
+        let mut group1 = BTreeSet::new();
+        group1.insert(roads.remove(0));
+        loop {
+            let next = roads.remove(0);
+            group1.insert(next);
+            if next == r2 {
+                break;
+            }
+        }
+        // This is only true for 4-ways...
+        assert_eq!(group1.len(), 2);
+        assert_eq!(roads.len(), 2);
+
+        DiagonalFilter {
+            r1,
+            r2,
+            i,
+            group1,
+            group2: roads.into_iter().collect(),
+        }
+    }
+
+    fn migrate(&self, migration: &IdMigration, map: &Map) -> Option<DiagonalFilter> {
+        let i = migration.i(self.i)?;
+        let r1 = migration.r(self.r1)?;
+        let r2 = migration.r(self.r2)?;
+        // The intersection has to still be a 4-way, with r2 right after r1
+        DiagonalFilter::between(map, i, r1, r2)
+    }
+
+    /// Physically where is the filter placed?
+    pub fn geometry(&self, map: &Map) -> Line {
+        let r1 = map.get_r(self.r1);
+        let r2 = map.get_r(self.r2);
+
+        // Orient the road to face the intersection
+        let mut pl1 = r1.center_pts.clone();
+        if r1.src_i == self.i {
+            pl1 = pl1.reversed();
+        }
+        let mut pl2 = r2.center_pts.clone();
+        if r2.src_i == self.i {
+            pl2 = pl2.reversed();
+        }
+
+        // The other combinations of left/right here would produce points or a line across just one
+        // road
+        let pt1 = pl1.must_shift_right(r1.get_half_width()).last_pt();
+        let pt2 = pl2.must_shift_left(r2.get_half_width()).last_pt();
+        Line::must_new(pt1, pt2)
+    }
+
+    pub fn allows_turn(&self, from: RoadID, to: RoadID) -> bool {
+        self.group1.contains(&from) == self.group1.contains(&to)
+    }
+
+    fn avoid_movements_between_roads(&self) -> Vec<(RoadID, RoadID)> {
+        let mut pairs = Vec::new();
+        for from in &self.group1 {
+            for to in &self.group2 {
+                pairs.push((*from, *to));
+                pairs.push((*to, *from));
+            }
+        }
+        pairs
+    }
+}
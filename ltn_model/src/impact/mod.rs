@@ -0,0 +1,79 @@
+//! Predicts how traffic changes after a proposal's filters. The quick prediction just routes every
+//! trip before and after; `simulation` runs the full traffic simulation.
+
+mod simulation;
+
+use std::collections::BTreeSet;
+
+use abstutil::Timer;
+use map_model::{Map, PathConstraints, PathRequest, PathfinderCaching};
+use synthpop::{Scenario, TrafficCounts, TripEndpoint, TripMode};
+
+pub use self::simulation::{BoundaryRoadDelay, SimulationImpact};
+use crate::ModalFilters;
+
+/// The routing request for every trip in a scenario that can be routed
+pub fn trips_from_scenario(map: &Map, scenario: &Scenario, timer: &mut Timer) -> Vec<PathRequest> {
+    timer
+        .parallelize("analyze trips", scenario.all_trips().collect(), |trip| {
+            TripEndpoint::path_req(trip.origin, trip.destination, trip.mode, map)
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Only keeps trips using some modes, and counts how many times each one happens
+pub fn filter_trips(
+    map: &Map,
+    all_trips: &[PathRequest],
+    modes: &BTreeSet<TripMode>,
+) -> Vec<(PathRequest, usize)> {
+    let constraints: BTreeSet<PathConstraints> = modes.iter().map(|m| m.to_constraints()).collect();
+    PathRequest::deduplicate(
+        map,
+        all_trips
+            .iter()
+            .filter(|req| constraints.contains(&req.constraints))
+            .cloned()
+            .collect(),
+    )
+}
+
+pub fn counts_before_filters(
+    map: &Map,
+    trips: &[(PathRequest, usize)],
+    timer: &mut Timer,
+) -> TrafficCounts {
+    TrafficCounts::from_path_requests(
+        map,
+        // Don't bother describing all the trip filtering
+        "before filters".to_string(),
+        trips,
+        map.routing_params().clone(),
+        PathfinderCaching::NoCache,
+        timer,
+    )
+}
+
+pub fn counts_after_filters(
+    map: &Map,
+    modal_filters: &ModalFilters,
+    trips: &[(PathRequest, usize)],
+    timer: &mut Timer,
+) -> TrafficCounts {
+    let mut params = map.routing_params().clone();
+    // TODO This assumes all of the trips are driving, the default
+    modal_filters.update_routing_params(&mut params, PathConstraints::Car);
+    // Since we're making so many requests, it's worth it to rebuild a contraction hierarchy. And
+    // since we're single-threaded, no complications there.
+    TrafficCounts::from_path_requests(
+        map,
+        // Don't bother describing all the trip filtering
+        "after filters".to_string(),
+        trips,
+        params,
+        PathfinderCaching::CacheCH,
+        timer,
+    )
+}
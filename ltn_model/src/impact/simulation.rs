@@ -0,0 +1,175 @@
+//! Runs a scenario through the full traffic simulation, before and after a proposal's filters.
+//! Unlike the static prediction, vehicles react to congestion, so this can show delay along main
+//! roads and changes to trip times. Trips still use the same mode, and map edits can't change
+//! partway through a simulation, so time-limited filters are in effect all day.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Time};
+use map_model::{Map, RoadID};
+use sim::{AgentType, AlertHandler, Analytics, Sim, SimFlags, SimOptions, TripID};
+use synthpop::{Scenario, TrafficCounts, TripMode};
+
+use crate::{ModalFilters, Partitioning};
+
+pub struct SimulationImpact {
+    /// Motor vehicles crossing every road and intersection
+    pub counts_before: TrafficCounts,
+    pub counts_after: TrafficCounts,
+    /// Trips that finished in both simulations: (ID, before, after, mode)
+    pub trip_times: Vec<(TripID, Duration, Duration, TripMode)>,
+    pub cancelled_before: usize,
+    pub cancelled_after: usize,
+    /// The perimeter roads of every neighborhood
+    pub boundary_roads: Vec<BoundaryRoadDelay>,
+    /// Motor vehicles crossing the interior roads of every neighborhood, before and after
+    pub interior_vehicles: (usize, usize),
+    /// Filters that couldn't be simulated, and anything else that went wrong
+    pub problems: Vec<String>,
+}
+
+pub struct BoundaryRoadDelay {
+    pub road: RoadID,
+    pub vehicles_before: usize,
+    pub vehicles_after: usize,
+    /// The average time for a car to cross the road, if any did
+    pub crossing_time_before: Option<Duration>,
+    pub crossing_time_after: Option<Duration>,
+}
+
+impl BoundaryRoadDelay {
+    /// How much longer it takes to cross the road after the filters. None if no cars crossed it in
+    /// one of the simulations.
+    pub fn delay(&self) -> Option<Duration> {
+        Some(self.crossing_time_after? - self.crossing_time_before?)
+    }
+}
+
+impl SimulationImpact {
+    /// Simulates the full day twice, so this is slow.
+    pub fn new(
+        map: &Map,
+        partitioning: &Partitioning,
+        modal_filters: &ModalFilters,
+        scenario: &Scenario,
+        timer: &mut Timer,
+    ) -> SimulationImpact {
+        let (edits, mut problems) = modal_filters.to_map_edits(map, None);
+        let mut edited_map = map.clone();
+        edited_map.must_apply_edits(edits, timer);
+        edited_map.recalculate_pathfinding_after_edits(timer);
+
+        timer.start("simulate before filters");
+        let before = run_scenario(map, scenario, "before filters", &mut problems, timer);
+        timer.stop("simulate before filters");
+        timer.start("simulate after filters");
+        let after = run_scenario(&edited_map, scenario, "after filters", &mut problems, timer);
+        timer.stop("simulate after filters");
+        // Both simulations run until the same time
+        let now = after.time();
+        let (before, after) = (before.get_analytics(), after.get_analytics());
+
+        let mut boundary_roads = BTreeSet::new();
+        let mut interior_roads = BTreeSet::new();
+        for (block, _) in partitioning.all_neighborhoods().values() {
+            boundary_roads.extend(block.perimeter.roads.iter().map(|id| id.road));
+            interior_roads.extend(block.perimeter.interior.iter().cloned());
+        }
+
+        let vehicles: BTreeSet<AgentType> =
+            vec![AgentType::Car, AgentType::Bus].into_iter().collect();
+        let counts_before = before.traffic_counts(map, "before filters".to_string(), &vehicles);
+        let counts_after = after.traffic_counts(map, "after filters".to_string(), &vehicles);
+
+        let crossing_time = |analytics: &Analytics, r: RoadID| -> Option<Duration> {
+            let kmph = analytics.road_speeds.kmph(r, AgentType::Car)?;
+            Some(Duration::seconds(
+                map.get_r(r).length().inner_meters() / (kmph / 3.6),
+            ))
+        };
+
+        SimulationImpact {
+            trip_times: after.both_finished_trips(now, before),
+            cancelled_before: count_cancelled(before),
+            cancelled_after: count_cancelled(after),
+            boundary_roads: boundary_roads
+                .into_iter()
+                .map(|r| BoundaryRoadDelay {
+                    road: r,
+                    vehicles_before: counts_before.per_road.get(r),
+                    vehicles_after: counts_after.per_road.get(r),
+                    crossing_time_before: crossing_time(before, r),
+                    crossing_time_after: crossing_time(after, r),
+                })
+                .collect(),
+            interior_vehicles: (
+                interior_roads
+                    .iter()
+                    .map(|r| counts_before.per_road.get(*r))
+                    .sum(),
+                interior_roads
+                    .iter()
+                    .map(|r| counts_after.per_road.get(*r))
+                    .sum(),
+            ),
+            counts_before,
+            counts_after,
+            problems,
+        }
+    }
+
+    /// Every mode with some finished trips, and how many trips got (faster, slower) after the
+    /// filters. Changes of less than a second are ignored.
+    pub fn faster_and_slower(&self) -> BTreeMap<TripMode, (usize, usize)> {
+        let mut result = BTreeMap::new();
+        for (_, before, after, mode) in &self.trip_times {
+            let entry = result.entry(*mode).or_insert((0, 0));
+            if *after < *before - Duration::seconds(1.0) {
+                entry.0 += 1;
+            } else if *after > *before + Duration::seconds(1.0) {
+                entry.1 += 1;
+            }
+        }
+        result
+    }
+}
+
+fn run_scenario(
+    map: &Map,
+    scenario: &Scenario,
+    label: &str,
+    problems: &mut Vec<String>,
+    timer: &mut Timer,
+) -> Sim {
+    let mut opts = SimOptions::new("ltn_impact");
+    opts.alerts = AlertHandler::Silence;
+    let mut sim = Sim::new(map, opts);
+    // Use the same seed both times, so trips match up
+    let mut rng = SimFlags::for_test("ltn_impact").make_rng();
+    sim.instantiate(scenario, map, &mut rng, timer);
+    // Some trips start close to midnight, so keep going for a while
+    sim.timed_step(
+        map,
+        sim.get_end_of_day() - Time::START_OF_DAY + Duration::hours(3),
+        &mut None,
+        timer,
+    );
+    if !sim.is_done() {
+        problems.push(format!(
+            "{} agents were still moving at {} {}. Gridlock likely...",
+            prettyprint_usize(sim.num_agents().sum()),
+            sim.time(),
+            label
+        ));
+    }
+    sim
+}
+
+fn count_cancelled(analytics: &Analytics) -> usize {
+    analytics
+        .finished_trips
+        .iter()
+        .filter(|(_, _, _, maybe_dt)| maybe_dt.is_none())
+        .count()
+}
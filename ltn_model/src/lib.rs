@@ -0,0 +1,35 @@
+//! The model behind the low traffic neighborhood tool: partitioning a map into neighborhoods,
+//! modal filters, rat-runs, and predicting the impact of a proposal. Nothing here depends on the
+//! UI, so the command line tools can use it headlessly.
+
+#![allow(clippy::type_complexity)]
+
+#[macro_use]
+extern crate anyhow;
+#[macro_use]
+extern crate log;
+
+pub use analysis::{BoundaryRoadImpact, CellAnalysis, NeighborhoodAnalysis, ProposalAnalysis};
+pub use export::to_wgs84;
+pub use filters::{DiagonalFilter, FilterType, ModalFilters};
+pub use impact::{BoundaryRoadDelay, SimulationImpact};
+pub use neighborhood::{Cell, DistanceInterval, Neighborhood};
+pub use optimize::{optimize_filters, FilterLayout};
+pub use partition::{BlockID, NeighborhoodID, Partitioning, NUM_COLORS};
+pub use rat_runs::{find_rat_runs, RatRuns};
+pub use render_cells::{CellColor, RenderCells};
+pub use save::{
+    import_geojson_filters, PermanentDiagonalFilter, PermanentProposal, PermanentRoadFilter,
+    Proposal,
+};
+
+mod analysis;
+mod export;
+mod filters;
+pub mod impact;
+mod neighborhood;
+mod optimize;
+mod partition;
+mod rat_runs;
+mod render_cells;
+mod save;
@@ -0,0 +1,299 @@
+//! Searches for the fewest modal filters that stop every rat-run through some neighborhoods. Unlike
+//! the heuristics in the LTN tool's `auto` module, this produces a ranked set of alternatives to
+//! choose between.
+//!
+//! Every layout has to keep all of the constraints:
+//!
+//! - No cell may be disconnected from the perimeter, so every building stays reachable by car.
+//!   Emergency vehicles need exactly this, so there's no separate check for them.
+//! - Roads used by any bus or train route are never filtered. Rat-runs that only cross those roads
+//!   can't be stopped, so they're reported as remaining.
+//!
+//! Neighborhoods are independent -- rat-runs are only found within one -- so each is searched
+//! separately and the results are combined. Within a neighborhood, every rat-run has to cross at
+//! least one filtered road, so the search branches on the roads of the rat-run with the fewest
+//! choices. Since filters reroute traffic and may create new rat-runs, every layout is checked
+//! again. The search is breadth-first by the number of filters, so the first layouts found are
+//! the smallest.
+
+use std::collections::{BTreeSet, HashSet};
+
+use abstutil::Timer;
+use map_model::{Map, PathStep, RoadID};
+
+use crate::rat_runs::find_rat_runs;
+use crate::{ModalFilters, Neighborhood, NeighborhoodID, Partitioning};
+
+/// How many layouts to check per neighborhood before giving up
+const MAX_EVALUATIONS: usize = 1000;
+
+/// One way of filtering all of the neighborhoods
+pub struct FilterLayout {
+    /// The original filters, plus the new ones
+    pub modal_filters: ModalFilters,
+    /// How many filters were added
+    pub new_filters: usize,
+    /// Rat-runs that couldn't be stopped without filtering a transit route. If the search ran out
+    /// of time, this also includes rat-runs that weren't dealt with yet.
+    pub remaining_rat_runs: usize,
+}
+
+/// A layout for one neighborhood
+struct Candidate {
+    roads: BTreeSet<RoadID>,
+    remaining_rat_runs: usize,
+    num_cells: usize,
+}
+
+impl Candidate {
+    // Lower is better. Fewer cells means less disruption for residents.
+    fn score(&self) -> (usize, usize, usize) {
+        (self.remaining_rat_runs, self.roads.len(), self.num_cells)
+    }
+}
+
+/// Returns up to `max_alternatives` layouts, best first. Existing filters are kept. Neighborhoods
+/// that already have a disconnected cell are left alone.
+pub fn optimize_filters(
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &ModalFilters,
+    neighborhoods: &[NeighborhoodID],
+    max_alternatives: usize,
+    timer: &mut Timer,
+) -> Vec<FilterLayout> {
+    let transit_roads = find_transit_roads(map, timer);
+
+    let mut per_neighborhood: Vec<Vec<Candidate>> = Vec::new();
+    timer.start_iter("optimize filters per neighborhood", neighborhoods.len());
+    for id in neighborhoods {
+        timer.next();
+        let candidates = optimize_neighborhood(
+            map,
+            partitioning,
+            modal_filters,
+            *id,
+            &transit_roads,
+            max_alternatives,
+        );
+        if !candidates.is_empty() {
+            per_neighborhood.push(candidates);
+        }
+    }
+    if per_neighborhood.is_empty() {
+        return Vec::new();
+    }
+
+    // Start with the best choice everywhere. Each alternative swaps in a worse choice for just one
+    // neighborhood.
+    let mut combinations: Vec<Vec<usize>> = vec![vec![0; per_neighborhood.len()]];
+    for (idx, candidates) in per_neighborhood.iter().enumerate() {
+        for choice in 1..candidates.len() {
+            let mut combo = vec![0; per_neighborhood.len()];
+            combo[idx] = choice;
+            combinations.push(combo);
+        }
+    }
+
+    let mut layouts: Vec<FilterLayout> = combinations
+        .into_iter()
+        .map(|combo| {
+            let mut layout = FilterLayout {
+                modal_filters: ModalFilters {
+                    roads: modal_filters.roads.clone(),
+                    intersections: modal_filters.intersections.clone(),
+                    road_filter_types: modal_filters.road_filter_types.clone(),
+                    ..Default::default()
+                },
+                new_filters: 0,
+                remaining_rat_runs: 0,
+            };
+            for (candidates, choice) in per_neighborhood.iter().zip(combo) {
+                let candidate = &candidates[choice];
+                for r in &candidate.roads {
+                    add_filter(map, &mut layout.modal_filters, *r);
+                }
+                layout.new_filters += candidate.roads.len();
+                layout.remaining_rat_runs += candidate.remaining_rat_runs;
+            }
+            layout
+        })
+        .collect();
+    // The sort is stable, so ties keep the order of the combinations
+    layouts.sort_by_key(|layout| (layout.remaining_rat_runs, layout.new_filters));
+    layouts.truncate(max_alternatives);
+    layouts
+}
+
+fn optimize_neighborhood(
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &ModalFilters,
+    id: NeighborhoodID,
+    transit_roads: &BTreeSet<RoadID>,
+    max_alternatives: usize,
+) -> Vec<Candidate> {
+    let neighborhood = Neighborhood::new(map, partitioning, modal_filters, id);
+    if neighborhood.cells.iter().any(|c| c.is_disconnected()) {
+        warn!(
+            "Not optimizing {:?}; it already has a disconnected cell",
+            id
+        );
+        return Vec::new();
+    }
+
+    let mut solutions: Vec<Candidate> = Vec::new();
+    // If the search runs out of time, settle for the layout that stops the most rat-runs
+    let mut best_partial: Option<Candidate> = None;
+    let mut seen: HashSet<BTreeSet<RoadID>> = HashSet::new();
+    let mut frontier = vec![BTreeSet::new()];
+    let mut evaluations = 0;
+
+    'search: while !frontier.is_empty() {
+        let mut next = Vec::new();
+        for roads in frontier {
+            if evaluations == MAX_EVALUATIONS {
+                warn!(
+                    "Optimizing {:?} ran out of time after checking {} layouts",
+                    id, evaluations
+                );
+                break 'search;
+            }
+            evaluations += 1;
+
+            let evaluation = match evaluate(map, partitioning, modal_filters, id, &roads) {
+                Some(evaluation) => evaluation,
+                None => {
+                    continue;
+                }
+            };
+            let candidate = Candidate {
+                roads: roads.clone(),
+                remaining_rat_runs: evaluation.rat_runs.len(),
+                num_cells: evaluation.num_cells,
+            };
+
+            // Which roads could stop each rat-run?
+            let choices: Vec<Vec<RoadID>> = evaluation
+                .rat_runs
+                .into_iter()
+                .map(|rat_run| {
+                    rat_run
+                        .into_iter()
+                        .filter(|r| !transit_roads.contains(r))
+                        .collect::<Vec<_>>()
+                })
+                .filter(|choices| !choices.is_empty())
+                .collect();
+            if choices.is_empty() {
+                solutions.push(candidate);
+                continue;
+            }
+            if best_partial
+                .as_ref()
+                .map(|best| candidate.score() < best.score())
+                .unwrap_or(true)
+            {
+                best_partial = Some(candidate);
+            }
+
+            for r in choices.iter().min_by_key(|choices| choices.len()).unwrap() {
+                let mut new_roads = roads.clone();
+                new_roads.insert(*r);
+                if seen.insert(new_roads.clone()) {
+                    next.push(new_roads);
+                }
+            }
+        }
+        if solutions.len() >= max_alternatives {
+            break;
+        }
+        frontier = next;
+    }
+
+    if solutions.is_empty() {
+        return best_partial.into_iter().collect();
+    }
+    solutions.sort_by_key(|c| c.score());
+    solutions.truncate(max_alternatives);
+    solutions
+}
+
+struct Evaluation {
+    /// Per rat-run, the interior roads it crosses
+    rat_runs: Vec<Vec<RoadID>>,
+    num_cells: usize,
+}
+
+/// Returns None if the new filters disconnect a cell
+fn evaluate(
+    map: &Map,
+    partitioning: &Partitioning,
+    modal_filters: &ModalFilters,
+    id: NeighborhoodID,
+    roads: &BTreeSet<RoadID>,
+) -> Option<Evaluation> {
+    // Don't clone the edit history
+    let mut filters = ModalFilters {
+        roads: modal_filters.roads.clone(),
+        intersections: modal_filters.intersections.clone(),
+        road_filter_types: modal_filters.road_filter_types.clone(),
+        ..Default::default()
+    };
+    for r in roads {
+        add_filter(map, &mut filters, *r);
+    }
+
+    let neighborhood = Neighborhood::new(map, partitioning, &filters, id);
+    if neighborhood.cells.iter().any(|c| c.is_disconnected()) {
+        return None;
+    }
+    // This spams too many logs, and can't be used within a start_iter anyway
+    let rat_runs = find_rat_runs(map, &neighborhood, &filters, &mut Timer::throwaway());
+    Some(Evaluation {
+        rat_runs: rat_runs
+            .paths
+            .iter()
+            .map(|path| {
+                let mut roads = Vec::new();
+                for step in path.get_steps() {
+                    if let PathStep::Lane(l) = step {
+                        if neighborhood.orig_perimeter.interior.contains(&l.road)
+                            && !filters.roads.contains_key(&l.road)
+                            && !roads.contains(&l.road)
+                        {
+                            roads.push(l.road);
+                        }
+                    }
+                }
+                roads
+            })
+            .collect(),
+        num_cells: neighborhood.cells.len(),
+    })
+}
+
+fn add_filter(map: &Map, modal_filters: &mut ModalFilters, r: RoadID) {
+    modal_filters.roads.insert(r, map.get_r(r).length() / 2.0);
+}
+
+/// Every road that a bus or train route crosses or stops along
+fn find_transit_roads(map: &Map, timer: &mut Timer) -> BTreeSet<RoadID> {
+    let mut roads = BTreeSet::new();
+    timer.start_iter("find roads used by transit", map.all_transit_routes().len());
+    for route in map.all_transit_routes() {
+        timer.next();
+        for req in route.all_path_requests(map) {
+            roads.insert(req.start.lane().road);
+            roads.insert(req.end.lane().road);
+            if let Ok(path) = map.pathfind(req) {
+                for step in path.get_steps() {
+                    if let PathStep::Lane(l) = step {
+                        roads.insert(l.road);
+                    }
+                }
+            }
+        }
+    }
+    roads
+}
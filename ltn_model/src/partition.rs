@@ -5,20 +5,13 @@ use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
 use abstutil::Timer;
+use geom::Polygon;
 use map_model::osm::RoadRank;
-use map_model::{Block, Map, Perimeter, RoadID, RoadSideID};
-use widgetry::Color;
+use map_model::{Block, CommonEndpoint, IdMigration, Map, Perimeter, RoadID, RoadSideID};
 
-use crate::App;
-
-const COLORS: [Color; 6] = [
-    Color::BLUE,
-    Color::YELLOW,
-    Color::GREEN,
-    Color::PURPLE,
-    Color::PINK,
-    Color::ORANGE,
-];
+/// Neighborhoods and cells are colored so that adjacent ones differ, using a palette this big. The
+/// GUI picks the actual colors.
+pub const NUM_COLORS: usize = 6;
 
 /// An opaque ID, won't be contiguous as we adjust boundaries
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -28,14 +21,11 @@ pub struct NeighborhoodID(usize);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct BlockID(usize);
 
-// Some states want this
-impl widgetry::mapspace::ObjectID for NeighborhoodID {}
-impl widgetry::mapspace::ObjectID for BlockID {}
-
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Partitioning {
     pub map: MapName,
-    neighborhoods: BTreeMap<NeighborhoodID, (Block, Color)>,
+    /// Each neighborhood has an index into a palette of `NUM_COLORS`
+    neighborhoods: BTreeMap<NeighborhoodID, (Block, usize)>,
     // The single / unmerged blocks never change
     single_blocks: Vec<Block>,
 
@@ -59,53 +49,41 @@ impl Partitioning {
         }
     }
 
-    pub fn seed_using_heuristics(app: &App, timer: &mut Timer) -> Partitioning {
-        let map = &app.map;
-        timer.start("find single blocks");
-        let mut single_blocks = Vec::new();
-        let mut single_block_perims = Vec::new();
-        for mut perim in Perimeter::find_all_single_blocks(map) {
-            // TODO Some perimeters don't blockify after collapsing dead-ends. So do this upfront,
-            // and separately work on any blocks that don't show up.
-            // https://github.com/a-b-street/abstreet/issues/841
-            perim.collapse_deadends();
-            if let Ok(block) = perim.to_block(map) {
-                single_block_perims.push(block.perimeter.clone());
-                single_blocks.push(block);
-            }
-        }
-        timer.stop("find single blocks");
-
-        timer.start("partition");
-        let partitions = Perimeter::partition_by_predicate(single_block_perims, |r| {
-            // "Interior" roads of a neighborhood aren't classified as arterial
-            map.get_r(r).get_rank() == RoadRank::Local
-        });
-
-        let mut merged = Vec::new();
-        for perimeters in partitions {
-            // If we got more than one result back, merging partially failed. Oh well?
-            merged.extend(Perimeter::merge_all(map, perimeters, false));
-        }
-        timer.stop("partition");
+    /// Used to upgrade proposals saved in an old format. The neighborhoods are colored again.
+    pub(crate) fn from_parts(
+        map: MapName,
+        neighborhoods: BTreeMap<NeighborhoodID, Block>,
+        single_blocks: Vec<Block>,
+        neighborhood_id_counter: usize,
+        block_to_neighborhood: BTreeMap<BlockID, NeighborhoodID>,
+    ) -> Partitioning {
+        let mut p = Partitioning {
+            map,
+            neighborhoods: neighborhoods
+                .into_iter()
+                .map(|(id, block)| (id, (block, 0)))
+                .collect(),
+            single_blocks,
 
-        timer.start_iter("blockify", merged.len());
-        let mut blocks = Vec::new();
-        for perimeter in merged {
-            timer.next();
-            match perimeter.to_block(map) {
-                Ok(block) => {
-                    blocks.push(block);
-                }
-                Err(err) => {
-                    warn!("Failed to make a block from a merged perimeter: {}", err);
-                }
-            }
-        }
+            neighborhood_id_counter,
+
+            block_to_neighborhood,
+        };
+        p.recalculate_coloring();
+        p
+    }
+
+    pub fn seed_using_heuristics(map: &Map, timer: &mut Timer) -> Partitioning {
+        let single_blocks = find_single_blocks(map, timer);
+        let blocks = merge_using_heuristics(
+            map,
+            single_blocks.iter().map(|b| b.perimeter.clone()).collect(),
+            timer,
+        );
 
         let mut neighborhoods = BTreeMap::new();
         for block in blocks {
-            neighborhoods.insert(NeighborhoodID(neighborhoods.len()), (block, Color::RED));
+            neighborhoods.insert(NeighborhoodID(neighborhoods.len()), (block, 0));
         }
         let neighborhood_id_counter = neighborhoods.len();
         let mut p = Partitioning {
@@ -134,6 +112,155 @@ impl Partitioning {
         p
     }
 
+    /// Recreates neighborhoods from their boundaries, such as ones saved from an older version of
+    /// the map. Every single block is assigned to the boundary containing it, and blocks outside
+    /// all of the boundaries are grouped using the usual heuristics. Returns a description of
+    /// every boundary that couldn't be matched exactly.
+    pub fn from_boundaries(
+        map: &Map,
+        boundaries: &[Polygon],
+        timer: &mut Timer,
+    ) -> (Partitioning, Vec<String>) {
+        let single_blocks = find_single_blocks(map, timer);
+        let mut problems = Vec::new();
+        let mut claimed = BTreeSet::new();
+        let mut blocks = Vec::new();
+        for (idx, boundary) in boundaries.iter().enumerate() {
+            let mut members = Vec::new();
+            for (block_idx, block) in single_blocks.iter().enumerate() {
+                if !claimed.contains(&block_idx) && boundary.contains_pt(block.polygon.polylabel())
+                {
+                    claimed.insert(block_idx);
+                    members.push(block_idx);
+                }
+            }
+            if members.is_empty() {
+                problems.push(format!(
+                    "Neighborhood #{} doesn't contain any blocks anymore",
+                    idx + 1
+                ));
+                continue;
+            }
+
+            let perimeters = members
+                .iter()
+                .map(|b| single_blocks[*b].perimeter.clone())
+                .collect();
+            let merged: Result<Vec<Block>> = Perimeter::merge_all(map, perimeters, false)
+                .into_iter()
+                .map(|perim| perim.to_block(map))
+                .collect();
+            match merged {
+                Ok(merged) => {
+                    if merged.len() > 1 {
+                        problems.push(format!(
+                            "Neighborhood #{} was split into {} pieces",
+                            idx + 1,
+                            merged.len()
+                        ));
+                    }
+                    blocks.extend(merged);
+                }
+                Err(err) => {
+                    problems.push(format!(
+                        "Neighborhood #{} couldn't be merged, so each of its blocks is separate: {}",
+                        idx + 1,
+                        err
+                    ));
+                    blocks.extend(members.iter().map(|b| single_blocks[*b].clone()));
+                }
+            }
+        }
+
+        let unclaimed = single_blocks
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !claimed.contains(idx))
+            .map(|(_, block)| block.perimeter.clone())
+            .collect();
+        blocks.extend(merge_using_heuristics(map, unclaimed, timer));
+
+        let mut neighborhoods = BTreeMap::new();
+        for block in blocks {
+            neighborhoods.insert(NeighborhoodID(neighborhoods.len()), (block, 0));
+        }
+        let mut p = Partitioning {
+            map: map.get_name().clone(),
+            neighborhood_id_counter: neighborhoods.len(),
+            neighborhoods,
+            single_blocks,
+
+            block_to_neighborhood: BTreeMap::new(),
+        };
+        for id in p.all_block_ids() {
+            let neighborhood = match p.neighborhood_containing(id) {
+                Some(neighborhood) => neighborhood,
+                None => {
+                    // Merging the leftover blocks partly failed. Keep this block by itself, so
+                    // nothing downstream breaks.
+                    let neighborhood = NeighborhoodID(p.neighborhood_id_counter);
+                    p.neighborhood_id_counter += 1;
+                    p.neighborhoods
+                        .insert(neighborhood, (p.get_block(id).clone(), 0));
+                    neighborhood
+                }
+            };
+            p.block_to_neighborhood.insert(id, neighborhood);
+        }
+
+        p.recalculate_coloring();
+        (p, problems)
+    }
+
+    /// Translate a partitioning made on an old version of a map to a new version. If any block's
+    /// perimeter can't be traced in the new map, the whole operation fails.
+    pub fn migrate(self, migration: &IdMigration, map: &Map) -> Result<Partitioning> {
+        let migrate_block = |block: Block| -> Result<Block> {
+            let mut roads = Vec::new();
+            for side in block.perimeter.roads {
+                roads.push(
+                    migration
+                        .road_side(side)
+                        .ok_or_else(|| anyhow!("{} doesn't exist anymore", side.road))?,
+                );
+            }
+            // If a road was split in the new map, the perimeter has a gap
+            for pair in roads.windows(2) {
+                let (r1, r2) = (map.get_r(pair[0].road), map.get_r(pair[1].road));
+                if r1.id != r2.id && matches!(r1.common_endpoint(r2), CommonEndpoint::None) {
+                    bail!("The perimeter between {} and {} has a gap", r1.id, r2.id);
+                }
+            }
+            let mut interior = BTreeSet::new();
+            for r in block.perimeter.interior {
+                interior.insert(
+                    migration
+                        .r(r)
+                        .ok_or_else(|| anyhow!("{} doesn't exist anymore", r))?,
+                );
+            }
+            Perimeter { roads, interior }.to_block(map)
+        };
+
+        let mut neighborhoods = BTreeMap::new();
+        for (id, (block, color)) in self.neighborhoods {
+            neighborhoods.insert(id, (migrate_block(block)?, color));
+        }
+        let mut single_blocks = Vec::new();
+        for block in self.single_blocks {
+            single_blocks.push(migrate_block(block)?);
+        }
+        Ok(Partitioning {
+            map: map.get_name().clone(),
+            neighborhoods,
+            single_blocks,
+
+            neighborhood_id_counter: self.neighborhood_id_counter,
+
+            block_to_neighborhood: self.block_to_neighborhood,
+        })
+    }
+
     /// True if the coloring changed
     pub fn recalculate_coloring(&mut self) -> bool {
         let perims: Vec<Perimeter> = self
@@ -141,13 +268,13 @@ impl Partitioning {
             .values()
             .map(|pair| pair.0.perimeter.clone())
             .collect();
-        let colors = Perimeter::calculate_coloring(&perims, COLORS.len())
+        let colors = Perimeter::calculate_coloring(&perims, NUM_COLORS)
             .unwrap_or_else(|| (0..perims.len()).collect());
-        let orig_coloring: Vec<Color> = self.neighborhoods.values().map(|pair| pair.1).collect();
+        let orig_coloring: Vec<usize> = self.neighborhoods.values().map(|pair| pair.1).collect();
         for (pair, color_idx) in self.neighborhoods.values_mut().zip(colors.into_iter()) {
-            pair.1 = COLORS[color_idx % COLORS.len()];
+            pair.1 = color_idx % NUM_COLORS;
         }
-        let new_coloring: Vec<Color> = self.neighborhoods.values().map(|pair| pair.1).collect();
+        let new_coloring: Vec<usize> = self.neighborhoods.values().map(|pair| pair.1).collect();
         orig_coloring != new_coloring
     }
 
@@ -219,7 +346,7 @@ impl Partitioning {
             self.neighborhood_id_counter += 1;
             // Temporary color
             self.neighborhoods
-                .insert(new_neighborhood, (split_piece, Color::RED));
+                .insert(new_neighborhood, (split_piece, 0));
         }
         if new_splits {
             // We need to update the owner of all single blocks in these new pieces
@@ -275,7 +402,7 @@ impl Partitioning {
         self.neighborhood_id_counter += 1;
         // Temporary color
         self.neighborhoods
-            .insert(new_owner, (self.get_block(id).clone(), Color::RED));
+            .insert(new_owner, (self.get_block(id).clone(), 0));
         let result = self.transfer_block(map, id, old_owner, new_owner);
         if result.is_err() {
             // Revert the change above!
@@ -291,11 +418,11 @@ impl Partitioning {
         &self.neighborhoods[&id].0
     }
 
-    pub fn neighborhood_color(&self, id: NeighborhoodID) -> Color {
+    pub fn neighborhood_color(&self, id: NeighborhoodID) -> usize {
         self.neighborhoods[&id].1
     }
 
-    pub fn all_neighborhoods(&self) -> &BTreeMap<NeighborhoodID, (Block, Color)> {
+    pub fn all_neighborhoods(&self) -> &BTreeMap<NeighborhoodID, (Block, usize)> {
         &self.neighborhoods
     }
 
@@ -363,3 +490,49 @@ impl Partitioning {
         Ok(blocks)
     }
 }
+
+fn find_single_blocks(map: &Map, timer: &mut Timer) -> Vec<Block> {
+    timer.start("find single blocks");
+    let mut single_blocks = Vec::new();
+    for mut perim in Perimeter::find_all_single_blocks(map) {
+        // TODO Some perimeters don't blockify after collapsing dead-ends. So do this upfront, and
+        // separately work on any blocks that don't show up.
+        // https://github.com/a-b-street/abstreet/issues/841
+        perim.collapse_deadends();
+        if let Ok(block) = perim.to_block(map) {
+            single_blocks.push(block);
+        }
+    }
+    timer.stop("find single blocks");
+    single_blocks
+}
+
+fn merge_using_heuristics(map: &Map, perimeters: Vec<Perimeter>, timer: &mut Timer) -> Vec<Block> {
+    timer.start("partition");
+    let partitions = Perimeter::partition_by_predicate(perimeters, |r| {
+        // "Interior" roads of a neighborhood aren't classified as arterial
+        map.get_r(r).get_rank() == RoadRank::Local
+    });
+
+    let mut merged = Vec::new();
+    for perimeters in partitions {
+        // If we got more than one result back, merging partially failed. Oh well?
+        merged.extend(Perimeter::merge_all(map, perimeters, false));
+    }
+    timer.stop("partition");
+
+    timer.start_iter("blockify", merged.len());
+    let mut blocks = Vec::new();
+    for perimeter in merged {
+        timer.next();
+        match perimeter.to_block(map) {
+            Ok(block) => {
+                blocks.push(block);
+            }
+            Err(err) => {
+                warn!("Failed to make a block from a merged perimeter: {}", err);
+            }
+        }
+    }
+    blocks
+}
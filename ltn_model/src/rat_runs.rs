@@ -7,7 +7,7 @@ use map_model::{
 };
 
 use super::{Cell, Neighborhood};
-use crate::App;
+use crate::ModalFilters;
 
 pub struct RatRuns {
     pub paths: Vec<Path>,
@@ -28,9 +28,12 @@ impl RatRuns {
     }
 }
 
-pub fn find_rat_runs(app: &App, neighborhood: &Neighborhood, timer: &mut Timer) -> RatRuns {
-    let map = &app.map;
-    let modal_filters = &app.session.modal_filters;
+pub fn find_rat_runs(
+    map: &Map,
+    neighborhood: &Neighborhood,
+    modal_filters: &ModalFilters,
+    timer: &mut Timer,
+) -> RatRuns {
     // The overall approach: look for all possible paths from an entrance to an exit, only if they
     // connect to different major roads.
     //
@@ -58,7 +61,7 @@ pub fn find_rat_runs(app: &App, neighborhood: &Neighborhood, timer: &mut Timer)
     }
 
     let mut params = map.routing_params().clone();
-    modal_filters.update_routing_params(&mut params);
+    modal_filters.update_routing_params(&mut params, PathConstraints::Car);
     let paths: Vec<Path> = timer
         .parallelize(
             "calculate paths between entrances and exits",
@@ -0,0 +1,74 @@
+//! Older versions of the LTN tool saved proposals in a binary format, referring directly to
+//! RoadIDs and blocks. Keep the old structures around just to read and upgrade those files.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use abstio::MapName;
+use geom::Distance;
+use map_model::{Block, IntersectionID, RoadID};
+
+use crate::{BlockID, DiagonalFilter, ModalFilters, NeighborhoodID, Partitioning, Proposal};
+
+/// Binary proposals used a different extension
+pub fn binary_path(json_path: &str) -> String {
+    format!("{}.bin", json_path.trim_end_matches(".json"))
+}
+
+/// Before filter types existed, and when each neighborhood stored the exact color to draw it
+#[derive(Deserialize)]
+pub struct ProposalV1 {
+    map: MapName,
+    name: String,
+    abst_version: String,
+
+    partitioning: PartitioningV1,
+    modal_filters: ModalFiltersV1,
+}
+
+#[derive(Deserialize)]
+struct PartitioningV1 {
+    map: MapName,
+    // The color was a widgetry::Color, which has the same layout
+    neighborhoods: BTreeMap<NeighborhoodID, (Block, [f32; 4])>,
+    single_blocks: Vec<Block>,
+    neighborhood_id_counter: usize,
+    block_to_neighborhood: BTreeMap<BlockID, NeighborhoodID>,
+}
+
+#[derive(Deserialize)]
+struct ModalFiltersV1 {
+    roads: BTreeMap<RoadID, Distance>,
+    intersections: BTreeMap<IntersectionID, DiagonalFilter>,
+}
+
+impl ProposalV1 {
+    /// All of the old road filters physically block vehicles. The neighborhood colors are
+    /// recalculated.
+    pub fn upgrade(self) -> Proposal {
+        let p = self.partitioning;
+        let partitioning = Partitioning::from_parts(
+            p.map,
+            p.neighborhoods
+                .into_iter()
+                .map(|(id, (block, _))| (id, block))
+                .collect(),
+            p.single_blocks,
+            p.neighborhood_id_counter,
+            p.block_to_neighborhood,
+        );
+        Proposal {
+            map: self.map,
+            name: self.name,
+            abst_version: self.abst_version,
+
+            partitioning,
+            modal_filters: ModalFilters {
+                roads: self.modal_filters.roads,
+                intersections: self.modal_filters.intersections,
+                ..Default::default()
+            },
+        }
+    }
+}
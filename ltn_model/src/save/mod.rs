@@ -0,0 +1,125 @@
+mod legacy;
+mod perma;
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstio::MapName;
+use abstutil::Timer;
+use map_model::{IdMigration, Map};
+
+pub use self::perma::{
+    import_geojson_filters, PermanentDiagonalFilter, PermanentProposal, PermanentRoadFilter,
+};
+use crate::{ModalFilters, Partitioning};
+
+/// Captures all of the edits somebody makes to a map in the LTN tool. Note this separate from
+/// `map_model::MapEdits`.
+///
+/// This refers to RoadIDs and blocks, which change whenever the map or the LTN blockfinding
+/// algorithm changes, so it's only used in memory. Proposals are saved as a `PermanentProposal`.
+#[derive(Serialize, Deserialize)]
+pub struct Proposal {
+    pub map: MapName,
+    pub name: String,
+    pub abst_version: String,
+
+    pub partitioning: Partitioning,
+    pub modal_filters: ModalFilters,
+}
+
+impl Proposal {
+    /// Translate a proposal made on an old version of a map to a new version. If the
+    /// neighborhood boundaries can't be carried over, they'll be regenerated from scratch the
+    /// next time the proposal is loaded. Returns a description of everything lost.
+    pub fn migrate(self, migration: &IdMigration, map: &Map) -> (Proposal, Vec<String>) {
+        let (modal_filters, mut problems) = self.modal_filters.migrate(migration, map);
+        let partitioning = match self.partitioning.migrate(migration, map) {
+            Ok(partitioning) => partitioning,
+            Err(err) => {
+                problems.push(format!(
+                    "Neighborhood boundaries couldn't be carried over and will be regenerated: {}",
+                    err
+                ));
+                Partitioning::empty()
+            }
+        };
+        (
+            Proposal {
+                map: map.get_name().clone(),
+                name: self.name,
+                abst_version: self.abst_version,
+
+                partitioning,
+                modal_filters,
+            },
+            problems,
+        )
+    }
+
+    /// Loads a proposal from any path, without any UI. JSON files are treated as a
+    /// `PermanentProposal`, and anything that can't be matched is skipped with a warning. Anything
+    /// else is a proposal saved in the old binary format; if its neighborhood boundaries don't
+    /// match the map anymore, they're regenerated.
+    pub fn load_from_file(map: &Map, path: String, timer: &mut Timer) -> Result<Proposal> {
+        if path.ends_with(".json") {
+            let proposal: PermanentProposal = abstio::maybe_read_json(path, timer)?;
+            let (proposal, problems) = proposal.resolve(map, timer)?;
+            for problem in problems {
+                warn!("{}", problem);
+            }
+            return Ok(proposal);
+        }
+
+        let mut proposal = Proposal::read_legacy_binary(path, timer)?;
+        if &proposal.map != map.get_name() {
+            bail!(
+                "Proposal is for {}, not {}",
+                proposal.map.describe(),
+                map.get_name().describe()
+            );
+        }
+        if &proposal.partitioning.map != map.get_name() {
+            proposal.partitioning = Partitioning::seed_using_heuristics(map, timer);
+        }
+        Ok(proposal)
+    }
+
+    /// Loads a proposal saved for this map by name, in either format.
+    pub fn load_by_name(map: &Map, name: &str, timer: &mut Timer) -> Result<Proposal> {
+        let path = abstio::path_ltn_proposals(map.get_name(), name);
+        if abstio::file_exists(&path) {
+            Proposal::load_from_file(map, path, timer)
+        } else {
+            Proposal::load_from_file(map, legacy::binary_path(&path), timer)
+        }
+    }
+
+    /// Reads a proposal saved in the old binary format, without matching it to any map.
+    fn read_legacy_binary(path: String, timer: &mut Timer) -> Result<Proposal> {
+        let proposal: legacy::ProposalV1 = abstio::maybe_read_binary(path, timer)?;
+        Ok(proposal.upgrade())
+    }
+
+    /// Saves a proposal under its name, as a `PermanentProposal`. Returns a description of every
+    /// filter that couldn't be saved.
+    pub fn save(&self, map: &Map) -> Vec<String> {
+        let (proposal, problems) = self.to_permanent(map);
+        abstio::write_json(
+            abstio::path_ltn_proposals(map.get_name(), &self.name),
+            &proposal,
+        );
+        problems
+    }
+
+    /// The names of all proposals saved for a map, in either format
+    pub fn list_all(name: &MapName) -> Vec<String> {
+        abstio::list_all_objects(abstio::path_all_ltn_proposals(name))
+            .into_iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
@@ -0,0 +1,358 @@
+//! Proposals refer to roads, intersections, and blocks by IDs that change whenever the map is
+//! rebuilt or the blockfinding algorithm changes. To share a proposal or keep it around for a long
+//! time, it's converted to this format instead. Roads and intersections are referenced by OSM IDs,
+//! filters also remember where they are, and neighborhoods are stored as boundary polygons.
+//! Loading the proposal resolves everything against the current map again.
+
+use anyhow::Result;
+use geojson::{GeoJson, Value};
+use serde::{Deserialize, Serialize};
+
+use abstio::MapName;
+use abstutil::Timer;
+use geom::{Distance, LonLat, Pt2D, Ring};
+use map_model::raw::OriginalRoad;
+use map_model::{osm, Map, RoadID};
+
+use crate::{DiagonalFilter, FilterType, ModalFilters, Partitioning, Proposal};
+
+/// Increase this when the format changes, and handle the older versions in `resolve`
+const VERSION: usize = 1;
+
+/// Filters further than this from a road or intersection can't be matched
+const MAX_SNAP_DISTANCE: Distance = Distance::const_meters(50.0);
+
+#[derive(Serialize, Deserialize)]
+pub struct PermanentProposal {
+    pub version: usize,
+    pub map: MapName,
+    pub name: String,
+    pub abst_version: String,
+
+    /// The outer boundary of every neighborhood
+    pub neighborhoods: Vec<Vec<LonLat>>,
+    pub road_filters: Vec<PermanentRoadFilter>,
+    pub diagonal_filters: Vec<PermanentDiagonalFilter>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PermanentRoadFilter {
+    /// The OSM way, and the nodes at each end of the piece of it that the filter is on
+    pub road: OriginalRoad,
+    /// The distance from `road.i1`
+    pub dist: Distance,
+    /// Where the filter is, in case the OSM way is split up differently now
+    pub pt: LonLat,
+    /// If None, the filter physically blocks all vehicles, all day
+    pub filter_type: Option<FilterType>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PermanentDiagonalFilter {
+    pub i: osm::NodeID,
+    /// `r2` immediately follows `r1` in clockwise order
+    pub r1: OriginalRoad,
+    pub r2: OriginalRoad,
+}
+
+impl Proposal {
+    /// Also returns a description of every filter that couldn't be converted.
+    pub fn to_permanent(&self, map: &Map) -> (PermanentProposal, Vec<String>) {
+        let gps_bounds = map.get_gps_bounds();
+        let mut problems = Vec::new();
+
+        let mut road_filters = Vec::new();
+        for (r, dist) in &self.modal_filters.roads {
+            let road = map.get_r(*r);
+            match road.center_pts.dist_along(*dist) {
+                Ok((pt, _)) => {
+                    road_filters.push(PermanentRoadFilter {
+                        road: road.orig_id,
+                        dist: *dist,
+                        pt: pt.to_gps(gps_bounds),
+                        filter_type: self.modal_filters.road_filter_types.get(r).cloned(),
+                    });
+                }
+                Err(err) => {
+                    problems.push(format!("Filter on {} couldn't be saved: {}", r, err));
+                }
+            }
+        }
+
+        let proposal = PermanentProposal {
+            version: VERSION,
+            map: self.map.clone(),
+            name: self.name.clone(),
+            abst_version: self.abst_version.clone(),
+
+            neighborhoods: self
+                .partitioning
+                .all_neighborhoods()
+                .values()
+                .filter_map(|(block, _)| block.polygon.get_outer_ring())
+                .map(|ring| gps_bounds.convert_back(ring.points()))
+                .collect(),
+            road_filters,
+            diagonal_filters: self
+                .modal_filters
+                .intersections
+                .values()
+                .map(|filter| PermanentDiagonalFilter {
+                    i: map.get_i(filter.i).orig_id,
+                    r1: map.get_r(filter.r1).orig_id,
+                    r2: map.get_r(filter.r2).orig_id,
+                })
+                .collect(),
+        };
+        (proposal, problems)
+    }
+}
+
+impl PermanentProposal {
+    /// Matches everything against the current map. Neighborhoods and filters that can't be
+    /// matched are skipped, and a description of each is returned.
+    pub fn resolve(self, map: &Map, timer: &mut Timer) -> Result<(Proposal, Vec<String>)> {
+        if self.version > VERSION {
+            bail!(
+                "This proposal uses version {} of the format, but only version {} is understood",
+                self.version,
+                VERSION
+            );
+        }
+        if &self.map != map.get_name() {
+            bail!(
+                "Proposal is for {}, not {}",
+                self.map.describe(),
+                map.get_name().describe()
+            );
+        }
+
+        let gps_bounds = map.get_gps_bounds();
+        let mut problems = Vec::new();
+        let mut boundaries = Vec::new();
+        for (idx, pts) in self.neighborhoods.iter().enumerate() {
+            match gps_bounds
+                .try_convert(pts)
+                .ok_or_else(|| anyhow!("it's outside the map"))
+                .and_then(Ring::new)
+            {
+                Ok(ring) => {
+                    boundaries.push(ring.into_polygon());
+                }
+                Err(err) => {
+                    problems.push(format!(
+                        "Neighborhood #{} has an invalid boundary: {}",
+                        idx + 1,
+                        err
+                    ));
+                }
+            }
+        }
+        let (partitioning, partitioning_problems) =
+            Partitioning::from_boundaries(map, &boundaries, timer);
+        problems.extend(partitioning_problems);
+
+        let mut modal_filters = ModalFilters::default();
+        for filter in self.road_filters {
+            match filter.resolve(map) {
+                Ok((r, dist)) => {
+                    modal_filters.roads.insert(r, dist);
+                    if let Some(filter_type) = filter.filter_type {
+                        modal_filters.road_filter_types.insert(r, filter_type);
+                    }
+                }
+                Err(err) => {
+                    problems.push(format!(
+                        "Filter on {} couldn't be matched: {}",
+                        filter.road, err
+                    ));
+                }
+            }
+        }
+        for filter in self.diagonal_filters {
+            match filter.resolve(map) {
+                Ok(filter) => {
+                    modal_filters.intersections.insert(filter.i, filter);
+                }
+                Err(err) => {
+                    problems.push(format!(
+                        "Diagonal filter at {} couldn't be matched: {}",
+                        filter.i, err
+                    ));
+                }
+            }
+        }
+
+        Ok((
+            Proposal {
+                map: self.map,
+                name: self.name,
+                abst_version: self.abst_version,
+
+                partitioning,
+                modal_filters,
+            },
+            problems,
+        ))
+    }
+}
+
+impl PermanentRoadFilter {
+    fn resolve(&self, map: &Map) -> Result<(RoadID, Distance)> {
+        if let Ok(r) = map.find_r_by_osm_id(self.road) {
+            if self.dist <= map.get_r(r).length() {
+                return Ok((r, self.dist));
+            }
+        }
+        // The way might be split up differently now, so find the closest piece of it
+        snap_to_road(
+            map,
+            Some(self.road.osm_way_id),
+            self.pt.to_pt(map.get_gps_bounds()),
+        )
+    }
+}
+
+impl PermanentDiagonalFilter {
+    fn resolve(&self, map: &Map) -> Result<DiagonalFilter> {
+        let i = map.find_i_by_osm_id(self.i)?;
+        let r1 = map.find_r_by_osm_id(self.r1)?;
+        let r2 = map.find_r_by_osm_id(self.r2)?;
+        DiagonalFilter::between(map, i, r1, r2)
+            .ok_or_else(|| anyhow!("it's not a 4-way intersection with those roads anymore"))
+    }
+}
+
+/// Reads filters from GeoJSON, like the files written by "Export to GeoJSON" or drawn in another
+/// tool. Road filters are points or lines crossing a road. If they have an `osm_way_id` property,
+/// only roads along that way are considered, and a `filter type` property like "bus gate" sets
+/// the type. Features with `"type": "diagonal filter"` are matched to the closest 4-way
+/// intersection. Other features, like neighborhoods, are skipped. Returns a description of
+/// everything that couldn't be matched.
+pub fn import_geojson_filters(map: &Map, raw: &str) -> Result<(ModalFilters, Vec<String>)> {
+    let features = match raw.parse::<GeoJson>()? {
+        GeoJson::FeatureCollection(collection) => collection.features,
+        GeoJson::Feature(feature) => vec![feature],
+        GeoJson::Geometry(_) => bail!("Expected features, not just a geometry"),
+    };
+
+    let gps_bounds = map.get_gps_bounds();
+    let mut modal_filters = ModalFilters::default();
+    let mut problems = Vec::new();
+    for (idx, feature) in features.into_iter().enumerate() {
+        let diagonal = match feature.property("type").and_then(|x| x.as_str()) {
+            None | Some("road filter") => false,
+            Some("diagonal filter") => true,
+            Some(_) => {
+                continue;
+            }
+        };
+        let pts: Vec<LonLat> = match feature.geometry.as_ref().map(|g| &g.value) {
+            Some(Value::Point(pt)) => vec![LonLat::new(pt[0], pt[1])],
+            Some(Value::LineString(line)) => {
+                line.iter().map(|pt| LonLat::new(pt[0], pt[1])).collect()
+            }
+            _ => {
+                problems.push(format!("Feature #{} isn't a point or line", idx + 1));
+                continue;
+            }
+        };
+        let center = match gps_bounds.try_convert(&pts) {
+            Some(pts) => Pt2D::center(&pts),
+            None => {
+                problems.push(format!("Feature #{} is outside the map", idx + 1));
+                continue;
+            }
+        };
+
+        if diagonal {
+            match snap_to_diagonal_filter(map, center) {
+                Ok(filter) => {
+                    modal_filters.intersections.insert(filter.i, filter);
+                }
+                Err(err) => {
+                    problems.push(format!(
+                        "Diagonal filter #{} couldn't be matched: {}",
+                        idx + 1,
+                        err
+                    ));
+                }
+            }
+            continue;
+        }
+
+        let way = feature
+            .property("osm_way_id")
+            .and_then(|x| x.as_i64())
+            .map(osm::WayID);
+        match snap_to_road(map, way, center) {
+            Ok((r, dist)) => {
+                modal_filters.roads.insert(r, dist);
+                if let Some(filter_type) = feature
+                    .property("filter type")
+                    .and_then(|x| x.as_str())
+                    .and_then(|x| FilterType::all().into_iter().find(|ft| ft.describe() == x))
+                {
+                    modal_filters.road_filter_types.insert(r, filter_type);
+                }
+            }
+            Err(err) => {
+                problems.push(format!(
+                    "Road filter #{} couldn't be matched: {}",
+                    idx + 1,
+                    err
+                ));
+            }
+        }
+    }
+    Ok((modal_filters, problems))
+}
+
+/// Finds the closest point to `pt` on any road, or just the roads along one OSM way
+fn snap_to_road(map: &Map, way: Option<osm::WayID>, pt: Pt2D) -> Result<(RoadID, Distance)> {
+    let mut best: Option<(Distance, RoadID, Distance)> = None;
+    for road in map.all_roads() {
+        if way
+            .map(|way| road.orig_id.osm_way_id != way)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let on_road = road.center_pts.project_pt(pt);
+        let dist_away = on_road.dist_to(pt);
+        if best.map(|(d, _, _)| dist_away < d).unwrap_or(true) {
+            if let Some((dist, _)) = road.center_pts.dist_along_of_point(on_road) {
+                best = Some((dist_away, road.id, dist));
+            }
+        }
+    }
+    let (dist_away, r, dist) = best.ok_or_else(|| anyhow!("there's no matching road"))?;
+    if dist_away > MAX_SNAP_DISTANCE {
+        bail!("the closest road is {} away", dist_away);
+    }
+    Ok((r, dist))
+}
+
+/// Finds the diagonal filter closest to `pt`
+fn snap_to_diagonal_filter(map: &Map, pt: Pt2D) -> Result<DiagonalFilter> {
+    let i = map
+        .all_intersections()
+        .iter()
+        .filter(|i| i.roads.len() == 4)
+        .min_by_key(|i| i.polygon.center().dist_to(pt))
+        .ok_or_else(|| anyhow!("there are no 4-way intersections"))?;
+    let dist_away = i.polygon.center().dist_to(pt);
+    if dist_away > MAX_SNAP_DISTANCE {
+        bail!("the closest 4-way intersection is {} away", dist_away);
+    }
+
+    // There are only two different filters at a 4-way
+    let roads = i.get_roads_sorted_by_incoming_angle(map);
+    (0..2)
+        .filter_map(|idx| DiagonalFilter::between(map, i.id, roads[idx], roads[idx + 1]))
+        .min_by_key(|filter| {
+            let line = filter.geometry(map);
+            Pt2D::center(&[line.pt1(), line.pt2()]).dist_to(pt)
+        })
+        .ok_or_else(|| anyhow!("{} can't have a diagonal filter", i.id))
+}
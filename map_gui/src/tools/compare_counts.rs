@@ -1,6 +1,8 @@
+use std::collections::BTreeMap;
+
 use abstutil::prettyprint_usize;
 use geom::{Distance, Histogram, Statistic};
-use map_model::{IntersectionID, RoadID};
+use map_model::{IntersectionID, MovementID, RoadID};
 use synthpop::TrafficCounts;
 use widgetry::mapspace::{ObjectID, ToggleZoomed, ToggleZoomedBuilder, World};
 use widgetry::{Color, EventCtx, GeomBatch, GfxCtx, Key, Line, Text, TextExt, Widget};
@@ -16,6 +18,8 @@ pub struct CompareCounts {
     pub counts_b: TrafficCounts,
     heatmap_b: ToggleZoomed,
     relative_heatmap: ToggleZoomed,
+    /// Names for every movement with turning movement counts
+    movement_labels: BTreeMap<MovementID, String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -43,6 +47,7 @@ impl CompareCounts {
         let heatmap_a = calculate_heatmap(ctx, app, counts_a.clone());
         let heatmap_b = calculate_heatmap(ctx, app, counts_b.clone());
         let relative_heatmap = calculate_relative_heatmap(ctx, app, &counts_a, &counts_b);
+        let movement_labels = movement_labels(app, &counts_a, &counts_b);
 
         CompareCounts {
             layer,
@@ -52,6 +57,7 @@ impl CompareCounts {
             counts_b,
             heatmap_b,
             relative_heatmap,
+            movement_labels,
         }
     }
 
@@ -71,6 +77,7 @@ impl CompareCounts {
         self.heatmap_b = calculate_heatmap(ctx, app, self.counts_b.clone());
         self.relative_heatmap =
             calculate_relative_heatmap(ctx, app, &self.counts_a, &self.counts_b);
+        self.movement_labels = movement_labels(app, &self.counts_a, &self.counts_b);
         if self.layer == Layer::A {
             self.autoselect_layer();
         }
@@ -85,6 +92,7 @@ impl CompareCounts {
             counts_b: TrafficCounts::default(),
             heatmap_b: ToggleZoomed::empty(ctx),
             relative_heatmap: ToggleZoomed::empty(ctx),
+            movement_labels: BTreeMap::new(),
         }
     }
 
@@ -145,6 +153,7 @@ impl CompareCounts {
                     Layer::A => self.counts_a.per_intersection.get(i),
                     Layer::B => self.counts_b.per_intersection.get(i),
                     Layer::Compare => {
+                        g.draw_mouse_tooltip(self.relative_intersection_tooltip(i));
                         return;
                     }
                 },
@@ -154,8 +163,39 @@ impl CompareCounts {
     }
 
     fn relative_road_tooltip(&self, r: RoadID) -> Text {
-        let a = self.counts_a.per_road.get(r);
-        let b = self.counts_b.per_road.get(r);
+        self.relative_tooltip(self.counts_a.per_road.get(r), self.counts_b.per_road.get(r))
+    }
+
+    /// Also shows differences per movement, if there are turning movement counts
+    fn relative_intersection_tooltip(&self, i: IntersectionID) -> Text {
+        let mut txt = self.relative_tooltip(
+            self.counts_a.per_intersection.get(i),
+            self.counts_b.per_intersection.get(i),
+        );
+        let movements_a = self.counts_a.per_movement.totals_at(i);
+        let movements_b = self.counts_b.per_movement.totals_at(i);
+        for (m, a, b) in movements_a.compare(movements_b) {
+            let label = self
+                .movement_labels
+                .get(&m)
+                .cloned()
+                .unwrap_or_else(|| "?".to_string());
+            txt.add_line(Line(format!(
+                "{}: {} vs {}",
+                label,
+                prettyprint_usize(a),
+                prettyprint_usize(b)
+            )));
+            if b > a {
+                txt.append(Line(format!(" (+{})", prettyprint_usize(b - a))).fg(Color::RED));
+            } else if a > b {
+                txt.append(Line(format!(" (-{})", prettyprint_usize(a - b))).fg(Color::GREEN));
+            }
+        }
+        txt
+    }
+
+    fn relative_tooltip(&self, a: usize, b: usize) -> Text {
         let ratio = (b as f64) / (a as f64);
 
         let mut txt = Text::from_multiline(vec![
@@ -269,6 +309,37 @@ fn calculate_relative_heatmap(
     ToggleZoomedBuilder::from(draw_roads).build(ctx)
 }
 
+fn movement_labels(
+    app: &dyn AppLike,
+    counts_a: &TrafficCounts,
+    counts_b: &TrafficCounts,
+) -> BTreeMap<MovementID, String> {
+    let map = app.map();
+    let mut labels = BTreeMap::new();
+    for counts in [counts_a, counts_b] {
+        for (m, _) in counts.per_movement.totals().consume() {
+            if labels.contains_key(&m) {
+                continue;
+            }
+            let label = map
+                .get_i(m.parent)
+                .movements
+                .get(&m)
+                .and_then(|movement| map_model::gmns::movement_txt_id(map, movement))
+                .unwrap_or_else(|| {
+                    format!(
+                        "{} to {}",
+                        map.get_r(m.from.road)
+                            .get_name(app.opts().language.as_ref()),
+                        map.get_r(m.to.road).get_name(app.opts().language.as_ref())
+                    )
+                });
+            labels.insert(m, label);
+        }
+    }
+    labels
+}
+
 fn make_world(ctx: &mut EventCtx, app: &dyn AppLike) -> World<Obj> {
     let mut world = World::bounded(app.map().get_bounds());
     for r in app.map().all_roads() {
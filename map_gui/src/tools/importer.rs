@@ -85,6 +85,7 @@ impl<A: AppLike + 'static> ImportCity<A> {
                         ),
                     ]),
                     Toggle::switch(ctx, "Filter crosswalks", None, false),
+                    Toggle::switch(ctx, "Use separately mapped sidewalks", None, false),
                     Toggle::switch(ctx, "Generate travel demand model (UK only)", None, false),
                 ])
                 .section(ctx),
@@ -130,6 +131,9 @@ impl<A: AppLike + 'static> State<A> for ImportCity<A> {
                     if self.panel.is_checked("Filter crosswalks") {
                         args.push("--filter-crosswalks".to_string());
                     }
+                    if self.panel.is_checked("Use separately mapped sidewalks") {
+                        args.push("--separate-sidewalks".to_string());
+                    }
                     if self
                         .panel
                         .is_checked("Generate travel demand model (UK only)")
@@ -7,10 +7,12 @@ use geom::Polygon;
 use map_model::{IntersectionID, Map, RoadID};
 use widgetry::{lctrl, EventCtx, GfxCtx, Key, Line, Text, Widget};
 
+pub use abstutil::Grid;
+
 pub use self::camera::{CameraState, DefaultMap};
 pub use self::city_picker::CityPicker;
 pub use self::colors::{ColorDiscrete, ColorLegend, ColorNetwork, ColorScale, DivergingScale};
-pub use self::heatmap::{draw_isochrone, make_heatmap, Grid, HeatmapOptions};
+pub use self::heatmap::{draw_isochrone, make_heatmap, HeatmapOptions};
 pub use self::icons::{goal_marker, start_marker};
 pub use self::labels::DrawRoadLabels;
 pub use self::minimap::{Minimap, MinimapControls};
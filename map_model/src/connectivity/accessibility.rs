@@ -0,0 +1,467 @@
+//! A map-wide accessibility index. For every residential building, this finds the travel time to
+//! the closest few amenities of each type, by walking, biking, and transit. Each household also
+//! gets a gravity-based cumulative opportunity score, counting every reachable amenity, but
+//! discounting ones further away.
+//!
+//! Walking takes the same time in both directions, so walking times are measured from each amenity
+//! to every household, to avoid searching from every building. One-way streets make biking
+//! asymmetric, and transit routes often run in only one direction or on a different schedule each
+//! way, so biking and transit times are measured from each household to the amenities.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use geojson::{Feature, FeatureCollection, GeoJson};
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Time};
+
+use crate::connectivity::{self, Spot, TransitCosts, TransitOptions, WalkingOptions};
+use crate::{AmenityType, Building, BuildingID, BuildingType, Map, PathConstraints};
+
+/// How somebody travels to amenities
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Mode {
+    Walk,
+    Bike,
+    Transit,
+}
+
+impl Mode {
+    pub fn all() -> Vec<Mode> {
+        vec![Mode::Walk, Mode::Bike, Mode::Transit]
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Mode::Walk => "walk",
+            Mode::Bike => "bike",
+            Mode::Transit => "transit",
+        }
+    }
+
+    fn times_from(
+        self,
+        map: &Map,
+        starts: Vec<Spot>,
+        opts: &AccessibilityOptions,
+        transit: &mut TransitCosts,
+    ) -> BTreeMap<BuildingID, Duration> {
+        let time_limit = opts.time_limit;
+        let results = match self {
+            Mode::Walk => connectivity::all_walking_costs_from(
+                map,
+                starts,
+                time_limit,
+                WalkingOptions::default(),
+            ),
+            Mode::Bike => {
+                connectivity::all_vehicle_costs_from(map, starts, time_limit, PathConstraints::Bike)
+            }
+            Mode::Transit => connectivity::all_walking_and_transit_costs_from(
+                map,
+                starts,
+                time_limit,
+                WalkingOptions::default(),
+                transit,
+            ),
+        };
+        results.into_iter().collect()
+    }
+}
+
+pub struct AccessibilityOptions {
+    pub modes: Vec<Mode>,
+    /// Find the travel time to this many of the closest amenities of each type
+    pub nearest: usize,
+    /// Ignore amenities further away than this
+    pub time_limit: Duration,
+    /// In the gravity score, an amenity this far away counts half as much as one right next door
+    pub half_life: Duration,
+    /// When transit trips leave the household, and how to wait for vehicles
+    pub transit: TransitOptions,
+}
+
+impl Default for AccessibilityOptions {
+    fn default() -> AccessibilityOptions {
+        AccessibilityOptions {
+            modes: Mode::all(),
+            nearest: 3,
+            time_limit: Duration::minutes(30),
+            half_life: Duration::minutes(10),
+            transit: TransitOptions {
+                departure: Time::START_OF_DAY + Duration::hours(8),
+                exact_waits: false,
+            },
+        }
+    }
+}
+
+pub struct AccessibilityIndex {
+    /// Every residential building
+    pub households: BTreeMap<BuildingID, HouseholdAccess>,
+    pub modes: Vec<Mode>,
+    pub nearest: usize,
+}
+
+pub struct HouseholdAccess {
+    pub residents: usize,
+    /// Per mode and type of amenity, the time to reach the closest amenities, in increasing order.
+    /// Types with nothing reachable within the time limit are missing.
+    pub times: BTreeMap<(Mode, AmenityType), Vec<Duration>>,
+    /// Per mode, the sum over every reachable amenity of `0.5 ^ (time / half_life)`
+    pub score: BTreeMap<Mode, f64>,
+}
+
+impl HouseholdAccess {
+    /// The time to reach the closest amenity of some type, if it's within the time limit
+    pub fn closest(&self, mode: Mode, amenity: AmenityType) -> Option<Duration> {
+        self.times
+            .get(&(mode, amenity))
+            .and_then(|times| times.first().cloned())
+    }
+}
+
+/// A household that gained or lost access to some type of amenity within 15 minutes
+pub struct AccessChange {
+    pub household: BuildingID,
+    pub residents: usize,
+    pub mode: Mode,
+    pub amenity: AmenityType,
+    /// The time to reach the closest amenity, if it's within the time limit
+    pub before: Option<Duration>,
+    pub after: Option<Duration>,
+}
+
+impl AccessChange {
+    pub fn gained(&self) -> bool {
+        self.after
+            .map(|t| t <= Duration::minutes(15))
+            .unwrap_or(false)
+    }
+}
+
+impl AccessibilityIndex {
+    /// Runs one walking search per building with amenities, and one search per household for
+    /// the other modes, so this is slow on large maps.
+    pub fn new(map: &Map, opts: &AccessibilityOptions, timer: &mut Timer) -> AccessibilityIndex {
+        let mut households: BTreeMap<BuildingID, HouseholdAccess> = map
+            .all_buildings()
+            .iter()
+            .filter_map(|b| {
+                residents(b).map(|residents| {
+                    (
+                        b.id,
+                        HouseholdAccess {
+                            residents,
+                            times: BTreeMap::new(),
+                            score: opts.modes.iter().map(|mode| (*mode, 0.0)).collect(),
+                        },
+                    )
+                })
+            })
+            .collect();
+
+        // How many amenities of each type are in each building
+        let mut amenities: BTreeMap<BuildingID, BTreeMap<AmenityType, usize>> = BTreeMap::new();
+        for b in map.all_buildings() {
+            for amenity in &b.amenities {
+                if let Some(category) = AmenityType::categorize(&amenity.amenity_type) {
+                    *amenities
+                        .entry(b.id)
+                        .or_insert_with(BTreeMap::new)
+                        .entry(category)
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+
+        // Ride times don't change between searches
+        let mut transit = TransitCosts::new(map, opts.transit.clone());
+        for mode in &opts.modes {
+            // (household, building with amenities, time from the household to there)
+            let mut reachable: Vec<(BuildingID, BuildingID, Duration)> = Vec::new();
+            let desc = format!("find amenities reachable by {}", mode.describe());
+            if *mode == Mode::Walk {
+                timer.start_iter(desc, amenities.len());
+                for amenity_bldg in amenities.keys() {
+                    timer.next();
+                    let start = vec![Spot::Building(*amenity_bldg)];
+                    for (b, time) in mode.times_from(map, start, opts, &mut transit) {
+                        if households.contains_key(&b) {
+                            reachable.push((b, *amenity_bldg, time));
+                        }
+                    }
+                }
+            } else {
+                timer.start_iter(desc, households.len());
+                for household in households.keys() {
+                    timer.next();
+                    let start = vec![Spot::Building(*household)];
+                    for (b, time) in mode.times_from(map, start, opts, &mut transit) {
+                        if amenities.contains_key(&b) {
+                            reachable.push((*household, b, time));
+                        }
+                    }
+                }
+            }
+
+            for (b, amenity_bldg, time) in reachable {
+                let household = households.get_mut(&b).unwrap();
+                let mut num_amenities = 0;
+                for (amenity, count) in &amenities[&amenity_bldg] {
+                    num_amenities += count;
+                    let closest = household
+                        .times
+                        .entry((*mode, *amenity))
+                        .or_insert_with(Vec::new);
+                    closest.push(time);
+                    closest.sort();
+                    closest.truncate(opts.nearest);
+                }
+                *household.score.get_mut(mode).unwrap() +=
+                    (num_amenities as f64) * 0.5_f64.powf(time / opts.half_life);
+            }
+        }
+
+        AccessibilityIndex {
+            households,
+            modes: opts.modes.clone(),
+            nearest: opts.nearest,
+        }
+    }
+
+    /// The average gravity score per person
+    pub fn population_weighted_score(&self, mode: Mode) -> f64 {
+        let mut total_residents = 0;
+        let mut total_score = 0.0;
+        for household in self.households.values() {
+            if let Some(score) = household.score.get(&mode) {
+                total_residents += household.residents;
+                total_score += (household.residents as f64) * score;
+            }
+        }
+        if total_residents == 0 {
+            return 0.0;
+        }
+        total_score / (total_residents as f64)
+    }
+
+    /// How many people can reach some type of amenity within 15 minutes
+    pub fn residents_with_access(&self, mode: Mode, amenity: AmenityType) -> usize {
+        self.households
+            .values()
+            .filter(|h| {
+                h.closest(mode, amenity)
+                    .map(|t| t <= Duration::minutes(15))
+                    .unwrap_or(false)
+            })
+            .map(|h| h.residents)
+            .sum()
+    }
+
+    /// Compares with the index for the same map after some edits. Finds every household that
+    /// gained or lost access to some type of amenity within 15 minutes.
+    pub fn changes(&self, after: &AccessibilityIndex) -> Vec<AccessChange> {
+        let mut changes = Vec::new();
+        for (b, household_before) in &self.households {
+            // Edits can't change buildings
+            let household_after = match after.households.get(b) {
+                Some(x) => x,
+                None => continue,
+            };
+            let mut keys: BTreeSet<(Mode, AmenityType)> =
+                household_before.times.keys().cloned().collect();
+            keys.extend(household_after.times.keys().cloned());
+            for (mode, amenity) in keys {
+                let change = AccessChange {
+                    household: *b,
+                    residents: household_before.residents,
+                    mode,
+                    amenity,
+                    before: household_before.closest(mode, amenity),
+                    after: household_after.closest(mode, amenity),
+                };
+                let had_access = change
+                    .before
+                    .map(|t| t <= Duration::minutes(15))
+                    .unwrap_or(false);
+                if had_access != change.gained() {
+                    changes.push(change);
+                }
+            }
+        }
+        changes
+    }
+
+    /// One row per household, with the gravity score per mode and the time in seconds to the
+    /// closest few amenities of each type per mode. Unreachable amenities have an empty value.
+    pub fn to_csv(&self, map: &Map) -> Result<String> {
+        let gps_bounds = map.get_gps_bounds();
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        let mut header = vec![
+            "building".to_string(),
+            "osm_id".to_string(),
+            "longitude".to_string(),
+            "latitude".to_string(),
+            "residents".to_string(),
+        ];
+        for mode in &self.modes {
+            header.push(format!("{}_score", mode.describe()));
+        }
+        for mode in &self.modes {
+            for amenity in AmenityType::all() {
+                for idx in 1..=self.nearest {
+                    header.push(format!("{}_{}_{}", mode.describe(), amenity, idx));
+                }
+            }
+        }
+        writer.write_record(&header)?;
+
+        for (b, household) in &self.households {
+            let bldg = map.get_b(*b);
+            let pt = bldg.polygon.center().to_gps(gps_bounds);
+            let mut row = vec![
+                b.0.to_string(),
+                bldg.orig_id.to_string(),
+                pt.x().to_string(),
+                pt.y().to_string(),
+                household.residents.to_string(),
+            ];
+            for mode in &self.modes {
+                row.push(household.score[mode].to_string());
+            }
+            for mode in &self.modes {
+                for amenity in AmenityType::all() {
+                    let times = household.times.get(&(*mode, amenity));
+                    for idx in 0..self.nearest {
+                        row.push(
+                            times
+                                .and_then(|times| times.get(idx))
+                                .map(|t| t.inner_seconds().round().to_string())
+                                .unwrap_or_else(String::new),
+                        );
+                    }
+                }
+            }
+            writer.write_record(&row)?;
+        }
+
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+
+    /// Every household's building, with the gravity score per mode and the time in seconds to the
+    /// closest amenity of each type per mode as properties.
+    pub fn to_geojson(&self, map: &Map) -> GeoJson {
+        let gps_bounds = map.get_gps_bounds();
+        let mut features = Vec::new();
+        for (b, household) in &self.households {
+            let bldg = map.get_b(*b);
+            let mut feature = Feature {
+                bbox: None,
+                geometry: Some(bldg.polygon.to_geojson(Some(gps_bounds))),
+                id: None,
+                properties: None,
+                foreign_members: None,
+            };
+            feature.set_property("building", b.0);
+            feature.set_property("osm_id", bldg.orig_id.to_string());
+            feature.set_property("residents", household.residents);
+            for mode in &self.modes {
+                feature.set_property(format!("{}_score", mode.describe()), household.score[mode]);
+            }
+            for ((mode, amenity), times) in &household.times {
+                feature.set_property(
+                    format!("{}_{}", mode.describe(), amenity),
+                    times[0].inner_seconds().round(),
+                );
+            }
+            features.push(feature);
+        }
+        GeoJson::from(FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        })
+    }
+}
+
+/// Households that gained or lost 15-minute access, with the amenity type, mode, and times in
+/// seconds as properties. A household appears once per change.
+pub fn changes_to_geojson(map: &Map, changes: &[AccessChange]) -> GeoJson {
+    let gps_bounds = map.get_gps_bounds();
+    let mut features = Vec::new();
+    for change in changes {
+        let mut feature = Feature {
+            bbox: None,
+            geometry: Some(
+                map.get_b(change.household)
+                    .polygon
+                    .to_geojson(Some(gps_bounds)),
+            ),
+            id: None,
+            properties: None,
+            foreign_members: None,
+        };
+        feature.set_property("building", change.household.0);
+        feature.set_property("residents", change.residents);
+        feature.set_property("mode", change.mode.describe());
+        feature.set_property("amenity", change.amenity.to_string());
+        feature.set_property("gained", change.gained());
+        if let Some(t) = change.before {
+            feature.set_property("before", t.inner_seconds().round());
+        }
+        if let Some(t) = change.after {
+            feature.set_property("after", t.inner_seconds().round());
+        }
+        features.push(feature);
+    }
+    GeoJson::from(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    })
+}
+
+/// Summarizes changes per mode and amenity type, like "walk to Supermarket: 12 households (30
+/// residents) gained 15-minute access, 1 household (2 residents) lost it"
+pub fn describe_changes(changes: &[AccessChange]) -> Vec<String> {
+    // (households gained, residents gained, households lost, residents lost)
+    let mut totals: BTreeMap<(Mode, AmenityType), (usize, usize, usize, usize)> = BTreeMap::new();
+    for change in changes {
+        let entry = totals
+            .entry((change.mode, change.amenity))
+            .or_insert((0, 0, 0, 0));
+        if change.gained() {
+            entry.0 += 1;
+            entry.1 += change.residents;
+        } else {
+            entry.2 += 1;
+            entry.3 += change.residents;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|((mode, amenity), (h1, r1, h2, r2))| {
+            format!(
+                "{} to {}: {} households ({} residents) gained 15-minute access, {} households \
+                 ({} residents) lost it",
+                mode.describe(),
+                amenity,
+                prettyprint_usize(h1),
+                prettyprint_usize(r1),
+                prettyprint_usize(h2),
+                prettyprint_usize(r2)
+            )
+        })
+        .collect()
+}
+
+fn residents(b: &Building) -> Option<usize> {
+    match b.bldg_type {
+        BuildingType::Residential { num_residents, .. }
+        | BuildingType::ResidentialCommercial(num_residents, _) => Some(num_residents),
+        _ => None,
+    }
+}
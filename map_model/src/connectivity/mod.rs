@@ -8,10 +8,18 @@ use petgraph::graphmap::DiGraphMap;
 
 use geom::Duration;
 
-pub use self::walking::{all_walking_costs_from, WalkingOptions};
+pub use self::accessibility::{
+    changes_to_geojson, describe_changes, AccessChange, AccessibilityIndex, AccessibilityOptions,
+    HouseholdAccess, Mode,
+};
+pub use self::walking::{
+    all_walking_and_transit_costs_from, all_walking_costs_from, TransitCosts, TransitOptions,
+    WalkingOptions,
+};
 pub use crate::pathfind::{vehicle_cost, WalkingNode};
 use crate::{BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, PathConstraints};
 
+mod accessibility;
 mod walking;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -65,6 +73,18 @@ pub fn all_vehicle_costs_from(
     starts: Vec<Spot>,
     time_limit: Duration,
     constraints: PathConstraints,
+) -> HashMap<BuildingID, Duration> {
+    all_vehicle_costs_with_delays_from(map, starts, time_limit, constraints, &HashMap::new())
+}
+
+/// Like `all_vehicle_costs_from`, but passing through some intersections takes extra time, like the
+/// average delay observed there during a simulation.
+pub fn all_vehicle_costs_with_delays_from(
+    map: &Map,
+    starts: Vec<Spot>,
+    time_limit: Duration,
+    constraints: PathConstraints,
+    intersection_delays: &HashMap<IntersectionID, Duration>,
 ) -> HashMap<BuildingID, Duration> {
     assert!(constraints != PathConstraints::Pedestrian);
     // TODO We have a graph of DirectedRoadIDs, but mapping a building to one isn't
@@ -134,8 +154,12 @@ pub fn all_vehicle_costs_from(
             if let Some(cost) =
                 vehicle_cost(mvmnt.from, mvmnt, constraints, map.routing_params(), map)
             {
+                let delay = intersection_delays
+                    .get(&mvmnt.parent)
+                    .cloned()
+                    .unwrap_or(Duration::ZERO);
                 queue.push(Item {
-                    cost: current.cost + cost,
+                    cost: current.cost + cost + delay,
                     node: mvmnt.to,
                 });
             }
@@ -2,17 +2,23 @@ use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
 use abstutil::MultiMap;
-use geom::{Duration, Speed};
+use geom::{Duration, Speed, Time};
 
 use crate::connectivity::Spot;
-use crate::pathfind::{zone_cost, WalkingNode};
-use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep};
+use crate::pathfind::{crossing_cost, WalkingNode};
+use crate::{
+    BuildingID, Lane, LaneID, LaneType, Map, PathConstraints, PathRequest, PathStep,
+    PedestrianProfile, Position, RoutingParams, TransitRouteID, TransitStopID,
+};
 
 #[derive(Clone)]
 pub struct WalkingOptions {
     /// If true, allow walking on shoulders.
     pub allow_shoulders: bool,
     pub walking_speed: Speed,
+    /// Some sidewalks and crossings may be penalized or impassable for somebody with particular
+    /// mobility needs.
+    pub profile: PedestrianProfile,
 }
 
 impl WalkingOptions {
@@ -20,6 +26,7 @@ impl WalkingOptions {
         WalkingOptions {
             allow_shoulders: true,
             walking_speed: WalkingOptions::default_speed(),
+            profile: PedestrianProfile::Default,
         }
     }
 
@@ -36,6 +43,16 @@ impl WalkingOptions {
     }
 }
 
+#[derive(Clone)]
+pub struct TransitOptions {
+    /// When the trip starts
+    pub departure: Time,
+    /// If true, wait at each stop for the next vehicle scheduled to arrive there. Otherwise, wait
+    /// half of the average time between vehicles on that route in the hour after reaching the
+    /// stop.
+    pub exact_waits: bool,
+}
+
 #[derive(PartialEq, Eq)]
 struct Item {
     cost: Duration,
@@ -70,6 +87,34 @@ pub fn all_walking_costs_from(
     time_limit: Duration,
     opts: WalkingOptions,
 ) -> HashMap<BuildingID, Duration> {
+    walking_costs(map, starts, time_limit, opts, None)
+}
+
+/// Like `all_walking_costs_from`, but also allows riding public transit. Riding between stops
+/// costs the time for the vehicle to drive there without any traffic. Ride times are cached in
+/// `transit`, so reuse it when searching many times.
+pub fn all_walking_and_transit_costs_from(
+    map: &Map,
+    starts: Vec<Spot>,
+    time_limit: Duration,
+    opts: WalkingOptions,
+    transit: &mut TransitCosts,
+) -> HashMap<BuildingID, Duration> {
+    walking_costs(map, starts, time_limit, opts, Some(transit))
+}
+
+fn walking_costs(
+    map: &Map,
+    starts: Vec<Spot>,
+    time_limit: Duration,
+    opts: WalkingOptions,
+    mut transit: Option<&mut TransitCosts>,
+) -> HashMap<BuildingID, Duration> {
+    // Cross roads the same way the pathfinder would, for this profile
+    let params = RoutingParams {
+        pedestrian_profile: opts.profile,
+        ..map.routing_params().clone()
+    };
     let mut queue: BinaryHeap<Item> = BinaryHeap::new();
 
     for spot in starts {
@@ -148,19 +193,51 @@ pub fn all_walking_costs_from(
 
         let (r, is_dst_i) = match current.node {
             WalkingNode::SidewalkEndpoint(r, is_dst_i) => (r, is_dst_i),
+            WalkingNode::RideTransit(stop) => {
+                // Ride every route from here, getting off at any later stop
+                let transit = transit.as_mut().unwrap();
+                for (route, idx) in transit.routes_from(stop) {
+                    let wait = match transit.wait(route, idx, current.cost) {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    let mut cost = current.cost + wait;
+                    for next_idx in (idx + 1)..map.get_tr(route).stops.len() {
+                        match transit.ride_time(route, next_idx - 1) {
+                            Some(x) => {
+                                cost += x;
+                            }
+                            None => break,
+                        }
+                        if cost > time_limit {
+                            break;
+                        }
+                        let next_stop = map.get_tr(route).stops[next_idx];
+                        leave_stop(
+                            map,
+                            &opts,
+                            next_stop,
+                            cost,
+                            time_limit,
+                            &sidewalk_to_bldgs,
+                            &mut results,
+                            &mut queue,
+                        );
+                        // Allow transferring to another route without leaving the stop
+                        queue.push(Item {
+                            cost,
+                            node: WalkingNode::RideTransit(next_stop),
+                        });
+                    }
+                }
+                continue;
+            }
             _ => unreachable!(),
         };
         let lane = map.get_l(r.must_get_sidewalk(map));
         // Cross the lane
-        if opts.allow_shoulders || lane.lane_type != LaneType::Shoulder {
+        if let Some(speed) = sidewalk_speed(map, &opts, lane, is_dst_i) {
             let sidewalk_len = lane.length();
-            let step = if is_dst_i {
-                PathStep::ContraflowLane(lane.id)
-            } else {
-                PathStep::Lane(lane.id)
-            };
-            let speed =
-                step.max_speed_along(Some(opts.walking_speed), PathConstraints::Pedestrian, map);
             let cross_to_node = WalkingNode::SidewalkEndpoint(r, !is_dst_i);
 
             // We're crossing the sidewalk from one end to the other. If we haven't already found a
@@ -179,7 +256,22 @@ pub fn all_walking_costs_from(
                     };
                     let bldg_cost = current.cost + dist_to_bldg / speed;
                     if bldg_cost <= time_limit {
-                        results.insert(*b, bldg_cost);
+                        insert_min(&mut results, *b, bldg_cost);
+                    }
+                }
+
+                if transit.is_some() {
+                    for stop in &lane.transit_stops {
+                        let stop_dist_along = map.get_ts(*stop).sidewalk_pos.dist_along();
+                        let dist_to_stop = if is_dst_i {
+                            sidewalk_len - stop_dist_along
+                        } else {
+                            stop_dist_along
+                        };
+                        queue.push(Item {
+                            cost: current.cost + dist_to_stop / speed,
+                            node: WalkingNode::RideTransit(*stop),
+                        });
                     }
                 }
 
@@ -194,15 +286,12 @@ pub fn all_walking_costs_from(
             if (turn.id.parent == lane.dst_i) != is_dst_i {
                 continue;
             }
+            let cost = match crossing_cost(turn, Some(opts.walking_speed), &params, map) {
+                Some(x) => x,
+                None => continue,
+            };
             queue.push(Item {
-                cost: current.cost
-                    + turn.geom.length()
-                        / PathStep::Turn(turn.id).max_speed_along(
-                            Some(opts.walking_speed),
-                            PathConstraints::Pedestrian,
-                            map,
-                        )
-                    + zone_cost(turn.id.to_movement(map), PathConstraints::Pedestrian, map),
+                cost: current.cost + cost,
                 node: WalkingNode::SidewalkEndpoint(
                     map.get_l(turn.id.dst).get_directed_parent(),
                     map.get_l(turn.id.dst).dst_i == turn.id.parent,
@@ -213,3 +302,173 @@ pub fn all_walking_costs_from(
 
     results
 }
+
+/// How quickly somebody can walk along a sidewalk, or None if they can't use it at all
+fn sidewalk_speed(
+    map: &Map,
+    opts: &WalkingOptions,
+    lane: &Lane,
+    contraflow: bool,
+) -> Option<Speed> {
+    if !opts.allow_shoulders && lane.lane_type == LaneType::Shoulder {
+        return None;
+    }
+    let profile_cost = opts.profile.road_cost(map.get_r(lane.id.road))?;
+    let step = if contraflow {
+        PathStep::ContraflowLane(lane.id)
+    } else {
+        PathStep::Lane(lane.id)
+    };
+    // Penalties from the profile act like slowing down
+    Some(
+        (1.0 / profile_cost)
+            * step.max_speed_along(Some(opts.walking_speed), PathConstraints::Pedestrian, map),
+    )
+}
+
+fn insert_min(results: &mut HashMap<BuildingID, Duration>, b: BuildingID, cost: Duration) {
+    let entry = results.entry(b).or_insert(cost);
+    if cost < *entry {
+        *entry = cost;
+    }
+}
+
+/// Get off transit at a stop, then walk to buildings along the same sidewalk and both ends of it.
+#[allow(clippy::too_many_arguments)]
+fn leave_stop(
+    map: &Map,
+    opts: &WalkingOptions,
+    stop: TransitStopID,
+    cost: Duration,
+    time_limit: Duration,
+    sidewalk_to_bldgs: &MultiMap<LaneID, BuildingID>,
+    results: &mut HashMap<BuildingID, Duration>,
+    queue: &mut BinaryHeap<Item>,
+) {
+    let pos = map.get_ts(stop).sidewalk_pos;
+    let lane = map.get_l(pos.lane());
+    for (contraflow, dist) in [
+        (true, pos.dist_along()),
+        (false, lane.length() - pos.dist_along()),
+    ] {
+        let speed = match sidewalk_speed(map, opts, lane, contraflow) {
+            Some(x) => x,
+            None => continue,
+        };
+        for b in sidewalk_to_bldgs.get(lane.id) {
+            let bldg_dist_along = map.get_b(*b).sidewalk_pos.dist_along();
+            let dist_to_bldg = if contraflow {
+                if bldg_dist_along > pos.dist_along() {
+                    continue;
+                }
+                pos.dist_along() - bldg_dist_along
+            } else {
+                if bldg_dist_along < pos.dist_along() {
+                    continue;
+                }
+                bldg_dist_along - pos.dist_along()
+            };
+            let bldg_cost = cost + dist_to_bldg / speed;
+            if bldg_cost <= time_limit {
+                insert_min(results, *b, bldg_cost);
+            }
+        }
+        queue.push(Item {
+            cost: cost + dist / speed,
+            node: WalkingNode::SidewalkEndpoint(lane.get_directed_parent(), !contraflow),
+        });
+    }
+}
+
+/// Lazily calculates the cost of riding transit, departing at some time
+pub struct TransitCosts<'a> {
+    map: &'a Map,
+    opts: TransitOptions,
+    /// Each stop is served by some routes, at some index along the route's stops
+    routes_per_stop: MultiMap<TransitStopID, (TransitRouteID, usize)>,
+    /// The time to drive from stop `idx` to `idx + 1` along a route
+    ride_times: HashMap<(TransitRouteID, usize), Option<Duration>>,
+    /// The time for a vehicle to reach the first stop of a route after it starts
+    first_leg_times: HashMap<TransitRouteID, Option<Duration>>,
+}
+
+impl<'a> TransitCosts<'a> {
+    pub fn new(map: &'a Map, opts: TransitOptions) -> TransitCosts<'a> {
+        let mut routes_per_stop = MultiMap::new();
+        for route in map.all_transit_routes() {
+            for (idx, stop) in route.stops.iter().enumerate() {
+                routes_per_stop.insert(*stop, (route.id, idx));
+            }
+        }
+        TransitCosts {
+            map,
+            opts,
+            routes_per_stop,
+            ride_times: HashMap::new(),
+            first_leg_times: HashMap::new(),
+        }
+    }
+
+    fn routes_from(&self, stop: TransitStopID) -> Vec<(TransitRouteID, usize)> {
+        self.routes_per_stop.get(stop).iter().cloned().collect()
+    }
+
+    /// How long to wait at stop `idx` of a route, reaching it some time after departing. None if
+    /// the route isn't running then.
+    fn wait(&mut self, route: TransitRouteID, idx: usize, elapsed: Duration) -> Option<Duration> {
+        let now = self.opts.departure + elapsed;
+        if !self.opts.exact_waits {
+            // Half of the average time between vehicles starting the route in the next hour
+            let num_vehicles = self
+                .map
+                .get_tr(route)
+                .spawn_times
+                .iter()
+                .filter(|t| **t >= now && **t < now + Duration::hours(1))
+                .count();
+            if num_vehicles == 0 {
+                return None;
+            }
+            return Some(Duration::hours(1) / (num_vehicles as f64) / 2.0);
+        }
+
+        // When does each vehicle reach this stop?
+        let mut offset = self.first_leg_time(route)?;
+        for i in 0..idx {
+            offset += self.ride_time(route, i)?;
+        }
+        self.map
+            .get_tr(route)
+            .spawn_times
+            .iter()
+            .map(|t| *t + offset)
+            .find(|t| *t >= now)
+            .map(|t| t - now)
+    }
+
+    fn ride_time(&mut self, route: TransitRouteID, idx: usize) -> Option<Duration> {
+        let map = self.map;
+        *self.ride_times.entry((route, idx)).or_insert_with(|| {
+            let route = map.get_tr(route);
+            let req = PathRequest::vehicle(
+                map.get_ts(route.stops[idx]).driving_pos,
+                map.get_ts(route.stops[idx + 1]).driving_pos,
+                route.route_type,
+            );
+            map.pathfind_v2(req).ok().map(|path| path.get_cost())
+        })
+    }
+
+    fn first_leg_time(&mut self, route: TransitRouteID) -> Option<Duration> {
+        let map = self.map;
+        *self.first_leg_times.entry(route).or_insert_with(|| {
+            let route = map.get_tr(route);
+            let req = PathRequest::vehicle(
+                Position::start(route.start),
+                map.get_ts(route.stops[0]).driving_pos,
+                route.route_type,
+            );
+            map.pathfind_v2(req).ok().map(|path| path.get_cost())
+        })
+    }
+}
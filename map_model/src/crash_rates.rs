@@ -0,0 +1,106 @@
+//! Raw counts of collisions mostly show where lots of people travel. To find places that're
+//! actually dangerous, this normalizes collisions by exposure, following the usual conventions for
+//! crash rates: collisions per million vehicle-miles along roads, and per million vehicles
+//! entering intersections.
+
+use std::collections::BTreeMap;
+
+use abstutil::Counter;
+use geom::{Distance, FindClosest, Pt2D};
+
+use crate::{IntersectionID, Map, RoadID};
+
+/// Collisions this close to an intersection count towards it, rather than a road.
+const INTERSECTION_RADIUS: Distance = Distance::const_meters(20.0);
+/// Collisions further than this from any road are ignored.
+const MAX_ROAD_DIST: Distance = Distance::const_meters(30.0);
+const METERS_PER_MILE: f64 = 1609.344;
+
+pub struct CrashRates {
+    /// How many collisions were snapped to each road
+    pub road_collisions: Counter<RoadID>,
+    /// How many collisions were snapped to each intersection
+    pub intersection_collisions: Counter<IntersectionID>,
+    /// Collisions per million vehicle-miles along each road. Roads without any exposure are
+    /// omitted.
+    pub per_road: BTreeMap<RoadID, f64>,
+    /// Collisions per million vehicles entering each intersection. Intersections without any
+    /// exposure are omitted.
+    pub per_intersection: BTreeMap<IntersectionID, f64>,
+    /// Collisions that weren't close to any road
+    pub unmatched: usize,
+}
+
+impl CrashRates {
+    /// Calculate crash rates. `collisions` happened over `days_of_data`. The exposure counts how
+    /// many vehicles enter each road and intersection during one typical day, usually from
+    /// simulation results.
+    pub fn new(
+        map: &Map,
+        collisions: &[Pt2D],
+        days_of_data: usize,
+        road_exposure: &Counter<RoadID>,
+        intersection_exposure: &Counter<IntersectionID>,
+    ) -> CrashRates {
+        let (road_collisions, intersection_collisions, unmatched) =
+            snap_collisions(map, collisions);
+
+        let days = days_of_data as f64;
+        let mut per_road = BTreeMap::new();
+        for r in map.all_roads() {
+            let miles = r.length().inner_meters() / METERS_PER_MILE;
+            let million_vmt = (road_exposure.get(r.id) as f64) * days * miles / 1_000_000.0;
+            if million_vmt > 0.0 {
+                per_road.insert(r.id, (road_collisions.get(r.id) as f64) / million_vmt);
+            }
+        }
+        let mut per_intersection = BTreeMap::new();
+        for i in map.all_intersections() {
+            let million_entering = (intersection_exposure.get(i.id) as f64) * days / 1_000_000.0;
+            if million_entering > 0.0 {
+                per_intersection.insert(
+                    i.id,
+                    (intersection_collisions.get(i.id) as f64) / million_entering,
+                );
+            }
+        }
+
+        CrashRates {
+            road_collisions,
+            intersection_collisions,
+            per_road,
+            per_intersection,
+            unmatched,
+        }
+    }
+}
+
+/// Assigns each collision to the closest intersection if it's close enough, or otherwise the
+/// closest road. Also returns the number of collisions not close to anything.
+pub fn snap_collisions(
+    map: &Map,
+    collisions: &[Pt2D],
+) -> (Counter<RoadID>, Counter<IntersectionID>, usize) {
+    let mut closest_intersection: FindClosest<IntersectionID> = FindClosest::new(map.get_bounds());
+    for i in map.all_intersections() {
+        closest_intersection.add(i.id, i.polygon.points());
+    }
+    let mut closest_road: FindClosest<RoadID> = FindClosest::new(map.get_bounds());
+    for r in map.all_roads() {
+        closest_road.add(r.id, r.center_pts.points());
+    }
+
+    let mut roads = Counter::new();
+    let mut intersections = Counter::new();
+    let mut unmatched = 0;
+    for pt in collisions {
+        if let Some((i, _)) = closest_intersection.closest_pt(*pt, INTERSECTION_RADIUS) {
+            intersections.inc(i);
+        } else if let Some((r, _)) = closest_road.closest_pt(*pt, MAX_ROAD_DIST) {
+            roads.inc(r);
+        } else {
+            unmatched += 1;
+        }
+    }
+    (roads, intersections, unmatched)
+}
@@ -269,7 +269,7 @@ impl MapEdits {
         );
     }
 
-    fn update_derived(&mut self, map: &Map) {
+    pub(crate) fn update_derived(&mut self, map: &Map) {
         self.changed_roads.clear();
         self.original_intersections.clear();
         self.changed_routes.clear();
@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use geom::Duration;
+
+use super::{link_id, movement_txt_id, to_wkt_linestring};
+use crate::{
+    osm, DirectedRoadID, Direction, DrivingSide, IntersectionType, LaneID, LaneType, Map,
+    MovementID, StageType, TurnType,
+};
+
+/// Writes the map's nodes, links, lanes, movements, and traffic signal timing as GMNS CSV files
+/// to a directory. Lengths are in meters, speeds in km/h, and durations in seconds, as described
+/// by the config.csv written alongside.
+pub fn export(map: &Map, dir: &str) -> Result<()> {
+    fs_err::create_dir_all(dir)?;
+    let gps_bounds = map.get_gps_bounds();
+
+    write_csv(
+        dir,
+        "config.csv",
+        vec![Config {
+            dataset_name: map.get_name().as_filename(),
+            short_length: "m",
+            long_length: "km",
+            speed: "kph",
+            crs: "EPSG:4326",
+            geometry_field_format: "WKT",
+            version_number: "0.95",
+        }],
+    )?;
+
+    let mut nodes = Vec::new();
+    for i in map.all_intersections() {
+        let pt = i.polygon.center().to_gps(gps_bounds);
+        let ctrl_type = match i.intersection_type {
+            IntersectionType::TrafficSignal => "signal",
+            IntersectionType::StopSign => {
+                let ss = map.get_stop_sign(i.id);
+                let num_stops = ss.roads.values().filter(|r| r.must_stop).count();
+                if num_stops == 0 {
+                    "no_control"
+                } else if num_stops == ss.roads.len() {
+                    "4_stop"
+                } else {
+                    "stop"
+                }
+            }
+            IntersectionType::Border | IntersectionType::Construction => "no_control",
+        };
+        nodes.push(Node {
+            node_id: i.id.0,
+            name: i.name(None, map),
+            x_coord: pt.x(),
+            y_coord: pt.y(),
+            node_type: if i.is_border() { "border" } else { "" },
+            ctrl_type,
+            osm_node_id: i.orig_id.0,
+        });
+    }
+    write_csv(dir, "node.csv", nodes)?;
+
+    let mut links = Vec::new();
+    let mut lanes = Vec::new();
+    // Lanes are numbered from the inside out
+    let mut lane_nums: BTreeMap<LaneID, usize> = BTreeMap::new();
+    for r in map.all_roads() {
+        for dir in [Direction::Fwd, Direction::Back] {
+            let mut lanes_inside_out: Vec<_> = r.lanes.iter().filter(|l| l.dir == dir).collect();
+            if lanes_inside_out.is_empty() {
+                continue;
+            }
+            if (dir == Direction::Fwd) != (map.get_config().driving_side == DrivingSide::Right) {
+                lanes_inside_out.reverse();
+            }
+            let dr = DirectedRoadID { road: r.id, dir };
+            let (from, to) = if dir == Direction::Fwd {
+                (r.src_i, r.dst_i)
+            } else {
+                (r.dst_i, r.src_i)
+            };
+            let mut pts = gps_bounds.convert_back(r.center_pts.points());
+            if dir == Direction::Back {
+                pts.reverse();
+            }
+
+            let mut allowed_uses = BTreeSet::new();
+            for (idx, l) in lanes_inside_out.iter().enumerate() {
+                allowed_uses.insert(lane_use(l.lane_type));
+                lane_nums.insert(l.id, idx + 1);
+                lanes.push(LaneRow {
+                    lane_id: lanes.len(),
+                    link_id: link_id(dr),
+                    lane_num: idx + 1,
+                    allowed_uses: lane_use(l.lane_type),
+                    width: l.width.inner_meters(),
+                });
+            }
+            allowed_uses.remove("none");
+            let has = |lt: LaneType| lanes_inside_out.iter().any(|l| l.lane_type == lt);
+
+            links.push(Link {
+                link_id: link_id(dr),
+                name: r.get_name(None),
+                from_node_id: from.0,
+                to_node_id: to.0,
+                directed: true,
+                geometry: to_wkt_linestring(&pts),
+                length: r.length().inner_meters(),
+                facility_type: r
+                    .osm_tags
+                    .get(osm::HIGHWAY)
+                    .cloned()
+                    .unwrap_or_else(String::new),
+                free_speed: r.speed_limit.inner_meters_per_second() * 3.6,
+                lanes: lanes_inside_out
+                    .iter()
+                    .filter(|l| l.lane_type == LaneType::Driving || l.lane_type == LaneType::Bus)
+                    .count(),
+                allowed_uses: allowed_uses.into_iter().collect::<Vec<_>>().join(","),
+                bike_facility: if has(LaneType::Biking) {
+                    "lane"
+                } else {
+                    "none"
+                },
+                ped_facility: if has(LaneType::Sidewalk) {
+                    "sidewalk"
+                } else if has(LaneType::Shoulder) {
+                    "shoulder"
+                } else {
+                    "none"
+                },
+                parking: if has(LaneType::Parking) {
+                    "parallel"
+                } else {
+                    "none"
+                },
+                osm_way_id: r.orig_id.osm_way_id.0,
+            });
+        }
+    }
+    write_csv(dir, "link.csv", links)?;
+    write_csv(dir, "lane.csv", lanes)?;
+
+    let mut movements = Vec::new();
+    let mut mvmt_ids: BTreeMap<MovementID, usize> = BTreeMap::new();
+    for i in map.all_intersections() {
+        for (id, movement) in &i.movements {
+            // Crosswalks aren't movements in GMNS
+            if id.crosswalk {
+                continue;
+            }
+            let turn_type = match movement.turn_type {
+                TurnType::Straight => "thru",
+                TurnType::Left => "left",
+                TurnType::Right => "right",
+                TurnType::UTurn => "uturn",
+                _ => continue,
+            };
+            let mvmt_txt_id = match movement_txt_id(map, movement) {
+                Some(x) => x,
+                None => continue,
+            };
+            let src_lanes: Vec<usize> =
+                movement.members.iter().map(|t| lane_nums[&t.src]).collect();
+            let dst_lanes: Vec<usize> =
+                movement.members.iter().map(|t| lane_nums[&t.dst]).collect();
+
+            mvmt_ids.insert(*id, movements.len());
+            movements.push(MovementRow {
+                mvmt_id: movements.len(),
+                node_id: i.id.0,
+                ib_link_id: link_id(id.from),
+                start_ib_lane: *src_lanes.iter().min().unwrap(),
+                end_ib_lane: *src_lanes.iter().max().unwrap(),
+                ob_link_id: link_id(id.to),
+                start_ob_lane: *dst_lanes.iter().min().unwrap(),
+                end_ob_lane: *dst_lanes.iter().max().unwrap(),
+                turn_type,
+                mvmt_txt_id,
+                geometry: to_wkt_linestring(&gps_bounds.convert_back(movement.geom.points())),
+            });
+        }
+    }
+    write_csv(dir, "movement.csv", movements)?;
+
+    let mut controllers = Vec::new();
+    let mut plans = Vec::new();
+    let mut phases = Vec::new();
+    let mut phase_movements = Vec::new();
+    for i in map.all_intersections() {
+        if !i.is_traffic_signal() {
+            continue;
+        }
+        let signal = map.get_traffic_signal(i.id);
+        // There's just one plan per signal, so use the node's ID for everything
+        controllers.push(Controller {
+            controller_id: i.id.0,
+            node_id: i.id.0,
+        });
+        plans.push(TimingPlan {
+            timing_plan_id: i.id.0,
+            controller_id: i.id.0,
+            cycle_length: signal.simple_cycle_duration().inner_seconds(),
+            offset: signal.offset.inner_seconds(),
+        });
+        for (idx, stage) in signal.stages.iter().enumerate() {
+            let (min_green, max_green, extension) = match stage.stage_type {
+                StageType::Fixed(d) => (d, d, Duration::ZERO),
+                StageType::Variable(min, delay, additional) => (min, min + additional, delay),
+            };
+            let timing_phase_id = phases.len();
+            phases.push(TimingPhase {
+                timing_phase_id,
+                timing_plan_id: i.id.0,
+                signal_phase_num: idx + 1,
+                min_green: min_green.inner_seconds(),
+                max_green: max_green.inner_seconds(),
+                extension: extension.inner_seconds(),
+            });
+            for (movements, protection) in [
+                (&stage.protected_movements, "protected"),
+                (&stage.yield_movements, "permitted"),
+            ] {
+                for id in movements {
+                    if let Some(mvmt_id) = mvmt_ids.get(id) {
+                        phase_movements.push(PhaseMovement {
+                            signal_phase_mvmt_id: phase_movements.len(),
+                            timing_phase_id,
+                            mvmt_id: *mvmt_id,
+                            protection,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    write_csv(dir, "signal_controller.csv", controllers)?;
+    write_csv(dir, "signal_timing_plan.csv", plans)?;
+    write_csv(dir, "signal_timing_phase.csv", phases)?;
+    write_csv(dir, "signal_phase_mvmt.csv", phase_movements)?;
+
+    Ok(())
+}
+
+fn write_csv<T: Serialize>(dir: &str, name: &str, rows: Vec<T>) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(fs_err::File::create(format!("{}/{}", dir, name))?);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn lane_use(lt: LaneType) -> &'static str {
+    match lt {
+        LaneType::Driving | LaneType::SharedLeftTurn => "auto",
+        LaneType::Bus => "bus",
+        LaneType::Biking => "bike",
+        LaneType::Sidewalk | LaneType::Shoulder => "walk",
+        LaneType::Parking => "parking",
+        LaneType::LightRail => "rail",
+        LaneType::Construction | LaneType::Buffer(_) => "none",
+    }
+}
+
+#[derive(Serialize)]
+struct Config {
+    dataset_name: String,
+    short_length: &'static str,
+    long_length: &'static str,
+    speed: &'static str,
+    crs: &'static str,
+    geometry_field_format: &'static str,
+    version_number: &'static str,
+}
+
+#[derive(Serialize)]
+struct Node {
+    node_id: usize,
+    name: String,
+    x_coord: f64,
+    y_coord: f64,
+    node_type: &'static str,
+    ctrl_type: &'static str,
+    osm_node_id: i64,
+}
+
+#[derive(Serialize)]
+struct Link {
+    link_id: usize,
+    name: String,
+    from_node_id: usize,
+    to_node_id: usize,
+    directed: bool,
+    geometry: String,
+    length: f64,
+    facility_type: String,
+    free_speed: f64,
+    lanes: usize,
+    allowed_uses: String,
+    bike_facility: &'static str,
+    ped_facility: &'static str,
+    parking: &'static str,
+    osm_way_id: i64,
+}
+
+#[derive(Serialize)]
+struct LaneRow {
+    lane_id: usize,
+    link_id: usize,
+    lane_num: usize,
+    allowed_uses: &'static str,
+    width: f64,
+}
+
+#[derive(Serialize)]
+struct MovementRow {
+    mvmt_id: usize,
+    node_id: usize,
+    ib_link_id: usize,
+    start_ib_lane: usize,
+    end_ib_lane: usize,
+    ob_link_id: usize,
+    start_ob_lane: usize,
+    end_ob_lane: usize,
+    #[serde(rename = "type")]
+    turn_type: &'static str,
+    mvmt_txt_id: String,
+    geometry: String,
+}
+
+#[derive(Serialize)]
+struct Controller {
+    controller_id: usize,
+    node_id: usize,
+}
+
+#[derive(Serialize)]
+struct TimingPlan {
+    timing_plan_id: usize,
+    controller_id: usize,
+    cycle_length: f64,
+    offset: f64,
+}
+
+#[derive(Serialize)]
+struct TimingPhase {
+    timing_phase_id: usize,
+    timing_plan_id: usize,
+    signal_phase_num: usize,
+    min_green: f64,
+    max_green: f64,
+    extension: f64,
+}
+
+#[derive(Serialize)]
+struct PhaseMovement {
+    signal_phase_mvmt_id: usize,
+    timing_phase_id: usize,
+    mvmt_id: usize,
+    protection: &'static str,
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use abstutil::Counter;
+use geom::Speed;
+
+use super::decode_link_id;
+use crate::{
+    osm, DirectedRoadID, Direction, DrivingSide, EditCmd, EditRoad, IntersectionID, LaneType, Map,
+    MapEdits, RoadID,
+};
+
+/// Speed limits closer than this to the current one aren't changed, to ignore rounding
+const SPEED_TOLERANCE_KPH: f64 = 1.0;
+
+/// Reads node.csv and link.csv from a directory of GMNS files, and turns the number of lanes and
+/// speed of every link into map edits. Links are matched to roads by their OSM way and node IDs
+/// when present, and otherwise by the link ID written by `export`. Returns the edits and a
+/// summary of anything that couldn't be used.
+///
+/// Driving lanes are added or removed from the inside of the road. Capacity isn't modeled, so it's
+/// ignored.
+pub fn import_link_attributes(map: &Map, dir: &str) -> Result<(MapEdits, Vec<String>)> {
+    let mph = read_csv::<Config>(&format!("{}/config.csv", dir))
+        .ok()
+        .and_then(|mut rows| rows.pop())
+        .and_then(|config| config.speed)
+        // GMNS defaults to mph
+        .map(|speed| speed != "kph")
+        .unwrap_or(true);
+    let osm_nodes: HashMap<usize, osm::NodeID> = read_csv::<NodeRow>(&format!("{}/node.csv", dir))?
+        .into_iter()
+        .filter_map(|n| n.osm_node_id.map(|id| (n.node_id, osm::NodeID(id))))
+        .collect();
+
+    let mut by_osm: HashMap<(osm::WayID, osm::NodeID, osm::NodeID), DirectedRoadID> =
+        HashMap::new();
+    for r in map.all_roads() {
+        let src = map.get_i(r.src_i).orig_id;
+        let dst = map.get_i(r.dst_i).orig_id;
+        let way = r.orig_id.osm_way_id;
+        by_osm.insert(
+            (way, src, dst),
+            DirectedRoadID {
+                road: r.id,
+                dir: Direction::Fwd,
+            },
+        );
+        by_osm.insert(
+            (way, dst, src),
+            DirectedRoadID {
+                road: r.id,
+                dir: Direction::Back,
+            },
+        );
+    }
+
+    let mut problems = Counter::new();
+    let mut per_road: BTreeMap<RoadID, Vec<(Direction, LinkRow)>> = BTreeMap::new();
+    for link in read_csv::<LinkRow>(&format!("{}/link.csv", dir))? {
+        if link.capacity.is_some() {
+            problems.inc("links with a capacity, which isn't modeled");
+        }
+        match match_link(map, &link, &osm_nodes, &by_osm) {
+            Some(dr) => {
+                per_road
+                    .entry(dr.road)
+                    .or_insert_with(Vec::new)
+                    .push((dr.dir, link));
+            }
+            None => {
+                problems.inc("links not matching any road");
+            }
+        }
+    }
+
+    let mut edits = map.get_edits().clone();
+    for (r, links) in per_road {
+        let road = map.get_r(r);
+        let mut new = map.get_r_edit(r);
+
+        let speeds: Vec<f64> = links.iter().filter_map(|(_, l)| l.free_speed).collect();
+        if let Some(speed) = speeds.into_iter().reduce(f64::max) {
+            let speed = if mph {
+                Speed::miles_per_hour(speed)
+            } else {
+                Speed::km_per_hour(speed)
+            };
+            if ((speed - road.speed_limit).inner_meters_per_second() * 3.6).abs()
+                > SPEED_TOLERANCE_KPH
+            {
+                new.speed_limit = speed;
+            }
+        }
+
+        for (dir, link) in &links {
+            if let Some(lanes) = link.lanes {
+                if let Err(err) = set_driving_lanes(&mut new, *dir, lanes, map) {
+                    problems.inc(err);
+                }
+            }
+        }
+
+        let old = map.get_r_edit(r);
+        if new != old {
+            edits.commands.push(EditCmd::ChangeRoad { r, old, new });
+        }
+    }
+
+    let summary = problems
+        .consume()
+        .into_iter()
+        .map(|(problem, cnt)| format!("{} {}", cnt, problem))
+        .collect();
+    Ok((edits, summary))
+}
+
+fn match_link(
+    map: &Map,
+    link: &LinkRow,
+    osm_nodes: &HashMap<usize, osm::NodeID>,
+    by_osm: &HashMap<(osm::WayID, osm::NodeID, osm::NodeID), DirectedRoadID>,
+) -> Option<DirectedRoadID> {
+    if let (Some(way), Some(from), Some(to)) = (
+        link.osm_way_id,
+        osm_nodes.get(&link.from_node_id),
+        osm_nodes.get(&link.to_node_id),
+    ) {
+        return by_osm.get(&(osm::WayID(way), *from, *to)).cloned();
+    }
+
+    // Fall back to the IDs from our own export, but make sure they're for this map
+    let dr = decode_link_id(link.link_id);
+    let r = map.maybe_get_r(dr.road)?;
+    let (from, to) = if dr.dir == Direction::Fwd {
+        (r.src_i, r.dst_i)
+    } else {
+        (r.dst_i, r.src_i)
+    };
+    if from == IntersectionID(link.from_node_id) && to == IntersectionID(link.to_node_id) {
+        Some(dr)
+    } else {
+        None
+    }
+}
+
+/// GMNS counts bus lanes too, but only driving lanes are added or removed.
+fn set_driving_lanes(
+    road: &mut EditRoad,
+    dir: Direction,
+    lanes: usize,
+    map: &Map,
+) -> Result<(), &'static str> {
+    let num_bus = road
+        .lanes_ltr
+        .iter()
+        .filter(|l| l.dir == dir && l.lt == LaneType::Bus)
+        .count();
+    // Indices of driving lanes in this direction, from the inside out
+    let mut driving: Vec<usize> = road
+        .lanes_ltr
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.dir == dir && l.lt == LaneType::Driving)
+        .map(|(idx, _)| idx)
+        .collect();
+    if (dir == Direction::Fwd) != (map.get_config().driving_side == DrivingSide::Right) {
+        driving.reverse();
+    }
+
+    // Directions with only sidewalks and other non-vehicle lanes export as 0 lanes
+    if lanes == num_bus + driving.len() {
+        return Ok(());
+    }
+    if lanes <= num_bus {
+        return Err("links that would lose all driving lanes in one direction");
+    }
+    let want = lanes - num_bus;
+    if driving.is_empty() {
+        return Err("links that would add a new direction of driving to a road");
+    }
+    if want > driving.len() {
+        let inside = driving[0];
+        for _ in driving.len()..want {
+            let spec = road.lanes_ltr[inside].clone();
+            road.lanes_ltr.insert(inside, spec);
+        }
+    } else {
+        // Remove from the highest index first, so the other indices stay valid
+        let mut remove: Vec<usize> = driving[0..driving.len() - want].to_vec();
+        remove.sort_unstable();
+        for idx in remove.into_iter().rev() {
+            road.lanes_ltr.remove(idx);
+        }
+    }
+    Ok(())
+}
+
+fn read_csv<T: DeserializeOwned>(path: &str) -> Result<Vec<T>> {
+    let mut rows = Vec::new();
+    for rec in csv::Reader::from_reader(fs_err::File::open(path)?).deserialize() {
+        rows.push(rec?);
+    }
+    Ok(rows)
+}
+
+#[derive(Deserialize)]
+struct Config {
+    speed: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NodeRow {
+    node_id: usize,
+    osm_node_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct LinkRow {
+    link_id: usize,
+    from_node_id: usize,
+    to_node_id: usize,
+    osm_way_id: Option<i64>,
+    lanes: Option<usize>,
+    free_speed: Option<f64>,
+    capacity: Option<f64>,
+}
@@ -0,0 +1,82 @@
+//! Support for the General Modeling Network Specification (https://github.com/zephyr-data-specs/GMNS),
+//! a CSV-based format many travel demand models and traffic tools speak.
+//!
+//! Every direction of a road becomes a directed link, and every intersection becomes a node. The
+//! link and node IDs are derived from `RoadID` and `IntersectionID`, but these change when a map is
+//! re-imported, so links also carry their OSM way ID, and nodes their OSM node ID. Importing
+//! matches by those, so data produced by other tools from an earlier export still lines up.
+
+use geom::{Angle, LonLat};
+
+use crate::{DirectedRoadID, Direction, Map, Movement, RoadID, TurnType};
+
+pub use self::export::export;
+pub use self::import::import_link_attributes;
+pub use self::timing::import_signal_timing;
+
+mod export;
+mod import;
+mod timing;
+
+fn link_id(dr: DirectedRoadID) -> usize {
+    dr.road.0 * 2 + if dr.dir == Direction::Fwd { 0 } else { 1 }
+}
+
+fn decode_link_id(id: usize) -> DirectedRoadID {
+    DirectedRoadID {
+        road: RoadID(id / 2),
+        dir: if id % 2 == 0 {
+            Direction::Fwd
+        } else {
+            Direction::Back
+        },
+    }
+}
+
+fn to_wkt_linestring(pts: &[LonLat]) -> String {
+    let pts: Vec<String> = pts
+        .iter()
+        .map(|pt| format!("{} {}", pt.x(), pt.y()))
+        .collect();
+    format!("LINESTRING ({})", pts.join(", "))
+}
+
+/// Names a vehicle movement the way turning movement counts usually do: the direction of travel
+/// approaching the intersection, then the turn. "NBL" is a left turn by somebody heading north.
+/// Returns None for crosswalks and other movements without a name.
+pub fn movement_txt_id(map: &Map, movement: &Movement) -> Option<String> {
+    if movement.id.crosswalk {
+        return None;
+    }
+    let turn = match movement.turn_type {
+        TurnType::Straight => "T",
+        TurnType::Left => "L",
+        TurnType::Right => "R",
+        TurnType::UTurn => "U",
+        _ => {
+            return None;
+        }
+    };
+    let direction = cardinal_direction(
+        map.get_l(movement.members[0].src)
+            .lane_center_pts
+            .overall_angle(),
+    );
+    Some(format!("{}{}", direction, turn))
+}
+
+/// Describes the direction of travel like "EB" for eastbound
+fn cardinal_direction(angle: Angle) -> &'static str {
+    // Note Y inversion, as usual
+    let deg = angle.normalized_degrees();
+    if deg >= 335.0 || deg <= 45.0 {
+        return "EB";
+    }
+    if (45.0..=135.0).contains(&deg) {
+        return "SB";
+    }
+    if (135.0..=225.0).contains(&deg) {
+        return "WB";
+    }
+    "NB"
+}
@@ -40,9 +40,12 @@ pub use crate::city::City;
 pub use crate::edits::{
     EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentMapEdits,
 };
+pub use crate::make::traffic_signals::coordination::{CorridorTiming, SignalCorridor};
 pub use crate::make::RawToMapOptions;
 pub use crate::map::{DrivingSide, MapConfig};
+pub use crate::migrate::IdMigration;
 pub use crate::objects::area::{Area, AreaID, AreaType};
+pub use crate::objects::bikeshare::{BikeshareDock, BikeshareDockID};
 pub use crate::objects::block::{Block, Perimeter};
 pub use crate::objects::building::{
     Amenity, AmenityType, Building, BuildingID, BuildingType, NamePerLanguage, OffstreetParking,
@@ -58,24 +61,28 @@ pub use crate::objects::road::{DirectedRoadID, Direction, Road, RoadID, RoadSide
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
 pub use crate::objects::traffic_signals::{ControlTrafficSignal, Stage, StageType};
 pub use crate::objects::transit::{TransitRoute, TransitRouteID, TransitStop, TransitStopID};
-pub use crate::objects::turn::{Turn, TurnID, TurnPriority, TurnType};
+pub use crate::objects::turn::{CrossingType, Turn, TurnID, TurnPriority, TurnType};
 pub use crate::objects::zone::{AccessRestrictions, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 use crate::pathfind::Pathfinder;
 pub use crate::pathfind::{
     Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2, PathfinderCaching,
-    RoutingParams,
+    PedestrianProfile, RoutingParams,
 };
 pub use crate::traversable::{Position, Traversable, MAX_BIKE_SPEED, MAX_WALKING_SPEED};
 
 mod city;
 pub mod connectivity;
+pub mod crash_rates;
 mod edits;
+pub mod gmns;
 mod make;
 mod map;
+mod migrate;
 mod objects;
 pub mod osm;
 mod pathfind;
+pub mod pois;
 pub mod raw;
 mod traversable;
 
@@ -94,6 +101,7 @@ pub struct Map {
     transit_routes: Vec<TransitRoute>,
     areas: Vec<Area>,
     parking_lots: Vec<ParkingLot>,
+    bikeshare_docks: Vec<BikeshareDock>,
     boundary_polygon: Polygon,
 
     // Note that border nodes belong in neither!
@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+
+use abstutil::Timer;
+use geom::{Distance, HashablePt2D};
+
+use crate::make::match_points_to_lanes;
+use crate::objects::building::sidewalk_to_bike;
+use crate::raw::RawBikeshareDock;
+use crate::{BikeshareDock, BikeshareDockID, Map, NamePerLanguage};
+
+/// When OSM doesn't say how many bikes fit at a dock, assume this many
+const DEFAULT_CAPACITY: usize = 10;
+
+/// Match bikeshare docks to the nearest sidewalk. Docks without a bike-able lane nearby are
+/// skipped, since nobody could ride away from them.
+pub fn make_all_bikeshare_docks(
+    input: &[RawBikeshareDock],
+    map: &Map,
+    timer: &mut Timer,
+) -> Vec<BikeshareDock> {
+    timer.start("convert bikeshare docks");
+    let query: HashSet<HashablePt2D> = input.iter().map(|d| d.pt.to_hashable()).collect();
+    let sidewalk_pts = match_points_to_lanes(
+        map,
+        query,
+        |l| l.is_walkable(),
+        // Docks are often right at the corner, but keep off the very end of the sidewalk
+        Distance::meters(1.0),
+        // Stations are usually on the curb; anything further away is probably inside a plaza or
+        // park with no mapped paths
+        Distance::meters(50.0),
+        timer,
+    );
+
+    let mut results = Vec::new();
+    for dock in input {
+        let sidewalk_pos = match sidewalk_pts.get(&dock.pt.to_hashable()) {
+            Some(pos) => *pos,
+            None => {
+                warn!(
+                    "Skipping bikeshare dock {}: no sidewalk nearby",
+                    dock.osm_id
+                );
+                continue;
+            }
+        };
+        if sidewalk_to_bike(sidewalk_pos, map).is_none() {
+            warn!(
+                "Skipping bikeshare dock {}: nowhere to ride from {}",
+                dock.osm_id, sidewalk_pos
+            );
+            continue;
+        }
+        let capacity = dock
+            .osm_tags
+            .get("capacity")
+            .and_then(|x| x.parse::<usize>().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(DEFAULT_CAPACITY);
+        results.push(BikeshareDock {
+            id: BikeshareDockID(results.len()),
+            osm_id: dock.osm_id,
+            name: NamePerLanguage::new(&dock.osm_tags),
+            pt: dock.pt,
+            sidewalk_pos,
+            capacity,
+        });
+    }
+    info!(
+        "Discarded {} bikeshare docks that couldn't be connected to the map",
+        input.len() - results.len()
+    );
+    timer.stop("convert bikeshare docks");
+    results
+}
@@ -283,7 +283,8 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         fwd_side.push(fwd(LaneType::Sidewalk));
         back_side.push(back(LaneType::Sidewalk));
     } else if tags.is(osm::SIDEWALK, "separate") && cfg.inferred_sidewalks {
-        // TODO Need to snap separate sidewalks to ways. Until then, just do this.
+        // convert_osm only snaps separately mapped sidewalks to ways when configured to. Otherwise,
+        // just do this.
         fwd_side.push(fwd(LaneType::Sidewalk));
         if !back_side.is_empty() {
             back_side.push(back(LaneType::Sidewalk));
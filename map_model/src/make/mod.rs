@@ -17,6 +17,7 @@ use crate::{
     Position, Road, RoadID, RoutingParams, Zone,
 };
 
+mod bikeshare;
 mod bridges;
 mod buildings;
 pub mod collapse_intersections;
@@ -63,6 +64,7 @@ impl Map {
             transit_routes: Vec::new(),
             areas: Vec::new(),
             parking_lots: Vec::new(),
+            bikeshare_docks: Vec::new(),
             zones: Vec::new(),
             boundary_polygon: raw.boundary_polygon.clone(),
             stop_signs: BTreeMap::new(),
@@ -241,6 +243,9 @@ impl Map {
             timer,
         );
 
+        map.bikeshare_docks =
+            bikeshare::make_all_bikeshare_docks(&raw.bikeshare_docks, &map, timer);
+
         map.zones = Zone::make_all(&map);
 
         for a in &raw.areas {
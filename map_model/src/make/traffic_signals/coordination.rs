@@ -0,0 +1,442 @@
+//! Coordinate the traffic signals along an arterial into a "green wave." All signals are given a
+//! common cycle length, then offsets are picked to maximize the two-directional bandwidth -- the
+//! width of the window a platoon traveling at the speed limit can use to pass through every signal
+//! without stopping. This is a simplified version of MAXBAND: instead of a mixed-integer program,
+//! offsets are found by coordinate descent over whole seconds, which is plenty for corridors of a
+//! few dozen signals.
+
+use anyhow::Result;
+
+use geom::Duration;
+
+use crate::{
+    ControlTrafficSignal, DirectedRoadID, EditCmd, EditIntersection, IntersectionID, Map, MapEdits,
+    MovementID, PathConstraints, StageType, TurnType,
+};
+
+/// Give up on coordinate descent after this many passes over the corridor, even if the bandwidth
+/// is still improving
+const MAX_PASSES: usize = 10;
+
+/// A sequence of traffic signals along a route.
+pub struct SignalCorridor {
+    /// The signals, in order from the start to the end of the route
+    pub signals: Vec<IntersectionID>,
+    /// How long it takes to drive from each signal to the next at the speed limit. This has one
+    /// fewer entry than `signals`.
+    pub travel_times: Vec<Duration>,
+    /// Per signal, the movement continuing along the corridor towards the last signal. None if the
+    /// corridor can't be followed in that direction, like on a one-way street.
+    outbound: Vec<Option<MovementID>>,
+    /// Per signal, the movement continuing along the corridor towards the first signal
+    inbound: Vec<Option<MovementID>>,
+}
+
+/// New timing for every signal in a corridor
+pub struct CorridorTiming {
+    /// The retimed signals, in corridor order
+    pub signals: Vec<ControlTrafficSignal>,
+    pub cycle_length: Duration,
+    /// How wide the green band is for traffic heading from the first signal to the last
+    pub outbound_bandwidth: Duration,
+    /// How wide the green band is for traffic heading from the last signal to the first
+    pub inbound_bandwidth: Duration,
+}
+
+impl SignalCorridor {
+    /// Finds the driving route between two intersections and all of the traffic signals along it.
+    pub fn new(map: &Map, from: IntersectionID, to: IntersectionID) -> Result<SignalCorridor> {
+        let (roads, intersections) = map
+            .simple_path_btwn_v2(from, to, PathConstraints::Car)
+            .ok_or_else(|| anyhow!("no driving route from {} to {}", from, to))?;
+
+        let mut corridor = SignalCorridor {
+            signals: Vec::new(),
+            travel_times: Vec::new(),
+            outbound: Vec::new(),
+            inbound: Vec::new(),
+        };
+        let mut time_since_last_signal = Duration::ZERO;
+        for (idx, i) in intersections.iter().enumerate() {
+            if idx > 0 {
+                let r = map.get_r(roads[idx - 1]);
+                time_since_last_signal += r.length() / r.speed_limit;
+            }
+            if !map.get_i(*i).is_traffic_signal() {
+                continue;
+            }
+            if !corridor.signals.is_empty() {
+                corridor.travel_times.push(time_since_last_signal);
+            }
+            time_since_last_signal = Duration::ZERO;
+
+            // The road before and after this intersection along the route
+            let before = idx.checked_sub(1).map(|x| map.get_r(roads[x]));
+            let after = roads.get(idx).map(|r| map.get_r(*r));
+            corridor.signals.push(*i);
+            corridor.outbound.push(pick_movement(
+                map,
+                *i,
+                before.map(|r| r.directed_id_to(*i)),
+                after.map(|r| r.directed_id_from(*i)),
+            ));
+            corridor.inbound.push(pick_movement(
+                map,
+                *i,
+                after.map(|r| r.directed_id_to(*i)),
+                before.map(|r| r.directed_id_from(*i)),
+            ));
+        }
+
+        if corridor.signals.len() < 2 {
+            bail!(
+                "the route from {} to {} only crosses {} traffic signals; there's nothing to \
+                 coordinate",
+                from,
+                to,
+                corridor.signals.len()
+            );
+        }
+        if corridor.outbound.iter().any(|m| m.is_none())
+            && corridor.inbound.iter().any(|m| m.is_none())
+        {
+            bail!(
+                "traffic can't continue along the route from {} to {} through every signal in \
+                 either direction",
+                from,
+                to
+            );
+        }
+        Ok(corridor)
+    }
+
+    /// Retime every signal to a common cycle length, then pick offsets to maximize the sum of the
+    /// bandwidth in both directions. Stage durations are scaled proportionally to fit the new
+    /// cycle. If no cycle length is specified, the longest existing cycle along the corridor is
+    /// used, so no stage gets shorter. The first signal's offset is left alone.
+    pub fn optimize_bandwidth(
+        &self,
+        map: &Map,
+        cycle_length: Option<Duration>,
+    ) -> Result<CorridorTiming> {
+        let cycle_length = cycle_length.unwrap_or_else(|| {
+            self.signals
+                .iter()
+                .map(|i| map.get_traffic_signal(*i).simple_cycle_duration())
+                .max()
+                .unwrap()
+        });
+        let cycle = cycle_length.inner_seconds().ceil() as usize;
+
+        let mut signals = Vec::new();
+        for i in &self.signals {
+            let mut ts = map.get_traffic_signal(*i).clone();
+            retime(&mut ts, cycle);
+            ts.validate(map.get_i(*i)).map_err(|err| {
+                anyhow!(
+                    "{} can't be retimed to a {}s cycle: {}",
+                    i,
+                    cycle,
+                    err.to_string().lines().next().unwrap_or("")
+                )
+            })?;
+            ts.offset = Duration::seconds((whole_seconds(ts.offset) % cycle) as f64);
+            signals.push(ts);
+        }
+
+        let bands = self.green_bands(&signals, cycle);
+        let mut offsets: Vec<usize> = signals.iter().map(|ts| whole_seconds(ts.offset)).collect();
+
+        // Start with the ideal one-way progression: each signal turns green as the platoon
+        // released by the first signal arrives.
+        if let Some((g0, _)) = bands.outbound[0] {
+            let o0 = offsets[0];
+            for ((offset, window), arrival) in offsets
+                .iter_mut()
+                .zip(bands.outbound.iter())
+                .zip(bands.outbound_arrivals())
+                .skip(1)
+            {
+                if let Some((g, _)) = window {
+                    *offset = (g + 2 * cycle - g0 + o0 - arrival % cycle) % cycle;
+                }
+            }
+        }
+
+        let mut best = bands.total_bandwidth(&offsets);
+        for _ in 0..MAX_PASSES {
+            let mut improved = false;
+            for idx in 1..offsets.len() {
+                let orig = offsets[idx];
+                let mut best_here = orig;
+                for candidate in 0..cycle {
+                    offsets[idx] = candidate;
+                    let score = bands.total_bandwidth(&offsets);
+                    if score > best {
+                        best = score;
+                        best_here = candidate;
+                        improved = true;
+                    }
+                }
+                offsets[idx] = best_here;
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        for (ts, offset) in signals.iter_mut().zip(offsets.iter()) {
+            ts.offset = Duration::seconds(*offset as f64);
+        }
+        Ok(CorridorTiming {
+            outbound_bandwidth: Duration::seconds(bands.outbound_bandwidth(&offsets) as f64),
+            inbound_bandwidth: Duration::seconds(bands.inbound_bandwidth(&offsets) as f64),
+            signals,
+            cycle_length: Duration::seconds(cycle as f64),
+        })
+    }
+
+    /// Measures (outbound, inbound) bandwidth for signals along this corridor. They must all
+    /// have the same cycle length.
+    pub fn bandwidth(&self, signals: &[ControlTrafficSignal]) -> (Duration, Duration) {
+        let cycle = whole_seconds(signals[0].simple_cycle_duration());
+        let bands = self.green_bands(signals, cycle);
+        let offsets: Vec<usize> = signals
+            .iter()
+            .map(|ts| whole_seconds(ts.offset) % cycle)
+            .collect();
+        (
+            Duration::seconds(bands.outbound_bandwidth(&offsets) as f64),
+            Duration::seconds(bands.inbound_bandwidth(&offsets) as f64),
+        )
+    }
+
+    fn green_bands(&self, signals: &[ControlTrafficSignal], cycle: usize) -> GreenBands {
+        GreenBands {
+            cycle,
+            travel_times: self
+                .travel_times
+                .iter()
+                .map(|t| whole_seconds(*t))
+                .collect(),
+            outbound: signals
+                .iter()
+                .zip(self.outbound.iter())
+                .map(|(ts, m)| m.and_then(|m| green_window(ts, m)))
+                .collect(),
+            inbound: signals
+                .iter()
+                .zip(self.inbound.iter())
+                .map(|(ts, m)| m.and_then(|m| green_window(ts, m)))
+                .collect(),
+        }
+    }
+}
+
+impl CorridorTiming {
+    /// Express the new timing as edits on top of whatever's currently applied to the map.
+    pub fn to_edits(&self, map: &Map) -> MapEdits {
+        let mut edits = map.get_edits().clone();
+        for ts in &self.signals {
+            edits.commands.push(EditCmd::ChangeIntersection {
+                i: ts.id,
+                old: map.get_i_edit(ts.id),
+                new: EditIntersection::TrafficSignal(ts.export(map)),
+            });
+        }
+        edits
+    }
+}
+
+/// Everything about a corridor's timing needed to calculate bandwidth, in whole seconds
+struct GreenBands {
+    cycle: usize,
+    travel_times: Vec<usize>,
+    /// Per signal, (start, duration) of the green for the corridor within the cycle, before
+    /// applying the offset
+    outbound: Vec<Option<(usize, usize)>>,
+    inbound: Vec<Option<(usize, usize)>>,
+}
+
+impl GreenBands {
+    /// How long after passing the first signal the platoon reaches each signal
+    fn outbound_arrivals(&self) -> Vec<usize> {
+        let mut arrivals = vec![0];
+        for t in &self.travel_times {
+            arrivals.push(arrivals.last().unwrap() + t);
+        }
+        arrivals
+    }
+
+    /// How long after passing the last signal the platoon reaches each signal
+    fn inbound_arrivals(&self) -> Vec<usize> {
+        let mut arrivals = vec![0];
+        for t in self.travel_times.iter().rev() {
+            arrivals.push(arrivals.last().unwrap() + t);
+        }
+        arrivals.reverse();
+        arrivals
+    }
+
+    fn outbound_bandwidth(&self, offsets: &[usize]) -> usize {
+        self.bandwidth(&self.outbound, &self.outbound_arrivals(), offsets)
+    }
+
+    fn inbound_bandwidth(&self, offsets: &[usize]) -> usize {
+        self.bandwidth(&self.inbound, &self.inbound_arrivals(), offsets)
+    }
+
+    fn total_bandwidth(&self, offsets: &[usize]) -> usize {
+        self.outbound_bandwidth(offsets) + self.inbound_bandwidth(offsets)
+    }
+
+    /// Counts the seconds in one cycle where a platoon could start and hit green at every signal.
+    /// A signal with offset `o` is at time `(t + o) % cycle` in its cycle at time `t`.
+    fn bandwidth(
+        &self,
+        windows: &[Option<(usize, usize)>],
+        arrivals: &[usize],
+        offsets: &[usize],
+    ) -> usize {
+        if windows.iter().any(|w| w.is_none()) {
+            return 0;
+        }
+        (0..self.cycle)
+            .filter(|t| {
+                windows.iter().zip(arrivals.iter().zip(offsets.iter())).all(
+                    |(window, (arrival, offset))| {
+                        let (start, duration) = window.unwrap();
+                        let time_in_cycle = (t + arrival + offset) % self.cycle;
+                        (time_in_cycle + self.cycle - start) % self.cycle < duration
+                    },
+                )
+            })
+            .count()
+    }
+}
+
+/// Picks the movement from one road to the next. At the ends of the corridor, only one road is
+/// known, so prefer going straight.
+fn pick_movement(
+    map: &Map,
+    i: IntersectionID,
+    from: Option<DirectedRoadID>,
+    to: Option<DirectedRoadID>,
+) -> Option<MovementID> {
+    let mut candidates: Vec<_> = map
+        .get_i(i)
+        .movements
+        .values()
+        .filter(|m| {
+            !m.id.crosswalk
+                && from.map(|dr| dr == m.id.from).unwrap_or(true)
+                && to.map(|dr| dr == m.id.to).unwrap_or(true)
+        })
+        .collect();
+    candidates.sort_by_key(|m| m.turn_type != TurnType::Straight);
+    candidates.get(0).map(|m| m.id)
+}
+
+/// Returns the (start, duration) of the longest run of consecutive stages where a movement can go.
+/// The run may wrap around the end of the cycle.
+fn green_window(ts: &ControlTrafficSignal, m: MovementID) -> Option<(usize, usize)> {
+    let durations: Vec<usize> = ts
+        .stages
+        .iter()
+        .map(|s| whole_seconds(s.stage_type.simple_duration()))
+        .collect();
+    let cycle: usize = durations.iter().sum();
+    let serves: Vec<bool> = ts
+        .stages
+        .iter()
+        .map(|s| s.protected_movements.contains(&m) || s.yield_movements.contains(&m))
+        .collect();
+    if serves.iter().all(|x| *x) {
+        return Some((0, cycle));
+    }
+    // Start scanning right after a red stage, so no run is split in two
+    let first = (serves.iter().position(|x| !*x)? + 1) % serves.len();
+    let mut start_time: usize = durations[0..first].iter().sum();
+
+    let mut best: Option<(usize, usize)> = None;
+    let mut current: Option<(usize, usize)> = None;
+    for step in 0..serves.len() {
+        let idx = (first + step) % serves.len();
+        if serves[idx] {
+            let (start, duration) = current.unwrap_or((start_time % cycle, 0));
+            current = Some((start, duration + durations[idx]));
+        } else if let Some(run) = current.take() {
+            if best.map(|(_, d)| run.1 > d).unwrap_or(true) {
+                best = Some(run);
+            }
+        }
+        start_time += durations[idx];
+    }
+    best
+}
+
+/// Scale stage durations to fit a new cycle length, giving any leftover seconds to the longest
+/// stage.
+fn retime(ts: &mut ControlTrafficSignal, cycle: usize) {
+    let old_cycle = ts.simple_cycle_duration().inner_seconds();
+    let mut new_durations: Vec<usize> = ts
+        .stages
+        .iter()
+        .map(|s| {
+            (s.stage_type.simple_duration().inner_seconds() / old_cycle * (cycle as f64)).floor()
+                as usize
+        })
+        .collect();
+    let leftover = cycle - new_durations.iter().sum::<usize>();
+    let longest = (0..new_durations.len())
+        .max_by_key(|idx| new_durations[*idx])
+        .unwrap();
+    new_durations[longest] += leftover;
+
+    for (stage, duration) in ts.stages.iter_mut().zip(new_durations) {
+        let duration = Duration::seconds(duration as f64);
+        stage.stage_type = match stage.stage_type {
+            StageType::Fixed(_) => StageType::Fixed(duration),
+            StageType::Variable(_, delay, additional) => {
+                StageType::Variable(duration, delay, additional)
+            }
+        };
+    }
+}
+
+fn whole_seconds(d: Duration) -> usize {
+    d.inner_seconds().round() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bands(windows: Vec<(usize, usize)>, travel_times: Vec<usize>) -> GreenBands {
+        GreenBands {
+            cycle: 60,
+            travel_times,
+            outbound: windows.iter().cloned().map(Some).collect(),
+            inbound: windows.into_iter().map(Some).collect(),
+        }
+    }
+
+    #[test]
+    fn test_bandwidth() {
+        // Two signals with 30s of green at the start of a 60s cycle, 10s apart
+        let b = bands(vec![(0, 30), (0, 30)], vec![10]);
+        // With no offset, the platoon from the first signal only has 20s to make it through
+        assert_eq!(b.outbound_bandwidth(&[0, 0]), 20);
+        assert_eq!(b.inbound_bandwidth(&[0, 0]), 20);
+        // Delaying the second signal by 10s gives outbound a perfect wave, at inbound's expense
+        assert_eq!(b.outbound_bandwidth(&[0, 50]), 30);
+        assert_eq!(b.inbound_bandwidth(&[0, 50]), 10);
+    }
+
+    #[test]
+    fn test_wrapping_window() {
+        // The green starts near the end of the cycle and continues into the next
+        let b = bands(vec![(50, 20), (50, 20)], vec![0]);
+        assert_eq!(b.outbound_bandwidth(&[0, 0]), 20);
+    }
+}
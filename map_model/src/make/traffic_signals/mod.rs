@@ -13,6 +13,7 @@ use crate::{
 };
 use geom::Duration;
 
+pub mod coordination;
 mod lagging_green;
 
 /// Applies a bunch of heuristics to a single intersection, returning the valid results in
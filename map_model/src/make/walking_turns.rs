@@ -1,7 +1,8 @@
 use geom::{Distance, PolyLine, Pt2D, Ring, EPSILON_DIST};
 
 use crate::{
-    Direction, DrivingSide, Intersection, IntersectionID, Lane, LaneID, Map, Turn, TurnID, TurnType,
+    osm, CrossingType, Direction, DrivingSide, Intersection, IntersectionID, Lane, LaneID, Map,
+    Turn, TurnID, TurnType,
 };
 
 /// Looks at all sidewalks (or lack thereof) in counter-clockwise order around an intersection.
@@ -139,8 +140,8 @@ pub fn make_walking_turns(map: &Map, i: &Intersection) -> Vec<Turn> {
 /// Filter out crosswalks on really short roads. In reality, these roads are usually located within
 /// an intersection, which isn't a valid place for a pedestrian crossing.
 ///
-/// And if the road is marked as having no crosswalks at an end, downgrade them to unmarked
-/// crossings.
+/// And if the road is marked as having no crosswalks at an end (or only an unmarked crossing),
+/// downgrade them to unmarked crossings.
 pub fn filter_turns(mut input: Vec<Turn>, map: &Map, i: &Intersection) -> Vec<Turn> {
     for r in &i.roads {
         if map.get_r(*r).is_extremely_short() {
@@ -153,12 +154,17 @@ pub fn filter_turns(mut input: Vec<Turn>, map: &Map, i: &Intersection) -> Vec<Tu
     for turn in &mut input {
         if let Some(dr) = turn.crosswalk_over_road(map) {
             let road = map.get_r(dr.road);
-            let keep = if dr.dir == Direction::Fwd {
-                road.crosswalk_forward
+            let (keep, crossing_key) = if dr.dir == Direction::Fwd {
+                (road.crosswalk_forward, osm::CROSSING_FWD)
             } else {
-                road.crosswalk_backward
+                (road.crosswalk_backward, osm::CROSSING_BACK)
             };
-            if !keep {
+            // A crossing node mapped in OSM might explicitly be unmarked
+            if !keep
+                || road
+                    .osm_tags
+                    .is(crossing_key, CrossingType::Unmarked.to_tag_value())
+            {
                 turn.turn_type = TurnType::UnmarkedCrossing;
             }
         } else if turn.turn_type.pedestrian_crossing() {
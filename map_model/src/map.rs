@@ -12,12 +12,12 @@ use geom::{Bounds, Distance, Duration, GPSBounds, Polygon, Pt2D, Ring, Time};
 
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
-    osm, Area, AreaID, AreaType, Building, BuildingID, BuildingType, CommonEndpoint,
-    CompressedMovementID, ControlStopSign, ControlTrafficSignal, DirectedRoadID, Direction,
-    Intersection, IntersectionID, Lane, LaneID, LaneType, Map, MapEdits, Movement, MovementID,
-    OffstreetParking, ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, PathV2,
-    Pathfinder, PathfinderCaching, Position, Road, RoadID, RoutingParams, TransitRoute,
-    TransitRouteID, TransitStop, TransitStopID, Turn, TurnID, TurnType, Zone,
+    osm, Area, AreaID, AreaType, BikeshareDock, BikeshareDockID, Building, BuildingID,
+    BuildingType, CommonEndpoint, CompressedMovementID, ControlStopSign, ControlTrafficSignal,
+    DirectedRoadID, Direction, Intersection, IntersectionID, Lane, LaneID, LaneType, Map, MapEdits,
+    Movement, MovementID, OffstreetParking, ParkingLot, ParkingLotID, Path, PathConstraints,
+    PathRequest, PathV2, Pathfinder, PathfinderCaching, Position, Road, RoadID, RoutingParams,
+    TransitRoute, TransitRouteID, TransitStop, TransitStopID, Turn, TurnID, TurnType, Zone,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -159,6 +159,7 @@ impl Map {
             transit_routes: Vec::new(),
             areas: Vec::new(),
             parking_lots: Vec::new(),
+            bikeshare_docks: Vec::new(),
             zones: Vec::new(),
             boundary_polygon: Ring::must_new(vec![
                 Pt2D::new(0.0, 0.0),
@@ -216,6 +217,10 @@ impl Map {
         &self.parking_lots
     }
 
+    pub fn all_bikeshare_docks(&self) -> &Vec<BikeshareDock> {
+        &self.bikeshare_docks
+    }
+
     pub fn all_zones(&self) -> &Vec<Zone> {
         &self.zones
     }
@@ -300,6 +305,10 @@ impl Map {
         &self.buildings[id.0]
     }
 
+    pub(crate) fn mut_building(&mut self, id: BuildingID) -> &mut Building {
+        &mut self.buildings[id.0]
+    }
+
     pub fn get_a(&self, id: AreaID) -> &Area {
         &self.areas[id.0]
     }
@@ -308,6 +317,10 @@ impl Map {
         &self.parking_lots[id.0]
     }
 
+    pub fn get_bd(&self, id: BikeshareDockID) -> &BikeshareDock {
+        &self.bikeshare_docks[id.0]
+    }
+
     pub fn get_stop_sign(&self, id: IntersectionID) -> &ControlStopSign {
         &self.stop_signs[&id]
     }
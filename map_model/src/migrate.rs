@@ -0,0 +1,536 @@
+//! When a map is regenerated from newer OSM data, the contiguous IDs like `RoadID` and
+//! `BuildingID` usually shift around. Anything saved by referencing those IDs -- scenarios, LTN
+//! proposals, prebaked results -- would silently point at the wrong objects. An `IdMigration`
+//! matches objects between the old and new version of a map, first by the OSM IDs each object
+//! retains from its `RawMap`, then by geometry, so callers can translate their saved data.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Distance, FindClosest, Pt2D};
+
+use crate::edits::{EditCmd, EditIntersection, MapEdits};
+use crate::{
+    BuildingID, CompressedMovementID, ControlStopSign, DirectedRoadID, IntersectionID, LaneID, Map,
+    MovementID, PathRequest, Position, RoadID, RoadSideID, SideOfRoad, TransitRouteID,
+    TransitStopID, Traversable, TurnID,
+};
+
+/// Objects further apart than this probably aren't the same thing.
+const MAX_GEOMETRY_MATCH_DIST: Distance = Distance::const_meters(10.0);
+
+/// A table mapping IDs in an old version of a map to IDs in a new version of the same map.
+/// Objects that no longer exist or couldn't be confidently matched are left out.
+pub struct IdMigration {
+    pub roads: BTreeMap<RoadID, RoadID>,
+    pub intersections: BTreeMap<IntersectionID, IntersectionID>,
+    pub buildings: BTreeMap<BuildingID, BuildingID>,
+    pub lanes: BTreeMap<LaneID, LaneID>,
+    pub transit_routes: BTreeMap<TransitRouteID, TransitRouteID>,
+    pub transit_stops: BTreeMap<TransitStopID, TransitStopID>,
+
+    /// Old roads that match a new road pointing the opposite direction. Lane order, directions,
+    /// and distances along these roads flip.
+    reversed_roads: BTreeSet<RoadID>,
+    /// Old road -> new road length, for clamping positions
+    new_road_lengths: BTreeMap<RoadID, Distance>,
+
+    /// Old objects that couldn't be matched at all, for reporting
+    pub unmatched: Vec<String>,
+    /// How many objects of each type were only matched by geometry, not by OSM ID
+    pub matched_by_geometry: BTreeMap<&'static str, usize>,
+}
+
+impl IdMigration {
+    pub fn new(old: &Map, new: &Map, timer: &mut Timer) -> IdMigration {
+        let mut migration = IdMigration {
+            roads: BTreeMap::new(),
+            intersections: BTreeMap::new(),
+            buildings: BTreeMap::new(),
+            lanes: BTreeMap::new(),
+            transit_routes: BTreeMap::new(),
+            transit_stops: BTreeMap::new(),
+
+            reversed_roads: BTreeSet::new(),
+            new_road_lengths: BTreeMap::new(),
+
+            unmatched: Vec::new(),
+            matched_by_geometry: BTreeMap::new(),
+        };
+        timer.start("match intersections");
+        migration.match_intersections(old, new);
+        timer.stop("match intersections");
+        timer.start("match roads and lanes");
+        migration.match_roads(old, new);
+        migration.match_lanes(old, new);
+        timer.stop("match roads and lanes");
+        timer.start("match buildings");
+        migration.match_buildings(old, new);
+        timer.stop("match buildings");
+
+        for tr in old.all_transit_routes() {
+            if let Some(id) = new.find_tr_by_gtfs(&tr.gtfs_id) {
+                migration.transit_routes.insert(tr.id, id);
+            } else {
+                migration
+                    .unmatched
+                    .push(format!("transit route {} ({})", tr.gtfs_id, tr.long_name));
+            }
+        }
+        let stops_by_gtfs: BTreeMap<_, _> = new
+            .all_transit_stops()
+            .values()
+            .map(|ts| (&ts.gtfs_id, ts.id))
+            .collect();
+        for ts in old.all_transit_stops().values() {
+            if let Some(id) = stops_by_gtfs.get(&ts.gtfs_id) {
+                migration.transit_stops.insert(ts.id, *id);
+            } else {
+                migration
+                    .unmatched
+                    .push(format!("transit stop {} ({})", ts.gtfs_id, ts.name));
+            }
+        }
+        migration
+    }
+
+    fn match_intersections(&mut self, old: &Map, new: &Map) {
+        let by_osm: BTreeMap<_, _> = new
+            .all_intersections()
+            .iter()
+            .map(|i| (i.orig_id, i.id))
+            .collect();
+        let mut closest = FindClosest::new(new.get_bounds());
+        for i in new.all_intersections() {
+            closest.add(i.id, i.polygon.points());
+        }
+
+        for i in old.all_intersections() {
+            if let Some(id) = by_osm.get(&i.orig_id) {
+                self.intersections.insert(i.id, *id);
+            } else if let Some((id, _)) = closest.closest_pt(
+                translate_pt(i.polygon.center(), old, new),
+                MAX_GEOMETRY_MATCH_DIST,
+            ) {
+                self.intersections.insert(i.id, id);
+                *self.matched_by_geometry.entry("intersections").or_insert(0) += 1;
+            } else {
+                self.unmatched
+                    .push(format!("{} (OSM node {})", i.id, i.orig_id));
+            }
+        }
+    }
+
+    fn match_roads(&mut self, old: &Map, new: &Map) {
+        let by_osm: BTreeMap<_, _> = new.all_roads().iter().map(|r| (r.orig_id, r.id)).collect();
+        let mut closest = FindClosest::new(new.get_bounds());
+        for r in new.all_roads() {
+            closest.add(r.id, r.center_pts.points());
+        }
+
+        for r in old.all_roads() {
+            if let Some(id) = by_osm.get(&r.orig_id) {
+                self.roads.insert(r.id, *id);
+                continue;
+            }
+
+            // The OSM way may have been split or had its endpoints change. If there's a new road
+            // between the same two intersections, use it.
+            if let (Some(i1), Some(i2)) = (
+                self.intersections.get(&r.src_i),
+                self.intersections.get(&r.dst_i),
+            ) {
+                if let Some(id) = new.find_road_between(*i1, *i2) {
+                    if new.get_r(id).src_i != *i1 {
+                        self.reversed_roads.insert(r.id);
+                    }
+                    self.roads.insert(r.id, id);
+                    *self.matched_by_geometry.entry("roads").or_insert(0) += 1;
+                    continue;
+                }
+            }
+
+            // Otherwise, find whatever new road is close to the middle of the old one. Prefer
+            // roads from the same OSM way.
+            let (mid_pt, old_angle) = r.center_pts.must_dist_along(r.center_pts.length() / 2.0);
+            let query_pt = translate_pt(mid_pt, old, new);
+            let candidate = closest
+                .all_close_pts(query_pt, MAX_GEOMETRY_MATCH_DIST)
+                .into_iter()
+                .min_by_key(|(id, _, dist)| {
+                    (
+                        new.get_r(*id).orig_id.osm_way_id != r.orig_id.osm_way_id,
+                        *dist,
+                    )
+                });
+            if let Some((id, pt, _)) = candidate {
+                if let Some((_, new_angle)) = new.get_r(id).center_pts.dist_along_of_point(pt) {
+                    if !old_angle.approx_eq(new_angle, 90.0) {
+                        self.reversed_roads.insert(r.id);
+                    }
+                }
+                self.roads.insert(r.id, id);
+                *self.matched_by_geometry.entry("roads").or_insert(0) += 1;
+            } else {
+                self.unmatched.push(format!("{} ({})", r.id, r.orig_id));
+            }
+        }
+
+        for (old_r, new_r) in &self.roads {
+            self.new_road_lengths
+                .insert(*old_r, new.get_r(*new_r).length());
+        }
+    }
+
+    /// Match lanes within each matched road. If the lane configuration is unchanged, this is
+    /// trivial. Otherwise, match the Nth lane of some type and direction to the Nth lane with the
+    /// same type and direction in the new road.
+    fn match_lanes(&mut self, old: &Map, new: &Map) {
+        for (old_r, new_r) in &self.roads {
+            let reversed = self.reversed_roads.contains(old_r);
+            let mut new_lanes: Vec<_> = new
+                .get_r(*new_r)
+                .lanes
+                .iter()
+                .map(|l| (l.id, l.lane_type, l.dir))
+                .collect();
+            if reversed {
+                new_lanes.reverse();
+                for (_, _, dir) in &mut new_lanes {
+                    *dir = dir.opposite();
+                }
+            }
+
+            let mut seen: BTreeMap<_, usize> = BTreeMap::new();
+            for lane in &old.get_r(*old_r).lanes {
+                let key = (lane.lane_type, lane.dir);
+                let rank = seen.entry(key).or_insert(0);
+                if let Some((id, _, _)) = new_lanes
+                    .iter()
+                    .filter(|(_, lt, dir)| (*lt, *dir) == key)
+                    .nth(*rank)
+                {
+                    self.lanes.insert(lane.id, *id);
+                } else {
+                    self.unmatched
+                        .push(format!("{} ({:?} on {})", lane.id, lane.lane_type, old_r));
+                }
+                *rank += 1;
+            }
+        }
+    }
+
+    fn match_buildings(&mut self, old: &Map, new: &Map) {
+        let by_osm: BTreeMap<_, _> = new
+            .all_buildings()
+            .iter()
+            .map(|b| (b.orig_id, b.id))
+            .collect();
+        let mut closest = FindClosest::new(new.get_bounds());
+        for b in new.all_buildings() {
+            closest.add(b.id, b.polygon.points());
+        }
+
+        for b in old.all_buildings() {
+            if let Some(id) = by_osm.get(&b.orig_id) {
+                self.buildings.insert(b.id, *id);
+            } else if let Some((id, _)) = closest.closest_pt(
+                translate_pt(b.polygon.center(), old, new),
+                MAX_GEOMETRY_MATCH_DIST,
+            ) {
+                self.buildings.insert(b.id, id);
+                *self.matched_by_geometry.entry("buildings").or_insert(0) += 1;
+            } else {
+                self.unmatched.push(format!("{} ({})", b.id, b.orig_id));
+            }
+        }
+    }
+
+    /// Summarize how well the two maps matched up.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("{} roads matched", prettyprint_usize(self.roads.len())),
+            format!(
+                "{} intersections matched",
+                prettyprint_usize(self.intersections.len())
+            ),
+            format!("{} lanes matched", prettyprint_usize(self.lanes.len())),
+            format!(
+                "{} buildings matched",
+                prettyprint_usize(self.buildings.len())
+            ),
+            format!(
+                "{} transit routes matched",
+                prettyprint_usize(self.transit_routes.len())
+            ),
+            format!(
+                "{} transit stops matched",
+                prettyprint_usize(self.transit_stops.len())
+            ),
+        ];
+        for (obj, cnt) in &self.matched_by_geometry {
+            lines.push(format!(
+                "{} {} only matched by geometry",
+                prettyprint_usize(*cnt),
+                obj
+            ));
+        }
+        lines.push(format!(
+            "{} objects couldn't be matched",
+            prettyprint_usize(self.unmatched.len())
+        ));
+        lines
+    }
+
+    pub fn r(&self, r: RoadID) -> Option<RoadID> {
+        self.roads.get(&r).cloned()
+    }
+
+    pub fn i(&self, i: IntersectionID) -> Option<IntersectionID> {
+        self.intersections.get(&i).cloned()
+    }
+
+    pub fn b(&self, b: BuildingID) -> Option<BuildingID> {
+        self.buildings.get(&b).cloned()
+    }
+
+    pub fn l(&self, l: LaneID) -> Option<LaneID> {
+        self.lanes.get(&l).cloned()
+    }
+
+    pub fn tr(&self, tr: TransitRouteID) -> Option<TransitRouteID> {
+        self.transit_routes.get(&tr).cloned()
+    }
+
+    pub fn ts(&self, ts: TransitStopID) -> Option<TransitStopID> {
+        self.transit_stops.get(&ts).cloned()
+    }
+
+    /// Translates a distance along an old road to the matching new road. The result is clamped to
+    /// the new road's length.
+    pub fn dist_along_road(&self, r: RoadID, dist: Distance) -> Option<Distance> {
+        let new_len = *self.new_road_lengths.get(&r)?;
+        let dist = dist.min(new_len);
+        Some(if self.reversed_roads.contains(&r) {
+            new_len - dist
+        } else {
+            dist
+        })
+    }
+
+    /// Lanes might change length, so the position is clamped to the new lane.
+    pub fn pos(&self, pos: Position, new: &Map) -> Option<Position> {
+        let l = self.l(pos.lane())?;
+        let len = new.get_l(l).length();
+        let dist = pos.dist_along().min(len);
+        Some(if self.reversed_roads.contains(&pos.lane().road) {
+            Position::new(l, len - dist)
+        } else {
+            Position::new(l, dist)
+        })
+    }
+
+    pub fn dr(&self, dr: DirectedRoadID) -> Option<DirectedRoadID> {
+        Some(DirectedRoadID {
+            road: self.r(dr.road)?,
+            dir: if self.reversed_roads.contains(&dr.road) {
+                dr.dir.opposite()
+            } else {
+                dr.dir
+            },
+        })
+    }
+
+    pub fn road_side(&self, side: RoadSideID) -> Option<RoadSideID> {
+        Some(RoadSideID {
+            road: self.r(side.road)?,
+            side: match (self.reversed_roads.contains(&side.road), side.side) {
+                (false, x) => x,
+                (true, SideOfRoad::Left) => SideOfRoad::Right,
+                (true, SideOfRoad::Right) => SideOfRoad::Left,
+            },
+        })
+    }
+
+    /// Turns are only translated if they still exist in the new map.
+    pub fn t(&self, t: TurnID, new: &Map) -> Option<TurnID> {
+        let id = TurnID {
+            parent: self.i(t.parent)?,
+            src: self.l(t.src)?,
+            dst: self.l(t.dst)?,
+        };
+        new.maybe_get_t(id).map(|_| id)
+    }
+
+    pub fn movement(&self, m: MovementID) -> Option<MovementID> {
+        Some(MovementID {
+            from: self.dr(m.from)?,
+            to: self.dr(m.to)?,
+            parent: self.i(m.parent)?,
+            crosswalk: m.crosswalk,
+        })
+    }
+
+    /// Compressed movements index into the movements of an intersection, so translating them needs
+    /// both versions of the map.
+    pub fn compressed_movement(
+        &self,
+        m: CompressedMovementID,
+        old: &Map,
+        new: &Map,
+    ) -> Option<CompressedMovementID> {
+        let old_movement = old.get_i(m.i).movements.keys().nth(m.idx as usize)?;
+        let new_movement = self.movement(*old_movement)?;
+        let idx = new
+            .get_i(new_movement.parent)
+            .movements
+            .keys()
+            .position(|id| *id == new_movement)?;
+        Some(CompressedMovementID {
+            i: new_movement.parent,
+            idx: u8::try_from(idx).ok()?,
+        })
+    }
+
+    pub fn path_request(&self, req: &PathRequest, new: &Map) -> Option<PathRequest> {
+        Some(PathRequest {
+            start: self.pos(req.start, new)?,
+            end: self.pos(req.end, new)?,
+            constraints: req.constraints,
+            alt_start: match req.alt_start {
+                Some((pos, cost)) => Some((self.pos(pos, new)?, cost)),
+                None => None,
+            },
+        })
+    }
+
+    pub fn traversable(&self, t: Traversable, new: &Map) -> Option<Traversable> {
+        match t {
+            Traversable::Lane(l) => self.l(l).map(Traversable::Lane),
+            Traversable::Turn(t) => self.t(t, new).map(Traversable::Turn),
+        }
+    }
+}
+
+impl MapEdits {
+    /// Translate edits made to an old version of a map to a new version. Returns the new edits
+    /// and a description of every command that couldn't be carried over.
+    pub fn migrate(&self, migration: &IdMigration, new: &Map) -> (MapEdits, Vec<String>) {
+        let mut problems = Vec::new();
+        let mut commands = Vec::new();
+        for cmd in &self.commands {
+            match migrate_cmd(cmd, migration, new) {
+                Ok(cmd) => commands.push(cmd),
+                Err(err) => problems.push(err.to_string()),
+            }
+        }
+        let mut edits = MapEdits {
+            edits_name: self.edits_name.clone(),
+            proposal_description: self.proposal_description.clone(),
+            proposal_link: self.proposal_link.clone(),
+            commands,
+            merge_zones: self.merge_zones,
+
+            changed_roads: BTreeSet::new(),
+            original_intersections: BTreeMap::new(),
+            changed_routes: BTreeSet::new(),
+        };
+        edits.update_derived(new);
+        (edits, problems)
+    }
+}
+
+fn migrate_cmd(cmd: &EditCmd, migration: &IdMigration, new: &Map) -> anyhow::Result<EditCmd> {
+    match cmd {
+        EditCmd::ChangeRoad { r, new: after, old } => {
+            let id = migration
+                .r(*r)
+                .ok_or_else(|| anyhow!("{} doesn't exist anymore", r))?;
+            let num_current = new.get_r(id).lanes.len();
+            // Same as loading PermanentMapEdits -- if the number of lanes changed, the original
+            // intent of the edit is unclear.
+            if num_current != old.lanes_ltr.len() {
+                bail!(
+                    "number of lanes in {} is {} now, but {} in the edits",
+                    r,
+                    num_current,
+                    old.lanes_ltr.len()
+                );
+            }
+            let mut after = after.clone();
+            let mut old = old.clone();
+            if migration.reversed_roads.contains(r) {
+                for edit in [&mut after, &mut old] {
+                    edit.lanes_ltr.reverse();
+                    for spec in &mut edit.lanes_ltr {
+                        spec.dir = spec.dir.opposite();
+                    }
+                }
+            }
+            Ok(EditCmd::ChangeRoad {
+                r: id,
+                new: after,
+                old,
+            })
+        }
+        EditCmd::ChangeIntersection { i, new: after, old } => {
+            let id = migration
+                .i(*i)
+                .ok_or_else(|| anyhow!("{} doesn't exist anymore", i))?;
+            Ok(EditCmd::ChangeIntersection {
+                i: id,
+                new: migrate_intersection(after, id, migration, new)?,
+                old: migrate_intersection(old, id, migration, new)?,
+            })
+        }
+        EditCmd::ChangeRouteSchedule {
+            id,
+            old,
+            new: after,
+        } => Ok(EditCmd::ChangeRouteSchedule {
+            id: migration
+                .tr(*id)
+                .ok_or_else(|| anyhow!("{} doesn't exist anymore", id))?,
+            old: old.clone(),
+            new: after.clone(),
+        }),
+    }
+}
+
+fn migrate_intersection(
+    edit: &EditIntersection,
+    i: IntersectionID,
+    migration: &IdMigration,
+    new: &Map,
+) -> anyhow::Result<EditIntersection> {
+    match edit {
+        EditIntersection::StopSign(old_ss) => {
+            let mut ss = ControlStopSign::new(new, i);
+            if ss.roads.len() != old_ss.roads.len() {
+                bail!(
+                    "Stop sign at {} has {} roads now, but {} from edits",
+                    i,
+                    ss.roads.len(),
+                    old_ss.roads.len()
+                );
+            }
+            for (r, old_road) in &old_ss.roads {
+                match migration.r(*r).and_then(|r| ss.roads.get_mut(&r)) {
+                    Some(road) => {
+                        road.must_stop = old_road.must_stop;
+                    }
+                    None => bail!("{} doesn't connect to {} anymore", i, r),
+                }
+            }
+            Ok(EditIntersection::StopSign(ss))
+        }
+        // This refers to OSM IDs already, and is validated when the edits are applied
+        EditIntersection::TrafficSignal(ts) => Ok(EditIntersection::TrafficSignal(ts.clone())),
+        EditIntersection::Closed => Ok(EditIntersection::Closed),
+    }
+}
+
+/// The two maps may have slightly different GPS bounds, so go through GPS coordinates.
+fn translate_pt(pt: Pt2D, old: &Map, new: &Map) -> Pt2D {
+    pt.to_gps(old.get_gps_bounds()).to_pt(new.get_gps_bounds())
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_usize, serialize_usize};
+use geom::Pt2D;
+
+use crate::objects::building::sidewalk_to_bike;
+use crate::{osm, Map, NamePerLanguage, Position};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BikeshareDockID(
+    #[serde(
+        serialize_with = "serialize_usize",
+        deserialize_with = "deserialize_usize"
+    )]
+    pub usize,
+);
+
+impl fmt::Display for BikeshareDockID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bikeshare dock #{}", self.0)
+    }
+}
+
+/// A station where shared bikes can be picked up and returned. It's attached to a sidewalk, like a
+/// transit stop.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BikeshareDock {
+    pub id: BikeshareDockID,
+    pub osm_id: osm::OsmID,
+    pub name: Option<NamePerLanguage>,
+    /// Where the dock was mapped
+    pub pt: Pt2D,
+    pub sidewalk_pos: Position,
+    /// How many bikes can be docked here at once
+    pub capacity: usize,
+}
+
+impl BikeshareDock {
+    /// Returns (bike position, sidewalk position) to start or stop riding from this dock. Like
+    /// buildings, the bike lane is looked up fresh, since it may be affected by map edits.
+    pub fn biking_connection(&self, map: &Map) -> Option<(Position, Position)> {
+        sidewalk_to_bike(self.sidewalk_pos, map)
+    }
+}
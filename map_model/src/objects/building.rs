@@ -214,7 +214,7 @@ impl Building {
     }
 }
 
-fn sidewalk_to_bike(sidewalk_pos: Position, map: &Map) -> Option<(Position, Position)> {
+pub(crate) fn sidewalk_to_bike(sidewalk_pos: Position, map: &Map) -> Option<(Position, Position)> {
     let lane = map
         .get_parent(sidewalk_pos.lane())
         .find_closest_lane(sidewalk_pos.lane(), |l| {
@@ -258,7 +258,7 @@ pub enum AmenityType {
 }
 
 impl AmenityType {
-    fn types(self) -> Vec<&'static str> {
+    pub(crate) fn types(self) -> Vec<&'static str> {
         match self {
             AmenityType::Bank => vec!["bank"],
             AmenityType::Bar => vec!["bar", "pub", "nightclub", "biergarten"],
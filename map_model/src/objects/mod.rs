@@ -1,4 +1,5 @@
 pub mod area;
+pub mod bikeshare;
 pub mod block;
 pub mod building;
 pub mod intersection;
@@ -2,11 +2,12 @@ use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
+use abstutil::Tags;
 use geom::{Angle, PolyLine};
 
 use crate::raw::RestrictionType;
 use crate::{
-    DirectedRoadID, Direction, Intersection, IntersectionID, LaneID, Map, MovementID,
+    osm, DirectedRoadID, Direction, Intersection, IntersectionID, LaneID, Map, MovementID,
     PathConstraints,
 };
 
@@ -52,6 +53,58 @@ impl TurnType {
     }
 }
 
+/// The kind of pedestrian crossing, distinguished mostly to affect walking route choice.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CrossingType {
+    /// Pedestrians cross with a dedicated signal phase
+    Signalized,
+    /// A marked zebra crossing, where pedestrians usually have priority
+    Marked,
+    /// Pedestrians may cross without priority over vehicles
+    Unmarked,
+}
+
+impl CrossingType {
+    /// Interprets the `crossing=*` tag (and the UK-specific `crossing_ref=*`) of a
+    /// `highway=crossing` node. Returns `None` for `crossing=no`.
+    pub fn from_osm(tags: &Tags) -> Option<CrossingType> {
+        if tags.is("crossing", "no") {
+            return None;
+        }
+        if tags.is("crossing", "traffic_signals")
+            || tags.is_any(
+                "crossing_ref",
+                vec!["pelican", "puffin", "toucan", "pegasus"],
+            )
+        {
+            return Some(CrossingType::Signalized);
+        }
+        if tags.is("crossing", "unmarked") {
+            return Some(CrossingType::Unmarked);
+        }
+        // marked, zebra, uncontrolled, island, or nothing specified
+        Some(CrossingType::Marked)
+    }
+
+    /// How this is stored in `osm::CROSSING_FWD` and `osm::CROSSING_BACK`
+    pub fn to_tag_value(self) -> &'static str {
+        match self {
+            CrossingType::Signalized => "signalized",
+            CrossingType::Marked => "marked",
+            CrossingType::Unmarked => "unmarked",
+        }
+    }
+
+    pub fn from_tag_value(value: &str) -> Option<CrossingType> {
+        match value {
+            "signalized" => Some(CrossingType::Signalized),
+            "marked" => Some(CrossingType::Marked),
+            "unmarked" => Some(CrossingType::Unmarked),
+            _ => None,
+        }
+    }
+}
+
 // TODO This concept may be dated, now that Movements exist. Within a movement, the lane-changing
 // turns should be treated as less important.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, PartialOrd)]
@@ -263,6 +316,37 @@ impl Turn {
         true
     }
 
+    /// What kind of pedestrian crossing is this? `None` for anything besides crosswalks and
+    /// unmarked crossings.
+    pub fn crossing_type(&self, map: &Map) -> Option<CrossingType> {
+        if !self.turn_type.pedestrian_crossing() {
+            return None;
+        }
+        if self.turn_type == TurnType::UnmarkedCrossing {
+            return Some(CrossingType::Unmarked);
+        }
+        if map.get_i(self.id.parent).is_traffic_signal() {
+            return Some(CrossingType::Signalized);
+        }
+        // A crossing node from OSM may have been snapped here
+        if let Some(dr) = self.crosswalk_over_road(map) {
+            let key = if dr.dir == Direction::Fwd {
+                osm::CROSSING_FWD
+            } else {
+                osm::CROSSING_BACK
+            };
+            if let Some(ct) = map
+                .get_r(dr.road)
+                .osm_tags
+                .get(key)
+                .and_then(|value| CrossingType::from_tag_value(value))
+            {
+                return Some(ct);
+            }
+        }
+        Some(CrossingType::Marked)
+    }
+
     /// If this turn is a crosswalk over a single road, return that road and which end of the road
     /// is crossed.
     pub fn crosswalk_over_road(&self, map: &Map) -> Option<DirectedRoadID> {
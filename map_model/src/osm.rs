@@ -30,6 +30,14 @@ pub const ENDPT_BACK: &str = "abst:endpt_back";
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
 pub const INFERRED_SIDEWALKS: &str = "abst:sidewalks_inferred";
+// If a `highway=crossing` node was snapped to one end of a road, what kind of crossing is it? The
+// value comes from `CrossingType::to_tag_value`.
+pub const CROSSING_FWD: &str = "abst:crossing_fwd";
+pub const CROSSING_BACK: &str = "abst:crossing_back";
+// From `kerb=*` on the same crossing nodes. "yes" if the curbs are lowered or flush, "no" if
+// they're raised. Missing if unknown.
+pub const CURB_RAMP_FWD: &str = "abst:curb_ramp_fwd";
+pub const CURB_RAMP_BACK: &str = "abst:curb_ramp_back";
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub enum RoadRank {
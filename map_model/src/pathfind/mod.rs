@@ -9,15 +9,18 @@ use geom::Duration;
 
 pub use self::engine::CreateEngine;
 pub use self::pathfinder::{Pathfinder, PathfinderCaching};
+pub use self::pedestrian_profile::PedestrianProfile;
 pub use self::v1::{Path, PathRequest, PathStep};
 pub use self::v2::{PathStepV2, PathV2};
 pub use self::vehicles::vehicle_cost;
+pub(crate) use self::walking::crossing_cost;
 pub use self::walking::WalkingNode;
 use crate::{osm, Lane, LaneID, LaneType, Map, MovementID, Road, RoadID, TurnType};
 
 mod engine;
 mod node_map;
 mod pathfinder;
+mod pedestrian_profile;
 // TODO tmp
 pub mod uber_turns;
 mod v1;
@@ -200,6 +203,25 @@ pub struct RoutingParams {
     /// pedestrian.
     #[serde(skip_serializing, skip_deserializing)]
     pub avoid_movements_between: BTreeSet<(RoadID, RoadID)>,
+
+    /// Route pedestrians for somebody with particular mobility needs. Some paths may become
+    /// impassable.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub pedestrian_profile: PedestrianProfile,
+
+    /// For pedestrian routing. Multiplies the base cost of crossing a road without a marked
+    /// crosswalk.
+    #[serde(
+        skip_serializing,
+        skip_deserializing,
+        default = "default_unmarked_crossing_penalty"
+    )]
+    pub unmarked_crossing_penalty: f64,
+    /// For pedestrian routing. Added to the cost of crossing at a traffic signal, as the expected
+    /// wait for the walk signal. The simulation models the real wait, but routes may zig-zag
+    /// across signals without this.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub signalized_crossing_delay: Duration,
 }
 
 impl Default for RoutingParams {
@@ -220,10 +242,19 @@ impl Default for RoutingParams {
 
             avoid_roads: BTreeSet::new(),
             avoid_movements_between: BTreeSet::new(),
+
+            pedestrian_profile: PedestrianProfile::Default,
+
+            unmarked_crossing_penalty: default_unmarked_crossing_penalty(),
+            signalized_crossing_delay: Duration::ZERO,
         }
     }
 }
 
+fn default_unmarked_crossing_penalty() -> f64 {
+    3.0
+}
+
 pub fn round(cost: Duration) -> usize {
     // Round up! 0 cost edges are ignored
     (cost.inner_seconds().round() as usize).max(1)
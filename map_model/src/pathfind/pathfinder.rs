@@ -116,12 +116,12 @@ impl Pathfinder {
         timer.stop("prepare pathfinding for trains");
 
         timer.start("prepare pathfinding for pedestrians");
-        let walking_graph = SidewalkPathfinder::new(map, None, &engine);
+        let walking_graph = SidewalkPathfinder::new(map, None, &params, &engine);
         timer.stop("prepare pathfinding for pedestrians");
 
         timer.start("prepare pathfinding for pedestrians using transit");
         let walking_with_transit_graph =
-            SidewalkPathfinder::new(map, Some((&bus_graph, &train_graph)), &engine);
+            SidewalkPathfinder::new(map, Some((&bus_graph, &train_graph)), &params, &engine);
         timer.stop("prepare pathfinding for pedestrians using transit");
 
         Pathfinder {
@@ -150,7 +150,7 @@ impl Pathfinder {
             timer.start(format!("prepare pathfinding for just {:?}", constraints));
             match constraints {
                 PathConstraints::Pedestrian => {
-                    p.walking_graph = SidewalkPathfinder::new(map, None, &engine);
+                    p.walking_graph = SidewalkPathfinder::new(map, None, &params, &engine);
                 }
                 PathConstraints::Car => {
                     p.car_graph = VehiclePathfinder::new(map, constraints, &params, &engine);
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{osm, Direction, Map, Road, Turn, TurnType};
+
+/// Wheelchair ramps can be at most 1:12, per the ADA.
+const MAX_WHEELCHAIR_INCLINE: f64 = 1.0 / 12.0;
+/// Walkers, canes, and people who tire easily can manage more than a wheelchair, but not much.
+const MAX_LIMITED_MOBILITY_INCLINE: f64 = 0.12;
+/// The ADA limit for running slope on an accessible route. Steeper than this is still possible,
+/// but harder.
+const COMFORTABLE_INCLINE: f64 = 0.05;
+
+/// Different people have different needs while walking. Besides the default, these profiles
+/// penalize some sidewalks and crossings, and make others impassable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PedestrianProfile {
+    Default,
+    /// Somebody using a walker or cane, or who otherwise has difficulty with stairs, rough
+    /// surfaces, and hills.
+    LimitedMobility,
+    /// A manual or powered wheelchair
+    Wheelchair,
+}
+
+impl Default for PedestrianProfile {
+    fn default() -> Self {
+        PedestrianProfile::Default
+    }
+}
+
+impl PedestrianProfile {
+    pub fn all() -> Vec<PedestrianProfile> {
+        vec![
+            PedestrianProfile::Default,
+            PedestrianProfile::LimitedMobility,
+            PedestrianProfile::Wheelchair,
+        ]
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            PedestrianProfile::Default => "default",
+            PedestrianProfile::LimitedMobility => "limited mobility",
+            PedestrianProfile::Wheelchair => "wheelchair",
+        }
+    }
+
+    /// Multiplies the cost of walking along a road's sidewalks or shoulders. `None` means they
+    /// can't be used at all.
+    pub fn road_cost(self, road: &Road) -> Option<f64> {
+        if self == PedestrianProfile::Default {
+            return Some(1.0);
+        }
+        let tags = &road.osm_tags;
+        let wheelchair = self == PedestrianProfile::Wheelchair;
+
+        if tags.is("wheelchair", "no") && wheelchair {
+            return None;
+        }
+        // Somebody's explicitly surveyed this, so trust it over the other tags
+        if tags.is_any("wheelchair", vec!["yes", "designated"]) {
+            return Some(1.0);
+        }
+
+        let mut cost = 1.0;
+
+        if tags.is(osm::HIGHWAY, "steps") {
+            if wheelchair || tags.is("handrail", "no") {
+                return None;
+            }
+            cost *= 4.0;
+        }
+
+        let incline = road.percent_incline.abs();
+        let max_incline = if wheelchair {
+            MAX_WHEELCHAIR_INCLINE
+        } else {
+            MAX_LIMITED_MOBILITY_INCLINE
+        };
+        if incline > max_incline {
+            return None;
+        }
+        if incline > COMFORTABLE_INCLINE {
+            cost *= 2.0;
+        }
+
+        match Surface::classify(road) {
+            Surface::Smooth => {}
+            Surface::Rough => {
+                cost *= if wheelchair { 3.0 } else { 1.5 };
+            }
+            Surface::Unpaved => {
+                if wheelchair {
+                    return None;
+                }
+                cost *= 2.0;
+            }
+        }
+
+        Some(cost)
+    }
+
+    /// Multiplies the cost of a crossing or a corner between two sidewalks. `None` means it can't
+    /// be used at all.
+    pub fn turn_cost(self, turn: &Turn, map: &Map) -> Option<f64> {
+        if self == PedestrianProfile::Default || !turn.turn_type.pedestrian_crossing() {
+            return Some(1.0);
+        }
+
+        // Is there a curb ramp on both sides of the crossing?
+        let curb_ramps = turn.crosswalk_over_road(map).and_then(|dr| {
+            let key = if dr.dir == Direction::Fwd {
+                osm::CURB_RAMP_FWD
+            } else {
+                osm::CURB_RAMP_BACK
+            };
+            map.get_r(dr.road).osm_tags.get(key).map(|x| x == "yes")
+        });
+        self.crossing_cost(curb_ramps, turn.turn_type)
+    }
+
+    /// `curb_ramps` is whether there's a ramp on both sides of the crossing, if that's known.
+    fn crossing_cost(self, curb_ramps: Option<bool>, turn_type: TurnType) -> Option<f64> {
+        let mut cost = match (self, curb_ramps) {
+            // A raised curb is a wall for a wheelchair
+            (PedestrianProfile::Wheelchair, Some(false)) => {
+                return None;
+            }
+            (_, Some(false)) => 2.0,
+            // Most places have ramps by now, but an unsurveyed crossing is a risk
+            (PedestrianProfile::Wheelchair, None) => 1.2,
+            _ => 1.0,
+        };
+
+        // Slower people need more of a gap in traffic to cross
+        if turn_type == TurnType::UnmarkedCrossing {
+            cost *= 2.0;
+        }
+        Some(cost)
+    }
+}
+
+enum Surface {
+    Smooth,
+    /// Passable with effort, like cobblestones
+    Rough,
+    Unpaved,
+}
+
+impl Surface {
+    fn classify(road: &Road) -> Surface {
+        let tags = &road.osm_tags;
+        // The surface of the road might not match its sidewalks
+        let surface = if tags.contains_key("sidewalk:surface") {
+            "sidewalk:surface"
+        } else {
+            "surface"
+        };
+        if tags.is_any(
+            "smoothness",
+            vec!["very_bad", "horrible", "very_horrible", "impassable"],
+        ) {
+            return Surface::Unpaved;
+        }
+        if tags.is_any(
+            surface,
+            vec![
+                "unpaved",
+                "gravel",
+                "fine_gravel",
+                "pebblestone",
+                "ground",
+                "dirt",
+                "earth",
+                "grass",
+                "mud",
+                "sand",
+                "woodchips",
+            ],
+        ) {
+            return Surface::Unpaved;
+        }
+        if tags.is("smoothness", "bad")
+            || tags.is_any(
+                surface,
+                vec![
+                    "compacted",
+                    "cobblestone",
+                    "sett",
+                    "unhewn_cobblestone",
+                    "grass_paver",
+                ],
+            )
+        {
+            return Surface::Rough;
+        }
+        Surface::Smooth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{PolyLine, Pt2D, Speed};
+
+    use super::*;
+    use crate::raw::OriginalRoad;
+    use crate::{AccessRestrictions, IntersectionID, RoadID};
+
+    fn road(tags: Vec<(&str, &str)>, percent_incline: f64) -> Road {
+        let mut osm_tags = abstutil::Tags::empty();
+        for (k, v) in tags {
+            osm_tags.insert(k, v);
+        }
+        let center_pts = PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]);
+        Road {
+            id: RoadID(0),
+            osm_tags,
+            turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+            orig_id: OriginalRoad::new(1, (1, 2)),
+            speed_limit: Speed::miles_per_hour(20.0),
+            access_restrictions: AccessRestrictions::new(),
+            zorder: 0,
+            percent_incline,
+            lanes: Vec::new(),
+            center_pts: center_pts.clone(),
+            untrimmed_center_pts: center_pts,
+            src_i: IntersectionID(0),
+            dst_i: IntersectionID(1),
+            crosswalk_forward: true,
+            crosswalk_backward: true,
+        }
+    }
+
+    #[test]
+    fn test_steps() {
+        let steps = road(vec![(osm::HIGHWAY, "steps")], 0.0);
+        assert_eq!(PedestrianProfile::Default.road_cost(&steps), Some(1.0));
+        assert_eq!(
+            PedestrianProfile::LimitedMobility.road_cost(&steps),
+            Some(4.0)
+        );
+        assert_eq!(PedestrianProfile::Wheelchair.road_cost(&steps), None);
+
+        let no_handrail = road(vec![(osm::HIGHWAY, "steps"), ("handrail", "no")], 0.0);
+        assert_eq!(
+            PedestrianProfile::LimitedMobility.road_cost(&no_handrail),
+            None
+        );
+
+        // A surveyed ramp alongside the steps overrides them
+        let ramp = road(vec![(osm::HIGHWAY, "steps"), ("wheelchair", "yes")], 0.0);
+        assert_eq!(PedestrianProfile::Wheelchair.road_cost(&ramp), Some(1.0));
+    }
+
+    #[test]
+    fn test_incline() {
+        for (incline, limited_mobility, wheelchair) in [
+            (0.0, Some(1.0), Some(1.0)),
+            (0.07, Some(2.0), Some(2.0)),
+            (-0.07, Some(2.0), Some(2.0)),
+            (0.1, Some(2.0), None),
+            (0.15, None, None),
+        ] {
+            let r = road(Vec::new(), incline);
+            assert_eq!(
+                PedestrianProfile::LimitedMobility.road_cost(&r),
+                limited_mobility,
+                "limited mobility on a {} incline",
+                incline
+            );
+            assert_eq!(
+                PedestrianProfile::Wheelchair.road_cost(&r),
+                wheelchair,
+                "wheelchair on a {} incline",
+                incline
+            );
+            assert_eq!(PedestrianProfile::Default.road_cost(&r), Some(1.0));
+        }
+    }
+
+    #[test]
+    fn test_curb_ramps() {
+        use PedestrianProfile::*;
+
+        for (profile, curb_ramps, expected) in [
+            (Wheelchair, Some(true), Some(1.0)),
+            (Wheelchair, None, Some(1.2)),
+            (Wheelchair, Some(false), None),
+            (LimitedMobility, Some(true), Some(1.0)),
+            (LimitedMobility, None, Some(1.0)),
+            (LimitedMobility, Some(false), Some(2.0)),
+        ] {
+            assert_eq!(
+                profile.crossing_cost(curb_ramps, TurnType::Crosswalk),
+                expected,
+                "{} crossing with curb ramps {:?}",
+                profile.describe(),
+                curb_ramps
+            );
+        }
+        // Unmarked crossings cost double on top of that
+        assert_eq!(
+            LimitedMobility.crossing_cost(Some(false), TurnType::UnmarkedCrossing),
+            Some(4.0)
+        );
+        assert_eq!(
+            Wheelchair.crossing_cost(Some(false), TurnType::UnmarkedCrossing),
+            None
+        );
+    }
+}
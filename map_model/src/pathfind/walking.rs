@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use fast_paths::InputGraph;
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration};
+use geom::{Distance, Duration, Speed};
 
 use crate::pathfind::engine::{CreateEngine, PathfindEngine};
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
@@ -13,8 +13,8 @@ use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::zone_cost;
 use crate::pathfind::{round, unround};
 use crate::{
-    DirectedRoadID, IntersectionID, Map, PathConstraints, PathRequest, PathStep, PathStepV2,
-    PathV2, Position, TransitRoute, TransitRouteID, TransitStopID, TurnType,
+    CrossingType, DirectedRoadID, IntersectionID, Map, PathConstraints, PathRequest, PathStep,
+    PathStepV2, PathV2, Position, RoutingParams, TransitRoute, TransitRouteID, TransitStopID, Turn,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -23,6 +23,9 @@ pub struct SidewalkPathfinder {
     nodes: NodeMap<WalkingNode>,
     use_transit: bool,
     engine: PathfindEngine,
+    // Maps always use the default params, so these don't need to be stored
+    #[serde(skip_serializing, skip_deserializing)]
+    params: RoutingParams,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Serialize, Deserialize)]
@@ -60,12 +63,14 @@ impl SidewalkPathfinder {
             nodes: NodeMap::new(),
             use_transit: false,
             engine: PathfindEngine::Empty,
+            params: RoutingParams::default(),
         }
     }
 
     pub fn new(
         map: &Map,
         use_transit: Option<(&VehiclePathfinder, &VehiclePathfinder)>,
+        params: &RoutingParams,
         engine: &CreateEngine,
     ) -> SidewalkPathfinder {
         let mut nodes = NodeMap::new();
@@ -89,13 +94,14 @@ impl SidewalkPathfinder {
             }
         }
 
-        let input_graph = make_input_graph(&nodes, use_transit, map);
+        let input_graph = make_input_graph(&nodes, use_transit, params, map);
         let engine = engine.create(input_graph);
 
         SidewalkPathfinder {
             nodes,
             use_transit: use_transit.is_some(),
             engine,
+            params: params.clone(),
         }
     }
 
@@ -108,7 +114,7 @@ impl SidewalkPathfinder {
             return;
         }
 
-        let input_graph = make_input_graph(&self.nodes, use_transit, map);
+        let input_graph = make_input_graph(&self.nodes, use_transit, &self.params, map);
         let engine = self.engine.reuse_ordering().create(input_graph);
         self.engine = engine;
     }
@@ -241,7 +247,7 @@ impl SidewalkPathfinder {
             self.engine.all_costs_from(start)
         } else {
             // The CH engine doesn't support this!
-            let input_graph = make_input_graph(&self.nodes, None, map);
+            let input_graph = make_input_graph(&self.nodes, None, &self.params, map);
             CreateEngine::Dijkstra
                 .create(input_graph)
                 .all_costs_from(start)
@@ -264,6 +270,7 @@ impl SidewalkPathfinder {
 fn make_input_graph(
     nodes: &NodeMap<WalkingNode>,
     use_transit: Option<(&VehiclePathfinder, &VehiclePathfinder)>,
+    params: &RoutingParams,
     map: &Map,
 ) -> InputGraph {
     let max_speed = Some(crate::MAX_WALKING_SPEED);
@@ -271,6 +278,10 @@ fn make_input_graph(
 
     for l in map.all_lanes() {
         if l.is_walkable() {
+            let profile_cost = match params.pedestrian_profile.road_cost(map.get_r(l.id.road)) {
+                Some(x) => x,
+                None => continue,
+            };
             // Sidewalks can be crossed in two directions. When there's a steep incline, of course
             // it flips.
             let n1 = nodes.get(WalkingNode::SidewalkEndpoint(
@@ -289,6 +300,7 @@ fn make_input_graph(
                 if l.is_shoulder() {
                     cost = 2.0 * cost;
                 }
+                cost = profile_cost * cost;
                 input_graph.add_edge(pair.0, pair.1, round(cost));
             }
         }
@@ -296,6 +308,10 @@ fn make_input_graph(
 
     for t in map.all_turns() {
         if t.between_sidewalks() {
+            let cost = match crossing_cost(t, max_speed, params, map) {
+                Some(x) => x,
+                None => continue,
+            };
             let src = map.get_l(t.id.src);
             let dst = map.get_l(t.id.dst);
             let from = nodes.get(WalkingNode::SidewalkEndpoint(
@@ -307,15 +323,6 @@ fn make_input_graph(
                 dst.dst_i == t.id.parent,
             ));
 
-            let mut cost = t.geom.length()
-                / PathStep::Turn(t.id).max_speed_along(max_speed, PathConstraints::Pedestrian, map)
-                + zone_cost(t.id.to_movement(map), PathConstraints::Pedestrian, map);
-
-            if t.turn_type == TurnType::UnmarkedCrossing {
-                // TODO Add to RoutingParams
-                cost = 3.0 * cost;
-            }
-
             input_graph.add_edge(from, to, round(cost));
             input_graph.add_edge(to, from, round(cost));
         }
@@ -330,6 +337,32 @@ fn make_input_graph(
     input_graph
 }
 
+/// The cost of walking along a turn between sidewalks, which might be a crossing. `None` means
+/// the pedestrian profile can't use it at all. Both the pathfinder and the connectivity search use
+/// this, so they agree about crossings.
+pub(crate) fn crossing_cost(
+    t: &Turn,
+    max_speed: Option<Speed>,
+    params: &RoutingParams,
+    map: &Map,
+) -> Option<Duration> {
+    let profile_cost = params.pedestrian_profile.turn_cost(t, map)?;
+    let mut cost = t.geom.length()
+        / PathStep::Turn(t.id).max_speed_along(max_speed, PathConstraints::Pedestrian, map)
+        + zone_cost(t.id.to_movement(map), PathConstraints::Pedestrian, map);
+
+    match t.crossing_type(map) {
+        Some(CrossingType::Unmarked) => {
+            cost = params.unmarked_crossing_penalty * cost;
+        }
+        Some(CrossingType::Signalized) => {
+            cost += params.signalized_crossing_delay;
+        }
+        Some(CrossingType::Marked) | None => {}
+    }
+    Some(profile_cost * cost)
+}
+
 fn transit_input_graph(
     input_graph: &mut InputGraph,
     nodes: &NodeMap<WalkingNode>,
@@ -0,0 +1,502 @@
+//! Imports points of interest from other datasets, like business licenses or lists of schools and
+//! clinics, as building amenities. Amenities normally only come from OSM tags, so this helps where
+//! OSM is incomplete.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::Result;
+use geojson::{GeoJson, Value};
+use serde::{Deserialize, Serialize};
+
+use abstutil::Tags;
+use geom::{Bounds, Distance, FindClosest, GPSBounds, LonLat, Polygon};
+
+use crate::raw::RawMap;
+use crate::{Amenity, AmenityType, Map, NamePerLanguage};
+
+/// Describes one dataset of points of interest, and how to categorize them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PoiSource {
+    /// A .csv or .geojson file. GeoJSON features must be points.
+    pub path: String,
+    /// The CSV column with the longitude of each POI
+    #[serde(default = "default_longitude_column")]
+    pub longitude_column: String,
+    /// The CSV column with the latitude of each POI
+    #[serde(default = "default_latitude_column")]
+    pub latitude_column: String,
+    /// The CSV column or GeoJSON property with the name of each POI
+    pub name_field: Option<String>,
+    /// The CSV column or GeoJSON property with the category of each POI
+    pub category_field: String,
+    /// Maps categories in the dataset to an `AmenityType` like "Medical", or to a specific OSM tag
+    /// value like "clinic" that belongs to one. POIs in other categories are skipped.
+    pub categories: BTreeMap<String, String>,
+    /// POIs further than this from every building are skipped
+    #[serde(default = "default_max_distance")]
+    pub max_distance: Distance,
+}
+
+fn default_longitude_column() -> String {
+    "longitude".to_string()
+}
+
+fn default_latitude_column() -> String {
+    "latitude".to_string()
+}
+
+fn default_max_distance() -> Distance {
+    Distance::meters(50.0)
+}
+
+pub struct Poi {
+    pub pt: LonLat,
+    pub name: Option<String>,
+    /// The specific type, like an OSM tag value, not the more general `AmenityType`
+    pub amenity_type: String,
+}
+
+pub struct PoiImportResults {
+    /// POIs added as new amenities
+    pub added: usize,
+    /// POIs matching an amenity that the building already had
+    pub duplicates: usize,
+    /// POIs that couldn't be used, and why
+    pub problems: Vec<String>,
+}
+
+impl PoiSource {
+    /// Reads and categorizes every POI from the file. Also returns a description of POIs that
+    /// couldn't be used.
+    pub fn read(&self) -> Result<(Vec<Poi>, Vec<String>)> {
+        self.parse(&abstio::slurp_file(&self.path)?)
+    }
+
+    /// Like `read`, but with the file's contents already loaded
+    pub fn parse(&self, bytes: &[u8]) -> Result<(Vec<Poi>, Vec<String>)> {
+        let categories = self.resolve_categories()?;
+        // (point, name, category)
+        let mut raw: Vec<(Option<LonLat>, Option<String>, Option<String>)> = Vec::new();
+        if self.path.ends_with(".geojson") {
+            let features = match String::from_utf8(bytes.to_vec())?.parse::<GeoJson>()? {
+                GeoJson::FeatureCollection(collection) => collection.features,
+                GeoJson::Feature(feature) => vec![feature],
+                GeoJson::Geometry(_) => bail!("Expected features, not just a geometry"),
+            };
+            for feature in features {
+                let pt = match feature.geometry.as_ref().map(|g| &g.value) {
+                    Some(Value::Point(pt)) => Some(LonLat::new(pt[0], pt[1])),
+                    _ => None,
+                };
+                let get = |field: &str| {
+                    feature.property(field).and_then(|value| match value {
+                        serde_json::Value::String(x) => Some(x.clone()),
+                        serde_json::Value::Number(x) => Some(x.to_string()),
+                        _ => None,
+                    })
+                };
+                raw.push((
+                    pt,
+                    self.name_field.as_ref().and_then(|field| get(field)),
+                    get(&self.category_field),
+                ));
+            }
+        } else if self.path.ends_with(".csv") {
+            let mut reader = csv::Reader::from_reader(bytes);
+            let headers = reader.headers()?.clone();
+            let column = |name: &str| -> Result<usize> {
+                headers
+                    .iter()
+                    .position(|x| x == name)
+                    .ok_or_else(|| anyhow!("{} has no {} column", self.path, name))
+            };
+            let lon_col = column(&self.longitude_column)?;
+            let lat_col = column(&self.latitude_column)?;
+            let category_col = column(&self.category_field)?;
+            let name_col = match self.name_field {
+                Some(ref name) => Some(column(name)?),
+                None => None,
+            };
+            for rec in reader.records() {
+                let rec = rec?;
+                let pt = match (
+                    rec.get(lon_col).and_then(|x| x.parse::<f64>().ok()),
+                    rec.get(lat_col).and_then(|x| x.parse::<f64>().ok()),
+                ) {
+                    (Some(lon), Some(lat)) => Some(LonLat::new(lon, lat)),
+                    _ => None,
+                };
+                raw.push((
+                    pt,
+                    name_col.and_then(|col| rec.get(col)).map(|x| x.to_string()),
+                    rec.get(category_col).map(|x| x.to_string()),
+                ));
+            }
+        } else {
+            bail!("{} must be a .csv or .geojson file", self.path);
+        }
+
+        let mut pois = Vec::new();
+        let mut problems = Vec::new();
+        for (idx, (pt, name, category)) in raw.into_iter().enumerate() {
+            let pt = match pt {
+                Some(pt) => pt,
+                None => {
+                    problems.push(format!("POI #{} doesn't have a valid point", idx + 1));
+                    continue;
+                }
+            };
+            let amenity_type = match category.and_then(|x| categories.get(&x).cloned()) {
+                Some(x) => x,
+                None => {
+                    continue;
+                }
+            };
+            pois.push(Poi {
+                pt,
+                name: name.filter(|x| !x.is_empty()),
+                amenity_type,
+            });
+        }
+        Ok((pois, problems))
+    }
+
+    /// Maps categories in the dataset to specific amenity types
+    fn resolve_categories(&self) -> Result<BTreeMap<String, String>> {
+        let mut result = BTreeMap::new();
+        for (category, target) in &self.categories {
+            let amenity_type = if let Ok(at) = AmenityType::from_str(target) {
+                at.types()[0].to_string()
+            } else if AmenityType::categorize(target).is_some() {
+                target.clone()
+            } else {
+                bail!(
+                    "{} is mapped to {}, which isn't an amenity type or a known OSM tag",
+                    category,
+                    target
+                );
+            };
+            result.insert(category.clone(), amenity_type);
+        }
+        Ok(result)
+    }
+}
+
+/// Snaps every POI to the closest building, and adds it as an amenity there, unless the building
+/// already has a matching one.
+pub fn import_pois(map: &mut Map, source: &PoiSource, pois: Vec<Poi>) -> PoiImportResults {
+    let matches = match_buildings(
+        map.get_gps_bounds(),
+        map.get_bounds(),
+        map.all_buildings()
+            .iter()
+            .map(|b| (b.id, &b.polygon))
+            .collect(),
+        source,
+        pois,
+    );
+    let mut results = PoiImportResults {
+        added: 0,
+        duplicates: 0,
+        problems: matches.1,
+    };
+    for (b, poi) in matches.0 {
+        if merge_amenity(&mut map.mut_building(b).amenities, poi, source) {
+            results.added += 1;
+        } else {
+            results.duplicates += 1;
+        }
+    }
+    results
+}
+
+/// Like `import_pois`, but for the importer, before the map is built
+pub fn import_pois_to_raw(
+    map: &mut RawMap,
+    source: &PoiSource,
+    pois: Vec<Poi>,
+) -> PoiImportResults {
+    let matches = match_buildings(
+        &map.gps_bounds,
+        &map.gps_bounds.to_bounds(),
+        map.buildings
+            .iter()
+            .map(|(id, b)| (*id, &b.polygon))
+            .collect(),
+        source,
+        pois,
+    );
+    let mut results = PoiImportResults {
+        added: 0,
+        duplicates: 0,
+        problems: matches.1,
+    };
+    for (id, poi) in matches.0 {
+        if merge_amenity(
+            &mut map.buildings.get_mut(&id).unwrap().amenities,
+            poi,
+            source,
+        ) {
+            results.added += 1;
+        } else {
+            results.duplicates += 1;
+        }
+    }
+    results
+}
+
+fn match_buildings<K: Clone + Ord + std::fmt::Debug>(
+    gps_bounds: &GPSBounds,
+    bounds: &Bounds,
+    buildings: Vec<(K, &Polygon)>,
+    source: &PoiSource,
+    pois: Vec<Poi>,
+) -> (Vec<(K, Poi)>, Vec<String>) {
+    let mut closest: FindClosest<K> = FindClosest::new(bounds);
+    for (id, polygon) in buildings {
+        closest.add(id, polygon.points());
+    }
+
+    let mut matches = Vec::new();
+    let mut problems = Vec::new();
+    for poi in pois {
+        if !gps_bounds.contains(poi.pt) {
+            problems.push(format!("{} is outside the map", describe(&poi)));
+            continue;
+        }
+        match closest.closest_pt(poi.pt.to_pt(gps_bounds), source.max_distance) {
+            Some((id, _)) => {
+                matches.push((id, poi));
+            }
+            None => {
+                problems.push(format!(
+                    "{} is more than {} from any building",
+                    describe(&poi),
+                    source.max_distance
+                ));
+            }
+        }
+    }
+    (matches, problems)
+}
+
+/// Adds the POI to a building's amenities, returning false if it's a duplicate. A POI duplicates
+/// an amenity in the same `AmenityType` with the same name, or where either one is unnamed. If
+/// only the POI has a name, the existing amenity gets it.
+fn merge_amenity(amenities: &mut Vec<Amenity>, poi: Poi, source: &PoiSource) -> bool {
+    let category = AmenityType::categorize(&poi.amenity_type);
+    for amenity in amenities.iter_mut() {
+        if AmenityType::categorize(&amenity.amenity_type) != category {
+            continue;
+        }
+        let existing_name = if amenity.names == NamePerLanguage::unnamed() {
+            None
+        } else {
+            Some(amenity.names.get(None))
+        };
+        match (existing_name, &poi.name) {
+            (Some(x), Some(y)) if normalize(x) != normalize(y) => {
+                continue;
+            }
+            (None, Some(name)) => {
+                amenity.names = NamePerLanguage(vec![(None, name.clone())].into_iter().collect());
+            }
+            _ => {}
+        }
+        return false;
+    }
+
+    let mut tags = Tags::empty();
+    if let Some(ref name) = poi.name {
+        tags.insert(crate::osm::NAME, name.clone());
+    }
+    tags.insert("source", source.path.clone());
+    amenities.push(Amenity {
+        names: NamePerLanguage::new(&tags).unwrap_or_else(NamePerLanguage::unnamed),
+        amenity_type: poi.amenity_type,
+        osm_tags: tags,
+    });
+    true
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn describe(poi: &Poi) -> String {
+    format!(
+        "{} at {}",
+        poi.name.as_ref().unwrap_or(&poi.amenity_type),
+        poi.pt
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str, categories: Vec<(&str, &str)>) -> PoiSource {
+        PoiSource {
+            path: path.to_string(),
+            longitude_column: default_longitude_column(),
+            latitude_column: default_latitude_column(),
+            name_field: Some("name".to_string()),
+            category_field: "kind".to_string(),
+            categories: categories
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            max_distance: default_max_distance(),
+        }
+    }
+
+    fn poi(name: Option<&str>, amenity_type: &str) -> Poi {
+        Poi {
+            pt: LonLat::new(-122.3, 47.6),
+            name: name.map(|x| x.to_string()),
+            amenity_type: amenity_type.to_string(),
+        }
+    }
+
+    fn amenity(name: Option<&str>, amenity_type: &str) -> Amenity {
+        let mut tags = Tags::empty();
+        if let Some(name) = name {
+            tags.insert(crate::osm::NAME, name);
+        }
+        Amenity {
+            names: NamePerLanguage::new(&tags).unwrap_or_else(NamePerLanguage::unnamed),
+            amenity_type: amenity_type.to_string(),
+            osm_tags: tags,
+        }
+    }
+
+    #[test]
+    fn test_resolve_categories() {
+        // An AmenityType becomes its first OSM tag value, and a known tag value is kept
+        let categories = source(
+            "x.csv",
+            vec![("Health center", "Medical"), ("Pharmacy", "pharmacy")],
+        )
+        .resolve_categories()
+        .unwrap();
+        assert_eq!(categories["Health center"], "clinic");
+        assert_eq!(categories["Pharmacy"], "pharmacy");
+
+        assert!(source("x.csv", vec![("Spaceport", "rocket")])
+            .resolve_categories()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "name,kind,longitude,latitude
+Northgate Clinic,Health center,-122.32,47.70
+Joe's Garage,Car repair,-122.33,47.71
+,Health center,-122.34,47.72
+Nowhere Clinic,Health center,,47.73
+";
+        let (pois, problems) = source("x.csv", vec![("Health center", "Medical")])
+            .parse(csv.as_bytes())
+            .unwrap();
+        // Unmapped categories are skipped silently, but missing points are reported
+        assert_eq!(pois.len(), 2);
+        assert_eq!(pois[0].name.as_deref(), Some("Northgate Clinic"));
+        assert_eq!(pois[0].amenity_type, "clinic");
+        assert_eq!(pois[0].pt, LonLat::new(-122.32, 47.70));
+        // Empty names become None
+        assert_eq!(pois[1].name, None);
+        assert_eq!(
+            problems,
+            vec!["POI #4 doesn't have a valid point".to_string()]
+        );
+
+        // A missing column is an error
+        let mut missing_column = source("x.csv", vec![("Health center", "Medical")]);
+        missing_column.latitude_column = "lat".to_string();
+        assert!(missing_column.parse(csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_geojson() {
+        let geojson = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [-122.32, 47.70] },
+                    "properties": { "name": "Corner Grocer", "kind": 5411 }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "LineString",
+                        "coordinates": [[-122.32, 47.70], [-122.33, 47.71]]
+                    },
+                    "properties": { "name": "Not a point", "kind": 5411 }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [-122.34, 47.72] },
+                    "properties": { "name": "Unknown", "kind": 9999 }
+                }
+            ]
+        }"#;
+        // Numeric categories are compared as strings
+        let (pois, problems) = source("x.geojson", vec![("5411", "supermarket")])
+            .parse(geojson.as_bytes())
+            .unwrap();
+        assert_eq!(pois.len(), 1);
+        assert_eq!(pois[0].name.as_deref(), Some("Corner Grocer"));
+        assert_eq!(pois[0].amenity_type, "supermarket");
+        assert_eq!(
+            problems,
+            vec!["POI #2 doesn't have a valid point".to_string()]
+        );
+
+        assert!(source("x.txt", Vec::new()).parse(b"").is_err());
+    }
+
+    #[test]
+    fn test_merge_amenity() {
+        let src = source("clinics.csv", Vec::new());
+        let mut amenities = vec![amenity(Some("Northgate Clinic"), "clinic")];
+
+        // Same type and name, ignoring case and punctuation
+        assert!(!merge_amenity(
+            &mut amenities,
+            poi(Some("NORTHGATE clinic!"), "doctors"),
+            &src
+        ));
+        // An unnamed POI matches anything of the same type
+        assert!(!merge_amenity(&mut amenities, poi(None, "clinic"), &src));
+        assert_eq!(amenities.len(), 1);
+
+        // A different name or type is a new amenity
+        assert!(merge_amenity(
+            &mut amenities,
+            poi(Some("Lake City Clinic"), "clinic"),
+            &src
+        ));
+        assert!(merge_amenity(
+            &mut amenities,
+            poi(Some("Northgate Clinic"), "cafe"),
+            &src
+        ));
+        assert_eq!(amenities.len(), 3);
+        assert_eq!(amenities[1].names.get(None), "Lake City Clinic");
+        assert_eq!(amenities[1].osm_tags.get("source").unwrap(), "clinics.csv");
+
+        // An unnamed amenity gets the POI's name
+        let mut amenities = vec![amenity(None, "pharmacy")];
+        assert!(!merge_amenity(
+            &mut amenities,
+            poi(Some("Corner Pharmacy"), "pharmacy"),
+            &src
+        ));
+        assert_eq!(amenities[0].names.get(None), "Corner Pharmacy");
+    }
+}
@@ -40,6 +40,7 @@ pub struct RawMap {
     pub areas: Vec<RawArea>,
     pub parking_lots: Vec<RawParkingLot>,
     pub parking_aisles: Vec<(osm::WayID, Vec<Pt2D>)>,
+    pub bikeshare_docks: Vec<RawBikeshareDock>,
     pub transit_routes: Vec<RawTransitRoute>,
     #[serde(
         serialize_with = "serialize_btreemap",
@@ -121,6 +122,7 @@ impl RawMap {
             areas: Vec::new(),
             parking_lots: Vec::new(),
             parking_aisles: Vec::new(),
+            bikeshare_docks: Vec::new(),
             transit_routes: Vec::new(),
             transit_stops: BTreeMap::new(),
             // Some nonsense thing
@@ -703,6 +705,14 @@ pub struct RawParkingLot {
     pub osm_tags: Tags,
 }
 
+/// A bikeshare station, from `amenity=bicycle_rental`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RawBikeshareDock {
+    pub osm_id: osm::OsmID,
+    pub pt: Pt2D,
+    pub osm_tags: Tags,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum RestrictionType {
     BanTurns,
@@ -4,6 +4,9 @@ extern crate log;
 use structopt::StructOpt;
 
 mod mapper;
+mod osmchange;
+mod session;
+mod tags;
 
 fn main() {
     let mut options = map_gui::options::Options::load_or_default();
@@ -1,18 +1,20 @@
-use std::collections::{BTreeMap, HashSet};
-
-use anyhow::Result;
+use std::collections::HashSet;
 
 use abstutil::{prettyprint_usize, Timer};
 use geom::{Distance, FindClosest, PolyLine, Polygon};
-use map_gui::tools::{open_browser, CityPicker, ColorLegend, PopupMsg};
+use map_gui::tools::{open_browser, ChooseSomething, CityPicker, ColorLegend, PopupMsg};
 use map_gui::{SimpleApp, ID};
-use map_model::{osm, RoadID};
+use map_model::{osm, Road, RoadID};
 use osm::WayID;
 use widgetry::{
     Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Menu,
     Outcome, Panel, State, Text, TextExt, Toggle, Transition, VerticalAlignment, Widget,
 };
 
+use crate::osmchange::generate_osmc;
+use crate::session::Session;
+use crate::tags::{existing_value, Schema, Value};
+
 type App = SimpleApp<()>;
 
 pub struct ParkingMapper {
@@ -21,7 +23,7 @@ pub struct ParkingMapper {
     show: Show,
     selected: Option<(HashSet<RoadID>, Drawable)>,
 
-    data: BTreeMap<WayID, Value>,
+    session: Session,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -33,26 +35,12 @@ enum Show {
     OverlappingStuff,
 }
 
-#[derive(PartialEq, Clone)]
-pub enum Value {
-    BothSides,
-    NoStopping,
-    RightOnly,
-    LeftOnly,
-    Complicated,
-}
-
 impl ParkingMapper {
     pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
-        ParkingMapper::make(ctx, app, Show::ToDo, BTreeMap::new())
+        ParkingMapper::make(ctx, app, Show::ToDo, Session::load(&app.map))
     }
 
-    fn make(
-        ctx: &mut EventCtx,
-        app: &App,
-        show: Show,
-        data: BTreeMap<WayID, Value>,
-    ) -> Box<dyn State<App>> {
+    fn make(ctx: &mut EventCtx, app: &App, show: Show, session: Session) -> Box<dyn State<App>> {
         let map = &app.map;
 
         let color = match show {
@@ -69,9 +57,7 @@ impl ParkingMapper {
             if r.is_light_rail() {
                 continue;
             }
-            if r.osm_tags.contains_key(osm::INFERRED_PARKING)
-                && !data.contains_key(&r.orig_id.osm_way_id)
-            {
+            if needs_mapping(r, &session) {
                 todo.insert(r.orig_id.osm_way_id);
                 if show == Show::ToDo {
                     batch.push(color, map.get_r(r.id).get_thick_polygon());
@@ -109,16 +95,16 @@ impl ParkingMapper {
 
         // Nicer display
         for i in map.all_intersections() {
-            let is_todo = i.roads.iter().any(|id| {
-                let r = map.get_r(*id);
-                r.osm_tags.contains_key(osm::INFERRED_PARKING)
-                    && !data.contains_key(&r.orig_id.osm_way_id)
-            });
+            let is_todo = i
+                .roads
+                .iter()
+                .any(|id| needs_mapping(map.get_r(*id), &session));
             if matches!((show, is_todo), (Show::ToDo, true) | (Show::Done, false)) {
                 batch.push(color, i.polygon.clone());
             }
         }
 
+        let num_problems = session.problems(map).len();
         Box::new(ParkingMapper {
             draw_layer: ctx.upload(batch),
             show,
@@ -128,9 +114,24 @@ impl ParkingMapper {
                     "{} / {} ways done (you've mapped {})",
                     prettyprint_usize(done.len()),
                     prettyprint_usize(done.len() + todo.len()),
-                    data.len()
+                    session.ways.len()
                 )
                 .text_widget(ctx),
+                Line("Your work is saved automatically")
+                    .secondary()
+                    .into_widget(ctx),
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_outline
+                        .text(format!("Review {} problems", num_problems))
+                        .disabled(num_problems == 0)
+                        .build_widget(ctx, "review problems"),
+                    ctx.style()
+                        .btn_outline
+                        .text("Start over")
+                        .disabled(session.ways.is_empty())
+                        .build_def(ctx),
+                ]),
                 Widget::row(vec![
                     Widget::dropdown(
                         ctx,
@@ -164,7 +165,26 @@ impl ParkingMapper {
                         },
                     ),
                 ]),
-                Toggle::checkbox(ctx, "max 3 days parking (default in Seattle)", None, false),
+                Widget::row(vec![
+                    "Write tags as".text_widget(ctx).centered_vert(),
+                    Widget::dropdown(
+                        ctx,
+                        "schema",
+                        session.schema,
+                        vec![
+                            Choice::new(Schema::Lane.describe(), Schema::Lane).tooltip(
+                                "The older schema, which the A/B Street importer understands",
+                            ),
+                            Choice::new(Schema::Side.describe(), Schema::Side),
+                        ],
+                    ),
+                ]),
+                Toggle::checkbox(
+                    ctx,
+                    "max 3 days parking (default in Seattle)",
+                    None,
+                    session.seattle_max_stay,
+                ),
                 ctx.style()
                     .btn_outline
                     .text("Generate OsmChange file")
@@ -174,7 +194,7 @@ impl ParkingMapper {
             .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
             .build(ctx),
             selected: None,
-            data,
+            session,
         })
     }
 }
@@ -233,12 +253,18 @@ impl State<App> for ParkingMapper {
                         Key::E.txt(ctx),
                         Line(" to edit OpenStreetMap for this way"),
                     ]);
+                    if let Some(mapped) = self.session.ways.get(&way) {
+                        txt.add_line(format!("You said: {}", mapped.value.describe()));
+                    }
                     for (k, v) in road.osm_tags.inner() {
                         if k.starts_with("abst:") {
                             continue;
                         }
                         if k.contains("parking") {
-                            if !road.osm_tags.contains_key(osm::INFERRED_PARKING) {
+                            // The importer fills in parking:lane:* when it's missing
+                            if !road.osm_tags.contains_key(osm::INFERRED_PARKING)
+                                || !k.starts_with("parking:lane:")
+                            {
                                 txt.add_line(format!("{} = {}", k, v));
                             }
                         } else if k == "sidewalk" {
@@ -263,7 +289,7 @@ impl State<App> for ParkingMapper {
                 app,
                 &self.selected.as_ref().unwrap().0,
                 self.show,
-                self.data.clone(),
+                self.session.clone(),
             ));
         }
         if self.selected.is_some() && ctx.input.pressed(Key::N) {
@@ -271,9 +297,9 @@ impl State<App> for ParkingMapper {
                 .get_r(*self.selected.as_ref().unwrap().0.iter().next().unwrap())
                 .orig_id
                 .osm_way_id;
-            let mut new_data = self.data.clone();
-            new_data.insert(osm_way_id, Value::NoStopping);
-            return Transition::Replace(ParkingMapper::make(ctx, app, self.show, new_data));
+            let mut session = self.session.clone();
+            session.set(map, osm_way_id, Value::NoStopping);
+            return Transition::Replace(ParkingMapper::make(ctx, app, self.show, session));
         }
         if self.selected.is_some() && ctx.input.pressed(Key::S) {
             if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
@@ -300,7 +326,7 @@ impl State<App> for ParkingMapper {
         match self.panel.event(ctx) {
             Outcome::Clicked(x) => match x.as_ref() {
                 "Generate OsmChange file" => {
-                    if self.data.is_empty() {
+                    if self.session.ways.is_empty() {
                         return Transition::Push(PopupMsg::new_state(
                             ctx,
                             "No changes yet",
@@ -309,17 +335,27 @@ impl State<App> for ParkingMapper {
                     }
                     return match ctx.loading_screen("generate OsmChange file", |_, timer| {
                         generate_osmc(
-                            &self.data,
-                            self.panel
-                                .is_checked("max 3 days parking (default in Seattle)"),
+                            &self.session,
+                            self.session.schema,
+                            self.session.seattle_max_stay,
                             timer,
                         )
                     }) {
-                        Ok(()) => Transition::Push(PopupMsg::new_state(
-                            ctx,
-                            "Diff generated",
-                            vec!["diff.osc created. Load it in JOSM, verify, and upload!"],
-                        )),
+                        Ok(result) => {
+                            let mut lines = if result.num_ways == 0 {
+                                vec!["None of the ways can be exported".to_string()]
+                            } else {
+                                vec![format!(
+                                    "{} created with {} ways. Load it in JOSM, verify, and upload!",
+                                    result.path,
+                                    prettyprint_usize(result.num_ways)
+                                )]
+                            };
+                            for (way, reason) in result.skipped {
+                                lines.push(format!("Skipped {}: {}", way, reason));
+                            }
+                            Transition::Push(PopupMsg::new_state(ctx, "Diff generated", lines))
+                        }
                         Err(err) => Transition::Push(PopupMsg::new_state(
                             ctx,
                             "Error",
@@ -327,6 +363,39 @@ impl State<App> for ParkingMapper {
                         )),
                     };
                 }
+                "review problems" => {
+                    return Transition::Push(PopupMsg::new_state(
+                        ctx,
+                        "Problems",
+                        self.session
+                            .problems(map)
+                            .into_iter()
+                            .map(|(way, problem)| format!("{}: {}", way, problem))
+                            .collect(),
+                    ));
+                }
+                "Start over" => {
+                    let show = self.show;
+                    return Transition::Push(ChooseSomething::new_state(
+                        ctx,
+                        "Discard everything you've mapped here?",
+                        vec![
+                            Choice::new("Yes, start over", true),
+                            Choice::new("No, keep going", false),
+                        ],
+                        Box::new(move |discard, ctx, app| {
+                            if !discard {
+                                return Transition::Pop;
+                            }
+                            let mut session = Session::load(&app.map);
+                            session.clear();
+                            Transition::Multi(vec![
+                                Transition::Pop,
+                                Transition::Replace(ParkingMapper::make(ctx, app, show, session)),
+                            ])
+                        }),
+                    ));
+                }
                 "Home" => {
                     return Transition::Pop;
                 }
@@ -341,7 +410,7 @@ impl State<App> for ParkingMapper {
                                     ctx,
                                     app,
                                     Show::ToDo,
-                                    BTreeMap::new(),
+                                    Session::load(&app.map),
                                 )),
                             ])
                         }),
@@ -349,14 +418,26 @@ impl State<App> for ParkingMapper {
                 }
                 _ => unreachable!(),
             },
-            Outcome::Changed(_) => {
-                return Transition::Replace(ParkingMapper::make(
-                    ctx,
-                    app,
-                    self.panel.dropdown_value("Show"),
-                    self.data.clone(),
-                ));
-            }
+            Outcome::Changed(x) => match x.as_ref() {
+                "schema" => {
+                    self.session.schema = self.panel.dropdown_value("schema");
+                    self.session.save();
+                }
+                "max 3 days parking (default in Seattle)" => {
+                    self.session.seattle_max_stay = self
+                        .panel
+                        .is_checked("max 3 days parking (default in Seattle)");
+                    self.session.save();
+                }
+                _ => {
+                    return Transition::Replace(ParkingMapper::make(
+                        ctx,
+                        app,
+                        self.panel.dropdown_value("Show"),
+                        self.session.clone(),
+                    ));
+                }
+            },
             _ => {}
         }
 
@@ -376,7 +457,7 @@ struct ChangeWay {
     panel: Panel,
     draw: Drawable,
     osm_way_id: WayID,
-    data: BTreeMap<WayID, Value>,
+    session: Session,
     show: Show,
 }
 
@@ -386,13 +467,22 @@ impl ChangeWay {
         app: &App,
         selected: &HashSet<RoadID>,
         show: Show,
-        data: BTreeMap<WayID, Value>,
+        session: Session,
     ) -> Box<dyn State<App>> {
         let map = &app.map;
-        let osm_way_id = map
-            .get_r(*selected.iter().next().unwrap())
-            .orig_id
-            .osm_way_id;
+        let road = map.get_r(*selected.iter().next().unwrap());
+        let osm_way_id = road.orig_id.osm_way_id;
+        let existing = match existing_value(&road.osm_tags) {
+            Some(value) => format!(
+                "OSM currently says {}",
+                match value {
+                    Value::RightOnly => "just the green side",
+                    Value::LeftOnly => "just the blue side",
+                    _ => value.describe(),
+                }
+            ),
+            None => "OSM doesn't say yet".to_string(),
+        };
 
         let mut batch = GeomBatch::new();
         let thickness = Distance::meters(2.0);
@@ -420,6 +510,7 @@ impl ChangeWay {
                         .into_widget(ctx),
                     ctx.style().btn_close_widget(ctx),
                 ]),
+                existing.text_widget(ctx),
                 Menu::widget(
                     ctx,
                     vec![
@@ -439,7 +530,7 @@ impl ChangeWay {
             .build(ctx),
             draw: ctx.upload(batch),
             osm_way_id,
-            data,
+            session,
             show,
         })
     }
@@ -463,14 +554,14 @@ impl State<App> for ChangeWay {
                             ],
                         ))
                     } else {
-                        self.data.insert(self.osm_way_id, value);
+                        self.session.set(&app.map, self.osm_way_id, value);
                         Transition::Multi(vec![
                             Transition::Pop,
                             Transition::Replace(ParkingMapper::make(
                                 ctx,
                                 app,
                                 self.show,
-                                self.data.clone(),
+                                self.session.clone(),
                             )),
                         ])
                     }
@@ -491,99 +582,11 @@ impl State<App> for ChangeWay {
     }
 }
 
-fn generate_osmc(data: &BTreeMap<WayID, Value>, in_seattle: bool, timer: &mut Timer) -> Result<()> {
-    use std::io::Write;
-
-    use fs_err::File;
-
-    use abstutil::Tags;
-
-    let mut modified_ways = Vec::new();
-    timer.start_iter("fetch latest OSM data per modified way", data.len());
-    for (way, value) in data {
-        timer.next();
-        if value == &Value::Complicated {
-            continue;
-        }
-
-        let url = format!("https://api.openstreetmap.org/api/0.6/way/{}", way.0);
-        info!("Fetching {}", url);
-        let resp = reqwest::blocking::get(&url)?.text()?;
-        let mut tree = xmltree::Element::parse(resp.as_bytes())?
-            .take_child("way")
-            .unwrap();
-        let mut osm_tags = Tags::empty();
-        let mut other_children = Vec::new();
-        for node in tree.children.drain(..) {
-            if let Some(elem) = node.as_element() {
-                if elem.name == "tag" {
-                    osm_tags.insert(elem.attributes["k"].clone(), elem.attributes["v"].clone());
-                    continue;
-                }
-            }
-            other_children.push(node);
-        }
-
-        // Fill out the tags.
-        osm_tags.remove(osm::PARKING_LEFT);
-        osm_tags.remove(osm::PARKING_RIGHT);
-        osm_tags.remove(osm::PARKING_BOTH);
-        match value {
-            Value::BothSides => {
-                osm_tags.insert(osm::PARKING_BOTH, "parallel");
-                if in_seattle {
-                    osm_tags.insert("parking:condition:both:maxstay", "3 days");
-                }
-            }
-            Value::NoStopping => {
-                osm_tags.insert(osm::PARKING_BOTH, "no_stopping");
-            }
-            Value::RightOnly => {
-                osm_tags.insert(osm::PARKING_RIGHT, "parallel");
-                osm_tags.insert(osm::PARKING_LEFT, "no_stopping");
-                if in_seattle {
-                    osm_tags.insert("parking:condition:right:maxstay", "3 days");
-                }
-            }
-            Value::LeftOnly => {
-                osm_tags.insert(osm::PARKING_LEFT, "parallel");
-                osm_tags.insert(osm::PARKING_RIGHT, "no_stopping");
-                if in_seattle {
-                    osm_tags.insert("parking:condition:left:maxstay", "3 days");
-                }
-            }
-            Value::Complicated => unreachable!(),
-        }
-
-        tree.children = other_children;
-        for (k, v) in osm_tags.inner() {
-            let mut new_elem = xmltree::Element::new("tag");
-            new_elem.attributes.insert("k".to_string(), k.to_string());
-            new_elem.attributes.insert("v".to_string(), v.to_string());
-            tree.children.push(xmltree::XMLNode::Element(new_elem));
-        }
-
-        tree.attributes.remove("timestamp");
-        tree.attributes.remove("changeset");
-        tree.attributes.remove("user");
-        tree.attributes.remove("uid");
-        tree.attributes.remove("visible");
-
-        let mut bytes: Vec<u8> = Vec::new();
-        tree.write(&mut bytes)?;
-        let out = String::from_utf8(bytes)?;
-        let stripped = out.trim_start_matches("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
-        modified_ways.push(stripped.to_string());
-    }
-
-    let mut f = File::create("diff.osc")?;
-    writeln!(f, "<osmChange version=\"0.6\" generator=\"abst\"><modify>")?;
-    for w in modified_ways {
-        writeln!(f, "  {}", w)?;
-    }
-    writeln!(f, "</modify></osmChange>")?;
-    info!("Wrote diff.osc");
-    Ok(())
+/// Does the way still need someone to look at it?
+fn needs_mapping(r: &Road, session: &Session) -> bool {
+    r.osm_tags.contains_key(osm::INFERRED_PARKING)
+        && existing_value(&r.osm_tags).is_none()
+        && !session.ways.contains_key(&r.orig_id.osm_way_id)
 }
 
 fn find_divided_highways(app: &App) -> HashSet<RoadID> {
@@ -0,0 +1,113 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use fs_err::File;
+
+use abstutil::{Tags, Timer};
+use map_model::osm::WayID;
+
+use crate::session::Session;
+use crate::tags::{parking_tags, Schema, Value};
+
+pub struct OsmChange {
+    /// Where the .osc file was written
+    pub path: String,
+    pub num_ways: usize,
+    /// Ways that weren't included, and why
+    pub skipped: Vec<(WayID, String)>,
+}
+
+/// Fetches the latest version of every mapped way from OSM, fills out the parking tags, and writes
+/// an OsmChange file to review and upload. Ways that changed in OSM since they were mapped are
+/// skipped, so somebody else's work isn't overwritten.
+pub fn generate_osmc(
+    session: &Session,
+    schema: Schema,
+    in_seattle: bool,
+    timer: &mut Timer,
+) -> Result<OsmChange> {
+    let mut modified_ways = Vec::new();
+    let mut skipped = Vec::new();
+    timer.start_iter("fetch latest OSM data per modified way", session.ways.len());
+    for (way, mapped) in &session.ways {
+        timer.next();
+        if mapped.value == Value::Complicated {
+            skipped.push((*way, "it needs to be split and tagged manually".to_string()));
+            continue;
+        }
+
+        let url = format!("https://api.openstreetmap.org/api/0.6/way/{}", way.0);
+        info!("Fetching {}", url);
+        let resp = reqwest::blocking::get(&url)?.text()?;
+        let mut tree = xmltree::Element::parse(resp.as_bytes())?
+            .take_child("way")
+            .ok_or_else(|| anyhow!("{} doesn't have a way", url))?;
+        let mut osm_tags = Tags::empty();
+        let mut other_children = Vec::new();
+        for node in tree.children.drain(..) {
+            if let Some(elem) = node.as_element() {
+                if elem.name == "tag" {
+                    osm_tags.insert(elem.attributes["k"].clone(), elem.attributes["v"].clone());
+                    continue;
+                }
+            }
+            other_children.push(node);
+        }
+
+        if parking_tags(&osm_tags) != mapped.original_tags {
+            skipped.push((
+                *way,
+                "its parking tags changed in OSM after you mapped it".to_string(),
+            ));
+            continue;
+        }
+
+        // Fill out the tags.
+        mapped.value.apply(
+            &mut osm_tags,
+            schema,
+            if in_seattle { Some("3 days") } else { None },
+        );
+
+        tree.children = other_children;
+        for (k, v) in osm_tags.inner() {
+            let mut new_elem = xmltree::Element::new("tag");
+            new_elem.attributes.insert("k".to_string(), k.to_string());
+            new_elem.attributes.insert("v".to_string(), v.to_string());
+            tree.children.push(xmltree::XMLNode::Element(new_elem));
+        }
+
+        // Keep the version, so uploading fails if the way changes again before then
+        tree.attributes.remove("timestamp");
+        tree.attributes.remove("changeset");
+        tree.attributes.remove("user");
+        tree.attributes.remove("uid");
+        tree.attributes.remove("visible");
+
+        let mut bytes: Vec<u8> = Vec::new();
+        tree.write(&mut bytes)?;
+        let out = String::from_utf8(bytes)?;
+        let stripped = out.trim_start_matches("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        modified_ways.push(stripped.to_string());
+    }
+
+    let path = format!(
+        "{}.osc",
+        abstio::path_parking_mapper_session(&session.map).trim_end_matches(".json")
+    );
+    let num_ways = modified_ways.len();
+    if num_ways > 0 {
+        let mut f = File::create(&path)?;
+        writeln!(f, "<osmChange version=\"0.6\" generator=\"abst\"><modify>")?;
+        for w in modified_ways {
+            writeln!(f, "  {}", w)?;
+        }
+        writeln!(f, "</modify></osmChange>")?;
+        info!("Wrote {}", path);
+    }
+    Ok(OsmChange {
+        path,
+        num_ways,
+        skipped,
+    })
+}
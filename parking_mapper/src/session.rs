@@ -0,0 +1,134 @@
+//! Everything mapped so far is saved after every change, so closing the app doesn't lose work.
+//! There's one session per map, resumed automatically.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstio::MapName;
+use abstutil::{deserialize_btreemap, serialize_btreemap, Tags, Timer};
+use map_model::osm::WayID;
+use map_model::{Map, Road};
+
+use crate::tags::{existing_value, parking_tags, validate, Schema, Value};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub map: MapName,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub ways: BTreeMap<WayID, MappedWay>,
+    /// How to write tags when exporting
+    #[serde(default)]
+    pub schema: Schema,
+    /// When exporting, limit parking to 3 days, the default in Seattle
+    #[serde(default)]
+    pub seattle_max_stay: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MappedWay {
+    pub value: Value,
+    /// The parking tags the way had in OSM when it was mapped. If OSM changes after this, the
+    /// answer might be stale.
+    pub original_tags: Tags,
+}
+
+impl Session {
+    /// Resumes the previous session for this map, or starts a new one
+    pub fn load(map: &Map) -> Session {
+        let path = abstio::path_parking_mapper_session(map.get_name());
+        if abstio::file_exists(&path) {
+            match abstio::maybe_read_json::<Session>(path.clone(), &mut Timer::throwaway()) {
+                Ok(session) => {
+                    return session;
+                }
+                Err(err) => {
+                    error!("Couldn't resume {}, starting over: {}", path, err);
+                }
+            }
+        }
+        Session {
+            map: map.get_name().clone(),
+            ways: BTreeMap::new(),
+            schema: Schema::default(),
+            seattle_max_stay: false,
+        }
+    }
+
+    pub fn save(&self) {
+        abstio::write_json(abstio::path_parking_mapper_session(&self.map), self);
+    }
+
+    /// Records an answer and saves immediately
+    pub fn set(&mut self, map: &Map, way: WayID, value: Value) {
+        let original_tags = first_road(map, way)
+            .map(|r| parking_tags(&r.osm_tags))
+            .unwrap_or_else(Tags::empty);
+        self.ways.insert(
+            way,
+            MappedWay {
+                value,
+                original_tags,
+            },
+        );
+        self.save();
+    }
+
+    /// Forgets every answer
+    pub fn clear(&mut self) {
+        self.ways.clear();
+        abstio::delete_file(abstio::path_parking_mapper_session(&self.map));
+    }
+
+    /// Describes answers that conflict with the map. If the map was rebuilt from newer OSM data
+    /// since an answer, the way might be gone or have different parking tags now. Answers that
+    /// disagree with existing tags and problems with the existing tags are also found.
+    pub fn problems(&self, map: &Map) -> Vec<(WayID, String)> {
+        let mut roads = BTreeMap::new();
+        for r in map.all_roads() {
+            roads.entry(r.orig_id.osm_way_id).or_insert(r);
+        }
+
+        let mut problems = Vec::new();
+        for (way, mapped) in &self.ways {
+            let road = match roads.get(way) {
+                Some(r) => r,
+                None => {
+                    problems.push((*way, "it isn't in the map anymore".to_string()));
+                    continue;
+                }
+            };
+            if parking_tags(&road.osm_tags) != mapped.original_tags {
+                problems.push((
+                    *way,
+                    "its parking tags changed in OSM after you mapped it".to_string(),
+                ));
+                continue;
+            }
+            if let Some(existing) = existing_value(&road.osm_tags) {
+                if existing != mapped.value && mapped.value != Value::Complicated {
+                    problems.push((
+                        *way,
+                        format!(
+                            "you said {}, but existing tags say {}",
+                            mapped.value.describe(),
+                            existing.describe()
+                        ),
+                    ));
+                }
+            }
+            for problem in validate(&road.osm_tags) {
+                problems.push((*way, problem));
+            }
+        }
+        problems
+    }
+}
+
+/// The tags on every piece of a way should match, so just look at one of them
+fn first_road(map: &Map, way: WayID) -> Option<&Road> {
+    map.all_roads().iter().find(|r| r.orig_id.osm_way_id == way)
+}
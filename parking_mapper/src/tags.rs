@@ -0,0 +1,364 @@
+//! Reads and writes parking tags on OSM ways. Both the older `parking:lane:left/right/both` schema
+//! and the newer `parking:left/right/both` schema are understood.
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::Tags;
+use map_model::osm;
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Value {
+    BothSides,
+    NoStopping,
+    RightOnly,
+    LeftOnly,
+    Complicated,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Schema {
+    /// parking:lane:right=parallel
+    Lane,
+    /// parking:right=lane, parking:right:orientation=parallel
+    Side,
+}
+
+impl Default for Schema {
+    /// The importer only understands the older schema
+    fn default() -> Schema {
+        Schema::Lane
+    }
+}
+
+impl Schema {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Schema::Lane => "parking:lane:left/right/both",
+            Schema::Side => "parking:left/right/both",
+        }
+    }
+}
+
+const SIDES: [&str; 3] = ["left", "right", "both"];
+
+impl Value {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Value::BothSides => "both sides",
+            Value::NoStopping => "no stopping or parking",
+            Value::RightOnly => "just the right side",
+            Value::LeftOnly => "just the left side",
+            Value::Complicated => "it's complicated",
+        }
+    }
+
+    /// Interprets existing tags using one schema. None if the schema doesn't say anything about
+    /// one of the sides.
+    pub fn from_tags(tags: &Tags, schema: Schema) -> Option<Value> {
+        let left = has_parking(tags, schema, "left")?;
+        let right = has_parking(tags, schema, "right")?;
+        Some(match (left, right) {
+            (true, true) => Value::BothSides,
+            (false, false) => Value::NoStopping,
+            (false, true) => Value::RightOnly,
+            (true, false) => Value::LeftOnly,
+        })
+    }
+
+    /// Replaces the parking tags from both schemas with this value, written in one schema. If
+    /// `max_stay` is set, parking is limited to that long.
+    pub fn apply(self, tags: &mut Tags, schema: Schema, max_stay: Option<&str>) {
+        for side in SIDES {
+            tags.remove(&format!("parking:lane:{}", side));
+            tags.remove(&format!("parking:{}", side));
+            tags.remove(&format!("parking:{}:orientation", side));
+            tags.remove(&format!("parking:{}:restriction", side));
+            tags.remove(&format!("parking:condition:{}:maxstay", side));
+            tags.remove(&format!("parking:{}:maxstay", side));
+        }
+
+        let (parking, no_stopping) = match self {
+            Value::BothSides => (Some("both"), None),
+            Value::NoStopping => (None, Some("both")),
+            Value::RightOnly => (Some("right"), Some("left")),
+            Value::LeftOnly => (Some("left"), Some("right")),
+            Value::Complicated => unreachable!(),
+        };
+        match schema {
+            Schema::Lane => {
+                if let Some(side) = parking {
+                    tags.insert(format!("parking:lane:{}", side), "parallel");
+                    if let Some(max_stay) = max_stay {
+                        tags.insert(format!("parking:condition:{}:maxstay", side), max_stay);
+                    }
+                }
+                if let Some(side) = no_stopping {
+                    tags.insert(format!("parking:lane:{}", side), "no_stopping");
+                }
+            }
+            Schema::Side => {
+                if let Some(side) = parking {
+                    tags.insert(format!("parking:{}", side), "lane");
+                    tags.insert(format!("parking:{}:orientation", side), "parallel");
+                    if let Some(max_stay) = max_stay {
+                        tags.insert(format!("parking:{}:maxstay", side), max_stay);
+                    }
+                }
+                if let Some(side) = no_stopping {
+                    tags.insert(format!("parking:{}", side), "no");
+                    tags.insert(format!("parking:{}:restriction", side), "no_stopping");
+                }
+            }
+        }
+    }
+}
+
+/// Does the side have some kind of parking? None if it's not tagged or the value isn't understood.
+fn has_parking(tags: &Tags, schema: Schema, side: &str) -> Option<bool> {
+    match schema {
+        Schema::Lane => {
+            let value = tags
+                .get(&format!("parking:lane:{}", side))
+                .or_else(|| tags.get(osm::PARKING_BOTH))?;
+            match value.as_ref() {
+                "parallel" | "diagonal" | "perpendicular" | "marked" => Some(true),
+                "no_parking" | "no_stopping" | "fire_lane" | "no" => Some(false),
+                _ => None,
+            }
+        }
+        Schema::Side => {
+            let value = tags
+                .get(&format!("parking:{}", side))
+                .or_else(|| tags.get("parking:both"))?;
+            match value.as_ref() {
+                "lane" | "street_side" | "on_kerb" | "half_on_kerb" | "shoulder" | "yes" => {
+                    Some(true)
+                }
+                "no" => Some(false),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// All of the parking tags that really come from OSM. Tags that the importer inferred are skipped.
+pub fn parking_tags(tags: &Tags) -> Tags {
+    let inferred = tags.contains_key(osm::INFERRED_PARKING);
+    let mut result = Tags::empty();
+    for (k, v) in tags.inner() {
+        if !k.starts_with("parking:") || (inferred && k.starts_with("parking:lane:")) {
+            continue;
+        }
+        result.insert(k, v);
+    }
+    result
+}
+
+/// Problems with the existing parking tags, like the two schemas disagreeing
+pub fn validate(tags: &Tags) -> Vec<String> {
+    let tags = parking_tags(tags);
+    let mut problems = Vec::new();
+    for schema in [Schema::Lane, Schema::Side] {
+        let prefix = match schema {
+            Schema::Lane => "parking:lane:",
+            Schema::Side => "parking:",
+        };
+        let both = format!("{}both", prefix);
+        if tags.contains_key(&both)
+            && (tags.contains_key(&format!("{}left", prefix))
+                || tags.contains_key(&format!("{}right", prefix)))
+        {
+            problems.push(format!("{} is mixed with tags for one side", both));
+        }
+    }
+    if let (Some(old), Some(new)) = (
+        Value::from_tags(&tags, Schema::Lane),
+        Value::from_tags(&tags, Schema::Side),
+    ) {
+        if old != new {
+            problems.push(format!(
+                "parking:lane:* says {}, but parking:* says {}",
+                old.describe(),
+                new.describe()
+            ));
+        }
+    }
+    problems
+}
+
+/// What the existing tags say about parking, if anything
+pub fn existing_value(tags: &Tags) -> Option<Value> {
+    let tags = parking_tags(tags);
+    Value::from_tags(&tags, Schema::Side).or_else(|| Value::from_tags(&tags, Schema::Lane))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<(&str, &str)>) -> Tags {
+        let mut tags = Tags::empty();
+        for (k, v) in kv {
+            tags.insert(k, v);
+        }
+        tags
+    }
+
+    #[test]
+    fn test_from_tags() {
+        for (kv, schema, expected) in [
+            (
+                vec![("parking:lane:both", "parallel")],
+                Schema::Lane,
+                Some(Value::BothSides),
+            ),
+            (
+                vec![
+                    ("parking:lane:left", "no_parking"),
+                    ("parking:lane:right", "diagonal"),
+                ],
+                Schema::Lane,
+                Some(Value::RightOnly),
+            ),
+            // One side isn't tagged
+            (vec![("parking:lane:left", "parallel")], Schema::Lane, None),
+            // The value isn't understood
+            (vec![("parking:lane:both", "separate")], Schema::Lane, None),
+            (
+                vec![("parking:both", "no")],
+                Schema::Side,
+                Some(Value::NoStopping),
+            ),
+            (
+                vec![("parking:left", "half_on_kerb"), ("parking:right", "no")],
+                Schema::Side,
+                Some(Value::LeftOnly),
+            ),
+            // Each schema ignores the other
+            (vec![("parking:both", "lane")], Schema::Lane, None),
+            (vec![("parking:lane:both", "parallel")], Schema::Side, None),
+        ] {
+            assert_eq!(
+                Value::from_tags(&tags(kv.clone()), schema),
+                expected,
+                "{:?}",
+                kv
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        // Start with tags from both schemas, including time limits
+        let original = tags(vec![
+            ("highway", "residential"),
+            ("parking:lane:both", "parallel"),
+            ("parking:condition:both:maxstay", "2 hours"),
+            ("parking:left", "lane"),
+            ("parking:left:orientation", "parallel"),
+            ("parking:left:maxstay", "1 hour"),
+            ("parking:right", "no"),
+            ("parking:right:restriction", "no_stopping"),
+        ]);
+
+        let mut lane = original.clone();
+        Value::RightOnly.apply(&mut lane, Schema::Lane, Some("3 days"));
+        assert_eq!(
+            lane,
+            tags(vec![
+                ("highway", "residential"),
+                ("parking:lane:right", "parallel"),
+                ("parking:condition:right:maxstay", "3 days"),
+                ("parking:lane:left", "no_stopping"),
+            ])
+        );
+
+        let mut side = original;
+        Value::LeftOnly.apply(&mut side, Schema::Side, None);
+        assert_eq!(
+            side,
+            tags(vec![
+                ("highway", "residential"),
+                ("parking:left", "lane"),
+                ("parking:left:orientation", "parallel"),
+                ("parking:right", "no"),
+                ("parking:right:restriction", "no_stopping"),
+            ])
+        );
+
+        // Reading the tags back gives the same value
+        for value in [
+            Value::BothSides,
+            Value::NoStopping,
+            Value::RightOnly,
+            Value::LeftOnly,
+        ] {
+            for schema in [Schema::Lane, Schema::Side] {
+                let mut tags = Tags::empty();
+                value.apply(&mut tags, schema, Some("3 days"));
+                assert_eq!(Value::from_tags(&tags, schema), Some(value));
+                assert!(validate(&tags).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&tags(vec![
+            ("parking:lane:both", "parallel"),
+            ("parking:both", "lane"),
+        ]))
+        .is_empty());
+
+        assert_eq!(
+            validate(&tags(vec![
+                ("parking:lane:both", "parallel"),
+                ("parking:lane:left", "no_parking"),
+            ])),
+            vec!["parking:lane:both is mixed with tags for one side".to_string()]
+        );
+
+        assert_eq!(
+            validate(&tags(vec![
+                ("parking:lane:both", "parallel"),
+                ("parking:both", "no"),
+            ])),
+            vec![
+                "parking:lane:* says both sides, but parking:* says no stopping or parking"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inferred_tags() {
+        // The importer filled in parking:lane:both, but parking:left and parking:right come from OSM
+        let inferred = tags(vec![
+            ("highway", "residential"),
+            (osm::INFERRED_PARKING, "true"),
+            ("parking:lane:both", "no_parking"),
+            ("parking:left", "lane"),
+            ("parking:right", "lane"),
+        ]);
+        assert_eq!(
+            parking_tags(&inferred),
+            tags(vec![("parking:left", "lane"), ("parking:right", "lane")])
+        );
+        // The inferred tags don't disagree with the real ones
+        assert!(validate(&inferred).is_empty());
+        assert_eq!(existing_value(&inferred), Some(Value::BothSides));
+
+        // Without real tags, nothing is known
+        let only_inferred = tags(vec![
+            (osm::INFERRED_PARKING, "true"),
+            ("parking:lane:both", "no_parking"),
+        ]);
+        assert_eq!(parking_tags(&only_inferred), Tags::empty());
+        assert_eq!(existing_value(&only_inferred), None);
+
+        // When the tags are real, they're kept
+        let real = tags(vec![("parking:lane:both", "no_parking")]);
+        assert_eq!(parking_tags(&real), real);
+        assert_eq!(existing_value(&real), Some(Value::NoStopping));
+    }
+}
@@ -5,15 +5,18 @@ use anyhow::Result;
 use fs_err::File;
 use serde::{Deserialize, Serialize};
 
-use abstutil::Counter;
+use abstutil::{prettyprint_usize, Counter};
 use geom::{Duration, Time};
 use map_model::{
-    CompressedMovementID, IntersectionID, LaneID, Map, MovementID, ParkingLotID, Path, PathRequest,
-    RoadID, TransitRouteID, TransitStopID, Traversable, TurnID,
+    BikeshareDockID, CompressedMovementID, IdMigration, IntersectionID, LaneID, Map, MovementID,
+    ParkingLotID, Path, PathRequest, RoadID, TransitRouteID, TransitStopID, Traversable, TurnID,
 };
-use synthpop::TripMode;
+use synthpop::{MovementCounts, TrafficCounts, TripMode};
 
-use crate::{AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripPhaseType};
+use crate::{
+    AgentID, AgentType, AlertLocation, CarID, Emissions, Event, ParkingSpot, RoadSpeeds,
+    SurrogateSafety, TripID, TripPhaseType,
+};
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
 /// organizing and storing some information from them. The UI queries Analytics to draw time-series
@@ -31,6 +34,9 @@ pub struct Analytics {
     // requires occasionally expensive or complicated summing or merging over all directions of an
     // intersection. So for now, eat the file size cost.
     pub traffic_signal_thruput: TimeSeriesCount<CompressedMovementID>,
+    /// Vehicles making each movement through every intersection, in the bins used by real-world
+    /// turning movement counts
+    pub movement_counts: MovementCounts,
 
     /// Most fields in Analytics are cumulative over time, but this is just for the current moment
     /// in time.
@@ -59,6 +65,14 @@ pub struct Analytics {
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    /// Per bikeshare dock, how many bikes are present after every change
+    pub bikeshare_dock_occupancy: BTreeMap<BikeshareDockID, Vec<(Time, usize)>>,
+    /// Estimated tailpipe emissions and energy use
+    pub emissions: Emissions,
+    /// Average vehicle speeds per road, for estimating noise
+    pub road_speeds: RoadSpeeds,
+    /// Near-misses between agents on conflicting turns
+    pub surrogate_safety: SurrogateSafety,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -84,6 +98,7 @@ impl Analytics {
             road_thruput: TimeSeriesCount::new(),
             intersection_thruput: TimeSeriesCount::new(),
             traffic_signal_thruput: TimeSeriesCount::new(),
+            movement_counts: MovementCounts::default(),
             demand: BTreeMap::new(),
             bus_arrivals: Vec::new(),
             passengers_boarding: BTreeMap::new(),
@@ -95,6 +110,10 @@ impl Analytics {
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            bikeshare_dock_occupancy: BTreeMap::new(),
+            emissions: Emissions::new(),
+            road_speeds: RoadSpeeds::new(),
+            surrogate_safety: SurrogateSafety::new(),
             alerts: Vec::new(),
             record_anything,
         }
@@ -105,6 +124,10 @@ impl Analytics {
             return;
         }
 
+        self.emissions.event(&ev, time, map);
+        self.road_speeds.event(&ev, time, map);
+        self.surrogate_safety.event(&ev, time, map);
+
         // Throughput
         if let Event::AgentEntersTraversable(a, _, to, passengers) = ev {
             match to {
@@ -118,6 +141,10 @@ impl Analytics {
                 Traversable::Turn(t) => {
                     self.intersection_thruput
                         .record(time, t.parent, a.to_type(), 1);
+                    if !matches!(a, AgentID::Pedestrian(_)) {
+                        let (movement, _) = map.get_i(t.parent).turn_to_movement(t);
+                        self.movement_counts.record(movement, time, 1);
+                    }
                     if let Some(n) = passengers {
                         self.intersection_thruput.record(
                             time,
@@ -246,6 +273,13 @@ impl Analytics {
             }
         }
 
+        if let Event::BikeshareDockOccupancy(id, cnt) = ev {
+            self.bikeshare_dock_occupancy
+                .entry(id)
+                .or_insert_with(Vec::new)
+                .push((time, cnt));
+        }
+
         // Safety metrics
         if let Event::AgentEntersTraversable(a, Some(trip), Traversable::Turn(t), _) = ev {
             if a.to_type() == AgentType::Bike && map.get_i(t.parent).roads.len() > 4 {
@@ -298,6 +332,210 @@ impl Analytics {
         }
     }
 
+    /// Translate prebaked results from an old version of a map to a new version. Anything
+    /// referring to objects that no longer exist is dropped. Returns a description of what was
+    /// dropped.
+    ///
+    /// Note the simulation results themselves will be stale -- trips may take different paths in
+    /// the new map. This just keeps the old results usable as a baseline until the scenario is run
+    /// again.
+    pub fn migrate(
+        self,
+        migration: &IdMigration,
+        old: &Map,
+        new: &Map,
+    ) -> (Analytics, Vec<String>) {
+        let mut dropped = Counter::new();
+        let mut result = Analytics::new(self.record_anything);
+
+        result.road_thruput =
+            self.road_thruput
+                .migrate(|r| migration.r(*r), "road throughput", &mut dropped);
+        result.intersection_thruput = self.intersection_thruput.migrate(
+            |i| migration.i(*i),
+            "intersection throughput",
+            &mut dropped,
+        );
+        result.traffic_signal_thruput = self.traffic_signal_thruput.migrate(
+            |m| migration.compressed_movement(*m, old, new),
+            "traffic signal throughput",
+            &mut dropped,
+        );
+        result.movement_counts = MovementCounts::new(self.movement_counts.bin_size);
+        for ((m, bin), cnt) in self.movement_counts.counts {
+            if let Some(m) = migration.movement(m) {
+                result.movement_counts.counts.insert((m, bin), cnt);
+            } else {
+                dropped.inc("movement counts");
+            }
+        }
+        // Demand is only for the current moment in time; don't bother translating it
+
+        for (time, car, route, stop) in self.bus_arrivals {
+            if let (Some(route), Some(stop)) = (migration.tr(route), migration.ts(stop)) {
+                result.bus_arrivals.push((time, car, route, stop));
+            } else {
+                dropped.inc("bus arrivals");
+            }
+        }
+        for (stop, list) in self.passengers_boarding {
+            if let Some(stop) = migration.ts(stop) {
+                for (time, route, wait) in list {
+                    if let Some(route) = migration.tr(route) {
+                        result
+                            .passengers_boarding
+                            .entry(stop)
+                            .or_insert_with(Vec::new)
+                            .push((time, route, wait));
+                    } else {
+                        dropped.inc("passengers boarding");
+                    }
+                }
+            } else {
+                dropped.add("passengers boarding", list.len());
+            }
+        }
+        for (stop, list) in self.passengers_alighting {
+            if let Some(stop) = migration.ts(stop) {
+                for (time, route) in list {
+                    if let Some(route) = migration.tr(route) {
+                        result
+                            .passengers_alighting
+                            .entry(stop)
+                            .or_insert_with(Vec::new)
+                            .push((time, route));
+                    } else {
+                        dropped.inc("passengers alighting");
+                    }
+                }
+            } else {
+                dropped.add("passengers alighting", list.len());
+            }
+        }
+
+        // Trips are identified by TripID, which only depends on the scenario, not the map
+        result.started_trips = self.started_trips;
+        result.finished_trips = self.finished_trips;
+
+        for (trip, problems) in self.problems_per_trip {
+            for (time, problem) in problems {
+                let translated = match problem {
+                    Problem::IntersectionDelay(i, delay) => {
+                        migration.i(i).map(|i| Problem::IntersectionDelay(i, delay))
+                    }
+                    Problem::ComplexIntersectionCrossing(i) => {
+                        migration.i(i).map(Problem::ComplexIntersectionCrossing)
+                    }
+                    Problem::ArterialIntersectionCrossing(t) => migration
+                        .t(t, new)
+                        .map(Problem::ArterialIntersectionCrossing),
+                    Problem::OvertakeDesired(on) => {
+                        migration.traversable(on, new).map(Problem::OvertakeDesired)
+                    }
+                };
+                if let Some(problem) = translated {
+                    result
+                        .problems_per_trip
+                        .entry(trip)
+                        .or_insert_with(Vec::new)
+                        .push((time, problem));
+                } else {
+                    dropped.inc("problems encountered by trips");
+                }
+            }
+        }
+
+        for (time, trip, maybe_req, phase_type) in self.trip_log {
+            // Keep the phase even if the path can't be translated, so trip timelines still work
+            let req = maybe_req.and_then(|req| {
+                let translated = migration.path_request(&req, new);
+                if translated.is_none() {
+                    dropped.inc("trip phase paths");
+                }
+                translated
+            });
+            let phase_type = match phase_type {
+                TripPhaseType::WaitingForBus(route, stop) => {
+                    match (migration.tr(route), migration.ts(stop)) {
+                        (Some(route), Some(stop)) => TripPhaseType::WaitingForBus(route, stop),
+                        _ => {
+                            dropped.inc("trip phases");
+                            continue;
+                        }
+                    }
+                }
+                TripPhaseType::RidingBus(route, stop, car) => {
+                    match (migration.tr(route), migration.ts(stop)) {
+                        (Some(route), Some(stop)) => TripPhaseType::RidingBus(route, stop, car),
+                        _ => {
+                            dropped.inc("trip phases");
+                            continue;
+                        }
+                    }
+                }
+                x => x,
+            };
+            result.trip_log.push((time, trip, req, phase_type));
+        }
+
+        for (i, delays) in self.intersection_delays {
+            // The u8 indexes into the movements, just like CompressedMovementID
+            for (idx, time, delay, agent_type) in delays {
+                if let Some(m) =
+                    migration.compressed_movement(CompressedMovementID { i, idx }, old, new)
+                {
+                    result
+                        .intersection_delays
+                        .entry(m.i)
+                        .or_insert_with(Vec::new)
+                        .push((m.idx, time, delay, agent_type));
+                } else {
+                    dropped.inc("intersection delays");
+                }
+            }
+        }
+
+        for (l, changes) in self.parking_lane_changes {
+            if let Some(l) = migration.l(l) {
+                result
+                    .parking_lane_changes
+                    .entry(l)
+                    .or_insert_with(Vec::new)
+                    .extend(changes);
+            } else {
+                dropped.add("parking lane changes", changes.len());
+            }
+        }
+        // Parking lots aren't matched between maps yet
+        dropped.add(
+            "parking lot changes",
+            self.parking_lot_changes.values().map(|x| x.len()).sum(),
+        );
+        // Neither are bikeshare docks
+        dropped.add(
+            "bikeshare dock changes",
+            self.bikeshare_dock_occupancy
+                .values()
+                .map(|x| x.len())
+                .sum(),
+        );
+
+        result.emissions = self.emissions.migrate(migration, &mut dropped);
+        result.road_speeds = self.road_speeds.migrate(migration, &mut dropped);
+        result.surrogate_safety = self.surrogate_safety.migrate(migration, new, &mut dropped);
+
+        // Alerts are just for debugging
+        dropped.add("alerts", self.alerts.len());
+
+        let problems = dropped
+            .consume()
+            .into_iter()
+            .filter(|(_, cnt)| *cnt > 0)
+            .map(|(what, cnt)| format!("Dropped {} {}", prettyprint_usize(cnt), what))
+            .collect();
+        (result, problems)
+    }
+
     pub fn record_demand(&mut self, path: &Path, map: &Map) {
         for step in path.get_steps() {
             if let Traversable::Turn(t) = step.as_traversable() {
@@ -311,6 +549,23 @@ impl Analytics {
     // TODO If these ever need to be speeded up, just cache the histogram and index in the events
     // list.
 
+    /// Summarizes throughput for some agent types as `TrafficCounts`, to compare against other
+    /// simulations or real-world counts. Movement counts always include all vehicles.
+    pub fn traffic_counts(
+        &self,
+        map: &Map,
+        description: String,
+        agent_types: &BTreeSet<AgentType>,
+    ) -> TrafficCounts {
+        TrafficCounts {
+            map: map.get_name().clone(),
+            description,
+            per_road: self.road_thruput.all_total_counts(agent_types),
+            per_intersection: self.intersection_thruput.all_total_counts(agent_types),
+            per_movement: self.movement_counts.clone(),
+        }
+    }
+
     /// Ignores the current time. Returns None for cancelled trips.
     pub fn finished_trip_time(&self, trip: TripID) -> Option<Duration> {
         // TODO This is so inefficient!
@@ -378,6 +633,15 @@ impl Analytics {
     }
 
     pub fn get_all_trip_phases(&self) -> BTreeMap<TripID, Vec<TripPhase>> {
+        self.all_trip_phases(None)
+    }
+
+    /// Like `get_all_trip_phases`, but also calculates the path of every phase. This is slow.
+    pub fn get_all_trip_phases_with_paths(&self, map: &Map) -> BTreeMap<TripID, Vec<TripPhase>> {
+        self.all_trip_phases(Some(map))
+    }
+
+    fn all_trip_phases(&self, map: Option<&Map>) -> BTreeMap<TripID, Vec<TripPhase>> {
         let mut trips = BTreeMap::new();
         for (t, id, maybe_req, phase_type) in &self.trip_log {
             let phases: &mut Vec<TripPhase> = trips.entry(*id).or_insert_with(Vec::new);
@@ -395,8 +659,7 @@ impl Analytics {
             phases.push(TripPhase {
                 start_time: *t,
                 end_time: None,
-                // Don't compute any paths
-                path: None,
+                path: map.and_then(|map| maybe_req.clone().and_then(|req| map.pathfind(req).ok())),
                 has_path_req: maybe_req.is_some(),
                 phase_type: *phase_type,
             })
@@ -472,6 +735,34 @@ impl Analytics {
         }
     }
 
+    /// Returns the number of bikes present over time
+    pub fn bikeshare_dock_occupancy(
+        &self,
+        now: Time,
+        id: BikeshareDockID,
+        capacity: usize,
+    ) -> Vec<(Time, usize)> {
+        let initial = crate::initial_bikes(capacity);
+        let mut pts = vec![(Time::START_OF_DAY, initial)];
+        let mut last = initial;
+        for (t, cnt) in self
+            .bikeshare_dock_occupancy
+            .get(&id)
+            .map(|x| x.as_slice())
+            .unwrap_or(&[])
+        {
+            if *t > now {
+                break;
+            }
+            // Step functions. Don't interpolate.
+            pts.push((*t, last));
+            pts.push((*t, *cnt));
+            last = *cnt;
+        }
+        pts.push((now, last));
+        pts
+    }
+
     fn parking_spot_availability(
         now: Time,
         changes: &[(Time, bool)],
@@ -557,6 +848,30 @@ impl<X: Ord + Clone> TimeSeriesCount<X> {
             .or_insert(0) += count;
     }
 
+    fn migrate<Y: Ord + Clone, F: Fn(&X) -> Option<Y>>(
+        self,
+        translate: F,
+        name: &'static str,
+        dropped: &mut Counter<&'static str>,
+    ) -> TimeSeriesCount<Y> {
+        let mut result = TimeSeriesCount::new();
+        for ((id, agent_type, hour), count) in self.counts {
+            if let Some(id) = translate(&id) {
+                *result.counts.entry((id, agent_type, hour)).or_insert(0) += count;
+            } else {
+                dropped.add(name, count);
+            }
+        }
+        for (time, agent_type, id) in self.raw {
+            if let Some(id) = translate(&id) {
+                result.raw.push((time, agent_type, id));
+            } else {
+                dropped.inc(name);
+            }
+        }
+        result
+    }
+
     pub fn total_for(&self, id: X) -> usize {
         self.total_for_with_agent_types(id, AgentType::all().into_iter().collect())
     }
@@ -12,6 +12,11 @@ struct Args {
     /// How many hours to simulate.
     #[structopt(long)]
     hours: usize,
+    /// Afterwards, write trips.csv, legs.csv, and legs.geojson describing every trip to this
+    /// directory. Leg routes are found by pathfinding again, so they may not match the route an
+    /// agent actually took after rerouting.
+    #[structopt(long)]
+    export_trips: Option<String>,
     #[structopt(flatten)]
     flags: sim::SimFlags,
 }
@@ -54,6 +59,7 @@ fn main() {
                 &mut None,
             );
             if sim.time() == goal_time {
+                export_trips(&args, &sim, &map);
                 return;
             }
         }
@@ -62,6 +68,7 @@ fn main() {
         for x in sim.describe_internal_stats() {
             println!("{}", x);
         }
+        export_trips(&args, &sim, &map);
     } else {
         sim.timed_step(
             &mut map,
@@ -69,5 +76,15 @@ fn main() {
             &mut None,
             &mut abstutil::Timer::new("run simulation"),
         );
+        export_trips(&args, &sim, &map);
+    }
+}
+
+fn export_trips(args: &Args, sim: &sim::Sim, map: &map_model::Map) {
+    if let Some(ref dir) = args.export_trips {
+        sim::TripExport::new(sim, map, true)
+            .write(map, dir)
+            .unwrap();
+        println!("Wrote trips to {}", dir);
     }
 }
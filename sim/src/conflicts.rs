@@ -0,0 +1,531 @@
+//! Surrogate safety measures. Real collisions are rare, so instead we look for near-misses between
+//! agents crossing an intersection at the same time on conflicting turns. This follows the ideas
+//! behind SSAM, but since the simulation doesn't model acceleration within a turn, each agent is
+//! assumed to cross its turn at a constant speed.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
+use geom::{Distance, Duration, Pt2D, Speed, Time};
+use map_model::{DrivingSide, IdMigration, IntersectionID, Map, Traversable, TurnID, TurnType};
+
+use crate::{
+    AgentID, AgentType, Event, BIKE_LENGTH, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
+};
+
+/// Agents reaching the same point further apart in time than this aren't considered a conflict.
+const MAX_PET: Duration = Duration::const_seconds(5.0);
+/// Roughly how much space a pedestrian takes up along their path
+const PEDESTRIAN_LENGTH: Distance = Distance::const_meters(0.5);
+/// Forget about agents that've been in an intersection longer than this. Usually they were deleted
+/// partway through a turn.
+const STALE_CROSSING: Duration = Duration::const_seconds(120.0);
+
+/// A near-miss between two agents on conflicting turns.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// When the second agent reached the conflict point
+    pub time: Time,
+    pub intersection: IntersectionID,
+    /// The turn of the agent reaching the conflict point first, then the second
+    pub turns: (TurnID, TurnID),
+    pub agents: (AgentType, AgentType),
+    pub kind: ConflictKind,
+    pub severity: ConflictSeverity,
+    /// Where the two turns cross or merge
+    pub pt: Pt2D,
+    /// Post-encroachment time: between the first agent passing the conflict point and the second
+    /// agent reaching it
+    pub pet: Duration,
+    /// Time-to-collision at the moment both agents were first heading towards the conflict point:
+    /// how long until the first one reached it, if they were on a collision course. None if the
+    /// second agent started its turn after the first passed through, or if the first agent would
+    /// clear the conflict point before the second reached it.
+    pub ttc: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ConflictKind {
+    /// A vehicle or cyclist and a pedestrian on a crossing
+    Pedestrian,
+    /// A vehicle turning across a cyclist going straight on the curb side
+    RightHook,
+    /// Two turns ending at the same lane
+    Merge,
+    /// Any other two turns crossing paths
+    Crossing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ConflictSeverity {
+    /// PET under 1s, or TTC under 1.5s
+    Critical,
+    /// PET under 2.5s
+    Serious,
+    /// PET under 5s
+    Minor,
+}
+
+impl ConflictKind {
+    pub fn all() -> Vec<ConflictKind> {
+        vec![
+            ConflictKind::Pedestrian,
+            ConflictKind::RightHook,
+            ConflictKind::Merge,
+            ConflictKind::Crossing,
+        ]
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            ConflictKind::Pedestrian => "pedestrian crossing",
+            ConflictKind::RightHook => "right hook",
+            ConflictKind::Merge => "merge",
+            ConflictKind::Crossing => "crossing paths",
+        }
+    }
+}
+
+impl ConflictSeverity {
+    fn new(pet: Duration, ttc: Option<Duration>) -> ConflictSeverity {
+        if pet < Duration::seconds(1.0)
+            || ttc.map(|ttc| ttc < Duration::seconds(1.5)).unwrap_or(false)
+        {
+            ConflictSeverity::Critical
+        } else if pet < Duration::seconds(2.5) {
+            ConflictSeverity::Serious
+        } else {
+            ConflictSeverity::Minor
+        }
+    }
+}
+
+/// One agent crossing an intersection
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Crossing {
+    agent: AgentID,
+    turn: TurnID,
+    entered: Time,
+    /// None while the agent is still on the turn
+    left: Option<Time>,
+    /// Pedestrians may walk a turn backwards
+    contraflow: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SurrogateSafety {
+    pub conflicts: Vec<Conflict>,
+
+    /// Recent crossings per intersection, to compare against the next ones
+    crossings: BTreeMap<IntersectionID, Vec<Crossing>>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    current_turn: BTreeMap<AgentID, TurnID>,
+}
+
+impl SurrogateSafety {
+    pub fn new() -> SurrogateSafety {
+        SurrogateSafety {
+            conflicts: Vec::new(),
+            crossings: BTreeMap::new(),
+            current_turn: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn event(&mut self, ev: &Event, time: Time, map: &Map) {
+        if let Event::AgentEntersTraversable(a, _, on, _) = ev {
+            // Finishing a turn always means entering a lane
+            if let Some(t) = self.current_turn.remove(a) {
+                self.finish_crossing(*a, t, *on == Traversable::Lane(t.src), time, map);
+            }
+            if let Traversable::Turn(t) = on {
+                self.current_turn.insert(*a, *t);
+                self.crossings
+                    .entry(t.parent)
+                    .or_insert_with(Vec::new)
+                    .push(Crossing {
+                        agent: *a,
+                        turn: *t,
+                        entered: time,
+                        left: None,
+                        contraflow: false,
+                    });
+            }
+        }
+    }
+
+    fn finish_crossing(
+        &mut self,
+        agent: AgentID,
+        turn: TurnID,
+        contraflow: bool,
+        now: Time,
+        map: &Map,
+    ) {
+        let crossings = self.crossings.get_mut(&turn.parent).unwrap();
+        crossings.retain(|c| c.left.unwrap_or(c.entered) + STALE_CROSSING > now);
+        let us = if let Some(c) = crossings.iter_mut().find(|c| c.agent == agent) {
+            c.left = Some(now);
+            c.contraflow = contraflow;
+            c.clone()
+        } else {
+            // It went stale
+            return;
+        };
+
+        // Each pair of crossings is compared once, when the later of the two finishes
+        for other in crossings.iter() {
+            if other.agent == agent || other.left.is_none() {
+                continue;
+            }
+            if let Some(conflict) = detect_conflict(&us, other, map) {
+                self.conflicts.push(conflict);
+            }
+        }
+
+        // Nobody still crossing can conflict with something that left long ago
+        let earliest_active = crossings
+            .iter()
+            .filter(|c| c.left.is_none())
+            .map(|c| c.entered)
+            .min()
+            .unwrap_or(now);
+        crossings.retain(|c| {
+            c.left
+                .map(|left| left + MAX_PET >= earliest_active)
+                .unwrap_or(true)
+        });
+    }
+
+    pub(crate) fn migrate(
+        self,
+        migration: &IdMigration,
+        new: &Map,
+        dropped: &mut Counter<&'static str>,
+    ) -> SurrogateSafety {
+        let mut result = SurrogateSafety::new();
+        for mut conflict in self.conflicts {
+            match (
+                migration.i(conflict.intersection),
+                migration.t(conflict.turns.0, new),
+                migration.t(conflict.turns.1, new),
+            ) {
+                (Some(i), Some(t1), Some(t2)) => {
+                    conflict.intersection = i;
+                    conflict.turns = (t1, t2);
+                    result.conflicts.push(conflict);
+                }
+                _ => {
+                    dropped.inc("conflicts");
+                }
+            }
+        }
+        // Agents in the middle of an intersection can't be tracked through a map change
+        dropped.add("agents crossing intersections", self.current_turn.len());
+        result
+    }
+}
+
+impl Default for SurrogateSafety {
+    fn default() -> SurrogateSafety {
+        SurrogateSafety::new()
+    }
+}
+
+fn detect_conflict(c1: &Crossing, c2: &Crossing, map: &Map) -> Option<Conflict> {
+    let turn1 = map.get_t(c1.turn);
+    let turn2 = map.get_t(c2.turn);
+    if !turn1.conflicts_with(turn2) {
+        return None;
+    }
+    let merge = turn1.geom.last_pt() == turn2.geom.last_pt();
+    let pt = if merge {
+        turn1.geom.last_pt()
+    } else {
+        turn1.geom.intersection(&turn2.geom)?.0
+    };
+
+    let (first, second, first_time, second_time) = order_arrivals(
+        (c1, fraction_along(c1, pt, map)?),
+        (c2, fraction_along(c2, pt, map)?),
+    )?;
+    let pet = second_time - first_time;
+    if pet > MAX_PET {
+        return None;
+    }
+    let ttc = time_to_collision(
+        (first, map.get_t(first.turn).geom.length(), first_time),
+        (second, map.get_t(second.turn).geom.length(), second_time),
+    );
+
+    let agents = (first.agent.to_type(), second.agent.to_type());
+    let kind = if agents.0 == AgentType::Pedestrian || agents.1 == AgentType::Pedestrian {
+        ConflictKind::Pedestrian
+    } else if is_right_hook(map, first, second) || is_right_hook(map, second, first) {
+        ConflictKind::RightHook
+    } else if merge {
+        ConflictKind::Merge
+    } else {
+        ConflictKind::Crossing
+    };
+
+    Some(Conflict {
+        time: second_time,
+        intersection: c1.turn.parent,
+        turns: (first.turn, second.turn),
+        agents,
+        kind,
+        severity: ConflictSeverity::new(pet, ttc),
+        pt,
+        pet,
+        ttc,
+    })
+}
+
+/// How far along its turn, from 0 to 1, an agent passes this point
+fn fraction_along(crossing: &Crossing, pt: Pt2D, map: &Map) -> Option<f64> {
+    let geom = &map.get_t(crossing.turn).geom;
+    let (dist, _) = geom.dist_along_of_point(pt)?;
+    Some(dist / geom.length())
+}
+
+/// Assuming the agent crossed its turn at a constant speed, when did it pass the point some
+/// fraction along the turn's geometry? None if it's still crossing.
+fn time_at_fraction(crossing: &Crossing, mut fraction: f64) -> Option<Time> {
+    if crossing.contraflow {
+        fraction = 1.0 - fraction;
+    }
+    Some(crossing.entered + (crossing.left? - crossing.entered) * fraction)
+}
+
+/// Given two crossings and how far along each one's turn the conflict point is, returns (the
+/// agent reaching the point first, the second, when the first got there, when the second got
+/// there).
+fn order_arrivals<'a>(
+    (c1, fraction1): (&'a Crossing, f64),
+    (c2, fraction2): (&'a Crossing, f64),
+) -> Option<(&'a Crossing, &'a Crossing, Time, Time)> {
+    let t1 = time_at_fraction(c1, fraction1)?;
+    let t2 = time_at_fraction(c2, fraction2)?;
+    if t1 <= t2 {
+        Some((c1, c2, t1, t2))
+    } else {
+        Some((c2, c1, t2, t1))
+    }
+}
+
+/// Each agent is given as (its crossing, the length of its turn, when it reached the conflict
+/// point), with the first to arrive first. Starting from when both agents are on their turns, each
+/// one's distance to the conflict point and speed project when it reaches the point. They're on a
+/// collision course if the second would get there before the back of the first clears it.
+fn time_to_collision(
+    (first, first_turn_length, first_arrival): (&Crossing, Distance, Time),
+    (second, second_turn_length, second_arrival): (&Crossing, Distance, Time),
+) -> Option<Duration> {
+    let start = first.entered.max(second.entered);
+    if start >= first_arrival {
+        return None;
+    }
+    let first_speed = crossing_speed(first, first_turn_length)?;
+    let second_speed = crossing_speed(second, second_turn_length)?;
+    let first_dist = first_speed * (first_arrival - start);
+    let second_dist = second_speed * (second_arrival - start);
+
+    let first_clears = (first_dist + body_length(first.agent.to_type())) / first_speed;
+    if second_dist / second_speed >= first_clears {
+        return None;
+    }
+    Some(first_dist / first_speed)
+}
+
+/// The constant speed an agent crossed its turn at. None if it's still crossing.
+fn crossing_speed(crossing: &Crossing, turn_length: Distance) -> Option<Speed> {
+    let dt = crossing.left? - crossing.entered;
+    if dt == Duration::ZERO {
+        return None;
+    }
+    Some(Speed::meters_per_second(
+        turn_length.inner_meters() / dt.inner_seconds(),
+    ))
+}
+
+fn body_length(agent: AgentType) -> Distance {
+    match agent {
+        AgentType::Car => MIN_CAR_LENGTH,
+        AgentType::Bike => BIKE_LENGTH,
+        AgentType::Bus | AgentType::TransitRider => BUS_LENGTH,
+        AgentType::Train => LIGHT_RAIL_LENGTH,
+        AgentType::Pedestrian => PEDESTRIAN_LENGTH,
+    }
+}
+
+fn is_right_hook(map: &Map, vehicle: &Crossing, cyclist: &Crossing) -> bool {
+    // On the left side of the road, it's a left hook
+    let curb_side_turn = if map.get_config().driving_side == DrivingSide::Right {
+        TurnType::Right
+    } else {
+        TurnType::Left
+    };
+    cyclist.agent.to_type() == AgentType::Bike
+        && vehicle.agent.to_type() != AgentType::Bike
+        && map.get_t(cyclist.turn).turn_type == TurnType::Straight
+        && map.get_t(vehicle.turn).turn_type == curb_side_turn
+}
+
+#[cfg(test)]
+mod tests {
+    use map_model::{IntersectionID, LaneID, RoadID};
+
+    use super::*;
+    use crate::{CarID, PedestrianID, VehicleType};
+
+    fn crossing(agent: AgentID, entered: f64, left: Option<f64>, contraflow: bool) -> Crossing {
+        let lane = LaneID {
+            road: RoadID(0),
+            offset: 0,
+        };
+        Crossing {
+            agent,
+            turn: TurnID {
+                parent: IntersectionID(0),
+                src: lane,
+                dst: lane,
+            },
+            entered: Time::START_OF_DAY + Duration::seconds(entered),
+            left: left.map(|t| Time::START_OF_DAY + Duration::seconds(t)),
+            contraflow,
+        }
+    }
+
+    fn car(id: usize) -> AgentID {
+        AgentID::Car(CarID {
+            id,
+            vehicle_type: VehicleType::Car,
+        })
+    }
+
+    #[test]
+    fn test_time_at_fraction() {
+        let c = crossing(car(0), 10.0, Some(20.0), false);
+        assert_eq!(
+            time_at_fraction(&c, 0.25),
+            Some(Time::START_OF_DAY + Duration::seconds(12.5))
+        );
+
+        // Walking the turn backwards
+        let c = crossing(AgentID::Pedestrian(PedestrianID(0)), 10.0, Some(20.0), true);
+        assert_eq!(
+            time_at_fraction(&c, 0.25),
+            Some(Time::START_OF_DAY + Duration::seconds(17.5))
+        );
+
+        // Still crossing
+        let c = crossing(car(0), 10.0, None, false);
+        assert_eq!(time_at_fraction(&c, 0.25), None);
+    }
+
+    #[test]
+    fn test_order_arrivals() {
+        // The first car enters later, but the conflict point is near the start of its turn
+        let c1 = crossing(car(1), 4.0, Some(8.0), false);
+        let c2 = crossing(car(2), 0.0, Some(10.0), false);
+        let (first, second, t1, t2) = order_arrivals((&c1, 0.25), (&c2, 0.8)).unwrap();
+        assert_eq!(first.agent, car(1));
+        assert_eq!(second.agent, car(2));
+        assert_eq!(t1, Time::START_OF_DAY + Duration::seconds(5.0));
+        assert_eq!(t2, Time::START_OF_DAY + Duration::seconds(8.0));
+        assert_eq!(
+            ConflictSeverity::new(t2 - t1, None),
+            ConflictSeverity::Minor
+        );
+
+        // Same order no matter which is passed first
+        let (first, _, _, _) = order_arrivals((&c2, 0.8), (&c1, 0.25)).unwrap();
+        assert_eq!(first.agent, car(1));
+    }
+
+    #[test]
+    fn test_severity_from_pet() {
+        // Both agents spent a long time approaching the conflict point together, but passed it far
+        // apart. This isn't critical.
+        let c1 = crossing(car(1), 0.0, Some(10.0), false);
+        let c2 = crossing(car(2), 0.0, Some(10.0), false);
+        let (_, _, t1, t2) = order_arrivals((&c1, 0.5), (&c2, 0.9)).unwrap();
+        assert_eq!(t2 - t1, Duration::seconds(4.0));
+        assert_eq!(
+            ConflictSeverity::new(t2 - t1, None),
+            ConflictSeverity::Minor
+        );
+
+        let (_, _, t1, t2) = order_arrivals((&c1, 0.5), (&c2, 0.6)).unwrap();
+        assert_eq!(
+            ConflictSeverity::new(t2 - t1, None),
+            ConflictSeverity::Serious
+        );
+
+        let (_, _, t1, t2) = order_arrivals((&c1, 0.5), (&c2, 0.55)).unwrap();
+        assert_eq!(
+            ConflictSeverity::new(t2 - t1, None),
+            ConflictSeverity::Critical
+        );
+    }
+
+    #[test]
+    fn test_time_to_collision() {
+        let at = |secs: f64| Time::START_OF_DAY + Duration::seconds(secs);
+        let length = Distance::meters(20.0);
+
+        // Both cross 20m turns in 4s, so 5m/s. The first car reaches the conflict point 2s after
+        // both are on their turns, and takes 0.9s to clear it.
+        let c1 = crossing(car(1), 0.0, Some(4.0), false);
+        let c2 = crossing(car(2), 0.0, Some(4.0), false);
+        assert_eq!(
+            time_to_collision((&c1, length, at(2.0)), (&c2, length, at(2.5))),
+            Some(Duration::seconds(2.0))
+        );
+        assert_eq!(
+            time_to_collision((&c1, length, at(2.0)), (&c2, length, at(3.0))),
+            None
+        );
+
+        // Measured from when the second agent starts its turn
+        let c2 = crossing(car(2), 1.0, Some(5.0), false);
+        assert_eq!(
+            time_to_collision((&c1, length, at(2.0)), (&c2, length, at(2.5))),
+            Some(Duration::seconds(1.0))
+        );
+
+        // The second agent started after the first was through
+        let c2 = crossing(car(2), 2.5, Some(6.5), false);
+        assert_eq!(
+            time_to_collision((&c1, length, at(2.0)), (&c2, length, at(2.6))),
+            None
+        );
+
+        // A slow bus takes 2.5s to clear the point, so a car arriving 1.5s later is still on a
+        // collision course. PET alone would call this serious, but the car had little warning.
+        let bus = crossing(
+            AgentID::Car(CarID {
+                id: 3,
+                vehicle_type: VehicleType::Bus,
+            }),
+            0.0,
+            Some(4.0),
+            false,
+        );
+        let ttc = time_to_collision((&bus, length, at(1.0)), (&c1, length, at(2.5)));
+        assert_eq!(ttc, Some(Duration::seconds(1.0)));
+        assert_eq!(
+            ConflictSeverity::new(Duration::seconds(1.5), None),
+            ConflictSeverity::Serious
+        );
+        assert_eq!(
+            ConflictSeverity::new(Duration::seconds(1.5), ttc),
+            ConflictSeverity::Critical
+        );
+    }
+}
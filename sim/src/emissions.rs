@@ -0,0 +1,310 @@
+//! Estimates tailpipe emissions and energy use from simulated trajectories. Every time a vehicle
+//! finishes crossing a lane or turn, its average speed over that traversable picks an emission
+//! factor from a speed-bin table, in the style of COPERT. The factors are corrected for road grade,
+//! like MOVES does with vehicle specific power.
+//!
+//! The factors are rough averages for a modern fleet. They're good enough to compare two
+//! scenarios, but not for absolute inventories -- plug in local values for that.
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
+use geom::{Duration, Time};
+use map_model::{Direction, IdMigration, Map, RoadID, Traversable};
+
+use crate::{AgentID, AgentType, Event, TripID};
+
+/// Per speed bin, (upper bound of the bin in km/h, grams per km of CO2, NOx, and PM2.5). PM2.5
+/// includes brake and tire wear.
+type FactorTable = [(f64, f64, f64, f64)];
+
+const PASSENGER_CAR: &FactorTable = &[
+    (10.0, 320.0, 0.60, 0.045),
+    (20.0, 240.0, 0.45, 0.035),
+    (30.0, 195.0, 0.36, 0.028),
+    (40.0, 170.0, 0.31, 0.024),
+    (50.0, 155.0, 0.28, 0.022),
+    (60.0, 145.0, 0.26, 0.021),
+    (80.0, 135.0, 0.25, 0.020),
+    (100.0, 140.0, 0.27, 0.021),
+    (130.0, 160.0, 0.33, 0.024),
+    (f64::INFINITY, 185.0, 0.40, 0.028),
+];
+
+const DIESEL_BUS: &FactorTable = &[
+    (10.0, 2100.0, 11.0, 0.30),
+    (20.0, 1500.0, 8.0, 0.22),
+    (30.0, 1200.0, 6.5, 0.17),
+    (40.0, 1050.0, 5.6, 0.15),
+    (50.0, 950.0, 5.0, 0.14),
+    (60.0, 900.0, 4.7, 0.13),
+    (80.0, 880.0, 4.5, 0.13),
+    (f64::INFINITY, 920.0, 4.8, 0.14),
+];
+
+/// How much exhaust emissions change per percent of grade. Climbing a 5% hill raises them by 40%.
+const GRADE_SENSITIVITY: f64 = 0.08;
+/// Going downhill can't reduce emissions below this fraction; the engine still idles.
+const MIN_GRADE_FACTOR: f64 = 0.3;
+/// Fuel energy released per gram of CO2, roughly the same for gasoline and diesel
+const MEGAJOULES_PER_GRAM_CO2: f64 = 0.0146;
+
+/// Emissions and energy for some set of travel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pollutants {
+    /// Grams
+    pub co2: f64,
+    /// Grams
+    pub nox: f64,
+    /// Grams
+    pub pm25: f64,
+    /// Megajoules of fuel
+    pub energy: f64,
+}
+
+impl AddAssign for Pollutants {
+    fn add_assign(&mut self, other: Pollutants) {
+        self.co2 += other.co2;
+        self.nox += other.nox;
+        self.pm25 += other.pm25;
+        self.energy += other.energy;
+    }
+}
+
+impl Pollutants {
+    fn scale(self, factor: f64) -> Pollutants {
+        Pollutants {
+            co2: self.co2 * factor,
+            nox: self.nox * factor,
+            pm25: self.pm25 * factor,
+            energy: self.energy * factor,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Emissions {
+    pub per_trip: BTreeMap<TripID, Pollutants>,
+    pub per_road: BTreeMap<RoadID, Pollutants>,
+    /// Indexed by the hour of the day
+    pub per_hour: Vec<Pollutants>,
+    /// When the most recent emissions happened. The last hour in `per_hour` only covers up to
+    /// here.
+    #[serde(default)]
+    recorded_until: Time,
+
+    /// Where each vehicle is right now, and when it got there
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    in_progress: BTreeMap<AgentID, (Traversable, Time, Option<TripID>)>,
+}
+
+impl Emissions {
+    pub fn new() -> Emissions {
+        Emissions {
+            per_trip: BTreeMap::new(),
+            per_road: BTreeMap::new(),
+            per_hour: Vec::new(),
+            recorded_until: Time::START_OF_DAY,
+            in_progress: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn event(&mut self, ev: &Event, time: Time, map: &Map) {
+        match ev {
+            Event::AgentEntersTraversable(a, trip, on, _) => {
+                if factors(a.to_type()).is_none() {
+                    return;
+                }
+                if let Some((prev, since, prev_trip)) =
+                    self.in_progress.insert(*a, (*on, time, *trip))
+                {
+                    self.record(a.to_type(), prev, time - since, prev_trip, time, map);
+                }
+            }
+            // Vehicles vanish partway along their last lane. Since we don't know how far they
+            // went, don't count it.
+            Event::CarReachedParkingSpot(car, _) => {
+                self.in_progress.remove(&AgentID::Car(*car));
+            }
+            Event::PersonLeavesMap(_, Some(a), _) => {
+                self.in_progress.remove(a);
+            }
+            _ => {}
+        }
+    }
+
+    fn record(
+        &mut self,
+        agent_type: AgentType,
+        on: Traversable,
+        dt: Duration,
+        trip: Option<TripID>,
+        now: Time,
+        map: &Map,
+    ) {
+        let dist = on.get_polyline(map).length();
+        if dt == Duration::ZERO || dist.inner_meters() == 0.0 {
+            return;
+        }
+        let kmph = dist.inner_meters() / dt.inner_seconds() * 3.6;
+        let grade = match on {
+            Traversable::Lane(l) => {
+                let lane = map.get_l(l);
+                let incline = map.get_r(lane.id.road).percent_incline;
+                if lane.dir == Direction::Fwd {
+                    incline
+                } else {
+                    -incline
+                }
+            }
+            Traversable::Turn(_) => 0.0,
+        };
+
+        let per_km = factors(agent_type).unwrap();
+        let (_, co2, nox, pm25) = per_km
+            .iter()
+            .find(|(max_kmph, _, _, _)| kmph <= *max_kmph)
+            .unwrap();
+        let grade_factor = (1.0 + GRADE_SENSITIVITY * grade * 100.0).max(MIN_GRADE_FACTOR);
+        let km = dist.inner_meters() / 1000.0;
+        let emitted = Pollutants {
+            co2: *co2,
+            nox: *nox,
+            pm25: *pm25,
+            energy: co2 * MEGAJOULES_PER_GRAM_CO2,
+        }
+        .scale(km * grade_factor);
+
+        if let Some(trip) = trip {
+            *self
+                .per_trip
+                .entry(trip)
+                .or_insert_with(Pollutants::default) += emitted;
+        }
+        if let Traversable::Lane(l) = on {
+            *self
+                .per_road
+                .entry(l.road)
+                .or_insert_with(Pollutants::default) += emitted;
+        }
+        self.add_hourly(now, emitted);
+    }
+
+    fn add_hourly(&mut self, now: Time, emitted: Pollutants) {
+        let hour = now.get_hours();
+        if self.per_hour.len() <= hour {
+            self.per_hour.resize(hour + 1, Pollutants::default());
+        }
+        self.per_hour[hour] += emitted;
+        self.recorded_until = self.recorded_until.max(now);
+    }
+
+    /// Everything emitted up to some time. For a live simulation, that's everything recorded so
+    /// far. For a finished run, the hour containing the time is prorated.
+    pub fn total_by(&self, time: Time) -> Pollutants {
+        let mut total = Pollutants::default();
+        let hour = time.get_hours();
+        for (idx, amount) in self.per_hour.iter().enumerate() {
+            if idx < hour {
+                total += *amount;
+            } else if idx == hour {
+                if time >= self.recorded_until {
+                    // Nothing's been recorded after this time yet, so the whole bucket counts
+                    total += *amount;
+                } else {
+                    // The bucket covers from the start of the hour until the end of the hour or
+                    // the last recorded emissions, whichever is first
+                    let start = Time::START_OF_DAY + Duration::hours(hour);
+                    let end = self.recorded_until.min(start + Duration::hours(1));
+                    total += amount.scale((time - start) / (end - start));
+                }
+            }
+        }
+        total
+    }
+
+    pub(crate) fn migrate(
+        self,
+        migration: &IdMigration,
+        dropped: &mut Counter<&'static str>,
+    ) -> Emissions {
+        let mut result = Emissions::new();
+        // Trips are identified by TripID, which only depends on the scenario, not the map
+        result.per_trip = self.per_trip;
+        result.per_hour = self.per_hour;
+        result.recorded_until = self.recorded_until;
+        for (r, amount) in self.per_road {
+            if let Some(r) = migration.r(r) {
+                result.per_road.insert(r, amount);
+            } else {
+                dropped.inc("road emissions");
+            }
+        }
+        // Vehicles in the middle of a lane can't be tracked through a map change
+        dropped.add("vehicles in progress for emissions", self.in_progress.len());
+        result
+    }
+}
+
+impl Default for Emissions {
+    fn default() -> Emissions {
+        Emissions::new()
+    }
+}
+
+fn factors(agent_type: AgentType) -> Option<&'static FactorTable> {
+    match agent_type {
+        AgentType::Car => Some(PASSENGER_CAR),
+        AgentType::Bus => Some(DIESEL_BUS),
+        // Trains are electric, and everything else is human-powered
+        AgentType::Train | AgentType::Bike | AgentType::Pedestrian | AgentType::TransitRider => {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn co2(grams: f64) -> Pollutants {
+        Pollutants {
+            co2: grams,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_total_by_live() {
+        let mut emissions = Emissions::new();
+        let mut sum = 0.0;
+        for (seconds, grams) in [(600.0, 10.0), (3000.0, 20.0), (4000.0, 5.0), (5400.0, 7.0)] {
+            let now = Time::START_OF_DAY + Duration::seconds(seconds);
+            emissions.add_hourly(now, co2(grams));
+            sum += grams;
+            // Partway through an hour, everything emitted so far counts
+            assert_eq!(emissions.total_by(now).co2, sum);
+        }
+    }
+
+    #[test]
+    fn test_total_by_finished() {
+        let mut emissions = Emissions::new();
+        emissions.add_hourly(Time::START_OF_DAY + Duration::minutes(30), co2(10.0));
+        emissions.add_hourly(Time::START_OF_DAY + Duration::minutes(90), co2(40.0));
+        emissions.add_hourly(Time::START_OF_DAY + Duration::hours(2), co2(60.0));
+
+        // Looking back at a finished run, the hour containing the time is prorated
+        let total = emissions.total_by(Time::START_OF_DAY + Duration::minutes(75));
+        assert_eq!(total.co2, 10.0 + 40.0 * 0.25);
+        // The last hour only covers until the last emissions
+        let total = emissions.total_by(Time::START_OF_DAY + Duration::hours(2));
+        assert_eq!(total.co2, 110.0);
+    }
+}
@@ -2,8 +2,8 @@ use serde::{Deserialize, Serialize};
 
 use geom::Duration;
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, TransitRouteID, TransitStopID,
-    Traversable, TurnID,
+    BikeshareDockID, BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, TransitRouteID,
+    TransitStopID, Traversable, TurnID,
 };
 use synthpop::TripMode;
 
@@ -37,6 +37,9 @@ pub enum Event {
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    /// The number of bikes at a dock changed. This includes bikes reserved by somebody walking
+    /// there.
+    BikeshareDockOccupancy(BikeshareDockID, usize),
 
     ProblemEncountered(TripID, Problem),
 
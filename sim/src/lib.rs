@@ -35,6 +35,8 @@ pub use crate::render::{
 };
 
 pub use self::analytics::{Analytics, Problem, SlidingWindow, TripPhase};
+pub use self::conflicts::{Conflict, ConflictKind, ConflictSeverity, SurrogateSafety};
+pub use self::emissions::{Emissions, Pollutants};
 pub(crate) use self::events::Event;
 pub use self::events::{AlertLocation, TripPhaseType};
 pub use self::make::{fork_rng, BorderSpawnOverTime, ScenarioGenerator, SimFlags, SpawnOverTime};
@@ -42,6 +44,9 @@ pub(crate) use self::make::{StartTripArgs, TripSpec};
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
 };
+pub use self::micromobility::initial_bikes;
+pub(crate) use self::micromobility::MicromobilitySimState;
+pub use self::noise::{NoiseEstimate, NoiseLevels, RoadSpeeds, LDEN_THRESHOLDS};
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::recorder::TrafficRecorder;
 pub(crate) use self::router::{ActionAtEnd, Router};
@@ -50,21 +55,29 @@ pub use self::sim::{
     count_parked_cars_per_bldg, rand_dist, AgentProperties, AlertHandler, DelayCause, Sim,
     SimCallback, SimOptions,
 };
+pub use self::trajectories::{Trajectory, TrajectoryPoint, TrajectoryRecorder};
 pub(crate) use self::transit::TransitSimState;
+pub use self::trip_export::{LegRecord, TripExport, TripRecord};
 pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, TripResult};
 pub(crate) use self::trips::{TripLeg, TripManager};
 
 mod analytics;
+mod conflicts;
+mod emissions;
 mod events;
 mod make;
 mod mechanics;
+mod micromobility;
+mod noise;
 mod pandemic;
 mod recorder;
 mod render;
 mod router;
 mod scheduler;
 mod sim;
+mod trajectories;
 mod transit;
+mod trip_export;
 mod trips;
 
 // http://pccsc.net/bicycle-parking-info/ says 68 inches, which is 1.73m
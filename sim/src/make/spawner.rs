@@ -55,6 +55,12 @@ pub(crate) enum TripSpec {
         stop1: TransitStopID,
         maybe_stop2: Option<TransitStopID>,
     },
+    /// Walk to a nearby shared bike or scooter, ride it close to the goal, and walk the rest. The
+    /// vehicle is picked when the trip starts, since it depends on what's available then.
+    UsingMicromobility {
+        start: SidewalkSpot,
+        goal: BuildingID,
+    },
 }
 
 impl TripSpec {
@@ -201,6 +207,11 @@ impl TripSpec {
                     legs = vec![TripLeg::Walk(walk_to), TripLeg::RideBus(*route, None)];
                 }
             }
+            TripSpec::UsingMicromobility { goal, .. } => {
+                // start_trip fills in the walk to the vehicle and the ride, if there's anything
+                // available.
+                legs.push(TripLeg::Walk(SidewalkSpot::building(*goal, map)));
+            }
         };
 
         (self, legs)
@@ -288,6 +299,14 @@ impl TripSpec {
                     TripSpec::JustWalking { start, goal }
                 }
             }
+            TripMode::Micromobility => match to {
+                TripEndpoint::Building(goal) => TripSpec::UsingMicromobility {
+                    start: start_sidewalk_spot(from, map)?,
+                    goal,
+                },
+                // The vehicle has to be left somewhere on the map
+                _ => bail!("can't end a {} trip at {:?}", mode.ongoing_verb(), to),
+            },
         })
     }
 }
@@ -0,0 +1,380 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Speed};
+use map_model::{BikeshareDockID, BuildingID, Map, Position, MAX_BIKE_SPEED};
+
+use crate::{
+    CarID, Event, PersonID, SidewalkPOI, SidewalkSpot, SimOptions, TripID, Vehicle, VehicleSpec,
+    VehicleType, BIKE_LENGTH,
+};
+
+/// People won't walk further than this (as the crow flies) to pick up a shared vehicle.
+const MAX_WALK_TO_VEHICLE: Distance = Distance::const_meters(500.0);
+/// Shared bikes are heavy and geared low.
+const SHARED_BIKE_SPEED: Speed = Speed::const_meters_per_second(4.0);
+/// E-scooters are usually capped around 15mph, but share bike lanes with everyone else.
+const SCOOTER_SPEED: Speed = MAX_BIKE_SPEED;
+/// Docks start the day this full, and rebalancing aims for this.
+const TARGET_DOCK_FULLNESS: f64 = 0.5;
+/// Scooters are scattered the same way every run.
+const SCOOTER_SEED: u64 = 42;
+
+/// Manages shared bikes and scooters. Bikeshare docks come from the map and have a fixed capacity;
+/// dockless scooters are scattered around buildings. Somebody using micromobility reserves the
+/// closest available vehicle when their trip starts, walks to it, and rides to a dock with room
+/// near their destination (or right to the destination, for a scooter).
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct MicromobilitySimState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    docks: BTreeMap<BikeshareDockID, Dock>,
+    /// How many scooters are parked near each building
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    scooters: BTreeMap<BuildingID, usize>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    rentals: BTreeMap<TripID, Rental>,
+    rebalance_interval: Option<Duration>,
+
+    events: Vec<Event>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Dock {
+    capacity: usize,
+    /// Bikes at the dock that anybody can take
+    available: usize,
+    /// Bikes still at the dock, but held for somebody walking here
+    reserved: usize,
+    /// Empty slots held for somebody riding here
+    incoming: usize,
+}
+
+impl Dock {
+    fn bikes_present(&self) -> usize {
+        self.available + self.reserved
+    }
+
+    fn free_slots(&self) -> usize {
+        self.capacity - self.available - self.reserved - self.incoming
+    }
+
+    fn target(&self) -> usize {
+        initial_bikes(self.capacity)
+    }
+}
+
+/// Docks start the day, and are rebalanced back to, this many bikes
+pub fn initial_bikes(capacity: usize) -> usize {
+    ((capacity as f64) * TARGET_DOCK_FULLNESS).round() as usize
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum Location {
+    Dock(BikeshareDockID),
+    Dockless(BuildingID),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Rental {
+    vehicle: Vehicle,
+    pickup: Location,
+    /// Only known once the ride starts
+    dropoff: Option<Location>,
+}
+
+impl MicromobilitySimState {
+    pub fn new(map: &Map, opts: &SimOptions) -> MicromobilitySimState {
+        let mut docks = BTreeMap::new();
+        for dock in map.all_bikeshare_docks() {
+            let mut state = Dock {
+                capacity: dock.capacity,
+                available: 0,
+                reserved: 0,
+                incoming: 0,
+            };
+            state.available = state.target();
+            docks.insert(dock.id, state);
+        }
+
+        let mut scooters = BTreeMap::new();
+        if opts.scooter_fleet_size > 0 {
+            // Only leave scooters where somebody could ride away
+            let bldgs: Vec<BuildingID> = map
+                .all_buildings()
+                .iter()
+                .filter(|b| b.biking_connection(map).is_some())
+                .map(|b| b.id)
+                .collect();
+            if bldgs.is_empty() {
+                warn!("Nowhere to put scooters; nothing's connected to a bike-able lane");
+            } else {
+                let mut rng = XorShiftRng::seed_from_u64(SCOOTER_SEED);
+                for _ in 0..opts.scooter_fleet_size {
+                    *scooters
+                        .entry(*bldgs.choose(&mut rng).unwrap())
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+
+        MicromobilitySimState {
+            docks,
+            scooters,
+            rentals: BTreeMap::new(),
+            rebalance_interval: opts.bikeshare_rebalance_interval,
+            events: Vec::new(),
+        }
+    }
+
+    /// Rebalancing is scheduled with this interval, if there are any docks.
+    pub fn rebalance_interval(&self) -> Option<Duration> {
+        if self.docks.is_empty() {
+            None
+        } else {
+            self.rebalance_interval
+        }
+    }
+
+    /// Hold the closest available bike or scooter for somebody about to walk there. Returns where
+    /// they'll start riding, or None if nothing's close enough.
+    pub fn reserve_vehicle(
+        &mut self,
+        trip: TripID,
+        person: PersonID,
+        car: CarID,
+        from: Position,
+        map: &Map,
+    ) -> Option<SidewalkSpot> {
+        let from = from.pt(map);
+        let mut candidates: Vec<(Distance, Location)> = Vec::new();
+        for (id, dock) in &self.docks {
+            if dock.available > 0 {
+                let pt = map.get_bd(*id).sidewalk_pos.pt(map);
+                candidates.push((pt.dist_to(from), Location::Dock(*id)));
+            }
+        }
+        for b in self.scooters.keys() {
+            let pt = map.get_b(*b).sidewalk_pos.pt(map);
+            candidates.push((pt.dist_to(from), Location::Dockless(*b)));
+        }
+        candidates.retain(|(dist, _)| *dist <= MAX_WALK_TO_VEHICLE);
+        candidates.sort_by_key(|(dist, _)| *dist);
+
+        // Some of the candidates might've been cut off from bike lanes by map edits
+        let (pickup, spot) = candidates.into_iter().find_map(|(_, loc)| {
+            let spot = riding_spot(loc, map)?;
+            Some((loc, spot))
+        })?;
+
+        let max_speed = match pickup {
+            Location::Dock(id) => {
+                let dock = self.docks.get_mut(&id).unwrap();
+                dock.available -= 1;
+                dock.reserved += 1;
+                SHARED_BIKE_SPEED
+            }
+            Location::Dockless(b) => {
+                self.take_scooter(b);
+                SCOOTER_SPEED
+            }
+        };
+        let vehicle = VehicleSpec {
+            vehicle_type: VehicleType::Bike,
+            length: BIKE_LENGTH,
+            max_speed: Some(max_speed),
+        }
+        .make(car, Some(person));
+        self.rentals.insert(
+            trip,
+            Rental {
+                vehicle,
+                pickup,
+                dropoff: None,
+            },
+        );
+        Some(spot)
+    }
+
+    /// Somebody's reached their reserved vehicle. Decide where they'll leave it -- at the
+    /// destination for a scooter, or the closest dock with room for a bike. Returns the vehicle
+    /// and where to stop riding.
+    pub fn start_ride(
+        &mut self,
+        trip: TripID,
+        goal: BuildingID,
+        map: &Map,
+    ) -> Option<(Vehicle, SidewalkSpot)> {
+        let pickup = self.rentals[&trip].pickup;
+        let (dropoff, spot) = match pickup {
+            Location::Dockless(_) => {
+                let loc = Location::Dockless(goal);
+                (loc, riding_spot(loc, map)?)
+            }
+            Location::Dock(_) => {
+                let goal_pt = map.get_b(goal).sidewalk_pos.pt(map);
+                let mut candidates: Vec<(Distance, BikeshareDockID)> = self
+                    .docks
+                    .iter()
+                    .filter(|(_, dock)| dock.free_slots() > 0)
+                    .map(|(id, _)| (map.get_bd(*id).sidewalk_pos.pt(map).dist_to(goal_pt), *id))
+                    .collect();
+                candidates.sort();
+                candidates.into_iter().find_map(|(_, id)| {
+                    let loc = Location::Dock(id);
+                    Some((loc, riding_spot(loc, map)?))
+                })?
+            }
+        };
+
+        if let Location::Dock(id) = pickup {
+            let dock = self.docks.get_mut(&id).unwrap();
+            dock.reserved -= 1;
+            self.events
+                .push(Event::BikeshareDockOccupancy(id, dock.bikes_present()));
+        }
+        if let Location::Dock(id) = dropoff {
+            self.docks.get_mut(&id).unwrap().incoming += 1;
+        }
+        let rental = self.rentals.get_mut(&trip).unwrap();
+        rental.dropoff = Some(dropoff);
+        Some((rental.vehicle.clone(), spot))
+    }
+
+    /// Somebody's arrived at their dock or destination, so leave the vehicle there.
+    pub fn end_ride(&mut self, trip: TripID) {
+        let rental = self.rentals.remove(&trip).unwrap();
+        self.drop_off(rental.dropoff.unwrap());
+    }
+
+    /// A trip using a shared vehicle was cancelled. Like cars, the vehicle is warped to wherever
+    /// it was headed. If the ride hadn't started yet, it stays where it is.
+    pub fn trip_cancelled(&mut self, trip: TripID) {
+        if let Some(rental) = self.rentals.remove(&trip) {
+            if let Some(dropoff) = rental.dropoff {
+                self.drop_off(dropoff);
+                return;
+            }
+            match rental.pickup {
+                Location::Dock(id) => {
+                    let dock = self.docks.get_mut(&id).unwrap();
+                    dock.reserved -= 1;
+                    dock.available += 1;
+                }
+                Location::Dockless(b) => {
+                    *self.scooters.entry(b).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    /// Move bikes from the fullest docks to the emptiest, bringing everything closer to the
+    /// target fullness. Bikes and slots that somebody's reserved are left alone.
+    pub fn rebalance(&mut self) {
+        let mut surplus = Vec::new();
+        let mut deficit = Vec::new();
+        for (id, dock) in &self.docks {
+            let expected = dock.bikes_present() + dock.incoming;
+            let target = dock.target();
+            if expected > target {
+                surplus.push((*id, (expected - target).min(dock.available)));
+            } else if expected < target {
+                deficit.push((*id, (target - expected).min(dock.free_slots())));
+            }
+        }
+        let mut to_move = surplus
+            .iter()
+            .map(|(_, n)| *n)
+            .sum::<usize>()
+            .min(deficit.iter().map(|(_, n)| *n).sum());
+        if to_move == 0 {
+            return;
+        }
+        info!("Rebalancing {} bikeshare bikes", to_move);
+
+        surplus.sort_by_key(|(_, n)| Reverse(*n));
+        deficit.sort_by_key(|(_, n)| Reverse(*n));
+        let mut to_place = to_move;
+        for (id, n) in surplus {
+            let n = n.min(to_move);
+            if n == 0 {
+                break;
+            }
+            to_move -= n;
+            let dock = self.docks.get_mut(&id).unwrap();
+            dock.available -= n;
+            self.events
+                .push(Event::BikeshareDockOccupancy(id, dock.bikes_present()));
+        }
+        for (id, n) in deficit {
+            let n = n.min(to_place);
+            if n == 0 {
+                break;
+            }
+            to_place -= n;
+            let dock = self.docks.get_mut(&id).unwrap();
+            dock.available += n;
+            self.events
+                .push(Event::BikeshareDockOccupancy(id, dock.bikes_present()));
+        }
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Returns (bikes at the dock, capacity)
+    pub fn dock_state(&self, id: BikeshareDockID) -> (usize, usize) {
+        let dock = &self.docks[&id];
+        (dock.bikes_present(), dock.capacity)
+    }
+
+    fn take_scooter(&mut self, b: BuildingID) {
+        let cnt = self.scooters.get_mut(&b).unwrap();
+        *cnt -= 1;
+        if *cnt == 0 {
+            self.scooters.remove(&b);
+        }
+    }
+
+    fn drop_off(&mut self, loc: Location) {
+        match loc {
+            Location::Dock(id) => {
+                let dock = self.docks.get_mut(&id).unwrap();
+                dock.incoming -= 1;
+                dock.available += 1;
+                self.events
+                    .push(Event::BikeshareDockOccupancy(id, dock.bikes_present()));
+            }
+            Location::Dockless(b) => {
+                *self.scooters.entry(b).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+/// Where to start or stop riding from a dock or building
+fn riding_spot(loc: Location, map: &Map) -> Option<SidewalkSpot> {
+    let (bike_pos, sidewalk_pos) = match loc {
+        Location::Dock(id) => map.get_bd(id).biking_connection(map)?,
+        Location::Dockless(b) => map.get_b(b).biking_connection(map)?,
+    };
+    Some(SidewalkSpot {
+        connection: SidewalkPOI::BikeRack(bike_pos),
+        sidewalk_pos,
+    })
+}
@@ -0,0 +1,374 @@
+//! Estimates road traffic noise from simulated volumes and speeds. This follows the FHWA highway
+//! traffic noise prediction model (FHWA-RD-77-108, the basis of TNM): every class of vehicle has a
+//! reference emission level depending on its speed, the hourly equivalent level at 15m from a road
+//! scales with the number of vehicles, and it attenuates with distance like a line source over
+//! hard ground. A road only contributes in proportion to the angle it subtends from the facade,
+//! since the reference levels are for an infinitely long road. Levels are combined into the EU's
+//! day-evening-night indicator, Lden.
+//!
+//! There are no barriers, ground effects, or reflections, and each building only hears roads from
+//! its closest facade point. Like the emissions estimates, use this to compare proposals, not
+//! for strategic noise mapping.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter, Timer};
+use geom::{Distance, FindClosest, Pt2D, Time};
+use map_model::{BuildingID, BuildingType, IdMigration, Map, RoadID, Traversable};
+
+use crate::{AgentID, AgentType, Analytics, Event};
+
+/// Reference emission levels are measured at this distance
+const REFERENCE_DISTANCE: Distance = Distance::const_meters(15.0);
+/// Roads further than this from a building don't contribute to its level
+const MAX_DISTANCE: Distance = Distance::const_meters(200.0);
+/// The model isn't valid right next to a road
+const MIN_DISTANCE: Distance = Distance::const_meters(5.0);
+/// The reference emission levels aren't valid at lower speeds, where engine and tire noise stop
+/// decreasing
+const MIN_SPEED_KMPH: f64 = 30.0;
+
+/// The usual bands for reporting population exposure, in dB(A) Lden
+pub const LDEN_THRESHOLDS: [f64; 5] = [55.0, 60.0, 65.0, 70.0, 75.0];
+
+/// Tracks the average speed of vehicles along every road, since noise depends on it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoadSpeeds {
+    /// Per road and type of vehicle, the total (meters, seconds) crossed
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    totals: BTreeMap<(RoadID, AgentType), (f64, f64)>,
+
+    /// Which lane each vehicle is on right now, and when it got there
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    in_progress: BTreeMap<AgentID, (Traversable, Time)>,
+}
+
+impl RoadSpeeds {
+    pub fn new() -> RoadSpeeds {
+        RoadSpeeds {
+            totals: BTreeMap::new(),
+            in_progress: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn event(&mut self, ev: &Event, time: Time, map: &Map) {
+        match ev {
+            Event::AgentEntersTraversable(a, _, on, _) => {
+                if !makes_noise(a.to_type()) {
+                    return;
+                }
+                if let Some((Traversable::Lane(l), since)) =
+                    self.in_progress.insert(*a, (*on, time))
+                {
+                    let dt = (time - since).inner_seconds();
+                    if dt > 0.0 {
+                        let entry = self
+                            .totals
+                            .entry((l.road, a.to_type()))
+                            .or_insert((0.0, 0.0));
+                        entry.0 += map.get_l(l).length().inner_meters();
+                        entry.1 += dt;
+                    }
+                }
+            }
+            // Vehicles vanish partway along their last lane, so don't count it
+            Event::CarReachedParkingSpot(car, _) => {
+                self.in_progress.remove(&AgentID::Car(*car));
+            }
+            Event::PersonLeavesMap(_, Some(a), _) => {
+                self.in_progress.remove(a);
+            }
+            _ => {}
+        }
+    }
+
+    /// The average speed of one type of vehicle along a road, if any have crossed it
+    pub fn kmph(&self, r: RoadID, agent_type: AgentType) -> Option<f64> {
+        let (meters, seconds) = self.totals.get(&(r, agent_type))?;
+        Some(meters / seconds * 3.6)
+    }
+
+    pub(crate) fn migrate(
+        self,
+        migration: &IdMigration,
+        dropped: &mut Counter<&'static str>,
+    ) -> RoadSpeeds {
+        let mut result = RoadSpeeds::new();
+        for ((r, agent_type), total) in self.totals {
+            if let Some(r) = migration.r(r) {
+                result.totals.insert((r, agent_type), total);
+            } else {
+                dropped.inc("road speeds");
+            }
+        }
+        dropped.add(
+            "vehicles in progress for road speeds",
+            self.in_progress.len(),
+        );
+        result
+    }
+}
+
+impl Default for RoadSpeeds {
+    fn default() -> RoadSpeeds {
+        RoadSpeeds::new()
+    }
+}
+
+/// Equivalent continuous sound levels in dB(A)
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct NoiseLevels {
+    /// 07:00 to 19:00
+    pub day: f64,
+    /// 19:00 to 23:00
+    pub evening: f64,
+    /// 23:00 to 07:00
+    pub night: f64,
+    /// The day-evening-night level, with a 5dB penalty in the evening and 10dB at night
+    pub lden: f64,
+}
+
+pub struct NoiseEstimate {
+    /// At 15m from the center of each road with some traffic
+    pub per_road: BTreeMap<RoadID, NoiseLevels>,
+    /// At the most exposed facade of each building near some traffic
+    pub per_building: BTreeMap<BuildingID, NoiseLevels>,
+}
+
+impl NoiseEstimate {
+    /// Uses the hourly volumes from `road_thruput`. Hours that haven't been simulated are silent,
+    /// so this is only meaningful after simulating the full day.
+    pub fn new(map: &Map, analytics: &Analytics, timer: &mut Timer) -> NoiseEstimate {
+        // Per road and hour of the day, the sound energy at the reference distance
+        let mut energy: BTreeMap<RoadID, [f64; 24]> = BTreeMap::new();
+        for ((r, agent_type, hour), count) in &analytics.road_thruput.counts {
+            if !makes_noise(*agent_type) {
+                continue;
+            }
+            let kmph = analytics
+                .road_speeds
+                .kmph(*r, *agent_type)
+                .unwrap_or_else(|| map.get_r(*r).speed_limit.inner_meters_per_second() * 3.6);
+            energy.entry(*r).or_insert([0.0; 24])[hour % 24] +=
+                hourly_energy(*agent_type, *count, kmph);
+        }
+
+        let mut closest = FindClosest::new(map.get_bounds());
+        for r in energy.keys() {
+            closest.add(*r, map.get_r(*r).center_pts.points());
+        }
+        let mut per_building = BTreeMap::new();
+        timer.start_iter("estimate noise at buildings", map.all_buildings().len());
+        for b in map.all_buildings() {
+            timer.next();
+            let mut total = [0.0; 24];
+            for (r, road_pt, _) in closest.all_close_pts(b.polygon.center(), MAX_DISTANCE) {
+                let facade_pt = *b
+                    .polygon
+                    .points()
+                    .iter()
+                    .min_by_key(|pt| pt.dist_to(road_pt))
+                    .unwrap();
+                let facade_dist = facade_pt.dist_to(road_pt).max(MIN_DISTANCE);
+                let attenuation = REFERENCE_DISTANCE / facade_dist
+                    * subtended_angle(facade_pt, map.get_r(r).center_pts.points())
+                    / std::f64::consts::PI;
+                for (hour, amount) in energy[&r].iter().enumerate() {
+                    total[hour] += amount * attenuation;
+                }
+            }
+            if total.iter().any(|x| *x > 0.0) {
+                per_building.insert(b.id, levels(&total));
+            }
+        }
+
+        NoiseEstimate {
+            per_road: energy.into_iter().map(|(r, e)| (r, levels(&e))).collect(),
+            per_building,
+        }
+    }
+
+    /// For each threshold, how many residents live in buildings with an Lden at or above it
+    pub fn exposure(&self, map: &Map, thresholds: &[f64]) -> Vec<(f64, usize)> {
+        thresholds
+            .iter()
+            .map(|threshold| {
+                let people = self
+                    .per_building
+                    .iter()
+                    .filter(|(_, levels)| levels.lden >= *threshold)
+                    .map(|(b, _)| residents(&map.get_b(*b).bldg_type))
+                    .sum();
+                (*threshold, people)
+            })
+            .collect()
+    }
+}
+
+/// Only motor vehicles are loud enough to matter. Trains are electric light rail here, and
+/// aren't covered by the road traffic model.
+fn makes_noise(agent_type: AgentType) -> bool {
+    matches!(agent_type, AgentType::Car | AgentType::Bus)
+}
+
+/// The sound energy at the reference distance from `count` vehicles passing in an hour
+fn hourly_energy(agent_type: AgentType, count: usize, kmph: f64) -> f64 {
+    let kmph = kmph.max(MIN_SPEED_KMPH);
+    // Reference energy mean emission levels at 15m
+    let reference_level = match agent_type {
+        AgentType::Car => 38.1 * kmph.log10() - 2.4,
+        // Buses are treated as heavy trucks
+        AgentType::Bus => 24.6 * kmph.log10() + 38.5,
+        _ => unreachable!(),
+    };
+    // How much of the hour the vehicles are close enough to contribute
+    let reference_km = REFERENCE_DISTANCE.inner_meters() / 1000.0;
+    let fraction = (count as f64) * std::f64::consts::PI * reference_km / kmph;
+    10.0_f64.powf(reference_level / 10.0) * fraction
+}
+
+/// The angle in radians that a road subtends from a point. The FHWA finite segment adjustment
+/// scales a road's energy by this angle over pi, so an infinitely long straight road counts fully.
+/// A road curving around the point can subtend more than that.
+fn subtended_angle(pt: Pt2D, road: &[Pt2D]) -> f64 {
+    road.windows(2)
+        .map(|pair| {
+            let (x1, y1) = (pair[0].x() - pt.x(), pair[0].y() - pt.y());
+            let (x2, y2) = (pair[1].x() - pt.x(), pair[1].y() - pt.y());
+            (x1 * y2 - y1 * x2).atan2(x1 * x2 + y1 * y2).abs()
+        })
+        .sum()
+}
+
+fn to_decibels(energy: f64) -> f64 {
+    if energy <= 0.0 {
+        0.0
+    } else {
+        10.0 * energy.log10()
+    }
+}
+
+fn levels(hourly_energy: &[f64; 24]) -> NoiseLevels {
+    let average = |hours: &[usize]| -> f64 {
+        hours.iter().map(|h| hourly_energy[*h]).sum::<f64>() / (hours.len() as f64)
+    };
+    let day = average(&(7..19).collect::<Vec<_>>());
+    let evening = average(&(19..23).collect::<Vec<_>>());
+    let night = average(&(0..7).chain(23..24).collect::<Vec<_>>());
+    // Each period's energy is already an average, so weighting by the number of hours and
+    // applying the penalties gives Lden
+    let lden = (12.0 * day + 4.0 * evening * 10.0_f64.powf(0.5) + 8.0 * night * 10.0) / 24.0;
+    NoiseLevels {
+        day: to_decibels(day),
+        evening: to_decibels(evening),
+        night: to_decibels(night),
+        lden: to_decibels(lden),
+    }
+}
+
+fn residents(bldg_type: &BuildingType) -> usize {
+    match bldg_type {
+        BuildingType::Residential { num_residents, .. } => *num_residents,
+        BuildingType::ResidentialCommercial(residents, _) => *residents,
+        BuildingType::Commercial(_) | BuildingType::Empty => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "got {}, expected {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_hourly_energy() {
+        // 1,000 cars an hour at 50km/h, worked out by hand from the reference levels
+        assert_close(
+            to_decibels(hourly_energy(AgentType::Car, 1000, 50.0)),
+            62.07,
+        );
+        assert_close(to_decibels(hourly_energy(AgentType::Bus, 100, 50.0)), 70.04);
+
+        // Doubling traffic adds 3dB, and 10 times the traffic adds 10dB
+        let base = to_decibels(hourly_energy(AgentType::Car, 500, 40.0));
+        assert_close(
+            to_decibels(hourly_energy(AgentType::Car, 1000, 40.0)) - base,
+            10.0 * 2.0_f64.log10(),
+        );
+        assert_close(
+            to_decibels(hourly_energy(AgentType::Car, 5000, 40.0)) - base,
+            10.0,
+        );
+
+        // Very slow traffic is treated like it's going the minimum speed
+        assert_close(
+            hourly_energy(AgentType::Car, 100, 5.0),
+            hourly_energy(AgentType::Car, 100, MIN_SPEED_KMPH),
+        );
+        assert_eq!(hourly_energy(AgentType::Car, 0, 50.0), 0.0);
+    }
+
+    #[test]
+    fn test_subtended_angle() {
+        let pt = Pt2D::new(0.0, 0.0);
+        // A 100m road centered 50m away subtends 2 * atan(50 / 50), a quarter of a circle
+        let road = vec![Pt2D::new(-50.0, 50.0), Pt2D::new(50.0, 50.0)];
+        assert_close(subtended_angle(pt, &road), std::f64::consts::FRAC_PI_2);
+        // Splitting the road into more points doesn't change anything, whichever way it's drawn
+        let split = vec![
+            Pt2D::new(50.0, 50.0),
+            Pt2D::new(10.0, 50.0),
+            Pt2D::new(-50.0, 50.0),
+        ];
+        assert_close(subtended_angle(pt, &split), std::f64::consts::FRAC_PI_2);
+        // A very long road approaches the infinite road of the reference levels
+        let long = vec![Pt2D::new(-100_000.0, 15.0), Pt2D::new(100_000.0, 15.0)];
+        assert_close(subtended_angle(pt, &long), std::f64::consts::PI);
+        // A short road far away barely counts, and a road heading straight away doesn't at all
+        let far = vec![Pt2D::new(-5.0, 150.0), Pt2D::new(5.0, 150.0)];
+        assert!(subtended_angle(pt, &far) < 0.07);
+        let away = vec![Pt2D::new(0.0, 20.0), Pt2D::new(0.0, 120.0)];
+        assert_close(subtended_angle(pt, &away), 0.0);
+    }
+
+    #[test]
+    fn test_levels() {
+        let energy = hourly_energy(AgentType::Car, 1000, 50.0);
+        let level = to_decibels(energy);
+
+        // The same traffic all day: the evening and night penalties raise Lden by about 6.4dB
+        let result = levels(&[energy; 24]);
+        assert_close(result.day, level);
+        assert_close(result.evening, level);
+        assert_close(result.night, level);
+        assert_close(result.lden, level + 6.40);
+
+        // Only daytime traffic is averaged over the whole day, so Lden is 3dB lower
+        let mut hourly = [0.0; 24];
+        for x in hourly.iter_mut().take(19).skip(7) {
+            *x = energy;
+        }
+        let result = levels(&hourly);
+        assert_close(result.day, level);
+        assert_eq!(result.evening, 0.0);
+        assert_eq!(result.night, 0.0);
+        assert_close(result.lden, level - 10.0 * 2.0_f64.log10());
+
+        assert_eq!(levels(&[0.0; 24]).lden, 0.0);
+    }
+}
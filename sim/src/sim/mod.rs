@@ -23,10 +23,11 @@ pub use self::queries::{AgentProperties, DelayCause};
 pub use self::scenario::{count_parked_cars_per_bldg, rand_dist};
 use crate::{
     AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrivingSimState, Event,
-    IntersectionSimState, PandemicModel, ParkedCar, ParkingSim, ParkingSimState, ParkingSpot,
-    Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
-    TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
+    IntersectionSimState, MicromobilitySimState, PandemicModel, ParkedCar, ParkingSim,
+    ParkingSimState, ParkingSpot, Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot,
+    StartTripArgs, TrafficRecorder, TransitSimState, TripID, TripInfo, TripManager, TripPhaseType,
+    Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH,
+    MIN_CAR_LENGTH,
 };
 
 mod queries;
@@ -43,6 +44,7 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    micromobility: MicromobilitySimState,
     trips: TripManager,
     #[serde(skip_serializing, skip_deserializing)]
     pandemic: Option<PandemicModel>,
@@ -71,6 +73,7 @@ pub(crate) struct Ctx<'a> {
     pub parking: &'a mut ParkingSimState,
     pub intersections: &'a mut IntersectionSimState,
     pub scheduler: &'a mut Scheduler,
+    pub micromobility: &'a mut MicromobilitySimState,
     pub map: &'a Map,
     /// If present, live map edits are being processed, and the agents specified are in the process
     /// of being deleted. Some regular work should maybe be skipped.
@@ -127,6 +130,13 @@ pub struct SimOptions {
     /// quickly.
     #[structopt(long)]
     pub skip_analytics: bool,
+    /// How many dockless scooters to scatter around the map at the start of the day.
+    #[structopt(long, default_value = "0")]
+    pub scooter_fleet_size: usize,
+    /// How often to move bikes from full bikeshare docks to empty ones. If unset, docks are never
+    /// rebalanced.
+    #[structopt(long, parse(try_from_str = Duration::parse))]
+    pub bikeshare_rebalance_interval: Option<Duration>,
 }
 
 impl SimOptions {
@@ -143,6 +153,8 @@ impl SimOptions {
             infinite_parking: false,
             disable_turn_conflicts: false,
             skip_analytics: false,
+            scooter_fleet_size: 0,
+            bikeshare_rebalance_interval: None,
         }
     }
 }
@@ -205,12 +217,21 @@ impl Sim {
             opts.allow_block_the_box = true;
         }
 
+        let micromobility = MicromobilitySimState::new(map, &opts);
+        if let Some(interval) = micromobility.rebalance_interval() {
+            scheduler.push(
+                Time::START_OF_DAY + interval,
+                Command::RebalanceBikeshare(interval),
+            );
+        }
+
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, &mut timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(map, &mut scheduler, &opts),
             transit: TransitSimState::new(map),
+            micromobility,
             trips: TripManager::new(),
             pandemic: opts.enable_pandemic_model.map(PandemicModel::new),
             scheduler,
@@ -438,6 +459,7 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             scheduler: &mut self.scheduler,
+            micromobility: &mut self.micromobility,
             map,
             handling_live_edits: None,
         };
@@ -633,6 +655,11 @@ impl Sim {
             Command::StartBus(r, _) => {
                 self.start_bus(map.get_tr(r), map);
             }
+            Command::RebalanceBikeshare(interval) => {
+                self.micromobility.rebalance();
+                self.scheduler
+                    .push(self.time + interval, Command::RebalanceBikeshare(interval));
+            }
         }
 
         // Record events at precisely the time they occur.
@@ -644,6 +671,7 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.micromobility.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
@@ -851,6 +879,7 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             scheduler: &mut self.scheduler,
+            micromobility: &mut self.micromobility,
             map,
             handling_live_edits: Some(affected_agents),
         };
@@ -966,6 +995,7 @@ impl Sim {
                 parking: &mut self.parking,
                 intersections: &mut self.intersections,
                 scheduler: &mut self.scheduler,
+                micromobility: &mut self.micromobility,
                 map,
                 handling_live_edits: None,
             };
@@ -7,8 +7,8 @@ use std::collections::{BTreeMap, BTreeSet};
 use abstutil::Counter;
 use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
-    BuildingID, IntersectionID, Lane, LaneID, Map, Path, Position, TransitRouteID, TransitStopID,
-    Traversable, TurnID,
+    BikeshareDockID, BuildingID, IntersectionID, Lane, LaneID, Map, Path, Position, TransitRouteID,
+    TransitStopID, Traversable, TurnID,
 };
 use synthpop::{OrigPersonID, Scenario, TripMode};
 
@@ -179,6 +179,10 @@ impl Sim {
             })
             .collect()
     }
+    /// Returns (bikes at the dock, capacity). Bikes somebody's walking towards still count.
+    pub fn get_bikeshare_dock_state(&self, id: BikeshareDockID) -> (usize, usize) {
+        self.micromobility.dock_state(id)
+    }
 
     pub fn lookup_person(&self, id: PersonID) -> Option<&Person> {
         self.trips.get_person(id)
@@ -437,6 +441,8 @@ impl Sim {
                             .unwrap()
                             .max_speed
                     }
+                    // Best case, a scooter is waiting right outside
+                    TripMode::Micromobility => Some(map_model::MAX_BIKE_SPEED),
                 };
                 Ok(path.estimate_duration(map, max_speed))
             }
@@ -122,7 +122,8 @@ fn get_vehicles(
     // TODO If the trip is cancelled, this should be affected...
     for trip in &person.trips {
         let use_for_trip = match trip.mode {
-            TripMode::Walk | TripMode::Transit => None,
+            // Shared vehicles are picked up along the way
+            TripMode::Walk | TripMode::Transit | TripMode::Micromobility => None,
             TripMode::Bike => {
                 if bike_idx.is_none() {
                     bike_idx = Some(vehicle_specs.len());
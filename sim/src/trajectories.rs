@@ -0,0 +1,324 @@
+//! Records where every agent is over a window of time, so the movement can be animated by other
+//! tools. Agents are sampled at a fixed interval, and the result can be written as deck.gl
+//! TripsLayer JSON, SUMO floating car data (FCD) XML, or CSV for Kepler.gl. Coordinates are
+//! always WGS84 longitude and latitude.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use abstutil::Timer;
+use geom::{Angle, Duration, Pt2D, Time};
+use map_model::{Map, Traversable};
+
+use crate::{AgentID, AgentType, CarStatus, PersonID, Sim};
+
+/// Where an agent was at one moment
+#[derive(Clone)]
+pub struct TrajectoryPoint {
+    pub time: Time,
+    pub pos: Pt2D,
+    pub facing: Angle,
+    pub on: Traversable,
+}
+
+/// The continuous movement of one agent. If an agent disappears (like a car parking) and later
+/// reappears, that's a separate trajectory.
+#[derive(Clone)]
+pub struct Trajectory {
+    pub agent: AgentID,
+    pub person: Option<PersonID>,
+    pub points: Vec<TrajectoryPoint>,
+}
+
+pub struct TrajectoryRecorder {
+    trajectories: Vec<Trajectory>,
+    /// Agents seen in the last sample, pointing into `trajectories`
+    active: BTreeMap<AgentID, usize>,
+}
+
+impl TrajectoryRecorder {
+    pub fn new() -> TrajectoryRecorder {
+        TrajectoryRecorder {
+            trajectories: Vec::new(),
+            active: BTreeMap::new(),
+        }
+    }
+
+    /// Runs the simulation until `end`, sampling every agent every `interval`, starting now.
+    pub fn record(
+        sim: &mut Sim,
+        map: &Map,
+        end: Time,
+        interval: Duration,
+        timer: &mut Timer,
+    ) -> TrajectoryRecorder {
+        let mut recorder = TrajectoryRecorder::new();
+        let num_samples = ((end - sim.time()) / interval).ceil().max(0.0) as usize;
+        timer.start_iter("sample agent positions", num_samples + 1);
+        loop {
+            timer.next();
+            recorder.sample(sim, map);
+            if sim.time() >= end {
+                break;
+            }
+            let dt = if sim.time() + interval > end {
+                end - sim.time()
+            } else {
+                interval
+            };
+            sim.timed_step(map, dt, &mut None, &mut Timer::throwaway());
+        }
+        recorder
+    }
+
+    /// Records the current position of every moving agent. Parked cars and people riding transit
+    /// are skipped; the transit vehicle itself is recorded.
+    pub fn sample(&mut self, sim: &Sim, map: &Map) {
+        let time = sim.time();
+        let mut seen = Vec::new();
+        for car in sim.get_all_draw_cars(map) {
+            if car.status == CarStatus::Parked {
+                continue;
+            }
+            seen.push((
+                AgentID::Car(car.id),
+                car.person,
+                TrajectoryPoint {
+                    time,
+                    pos: car.body.last_pt(),
+                    facing: car.body.last_line().angle(),
+                    on: car.on,
+                },
+            ));
+        }
+        for ped in sim.get_all_draw_peds(map) {
+            seen.push((
+                AgentID::Pedestrian(ped.id),
+                Some(ped.person),
+                TrajectoryPoint {
+                    time,
+                    pos: ped.pos,
+                    facing: ped.facing,
+                    on: ped.on,
+                },
+            ));
+        }
+
+        let mut active = BTreeMap::new();
+        for (agent, person, pt) in seen {
+            let idx = match self.active.get(&agent) {
+                Some(idx) => *idx,
+                None => {
+                    self.trajectories.push(Trajectory {
+                        agent,
+                        person,
+                        points: Vec::new(),
+                    });
+                    self.trajectories.len() - 1
+                }
+            };
+            self.trajectories[idx].points.push(pt);
+            active.insert(agent, idx);
+        }
+        self.active = active;
+    }
+
+    pub fn get_trajectories(&self) -> &Vec<Trajectory> {
+        &self.trajectories
+    }
+
+    /// Produces a JSON array for deck.gl's TripsLayer, with `path` and `timestamps` for each
+    /// trajectory. Timestamps are seconds after midnight.
+    pub fn to_deckgl_trips(&self, map: &Map) -> Result<String> {
+        #[derive(Serialize)]
+        struct DeckTrip {
+            agent: String,
+            agent_type: AgentType,
+            person: Option<PersonID>,
+            path: Vec<[f64; 2]>,
+            timestamps: Vec<f64>,
+        }
+
+        let gps_bounds = map.get_gps_bounds();
+        let mut trips = Vec::new();
+        for traj in &self.trajectories {
+            // A single point can't be animated
+            if traj.points.len() < 2 {
+                continue;
+            }
+            trips.push(DeckTrip {
+                agent: agent_label(traj.agent),
+                agent_type: traj.agent.to_type(),
+                person: traj.person,
+                path: traj
+                    .points
+                    .iter()
+                    .map(|pt| {
+                        let gps = pt.pos.to_gps(gps_bounds);
+                        [gps.x(), gps.y()]
+                    })
+                    .collect(),
+                timestamps: traj
+                    .points
+                    .iter()
+                    .map(|pt| pt.time.inner_seconds())
+                    .collect(),
+            });
+        }
+        Ok(serde_json::to_string(&trips)?)
+    }
+
+    /// Produces SUMO's floating car data XML format, as if sumo had been run with
+    /// `--fcd-output.geo`. Lanes and turns are named by our own IDs, not a SUMO network.
+    pub fn to_sumo_fcd(&self, map: &Map) -> String {
+        let gps_bounds = map.get_gps_bounds();
+        let mut per_time: BTreeMap<Time, Vec<(&Trajectory, usize)>> = BTreeMap::new();
+        for traj in &self.trajectories {
+            for idx in 0..traj.points.len() {
+                per_time
+                    .entry(traj.points[idx].time)
+                    .or_insert_with(Vec::new)
+                    .push((traj, idx));
+            }
+        }
+
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<fcd-export>\n");
+        for (time, samples) in per_time {
+            writeln!(out, "  <timestep time=\"{:.2}\">", time.inner_seconds()).unwrap();
+            for (traj, idx) in samples {
+                let pt = &traj.points[idx];
+                let gps = pt.pos.to_gps(gps_bounds);
+                let (tag, vtype) = match traj.agent.to_type() {
+                    AgentType::Pedestrian => ("person", "DEFAULT_PEDTYPE"),
+                    AgentType::Car => ("vehicle", "DEFAULT_VEHTYPE"),
+                    AgentType::Bike => ("vehicle", "DEFAULT_BIKETYPE"),
+                    AgentType::Bus => ("vehicle", "bus"),
+                    AgentType::Train => ("vehicle", "rail"),
+                    AgentType::TransitRider => unreachable!(),
+                };
+                writeln!(
+                    out,
+                    "    <{} id=\"{}\" x=\"{:.7}\" y=\"{:.7}\" angle=\"{:.2}\" type=\"{}\" \
+                     speed=\"{:.2}\" {}=\"{}\"/>",
+                    tag,
+                    agent_label(traj.agent),
+                    gps.x(),
+                    gps.y(),
+                    heading(pt.facing),
+                    vtype,
+                    speed(traj, idx),
+                    if tag == "person" { "edge" } else { "lane" },
+                    traversable_label(pt.on),
+                )
+                .unwrap();
+            }
+            out.push_str("  </timestep>\n");
+        }
+        out.push_str("</fcd-export>\n");
+        out
+    }
+
+    /// Produces one CSV row per sample, with a `datetime` column that Kepler.gl recognizes for
+    /// time playback. The simulation has no date, so times are on 1970-01-01.
+    pub fn to_kepler_csv(&self, map: &Map) -> Result<String> {
+        #[derive(Serialize)]
+        struct Row {
+            agent: String,
+            agent_type: AgentType,
+            person: Option<PersonID>,
+            /// Distinguishes different trajectories of the same agent
+            trajectory: usize,
+            datetime: String,
+            seconds: f64,
+            longitude: f64,
+            latitude: f64,
+            heading: f64,
+            speed: f64,
+            on: String,
+        }
+
+        let gps_bounds = map.get_gps_bounds();
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for (traj_idx, traj) in self.trajectories.iter().enumerate() {
+            for (idx, pt) in traj.points.iter().enumerate() {
+                let gps = pt.pos.to_gps(gps_bounds);
+                writer.serialize(Row {
+                    agent: agent_label(traj.agent),
+                    agent_type: traj.agent.to_type(),
+                    person: traj.person,
+                    trajectory: traj_idx,
+                    datetime: datetime(pt.time),
+                    seconds: pt.time.inner_seconds(),
+                    longitude: gps.x(),
+                    latitude: gps.y(),
+                    heading: heading(pt.facing),
+                    speed: speed(traj, idx),
+                    on: traversable_label(pt.on),
+                })?;
+            }
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+impl Default for TrajectoryRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn agent_label(id: AgentID) -> String {
+    match id {
+        AgentID::Car(car) => format!("{}_{}", car.vehicle_type, car.id),
+        AgentID::Pedestrian(ped) => format!("ped_{}", ped.0),
+        AgentID::BusPassenger(person, _) => format!("person_{}", person.0),
+    }
+}
+
+fn traversable_label(on: Traversable) -> String {
+    match on {
+        Traversable::Lane(l) => format!("lane_{}", l.encode_u32()),
+        Traversable::Turn(t) => format!(
+            "turn_{}_{}_{}",
+            t.parent.0,
+            t.src.encode_u32(),
+            t.dst.encode_u32()
+        ),
+    }
+}
+
+/// Degrees clockwise from north. Map-space has y pointing down, so this is a quarter turn from
+/// the usual angle.
+fn heading(angle: Angle) -> f64 {
+    (angle.normalized_degrees() + 90.0) % 360.0
+}
+
+/// In meters per second, estimated from the distance to the previous sample
+fn speed(traj: &Trajectory, idx: usize) -> f64 {
+    if idx == 0 {
+        return 0.0;
+    }
+    let prev = &traj.points[idx - 1];
+    let pt = &traj.points[idx];
+    let dt = (pt.time - prev.time).inner_seconds();
+    if dt == 0.0 {
+        return 0.0;
+    }
+    pt.pos.dist_to(prev.pos).inner_meters() / dt
+}
+
+fn datetime(time: Time) -> String {
+    let secs = time.inner_seconds().round() as usize;
+    format!(
+        "1970-01-{:02} {:02}:{:02}:{:02}",
+        1 + secs / 86400,
+        (secs % 86400) / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
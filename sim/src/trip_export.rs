@@ -0,0 +1,280 @@
+//! Exports everything known about every trip, for analysis in other tools like pandas or QGIS.
+//! There's one record per trip and one per leg (or phase) of each trip. Both can be written as CSV,
+//! and legs with a known route can be written as GeoJSON. All times are in seconds after midnight,
+//! durations in seconds, and distances in meters.
+//!
+//! The simulation doesn't remember the route each agent actually took. A leg's route is found
+//! again by pathfinding the same request on the current map, so it can differ from what happened
+//! if the agent rerouted, or if the map was edited since the leg started.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use geom::{PolyLine, Time};
+use map_model::Map;
+use synthpop::{TripMode, TripPurpose};
+
+use crate::{PersonID, Problem, Sim, TripID, TripPhaseType};
+
+#[derive(Serialize)]
+pub struct TripRecord {
+    pub trip: TripID,
+    pub person: PersonID,
+    pub mode: TripMode,
+    pub purpose: TripPurpose,
+    /// Did a ScenarioModifier apply to this?
+    pub modified: bool,
+    /// "finished", "cancelled", or "unfinished"
+    pub outcome: &'static str,
+    /// The scheduled departure
+    pub departure: f64,
+    /// The actual start, which may be later if a previous trip took too long
+    pub start: Option<f64>,
+    pub arrival: Option<f64>,
+    pub duration: Option<f64>,
+    /// Only known for finished trips
+    pub distance: Option<f64>,
+    /// Time spent waiting at intersections or otherwise stuck in traffic
+    pub blocked: f64,
+
+    /// Time spent in each type of phase. Ongoing phases count up to the current time.
+    pub driving: f64,
+    pub walking: f64,
+    pub biking: f64,
+    pub parking: f64,
+    pub waiting_for_transit: f64,
+    pub riding_transit: f64,
+    pub delayed_start: f64,
+
+    pub intersection_delays: usize,
+    pub intersection_delay_total: f64,
+    pub complex_intersection_crossings: usize,
+    pub arterial_intersection_crossings: usize,
+    pub overtakes_desired: usize,
+
+    pub origin_lon: f64,
+    pub origin_lat: f64,
+    pub destination_lon: f64,
+    pub destination_lat: f64,
+}
+
+#[derive(Serialize)]
+pub struct LegRecord {
+    pub trip: TripID,
+    /// The order of this leg within the trip, starting from 0
+    pub leg: usize,
+    pub mode: TripMode,
+    /// "driving", "walking", "biking", "parking", "waiting_for_transit", "riding_transit", or
+    /// "delayed_start"
+    pub phase: &'static str,
+    pub description: String,
+    pub start: f64,
+    /// None if the leg is still happening
+    pub end: Option<f64>,
+    pub duration: f64,
+    /// The length of the route, if the leg has one
+    pub distance: Option<f64>,
+    #[serde(skip)]
+    pub geometry: Option<PolyLine>,
+}
+
+pub struct TripExport {
+    pub trips: Vec<TripRecord>,
+    pub legs: Vec<LegRecord>,
+}
+
+impl TripExport {
+    /// Gathers records for every trip in the simulation, including ones that haven't started yet.
+    /// Tracing the geometry of every leg's route is slow, so `geometry` can skip this. Cancelled
+    /// trips don't have any legs.
+    pub fn new(sim: &Sim, map: &Map, geometry: bool) -> TripExport {
+        let now = sim.time();
+        let analytics = sim.get_analytics();
+        let gps_bounds = map.get_gps_bounds();
+        let mut all_phases = analytics.get_all_trip_phases_with_paths(map);
+        let arrivals: BTreeMap<TripID, Time> = analytics
+            .finished_trips
+            .iter()
+            .filter(|(_, _, _, maybe_duration)| maybe_duration.is_some())
+            .map(|(t, id, _, _)| (*id, *t))
+            .collect();
+        let no_problems = Vec::new();
+
+        let mut trips = Vec::new();
+        let mut legs = Vec::new();
+        for (id, info) in sim.all_trip_info() {
+            let details = sim.finished_trip_details(id);
+            let origin = info.start.pt(map).to_gps(gps_bounds);
+            let destination = info.end.pt(map).to_gps(gps_bounds);
+            let mut record = TripRecord {
+                trip: id,
+                person: sim.trip_to_person(id).unwrap(),
+                mode: info.mode,
+                purpose: info.purpose,
+                modified: info.modified,
+                outcome: if info.cancellation_reason.is_some() {
+                    "cancelled"
+                } else if details.is_some() {
+                    "finished"
+                } else {
+                    "unfinished"
+                },
+                departure: info.departure.inner_seconds(),
+                start: analytics.started_trips.get(&id).map(|t| t.inner_seconds()),
+                arrival: arrivals.get(&id).map(|t| t.inner_seconds()),
+                duration: details.map(|(d, _, _)| d.inner_seconds()),
+                distance: details.map(|(_, _, dist)| dist.inner_meters()),
+                blocked: sim.trip_blocked_time(id).inner_seconds(),
+
+                driving: 0.0,
+                walking: 0.0,
+                biking: 0.0,
+                parking: 0.0,
+                waiting_for_transit: 0.0,
+                riding_transit: 0.0,
+                delayed_start: 0.0,
+
+                intersection_delays: 0,
+                intersection_delay_total: 0.0,
+                complex_intersection_crossings: 0,
+                arterial_intersection_crossings: 0,
+                overtakes_desired: 0,
+
+                origin_lon: origin.x(),
+                origin_lat: origin.y(),
+                destination_lon: destination.x(),
+                destination_lat: destination.y(),
+            };
+
+            for (idx, phase) in all_phases
+                .remove(&id)
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+            {
+                let duration = (phase.end_time.unwrap_or(now) - phase.start_time).inner_seconds();
+                let total = match phase.phase_type {
+                    TripPhaseType::Driving => &mut record.driving,
+                    TripPhaseType::Walking => &mut record.walking,
+                    TripPhaseType::Biking => &mut record.biking,
+                    TripPhaseType::Parking => &mut record.parking,
+                    TripPhaseType::WaitingForBus(_, _) => &mut record.waiting_for_transit,
+                    TripPhaseType::RidingBus(_, _, _) => &mut record.riding_transit,
+                    TripPhaseType::DelayedStart => &mut record.delayed_start,
+                    TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
+                };
+                *total += duration;
+
+                legs.push(LegRecord {
+                    trip: id,
+                    leg: idx,
+                    mode: info.mode,
+                    phase: phase_name(phase.phase_type),
+                    description: phase.phase_type.describe(map),
+                    start: phase.start_time.inner_seconds(),
+                    end: phase.end_time.map(|t| t.inner_seconds()),
+                    duration,
+                    distance: phase.path.as_ref().map(|p| p.total_length().inner_meters()),
+                    geometry: if geometry {
+                        phase.path.and_then(|p| p.trace(map))
+                    } else {
+                        None
+                    },
+                });
+            }
+
+            for (_, problem) in analytics.problems_per_trip.get(&id).unwrap_or(&no_problems) {
+                match problem {
+                    Problem::IntersectionDelay(_, delay) => {
+                        record.intersection_delays += 1;
+                        record.intersection_delay_total += delay.inner_seconds();
+                    }
+                    Problem::ComplexIntersectionCrossing(_) => {
+                        record.complex_intersection_crossings += 1;
+                    }
+                    Problem::ArterialIntersectionCrossing(_) => {
+                        record.arterial_intersection_crossings += 1;
+                    }
+                    Problem::OvertakeDesired(_) => {
+                        record.overtakes_desired += 1;
+                    }
+                }
+            }
+
+            trips.push(record);
+        }
+
+        TripExport { trips, legs }
+    }
+
+    pub fn trips_csv(&self) -> Result<String> {
+        to_csv(&self.trips)
+    }
+
+    pub fn legs_csv(&self) -> Result<String> {
+        to_csv(&self.legs)
+    }
+
+    /// One LineString per leg with a known route, with all of the leg's fields as properties. Join
+    /// to the trips by the `trip` property. Legs are only included if the export was created with
+    /// `geometry`.
+    pub fn legs_geojson(&self, map: &Map) -> Result<geojson::GeoJson> {
+        let mut features = Vec::new();
+        for leg in &self.legs {
+            if let Some(ref pl) = leg.geometry {
+                let properties = match serde_json::to_value(leg)? {
+                    serde_json::Value::Object(properties) => properties,
+                    _ => unreachable!(),
+                };
+                features.push(geojson::Feature {
+                    bbox: None,
+                    geometry: Some(pl.to_geojson(Some(map.get_gps_bounds()))),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                });
+            }
+        }
+        Ok(geojson::GeoJson::from(geojson::FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        }))
+    }
+
+    /// Writes trips.csv, legs.csv, and legs.geojson to a directory
+    pub fn write(&self, map: &Map, dir: &str) -> Result<()> {
+        fs_err::create_dir_all(dir)?;
+        fs_err::write(format!("{}/trips.csv", dir), self.trips_csv()?)?;
+        fs_err::write(format!("{}/legs.csv", dir), self.legs_csv()?)?;
+        fs_err::write(
+            format!("{}/legs.geojson", dir),
+            self.legs_geojson(map)?.to_string(),
+        )?;
+        Ok(())
+    }
+}
+
+fn phase_name(phase: TripPhaseType) -> &'static str {
+    match phase {
+        TripPhaseType::Driving => "driving",
+        TripPhaseType::Walking => "walking",
+        TripPhaseType::Biking => "biking",
+        TripPhaseType::Parking => "parking",
+        TripPhaseType::WaitingForBus(_, _) => "waiting_for_transit",
+        TripPhaseType::RidingBus(_, _, _) => "riding_transit",
+        TripPhaseType::DelayedStart => "delayed_start",
+        TripPhaseType::Cancelled => "cancelled",
+        TripPhaseType::Finished => "finished",
+    }
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
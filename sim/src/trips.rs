@@ -15,9 +15,9 @@ use synthpop::{
 use crate::sim::Ctx;
 use crate::{
     AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, SidewalkPOI, SidewalkSpot,
-    StartTripArgs, TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState,
+    Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, Router, SidewalkPOI,
+    SidewalkSpot, StartTripArgs, TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle,
+    VehicleSpec, VehicleType, WalkingSimState,
 };
 
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
@@ -432,6 +432,78 @@ impl TripManager {
                     }
                 }
             }
+            TripSpec::UsingMicromobility { start, goal } => {
+                assert_eq!(
+                    person.state,
+                    match start.connection {
+                        SidewalkPOI::Building(b) => PersonState::Inside(b),
+                        SidewalkPOI::Border(i) => {
+                            self.events.push(Event::PersonEntersMap(
+                                person.id,
+                                AgentID::Pedestrian(person.ped),
+                                i,
+                            ));
+                            PersonState::OffMap
+                        }
+                        SidewalkPOI::SuddenlyAppear => {
+                            self.events.push(Event::PersonEntersMap(
+                                person.id,
+                                AgentID::Pedestrian(person.ped),
+                                ctx.map.get_l(start.sidewalk_pos.lane()).src_i,
+                            ));
+                            PersonState::OffMap
+                        }
+                        _ => unreachable!(),
+                    }
+                );
+                person.state = PersonState::Trip(trip);
+
+                let car = CarID {
+                    id: self.car_id_counter,
+                    vehicle_type: VehicleType::Bike,
+                };
+                self.car_id_counter += 1;
+                let walk_to = if let Some(spot) = ctx.micromobility.reserve_vehicle(
+                    trip,
+                    person.id,
+                    car,
+                    start.sidewalk_pos,
+                    ctx.map,
+                ) {
+                    let legs = &mut self.trips[trip.0].legs;
+                    legs.push_front(TripLeg::RideShared(car));
+                    legs.push_front(TripLeg::Walk(spot.clone()));
+                    spot
+                } else {
+                    // Like transit trips, just walk if there's nothing useful nearby
+                    info!(
+                        "{} found no shared bikes or scooters nearby, so walking instead",
+                        trip
+                    );
+                    SidewalkSpot::building(goal, ctx.map)
+                };
+
+                let req = PathRequest::walking(start.sidewalk_pos, walk_to.sidewalk_pos);
+                match ctx.map.pathfind(req) {
+                    Ok(path) => {
+                        ctx.scheduler.push(
+                            now,
+                            Command::SpawnPed(CreatePedestrian {
+                                id: person.ped,
+                                speed: person.ped_speed,
+                                start,
+                                goal: walk_to,
+                                path,
+                                trip,
+                                person: person.id,
+                            }),
+                        );
+                    }
+                    Err(err) => {
+                        self.cancel_trip(now, trip, err.to_string(), None, ctx);
+                    }
+                }
+            }
         }
     }
 
@@ -584,6 +656,11 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         trip.assert_walking_leg(spot.clone());
+        if let TripLeg::RideShared(_) = trip.legs[0] {
+            let trip = trip.id;
+            self.start_shared_ride(now, trip, spot, ctx);
+            return;
+        }
         let (bike, drive_to) = match trip.legs[0] {
             TripLeg::Drive(bike, ref to) => (bike, to.clone()),
             _ => unreachable!(),
@@ -641,6 +718,58 @@ impl TripManager {
         }
     }
 
+    fn start_shared_ride(&mut self, now: Time, id: TripID, spot: SidewalkSpot, ctx: &mut Ctx) {
+        let person = self.trips[id.0].person;
+        let goal = match self.trips[id.0].info.end {
+            TripEndpoint::Building(b) => b,
+            // TripSpec::maybe_new disallows anything else
+            _ => unreachable!(),
+        };
+        let driving_pos = match spot.connection {
+            SidewalkPOI::BikeRack(p) => p,
+            _ => unreachable!(),
+        };
+        let (vehicle, dropoff) = if let Some(pair) = ctx.micromobility.start_ride(id, goal, ctx.map)
+        {
+            pair
+        } else {
+            self.cancel_trip(
+                now,
+                id,
+                format!("nowhere to leave a shared vehicle near {}", goal),
+                None,
+                ctx,
+            );
+            return;
+        };
+        let end = match dropoff.connection {
+            SidewalkPOI::BikeRack(p) => p,
+            _ => unreachable!(),
+        };
+
+        let req = PathRequest::vehicle(driving_pos, end, PathConstraints::Bike);
+        let maybe_path = if req.start.lane() == req.end.lane() {
+            Err(anyhow!(
+                "riding to a different part of {} is silly, why not walk?",
+                req.start.lane()
+            ))
+        } else {
+            ctx.map.pathfind(req)
+        };
+        match maybe_path {
+            Ok(path) => {
+                let router = Router::bike_then_stop(vehicle.id, path, dropoff);
+                ctx.scheduler.push(
+                    now,
+                    Command::SpawnCar(CreateCar::for_appearing(vehicle, router, id, person), true),
+                );
+            }
+            Err(err) => {
+                self.cancel_trip(now, id, err.to_string(), None, ctx);
+            }
+        }
+    }
+
     pub fn bike_reached_end(
         &mut self,
         now: Time,
@@ -662,6 +791,10 @@ impl TripManager {
             Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
                 assert_eq!(c, bike);
             }
+            Some(TripLeg::RideShared(c)) => {
+                assert_eq!(c, bike);
+                ctx.micromobility.end_ride(trip.id);
+            }
             _ => unreachable!(),
         };
 
@@ -1006,6 +1139,9 @@ impl TripManager {
             TripEndpoint::SuddenlyAppear(_) => unreachable!(),
         };
 
+        // Shared vehicles are tracked separately
+        ctx.micromobility.trip_cancelled(id);
+
         // Don't forget the car!
         if let Some(vehicle) = abandoned_vehicle {
             if vehicle.vehicle_type == VehicleType::Car {
@@ -1057,7 +1193,7 @@ impl TripManager {
         } else {
             // If the trip was cancelled because we'e totally out of parking, don't forget to clean
             // this up.
-            if let TripLeg::Drive(c, _) = &trip.legs[0] {
+            if let TripLeg::Drive(c, _) | TripLeg::RideShared(c) = &trip.legs[0] {
                 if let Some(t) = self.active_trip_mode.remove(&AgentID::Car(*c)) {
                     assert_eq!(t, trip.id);
                 }
@@ -1103,7 +1239,7 @@ impl TripManager {
         let person = &self.people[trip.person.0];
         let a = match &trip.legs[0] {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
-            TripLeg::Drive(c, _) => AgentID::Car(*c),
+            TripLeg::Drive(c, _) | TripLeg::RideShared(c) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
         };
         if self.active_trip_mode.get(&a) == Some(&id) {
@@ -1266,6 +1402,7 @@ impl TripManager {
                     let agent_type = match t.info.mode {
                         TripMode::Walk => AgentType::Pedestrian,
                         TripMode::Bike => AgentType::Bike,
+                        TripMode::Micromobility => AgentType::Pedestrian,
                         TripMode::Drive => AgentType::Car,
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
@@ -1352,6 +1489,9 @@ pub(crate) enum TripLeg {
     Drive(CarID, DrivingGoal),
     /// Maybe get off at a stop, maybe ride off-map
     RideBus(TransitRouteID, Option<TransitStopID>),
+    /// Ride a shared bike or scooter, borrowed from MicromobilitySimState. Where it'll be dropped
+    /// off is decided when the ride starts.
+    RideShared(CarID),
 }
 
 pub enum TripResult<T> {
@@ -83,7 +83,10 @@ impl MapBorders {
     /// Returns the (incoming, outgoing) borders for the specififed mode.
     pub fn for_mode(&self, mode: TripMode) -> (&Vec<MapBorder>, &Vec<MapBorder>) {
         match mode {
-            TripMode::Walk | TripMode::Transit => (&self.incoming_walking, &self.outgoing_walking),
+            // People walk to and from shared vehicles
+            TripMode::Walk | TripMode::Transit | TripMode::Micromobility => {
+                (&self.incoming_walking, &self.outgoing_walking)
+            }
             TripMode::Drive => (&self.incoming_driving, &self.outgoing_driving),
             TripMode::Bike => (&self.incoming_biking, &self.outgoing_biking),
         }
@@ -0,0 +1,286 @@
+//! Calibrates a scenario against observed traffic counts. Each person gets a weight, and weights
+//! are adjusted multiplicatively: somebody whose trips pass through locations with too much
+//! modeled traffic is scaled down, and vice versa. The weights are then turned back into a
+//! scenario by duplicating or removing people. This is a simple form of origin-destination matrix
+//! estimation that keeps every person's schedule intact.
+//!
+//! Which count locations each person passes is found once by routing their trips on the
+//! unchanged map. How well a candidate scenario matches the counts is measured by a caller-provided
+//! function, usually running the full simulation.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{prettyprint_usize, Timer};
+use map_model::{IntersectionID, Map, RoadID};
+
+use crate::counts::crossed_by_path;
+use crate::{Scenario, TrafficCounts, TripEndpoint, TripMode};
+
+/// Weights change at most by this factor per iteration, to avoid oscillating
+const MAX_STEP: f64 = 2.0;
+/// Nobody can be duplicated more than this many times
+const MAX_WEIGHT: f64 = 5.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CalibrationConfig {
+    /// Only trips with these modes are routed and compared against the counts. Transit isn't
+    /// supported.
+    pub modes: BTreeSet<TripMode>,
+    /// How many hours the counts cover. GEH is defined for hourly flows, so counts are divided by
+    /// this first.
+    pub hours: f64,
+    /// Count locations with a GEH below this are considered a match. 5 is the usual threshold.
+    pub target_geh: f64,
+    /// Stop when this fraction of count locations match. 0.85 is the usual threshold.
+    pub target_fraction: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            modes: vec![TripMode::Drive].into_iter().collect(),
+            hours: 24.0,
+            target_geh: 5.0,
+            target_fraction: 0.85,
+            max_iterations: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CountLocation {
+    Road(RoadID),
+    Intersection(IntersectionID),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CountComparison {
+    pub location: CountLocation,
+    pub observed: usize,
+    pub modeled: usize,
+    /// Calculated from the hourly flows
+    pub geh: f64,
+}
+
+/// The result of calibrating
+pub struct Calibration {
+    pub scenario: Scenario,
+    /// How many times the scenario was evaluated
+    pub iterations: usize,
+    /// The comparison for the final scenario, per observed count location
+    pub comparisons: Vec<CountComparison>,
+    /// The fraction of count locations meeting the GEH target
+    pub fraction_matching: f64,
+}
+
+/// The GEH statistic compares a modeled and observed hourly flow. Unlike a percentage error, it
+/// tolerates bigger relative differences at low volumes.
+pub fn geh(modeled: f64, observed: f64) -> f64 {
+    if modeled + observed == 0.0 {
+        return 0.0;
+    }
+    (2.0 * (modeled - observed).powi(2) / (modeled + observed)).sqrt()
+}
+
+impl TrafficCounts {
+    /// Compares modeled counts against `self`, treated as the observed counts. Only locations in
+    /// `self` are compared. Both cover `hours`.
+    pub fn compare_geh(&self, modeled: &TrafficCounts, hours: f64) -> Vec<CountComparison> {
+        let mut results = Vec::new();
+        let mut compare = |location, observed: usize, modeled: usize| {
+            results.push(CountComparison {
+                location,
+                observed,
+                modeled,
+                geh: geh(modeled as f64 / hours, observed as f64 / hours),
+            });
+        };
+        for (r, cnt) in self.per_road.borrow() {
+            compare(CountLocation::Road(*r), *cnt, modeled.per_road.get(*r));
+        }
+        for (i, cnt) in self.per_intersection.borrow() {
+            compare(
+                CountLocation::Intersection(*i),
+                *cnt,
+                modeled.per_intersection.get(*i),
+            );
+        }
+        results
+    }
+}
+
+/// Adjusts the number of people in a scenario until the counts from `evaluate` match `observed`,
+/// or until the iteration limit. `evaluate` runs a scenario and measures the resulting counts.
+pub fn calibrate<F: FnMut(&Scenario, &mut Timer) -> TrafficCounts>(
+    map: &Map,
+    scenario: &Scenario,
+    observed: &TrafficCounts,
+    config: &CalibrationConfig,
+    mut evaluate: F,
+    timer: &mut Timer,
+) -> Calibration {
+    let crossings = person_crossings(map, scenario, observed, config, timer);
+    let mut weights = vec![1.0; scenario.people.len()];
+
+    let mut iterations = 0;
+    loop {
+        let candidate = apply_weights(scenario, &weights);
+        let modeled = evaluate(&candidate, timer);
+        iterations += 1;
+        let comparisons = observed.compare_geh(&modeled, config.hours);
+        let fraction_matching = fraction_matching(&comparisons, config.target_geh);
+        info!(
+            "Calibration iteration {}: {} people, {:.1}% of counts have GEH < {}",
+            iterations,
+            prettyprint_usize(candidate.people.len()),
+            100.0 * fraction_matching,
+            config.target_geh
+        );
+        if fraction_matching >= config.target_fraction || iterations >= config.max_iterations {
+            return Calibration {
+                scenario: candidate,
+                iterations,
+                comparisons,
+                fraction_matching,
+            };
+        }
+
+        // The ratio of observed to modeled counts at each location. Add 1 to both to handle zeroes
+        // gracefully.
+        let ratios: BTreeMap<CountLocation, f64> = comparisons
+            .iter()
+            .map(|c| {
+                (
+                    c.location,
+                    (c.observed as f64 + 1.0) / (c.modeled as f64 + 1.0),
+                )
+            })
+            .collect();
+        for (weight, locations) in weights.iter_mut().zip(crossings.iter()) {
+            if locations.is_empty() {
+                continue;
+            }
+            // Use the geometric mean of the ratios at every location the person passes
+            let log_sum: f64 = locations.iter().map(|loc| ratios[loc].ln()).sum();
+            let step = (log_sum / locations.len() as f64)
+                .exp()
+                .clamp(1.0 / MAX_STEP, MAX_STEP);
+            *weight = (*weight * step).min(MAX_WEIGHT);
+        }
+    }
+}
+
+/// For every person, find all observed count locations their trips pass through.
+fn person_crossings(
+    map: &Map,
+    scenario: &Scenario,
+    observed: &TrafficCounts,
+    config: &CalibrationConfig,
+    timer: &mut Timer,
+) -> Vec<Vec<CountLocation>> {
+    let mut results = Vec::new();
+    timer.start_iter(
+        "route trips to find counted locations",
+        scenario.people.len(),
+    );
+    for person in &scenario.people {
+        timer.next();
+        let mut locations = Vec::new();
+        for trip in &person.trips {
+            if !config.modes.contains(&trip.mode) {
+                continue;
+            }
+            let req = match TripEndpoint::path_req(trip.origin, trip.destination, trip.mode, map) {
+                Some(req) => req,
+                None => continue,
+            };
+            if let Ok(path) = map.pathfind_v2(req.clone()) {
+                let (roads, intersections) = crossed_by_path(map, &req, &path);
+                for r in roads {
+                    if observed.per_road.borrow().contains_key(&r) {
+                        locations.push(CountLocation::Road(r));
+                    }
+                }
+                for i in intersections {
+                    if observed.per_intersection.borrow().contains_key(&i) {
+                        locations.push(CountLocation::Intersection(i));
+                    }
+                }
+            }
+        }
+        results.push(locations);
+    }
+    results
+}
+
+/// Turns fractional weights into whole copies of each person. The rounding error is carried
+/// forward, so the total number of people matches the sum of weights.
+fn apply_weights(scenario: &Scenario, weights: &[f64]) -> Scenario {
+    let mut result = scenario.clone();
+    result.people.clear();
+    let mut total = 0.0;
+    for (person, weight) in scenario.people.iter().zip(weights) {
+        let before = total.round() as usize;
+        total += weight;
+        for _ in before..(total.round() as usize) {
+            result.people.push(person.clone());
+        }
+    }
+    result
+}
+
+fn fraction_matching(comparisons: &[CountComparison], target_geh: f64) -> f64 {
+    if comparisons.is_empty() {
+        return 1.0;
+    }
+    let matching = comparisons.iter().filter(|c| c.geh < target_geh).count();
+    (matching as f64) / (comparisons.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use abstio::MapName;
+
+    use super::*;
+    use crate::{OrigPersonID, PersonSpec};
+
+    #[test]
+    fn test_geh() {
+        assert_eq!(geh(0.0, 0.0), 0.0);
+        assert_eq!(geh(100.0, 100.0), 0.0);
+        // 150 modeled against 100 observed is just under the usual target of 5
+        assert!((geh(150.0, 100.0) - 20.0_f64.sqrt()).abs() < 1e-9);
+        // Symmetric
+        assert_eq!(geh(150.0, 100.0), geh(100.0, 150.0));
+        // The same relative error matters more at higher volumes
+        assert!(geh(1500.0, 1000.0) > geh(150.0, 100.0));
+    }
+
+    #[test]
+    fn test_apply_weights() {
+        // Tell people apart by their original ID
+        let scenario = Scenario {
+            scenario_name: "calibrate_test".to_string(),
+            map_name: MapName::new("zz", "test", "calibrate"),
+            people: (0..4)
+                .map(|idx| PersonSpec {
+                    orig_id: Some(OrigPersonID(idx, idx)),
+                    trips: Vec::new(),
+                })
+                .collect(),
+            only_seed_buses: None,
+        };
+
+        let result = apply_weights(&scenario, &[1.0, 0.0, 2.0, 1.0]);
+        let ids: Vec<usize> = result.people.iter().map(|p| p.orig_id.unwrap().0).collect();
+        assert_eq!(ids, vec![0, 2, 2, 3]);
+
+        // Fractions carry over, so the total matches the sum of the weights
+        let result = apply_weights(&scenario, &[0.4, 0.4, 0.4, 0.4]);
+        let ids: Vec<usize> = result.people.iter().map(|p| p.orig_id.unwrap().0).collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+}
@@ -1,10 +1,13 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
-use abstutil::{prettyprint_usize, Counter, Timer};
-use geom::Distance;
+use abstutil::{deserialize_btreemap, prettyprint_usize, serialize_btreemap, Counter, Timer};
+use geom::{Distance, Duration, Time};
 use map_model::{
-    IntersectionID, Map, PathRequest, PathStepV2, PathfinderCaching, RoadID, RoutingParams,
+    IntersectionID, Map, MovementID, PathRequest, PathStepV2, PathV2, PathfinderCaching, RoadID,
+    RoutingParams,
 };
 
 /// This represents the number of vehicles (or trips, or something else) crossing roads and
@@ -18,9 +21,89 @@ pub struct TrafficCounts {
     // TODO For now, squeeze everything into this -- mode, weekday/weekend, time of day, data
     // source, etc
     pub description: String,
-    // TODO Maybe per direction, movement
+    // TODO Maybe per direction
     pub per_road: Counter<RoadID>,
     pub per_intersection: Counter<IntersectionID>,
+    /// Turning movement counts, if they're known. The direction of travel is part of each
+    /// movement.
+    #[serde(default)]
+    pub per_movement: MovementCounts,
+}
+
+/// Counts how many vehicles make each movement through intersections, grouped into time bins.
+/// This is the usual format for real-world turning movement counts (TMCs).
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovementCounts {
+    pub bin_size: Duration,
+    /// Keyed by the movement and the index of the time bin
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub counts: BTreeMap<(MovementID, usize), usize>,
+}
+
+impl Default for MovementCounts {
+    fn default() -> Self {
+        // Most TMCs use 15 minute bins
+        Self::new(Duration::minutes(15))
+    }
+}
+
+impl MovementCounts {
+    pub fn new(bin_size: Duration) -> Self {
+        Self {
+            bin_size,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, movement: MovementID, time: Time, count: usize) {
+        let bin = ((time - Time::START_OF_DAY) / self.bin_size).floor() as usize;
+        *self.counts.entry((movement, bin)).or_insert(0) += count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The total count for each movement over all time bins
+    pub fn totals(&self) -> Counter<MovementID> {
+        let mut totals = Counter::new();
+        for ((movement, _), cnt) in &self.counts {
+            totals.add(*movement, *cnt);
+        }
+        totals
+    }
+
+    /// The total count for each movement through one intersection
+    pub fn totals_at(&self, i: IntersectionID) -> Counter<MovementID> {
+        let mut totals = Counter::new();
+        for ((movement, _), cnt) in &self.counts {
+            if movement.parent == i {
+                totals.add(*movement, *cnt);
+            }
+        }
+        totals
+    }
+
+    /// The count per time bin for one movement, starting with the first bin, including empty bins
+    pub fn bins_for(&self, movement: MovementID) -> Vec<(Time, usize)> {
+        let mut results = Vec::new();
+        for ((m, bin), cnt) in &self.counts {
+            if *m != movement {
+                continue;
+            }
+            while results.len() < *bin {
+                results.push((
+                    Time::START_OF_DAY + self.bin_size * (results.len() as f64),
+                    0,
+                ));
+            }
+            results.push((Time::START_OF_DAY + self.bin_size * (*bin as f64), *cnt));
+        }
+        results
+    }
 }
 
 impl Default for TrafficCounts {
@@ -30,6 +113,7 @@ impl Default for TrafficCounts {
             description: String::new(),
             per_road: Counter::new(),
             per_intersection: Counter::new(),
+            per_movement: MovementCounts::default(),
         }
     }
 }
@@ -51,6 +135,7 @@ impl TrafficCounts {
             description,
             per_road: Counter::new(),
             per_intersection: Counter::new(),
+            per_movement: MovementCounts::default(),
         };
 
         // Statistic::Min will be wrong later for roads that're 0. So explicitly start with 0 for every
@@ -73,30 +158,12 @@ impl TrafficCounts {
         for (req, count) in requests {
             timer.next();
             if let Ok(path) = map.pathfind_v2_with_params(req.clone(), &params, cache_custom) {
-                let count = *count;
-                for step in path.get_steps() {
-                    match step {
-                        PathStepV2::Along(dr) | PathStepV2::Contraflow(dr) => {
-                            counts.per_road.add(dr.road, count);
-                        }
-                        PathStepV2::Movement(m) | PathStepV2::ContraflowMovement(m) => {
-                            counts.per_intersection.add(m.parent, count);
-                        }
-                    }
+                let (roads, intersections) = crossed_by_path(map, req, &path);
+                for r in roads {
+                    counts.per_road.add(r, *count);
                 }
-
-                // If we're starting or ending at a border, count it
-                if req.start.dist_along() == Distance::ZERO {
-                    // TODO src_i and dst_i may not work for pedestrians on contraflow sidewalks
-                    let i = map.get_l(req.start.lane()).src_i;
-                    if map.get_i(i).is_border() {
-                        counts.per_intersection.add(i, count);
-                    }
-                } else {
-                    let i = map.get_l(req.end.lane()).dst_i;
-                    if map.get_i(i).is_border() {
-                        counts.per_intersection.add(i, count);
-                    }
+                for i in intersections {
+                    counts.per_intersection.add(i, *count);
                 }
             }
         }
@@ -136,3 +203,39 @@ impl TrafficCounts {
         println!("RMSE = {:.2}", (sum / n as f64).sqrt());
     }
 }
+
+/// Returns every road and intersection crossed by a path, including the border where it starts or
+/// ends.
+pub(crate) fn crossed_by_path(
+    map: &Map,
+    req: &PathRequest,
+    path: &PathV2,
+) -> (Vec<RoadID>, Vec<IntersectionID>) {
+    let mut roads = Vec::new();
+    let mut intersections = Vec::new();
+    for step in path.get_steps() {
+        match step {
+            PathStepV2::Along(dr) | PathStepV2::Contraflow(dr) => {
+                roads.push(dr.road);
+            }
+            PathStepV2::Movement(m) | PathStepV2::ContraflowMovement(m) => {
+                intersections.push(m.parent);
+            }
+        }
+    }
+
+    // If we're starting or ending at a border, count it
+    if req.start.dist_along() == Distance::ZERO {
+        // TODO src_i and dst_i may not work for pedestrians on contraflow sidewalks
+        let i = map.get_l(req.start.lane()).src_i;
+        if map.get_i(i).is_border() {
+            intersections.push(i);
+        }
+    } else {
+        let i = map.get_l(req.end.lane()).dst_i;
+        if map.get_i(i).is_border() {
+            intersections.push(i);
+        }
+    }
+    (roads, intersections)
+}
@@ -1,5 +1,7 @@
 use geom::Pt2D;
-use map_model::{BuildingID, IntersectionID, Map, PathConstraints, PathRequest, Position};
+use map_model::{
+    BuildingID, IdMigration, IntersectionID, Map, PathConstraints, PathRequest, Position,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::TripMode;
@@ -23,6 +25,21 @@ impl TripEndpoint {
         }
     }
 
+    /// Translate this endpoint from an old version of a map to a new one. Returns None if the
+    /// building, border, or lane no longer exists.
+    pub fn migrate(self, migration: &IdMigration, new: &Map) -> Option<TripEndpoint> {
+        match self {
+            TripEndpoint::Building(b) => migration.b(b).map(TripEndpoint::Building),
+            TripEndpoint::Border(i) => migration
+                .i(i)
+                .filter(|i| new.get_i(*i).is_border())
+                .map(TripEndpoint::Border),
+            TripEndpoint::SuddenlyAppear(pos) => {
+                migration.pos(pos, new).map(TripEndpoint::SuddenlyAppear)
+            }
+        }
+    }
+
     /// Figure out a single PathRequest that goes between two TripEndpoints. Assume a single mode
     /// the entire time -- no walking to a car before driving, for instance. The result probably
     /// won't be exactly what would happen on a real trip between the endpoints because of this
@@ -37,7 +54,9 @@ impl TripEndpoint {
         let end = to.pos(mode, false, map)?;
         Some(match mode {
             TripMode::Walk | TripMode::Transit => PathRequest::walking(start, end),
-            TripMode::Bike => PathRequest::vehicle(start, end, PathConstraints::Bike),
+            TripMode::Bike | TripMode::Micromobility => {
+                PathRequest::vehicle(start, end, PathConstraints::Bike)
+            }
             // Only cars leaving from a building might turn out from the driveway in a special way
             TripMode::Drive => {
                 if matches!(from, TripEndpoint::Building(_)) {
@@ -52,7 +71,7 @@ impl TripEndpoint {
     fn pos(self, mode: TripMode, from: bool, map: &Map) -> Option<Position> {
         match mode {
             TripMode::Walk | TripMode::Transit => self.sidewalk_pos(map, from),
-            TripMode::Drive | TripMode::Bike => {
+            TripMode::Drive | TripMode::Bike | TripMode::Micromobility => {
                 let constraints = mode.to_constraints();
                 if from {
                     match self {
@@ -16,18 +16,25 @@ use abstutil::{deserialize_usize, serialize_usize};
 use map_model::PathConstraints;
 
 pub use self::borders::{MapBorder, MapBorders};
-pub use self::counts::TrafficCounts;
+pub use self::calibrate::{
+    calibrate, geh, Calibration, CalibrationConfig, CountComparison, CountLocation,
+};
+pub use self::counts::{MovementCounts, TrafficCounts};
 pub use self::endpoint::TripEndpoint;
 pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint};
 pub use self::modifier::ScenarioModifier;
 pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
+pub use self::tmc::{import_tmc, TmcLayout, TmcLocation};
 
 mod borders;
+mod calibrate;
 mod counts;
 mod endpoint;
 mod external;
+pub mod matsim;
 mod modifier;
 mod scenario;
+mod tmc;
 
 /// How does a trip primarily happen?
 ///
@@ -39,6 +46,8 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    /// Walk to a shared bike or scooter, ride it, and leave it near the destination
+    Micromobility,
 }
 
 impl TripMode {
@@ -48,6 +57,7 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::Micromobility,
         ]
     }
 
@@ -57,6 +67,7 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::Micromobility => "ride a shared bike or scooter",
         }
     }
 
@@ -67,6 +78,7 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::Micromobility => "riding a shared bike or scooter",
         }
     }
 
@@ -76,13 +88,14 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::Micromobility => "Shared bike",
         }
     }
 
     pub fn to_constraints(self) -> PathConstraints {
         match self {
             TripMode::Walk => PathConstraints::Pedestrian,
-            TripMode::Bike => PathConstraints::Bike,
+            TripMode::Bike | TripMode::Micromobility => PathConstraints::Bike,
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
             TripMode::Drive => PathConstraints::Car,
@@ -0,0 +1,400 @@
+//! Converts to and from MATSim (https://www.matsim.org) plans and networks, so the same population
+//! can be simulated by both tools.
+//!
+//! A/B Street can't reproject coordinates, so all coordinates are expected to be (and are written
+//! as) WGS84 longitude and latitude, with `x` as longitude. Convert MATSim inputs to EPSG:4326
+//! first.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use geom::{LonLat, Time};
+use map_model::{Direction, LaneType, Map};
+
+use crate::{
+    ExternalPerson, ExternalTrip, ExternalTripEndpoint, PersonSpec, Scenario, TripMode, TripPurpose,
+};
+
+/// MATSim needs a flow capacity for every link. A/B Street doesn't model this, so assume a
+/// typical value per driving lane.
+const VEHICLES_PER_LANE_PER_HOUR: usize = 1800;
+
+/// Reads the selected plan of everybody in a MATSim plans.xml file. Activities are snapped to
+/// buildings or borders the same way as `ExternalPerson::import`. Legs with modes that don't map
+/// to a `TripMode` are skipped.
+pub fn import_plans(map: &Map, path: &str, skip_problems: bool) -> Result<Vec<PersonSpec>> {
+    let bytes = abstio::slurp_file(path)?;
+    let raw_string = std::str::from_utf8(&bytes)?;
+    let people = parse_plans(raw_string, skip_problems)?;
+    ExternalPerson::import(map, people, skip_problems)
+}
+
+/// If `skip_problems` is true, people with activities that can't be located are skipped.
+/// Otherwise, they're an error.
+fn parse_plans(raw_string: &str, skip_problems: bool) -> Result<Vec<ExternalPerson>> {
+    let tree = roxmltree::Document::parse(raw_string)?;
+    let mut people = Vec::new();
+    'people: for person in tree.descendants().filter(|n| n.has_tag_name("person")) {
+        let id = person.attribute("id").unwrap_or("?");
+        let mut plans = person.children().filter(|n| n.has_tag_name("plan"));
+        let plan = match plans
+            .clone()
+            .find(|n| n.attribute("selected") == Some("yes"))
+            .or_else(|| plans.next())
+        {
+            Some(plan) => plan,
+            None => {
+                warn!("Person {} has no plan", id);
+                continue;
+            }
+        };
+
+        let mut trips = Vec::new();
+        // The last real activity, and the time somebody left it
+        let mut prev: Option<(LonLat, Option<Time>)> = None;
+        // MATSim splits trips into legs, joined by "interaction" activities. These are the legs
+        // since the last real activity, as (mode, departure time).
+        let mut legs: Vec<(Option<TripMode>, Option<Time>)> = Vec::new();
+        for node in plan.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "act" | "activity" => {
+                    let act_type = node.attribute("type").unwrap_or("");
+                    if act_type.ends_with("interaction") {
+                        continue;
+                    }
+                    let pos = match (node.attribute("x"), node.attribute("y")) {
+                        (Some(x), Some(y)) => LonLat::new(x.parse::<f64>()?, y.parse::<f64>()?),
+                        _ => {
+                            if skip_problems {
+                                warn!("Skipping person {}: an activity has no coordinates", id);
+                                continue 'people;
+                            }
+                            bail!("Person {} has an activity without coordinates", id);
+                        }
+                    };
+                    if let Some((from, left)) = prev {
+                        if !legs.is_empty() {
+                            match main_mode(&legs) {
+                                Some(mode) => match legs[0].1.or(left) {
+                                    Some(departure) => {
+                                        trips.push(ExternalTrip {
+                                            departure,
+                                            origin: ExternalTripEndpoint::Position(from),
+                                            destination: ExternalTripEndpoint::Position(pos),
+                                            mode,
+                                            purpose: activity_to_purpose(act_type),
+                                        });
+                                    }
+                                    None => {
+                                        warn!("Person {} has a leg without a departure time", id);
+                                    }
+                                },
+                                None => {
+                                    warn!("Person {} has a leg with an unsupported mode", id);
+                                }
+                            }
+                        }
+                    }
+                    legs.clear();
+                    let end_time = node.attribute("end_time").map(Time::parse).transpose()?;
+                    prev = Some((pos, end_time));
+                }
+                "leg" => {
+                    legs.push((
+                        mode_from_matsim(node.attribute("mode").unwrap_or("")),
+                        node.attribute("dep_time").map(Time::parse).transpose()?,
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if !trips.is_empty() {
+            people.push(ExternalPerson { trips });
+        }
+    }
+    Ok(people)
+}
+
+/// Writes every person in a scenario as a MATSim plans.xml file, with activities at the center of
+/// each trip endpoint.
+pub fn export_plans(scenario: &Scenario, map: &Map) -> String {
+    let gps_bounds = map.get_gps_bounds();
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str(
+        "<!DOCTYPE population SYSTEM \"http://www.matsim.org/files/dtd/population_v6.dtd\">\n",
+    );
+    out.push_str("<population>\n");
+    for (idx, person) in scenario.people.iter().enumerate() {
+        if person.trips.is_empty() {
+            continue;
+        }
+        writeln!(out, "  <person id=\"{}\">", idx).unwrap();
+        out.push_str("    <plan selected=\"yes\">\n");
+        // The first activity doesn't have a purpose; assume people start at home
+        let mut act_type = "home";
+        for trip in &person.trips {
+            let pt = trip.origin.pt(map).to_gps(gps_bounds);
+            writeln!(
+                out,
+                "      <act type=\"{}\" x=\"{}\" y=\"{}\" end_time=\"{}\" />",
+                act_type,
+                pt.x(),
+                pt.y(),
+                matsim_time(trip.depart)
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "      <leg mode=\"{}\" dep_time=\"{}\" />",
+                mode_to_matsim(trip.mode),
+                matsim_time(trip.depart)
+            )
+            .unwrap();
+            act_type = purpose_to_activity(trip.purpose);
+        }
+        let pt = person
+            .trips
+            .last()
+            .unwrap()
+            .destination
+            .pt(map)
+            .to_gps(gps_bounds);
+        writeln!(
+            out,
+            "      <act type=\"{}\" x=\"{}\" y=\"{}\" />",
+            act_type,
+            pt.x(),
+            pt.y()
+        )
+        .unwrap();
+        out.push_str("    </plan>\n");
+        out.push_str("  </person>\n");
+    }
+    out.push_str("</population>\n");
+    out
+}
+
+/// Writes the map as a MATSim network.xml file. Every intersection becomes a node, and every
+/// direction of a road with some vehicle lanes becomes a link.
+pub fn export_network(map: &Map) -> String {
+    let gps_bounds = map.get_gps_bounds();
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<!DOCTYPE network SYSTEM \"http://www.matsim.org/files/dtd/network_v2.dtd\">\n");
+    writeln!(out, "<network name=\"{}\">", map.get_name().as_filename()).unwrap();
+
+    out.push_str("  <nodes>\n");
+    for i in map.all_intersections() {
+        let pt = i.polygon.center().to_gps(gps_bounds);
+        writeln!(
+            out,
+            "    <node id=\"{}\" x=\"{}\" y=\"{}\" />",
+            i.id.0,
+            pt.x(),
+            pt.y()
+        )
+        .unwrap();
+    }
+    out.push_str("  </nodes>\n");
+
+    out.push_str("  <links>\n");
+    for r in map.all_roads() {
+        for dir in [Direction::Fwd, Direction::Back] {
+            let lanes: Vec<LaneType> = r
+                .lanes
+                .iter()
+                .filter(|l| l.dir == dir)
+                .map(|l| l.lane_type)
+                .collect();
+            let mut modes = Vec::new();
+            if lanes.contains(&LaneType::Driving) {
+                modes.push("car");
+            }
+            if lanes.contains(&LaneType::Driving) || lanes.contains(&LaneType::Bus) {
+                modes.push("pt");
+            }
+            if lanes.contains(&LaneType::Driving) || lanes.contains(&LaneType::Biking) {
+                modes.push("bike");
+            }
+            if modes.is_empty() {
+                continue;
+            }
+            let num_lanes = lanes
+                .iter()
+                .filter(|lt| **lt == LaneType::Driving)
+                .count()
+                .max(1);
+            let (from, to) = if dir == Direction::Fwd {
+                (r.src_i, r.dst_i)
+            } else {
+                (r.dst_i, r.src_i)
+            };
+            writeln!(
+                out,
+                "    <link id=\"{}_{}\" from=\"{}\" to=\"{}\" length=\"{}\" freespeed=\"{}\" \
+                 capacity=\"{}\" permlanes=\"{}\" modes=\"{}\" />",
+                r.id.0,
+                if dir == Direction::Fwd { "fwd" } else { "back" },
+                from.0,
+                to.0,
+                r.length().inner_meters(),
+                r.speed_limit.inner_meters_per_second(),
+                num_lanes * VEHICLES_PER_LANE_PER_HOUR,
+                num_lanes,
+                modes.join(",")
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("  </links>\n");
+    out.push_str("</network>\n");
+    out
+}
+
+/// A trip made of several legs counts as the "biggest" mode used, ignoring walking to and from
+/// it. None if any leg uses an unknown mode.
+fn main_mode(legs: &[(Option<TripMode>, Option<Time>)]) -> Option<TripMode> {
+    let mut modes = Vec::new();
+    for (mode, _) in legs {
+        modes.push((*mode)?);
+    }
+    for mode in [
+        TripMode::Transit,
+        TripMode::Drive,
+        TripMode::Micromobility,
+        TripMode::Bike,
+    ] {
+        if modes.contains(&mode) {
+            return Some(mode);
+        }
+    }
+    Some(TripMode::Walk)
+}
+
+fn mode_from_matsim(mode: &str) -> Option<TripMode> {
+    match mode {
+        "car" | "ride" => Some(TripMode::Drive),
+        "pt" | "bus" | "train" | "tram" | "subway" => Some(TripMode::Transit),
+        "bike" | "bicycle" => Some(TripMode::Bike),
+        "walk" | "transit_walk" | "non_network_walk" => Some(TripMode::Walk),
+        "scooter" | "bikeshare" => Some(TripMode::Micromobility),
+        _ => None,
+    }
+}
+
+fn mode_to_matsim(mode: TripMode) -> &'static str {
+    match mode {
+        TripMode::Walk => "walk",
+        TripMode::Bike => "bike",
+        TripMode::Transit => "pt",
+        TripMode::Drive => "car",
+        TripMode::Micromobility => "bikeshare",
+    }
+}
+
+/// Activity types aren't standardized in MATSim, so this just recognizes common names.
+fn activity_to_purpose(act: &str) -> TripPurpose {
+    let act = act.to_lowercase();
+    // Types often have a duration suffix, like "work_28800"
+    let act = act.split('_').next().unwrap();
+    match act {
+        "home" | "h" => TripPurpose::Home,
+        "work" | "w" => TripPurpose::Work,
+        "education" | "school" | "university" | "e" => TripPurpose::School,
+        "shop" | "shopping" | "s" => TripPurpose::Shopping,
+        "leisure" | "l" => TripPurpose::Recreation,
+        "eat" | "meal" | "restaurant" => TripPurpose::Meal,
+        "visit" | "social" => TripPurpose::Social,
+        "escort" | "dropoff" | "pickup" => TripPurpose::Escort,
+        "medical" | "doctor" => TripPurpose::Medical,
+        _ => TripPurpose::PersonalBusiness,
+    }
+}
+
+fn purpose_to_activity(purpose: TripPurpose) -> &'static str {
+    match purpose {
+        TripPurpose::Home => "home",
+        TripPurpose::Work => "work",
+        TripPurpose::School => "education",
+        TripPurpose::Escort => "escort",
+        TripPurpose::PersonalBusiness => "other",
+        TripPurpose::Shopping => "shop",
+        TripPurpose::Meal => "eat",
+        TripPurpose::Social => "visit",
+        TripPurpose::Recreation => "leisure",
+        TripPurpose::Medical => "medical",
+        TripPurpose::ParkAndRideTransfer => "other",
+    }
+}
+
+/// MATSim times are HH:MM:SS, with hours continuing past midnight
+fn matsim_time(time: Time) -> String {
+    let secs = time.inner_seconds().round() as usize;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<population>
+  <person id="1">
+    <plan selected="no">
+      <act type="home" x="-122.30" y="47.60" end_time="07:00:00" />
+      <leg mode="walk" />
+      <act type="work" x="-122.31" y="47.61" />
+    </plan>
+    <plan selected="yes">
+      <act type="home" x="-122.30" y="47.60" end_time="08:00:00" />
+      <leg mode="car" />
+      <act type="work_28800" x="-122.31" y="47.61" end_time="17:00:00" />
+      <leg mode="transit_walk" />
+      <act type="pt interaction" x="-122.305" y="47.605" />
+      <leg mode="pt" dep_time="17:05:00" />
+      <act type="pt interaction" x="-122.301" y="47.601" />
+      <leg mode="transit_walk" />
+      <act type="home" x="-122.30" y="47.60" />
+    </plan>
+  </person>
+  <person id="2">
+    <plan selected="yes">
+      <act type="home" x="-122.30" y="47.60" end_time="09:00:00" />
+      <leg mode="bike" />
+      <act type="shop" link="123" />
+    </plan>
+  </person>
+</population>"#;
+
+    #[test]
+    fn test_parse_plans() {
+        let people = parse_plans(PLANS, true).unwrap();
+        // The second person is skipped
+        assert_eq!(people.len(), 1);
+        let trips = &people[0].trips;
+        assert_eq!(trips.len(), 2);
+
+        // Only the selected plan is used
+        assert_eq!(trips[0].departure, Time::parse("08:00:00").unwrap());
+        assert_eq!(trips[0].mode, TripMode::Drive);
+        assert!(matches!(trips[0].purpose, TripPurpose::Work));
+        assert!(matches!(
+            trips[0].destination,
+            ExternalTripEndpoint::Position(pt) if pt == LonLat::new(-122.31, 47.61)
+        ));
+
+        // The legs joined by interactions are one trip, departing when the first leg does
+        assert_eq!(trips[1].departure, Time::parse("17:00:00").unwrap());
+        assert_eq!(trips[1].mode, TripMode::Transit);
+        assert!(matches!(trips[1].purpose, TripPurpose::Home));
+
+        assert!(parse_plans(PLANS, false).is_err());
+    }
+}
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use abstio::{CityName, MapName};
 use abstutil::prettyprint_usize;
 use geom::Time;
-use map_model::Map;
+use map_model::{IdMigration, Map};
 
 use crate::{OrigPersonID, TripEndpoint, TripMode};
 
@@ -139,6 +139,38 @@ impl Scenario {
         self
     }
 
+    /// Translate a scenario made for an old version of a map to a new version. Anybody with a
+    /// trip starting or ending somewhere that can't be matched in the new map is removed. Returns
+    /// a description of every removal.
+    pub fn migrate(&mut self, migration: &IdMigration, new: &Map) -> Vec<String> {
+        let mut problems = Vec::new();
+        self.map_name = new.get_name().clone();
+        let mut people = Vec::new();
+        'PERSON: for mut person in self.people.drain(..) {
+            for trip in &mut person.trips {
+                match (
+                    trip.origin.migrate(migration, new),
+                    trip.destination.migrate(migration, new),
+                ) {
+                    (Some(origin), Some(destination)) => {
+                        trip.origin = origin;
+                        trip.destination = destination;
+                    }
+                    _ => {
+                        problems.push(format!(
+                            "Person ({:?}) has a trip from {:?} to {:?} that no longer exists",
+                            person.orig_id, trip.origin, trip.destination
+                        ));
+                        continue 'PERSON;
+                    }
+                }
+            }
+            people.push(person);
+        }
+        self.people = people;
+        problems
+    }
+
     pub fn all_trips(&self) -> impl Iterator<Item = &IndividTrip> {
         self.people.iter().flat_map(|p| p.trips.iter())
     }
@@ -0,0 +1,307 @@
+//! Imports real-world turning movement counts (TMCs) from CSV files. Count vendors and open data
+//! portals use many slightly different layouts, but they almost always name movements by the
+//! direction of travel approaching the intersection and the turn, like "NBL" or "Northbound Left".
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use abstutil::Counter;
+use geom::{Distance, Duration, FindClosest, LonLat, Time};
+use map_model::{osm, IntersectionID, Map, MovementID};
+
+use crate::{MovementCounts, TrafficCounts};
+
+/// Positions further than this from any intersection don't match
+const MAX_DIST_TO_INTERSECTION: Distance = Distance::const_meters(50.0);
+
+/// How a CSV file of turning movement counts is organized
+pub enum TmcLayout {
+    /// One row per movement and time bin, with columns `start_time`, `movement`, `count`, and
+    /// either `osm_node_id` or `longitude` and `latitude` to find the intersection. Files can cover
+    /// many intersections.
+    Long,
+    /// One row per time bin for a single intersection, with a `start_time` column and one column
+    /// per movement. Other columns are ignored. This is how most count vendors export data.
+    Wide(TmcLocation),
+}
+
+/// Where a count was taken
+pub enum TmcLocation {
+    OsmNode(i64),
+    Position(LonLat),
+}
+
+/// Reads turning movement counts from a CSV file. Times can be like "07:15", "7:15 AM", or
+/// "2021-05-04 07:15:00"; the date is ignored. Also fills out `per_intersection` with the total of
+/// all movements. Returns a summary of anything that couldn't be matched to the map.
+pub fn import_tmc(
+    map: &Map,
+    path: &str,
+    layout: TmcLayout,
+    bin_size: Duration,
+) -> Result<(TrafficCounts, Vec<String>)> {
+    let bytes = abstio::slurp_file(path)?;
+    let mut reader = csv::Reader::from_reader(&bytes[..]);
+    let mut matcher = Matcher::new(map);
+    let mut counts = TrafficCounts {
+        map: map.get_name().clone(),
+        description: abstutil::basename(path),
+        per_movement: MovementCounts::new(bin_size),
+        ..Default::default()
+    };
+
+    match layout {
+        TmcLayout::Long => {
+            for rec in reader.deserialize() {
+                let rec: LongRecord = rec?;
+                let location = match (rec.osm_node_id, rec.longitude, rec.latitude) {
+                    (Some(id), _, _) => TmcLocation::OsmNode(id),
+                    (None, Some(lon), Some(lat)) => TmcLocation::Position(LonLat::new(lon, lat)),
+                    _ => bail!("A row doesn't have osm_node_id or longitude and latitude"),
+                };
+                let time = parse_start_time(&rec.start_time)?;
+                if let Some(movement) = matcher.find(&location, &rec.movement) {
+                    counts.per_movement.record(movement, time, rec.count);
+                }
+            }
+        }
+        TmcLayout::Wide(location) => {
+            let headers = reader.headers()?.clone();
+            let time_col = headers
+                .iter()
+                .position(|h| h.trim() == "start_time")
+                .ok_or_else(|| anyhow!("No start_time column"))?;
+            // Only the columns that look like movements
+            let movement_cols: Vec<(usize, String)> = headers
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, h)| normalize_movement(h).map(|_| (idx, h.to_string())))
+                .collect();
+            if movement_cols.is_empty() {
+                bail!("No columns describe movements, like NBL or Northbound Left");
+            }
+            for rec in reader.records() {
+                let rec = rec?;
+                let time = parse_start_time(&rec[time_col])?;
+                for (idx, label) in &movement_cols {
+                    let value = rec[*idx].trim();
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let count = value.parse::<usize>()?;
+                    if let Some(movement) = matcher.find(&location, label) {
+                        counts.per_movement.record(movement, time, count);
+                    }
+                }
+            }
+        }
+    }
+
+    for (movement, cnt) in counts.per_movement.totals().consume() {
+        counts.per_intersection.add(movement.parent, cnt);
+    }
+    let problems = matcher
+        .problems
+        .consume()
+        .into_iter()
+        .map(|(problem, cnt)| format!("{} {}", cnt, problem))
+        .collect();
+    Ok((counts, problems))
+}
+
+#[derive(Deserialize)]
+struct LongRecord {
+    start_time: String,
+    movement: String,
+    count: usize,
+    osm_node_id: Option<i64>,
+    longitude: Option<f64>,
+    latitude: Option<f64>,
+}
+
+struct Matcher<'a> {
+    map: &'a Map,
+    closest: FindClosest<IntersectionID>,
+    by_osm: HashMap<osm::NodeID, IntersectionID>,
+    /// Per intersection, the movements by name
+    names: BTreeMap<IntersectionID, BTreeMap<String, Vec<MovementID>>>,
+    problems: Counter<&'static str>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(map: &'a Map) -> Matcher<'a> {
+        let mut closest = FindClosest::new(map.get_bounds());
+        let mut by_osm = HashMap::new();
+        for i in map.all_intersections() {
+            closest.add(i.id, i.polygon.points());
+            by_osm.insert(i.orig_id, i.id);
+        }
+        Matcher {
+            map,
+            closest,
+            by_osm,
+            names: BTreeMap::new(),
+            problems: Counter::new(),
+        }
+    }
+
+    fn find(&mut self, location: &TmcLocation, label: &str) -> Option<MovementID> {
+        let i = match location {
+            TmcLocation::OsmNode(id) => self.by_osm.get(&osm::NodeID(*id)).cloned(),
+            TmcLocation::Position(gps) => {
+                let pt = gps.to_pt(self.map.get_gps_bounds());
+                self.closest
+                    .closest_pt(pt, MAX_DIST_TO_INTERSECTION)
+                    .map(|(i, _)| i)
+            }
+        };
+        let i = match i {
+            Some(i) => i,
+            None => {
+                self.problems.inc("counts not near any intersection");
+                return None;
+            }
+        };
+        let name = match normalize_movement(label) {
+            Some(name) => name,
+            None => {
+                self.problems.inc("counts with an unknown movement name");
+                return None;
+            }
+        };
+
+        let map = self.map;
+        let names = self.names.entry(i).or_insert_with(|| {
+            let mut names = BTreeMap::new();
+            for movement in map.get_i(i).movements.values() {
+                if let Some(name) = map_model::gmns::movement_txt_id(map, movement) {
+                    names.entry(name).or_insert_with(Vec::new).push(movement.id);
+                }
+            }
+            names
+        });
+        match names.get(&name) {
+            Some(movements) => {
+                if movements.len() > 1 {
+                    // Complex intersections can have two movements with the same name. Just
+                    // pick one.
+                    self.problems
+                        .inc("counts for movements that match more than one movement");
+                }
+                Some(movements[0])
+            }
+            None => {
+                self.problems
+                    .inc("counts for movements that don't exist at the intersection");
+                None
+            }
+        }
+    }
+}
+
+/// Transforms labels like "NBL", "NB Thru", or "Southbound Right" into a name like "NBL". Returns
+/// None if the label doesn't look like a movement.
+fn normalize_movement(label: &str) -> Option<String> {
+    let mut x = label.trim().to_lowercase();
+    for (from, to) in [
+        ("northbound", "nb"),
+        ("southbound", "sb"),
+        ("eastbound", "eb"),
+        ("westbound", "wb"),
+        ("u-turn", "u"),
+        ("u turn", "u"),
+        ("uturn", "u"),
+        ("through", "t"),
+        ("thru", "t"),
+        ("left", "l"),
+        ("right", "r"),
+    ] {
+        x = x.replace(from, to);
+    }
+    let mut x: String = x
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_uppercase();
+    if x.len() == 4 && x.ends_with("TH") {
+        x.pop();
+    }
+    if x.len() == 3
+        && ["NB", "SB", "EB", "WB"].contains(&&x[0..2])
+        && ["L", "T", "R", "U"].contains(&&x[2..3])
+    {
+        Some(x)
+    } else {
+        None
+    }
+}
+
+fn parse_start_time(raw: &str) -> Result<Time> {
+    let parts: Vec<&str> = raw.split_whitespace().collect();
+    let (time, ampm) = match parts.as_slice() {
+        [.., time, ampm] if ampm.eq_ignore_ascii_case("am") || ampm.eq_ignore_ascii_case("pm") => {
+            (*time, Some(ampm.eq_ignore_ascii_case("pm")))
+        }
+        [.., time] => (*time, None),
+        [] => bail!("Empty start_time"),
+    };
+    let mut time = Time::parse(time)?;
+    if let Some(pm) = ampm {
+        let hours = time.get_hours();
+        if hours == 12 && !pm {
+            time = time - Duration::hours(12);
+        } else if hours < 12 && pm {
+            time = time + Duration::hours(12);
+        }
+    }
+    Ok(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_movement() {
+        for (input, expected) in [
+            ("NBL", Some("NBL")),
+            ("nbl", Some("NBL")),
+            ("NB Thru", Some("NBT")),
+            ("SB Through", Some("SBT")),
+            ("SBTH", Some("SBT")),
+            ("Southbound Right", Some("SBR")),
+            ("Eastbound Left", Some("EBL")),
+            (" WB U-Turn ", Some("WBU")),
+            ("Westbound U Turn", Some("WBU")),
+            ("EB-R", Some("EBR")),
+            ("Total", None),
+            ("NB", None),
+            ("Northbound Pedestrians", None),
+            ("XBL", None),
+        ] {
+            assert_eq!(
+                normalize_movement(input),
+                expected.map(|x| x.to_string()),
+                "normalizing {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_start_time() {
+        let t = |h, m| Time::START_OF_DAY + Duration::hours(h) + Duration::minutes(m);
+        assert_eq!(parse_start_time("07:15").unwrap(), t(7, 15));
+        assert_eq!(parse_start_time("7:15 AM").unwrap(), t(7, 15));
+        assert_eq!(parse_start_time("7:15 pm").unwrap(), t(19, 15));
+        assert_eq!(parse_start_time("12:30 PM").unwrap(), t(12, 30));
+        assert_eq!(parse_start_time("12:30 AM").unwrap(), t(0, 30));
+        // The date is ignored
+        assert_eq!(parse_start_time("2021-05-04 07:15:00").unwrap(), t(7, 15));
+        assert_eq!(parse_start_time("5/4/2021 4:45 PM").unwrap(), t(16, 45));
+        assert!(parse_start_time("").is_err());
+        assert!(parse_start_time("noon").is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! Simulate a few small scenarios to completion and compare summary metrics against baselines
+//! under version control. The simulation is deterministic, so any difference means behavior
+//! changed; the tolerances just keep harmless drift from failing the test. To record the first
+//! baselines or after an intended change, run with `--update-golden-metrics` and commit the new
+//! baselines. A missing baseline file is an error otherwise.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use anyhow::Result;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use abstio::MapName;
+use abstutil::{deserialize_btreemap, prettyprint_usize, serialize_btreemap, Timer};
+use geom::{Duration, Time};
+use map_model::raw::OriginalRoad;
+use map_model::{osm, IntersectionID, Map};
+use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
+
+const BASELINES: &str = "../tests/goldenfiles/golden_metrics.json";
+
+/// Trip counts can differ by this fraction, or at least one trip
+const COUNT_TOLERANCE: f64 = 0.01;
+/// Mean trip times and intersection delays can differ by this fraction...
+const TIME_TOLERANCE: f64 = 0.05;
+/// ... or at least this many seconds
+const MIN_TIME_TOLERANCE: f64 = 5.0;
+
+#[derive(Serialize, Deserialize)]
+struct GoldenMetrics {
+    map: String,
+    scenario: String,
+    finished_trips: usize,
+    cancelled_trips: usize,
+    /// Only finished trips count. Use f64 seconds, since a serialized Duration has a low cap.
+    mean_trip_time_seconds: BTreeMap<TripMode, f64>,
+    /// Agents still on the map a few hours after the scenario should've ended
+    gridlocked_agents: usize,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    intersection_delays: BTreeMap<IntersectionKey, IntersectionDelay>,
+}
+
+/// IntersectionIDs change whenever the importer does, so identify intersections by OSM IDs
+/// instead. Intersections made by the importer can share an OSM node, so the roads connected to
+/// each one tell them apart.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+struct IntersectionKey {
+    node: osm::NodeID,
+    roads: Vec<OriginalRoad>,
+}
+
+impl IntersectionKey {
+    fn new(map: &Map, i: IntersectionID) -> IntersectionKey {
+        let i = map.get_i(i);
+        let mut roads: Vec<OriginalRoad> = i.roads.iter().map(|r| map.get_r(*r).orig_id).collect();
+        roads.sort();
+        IntersectionKey {
+            node: i.orig_id,
+            roads,
+        }
+    }
+}
+
+impl fmt::Display for IntersectionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (connecting", self.node)?;
+        for r in &self.roads {
+            write!(f, " {}", r)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct IntersectionDelay {
+    agents: usize,
+    mean_delay_seconds: f64,
+}
+
+pub fn test_golden_metrics() -> Result<()> {
+    let update = std::env::args().any(|arg| arg == "--update-golden-metrics");
+    let path = abstio::path(BASELINES);
+    // Don't silently record whatever the current behavior is
+    if !update && !abstio::file_exists(&path) {
+        anyhow::bail!(
+            "{} is missing. Run with --update-golden-metrics to record baselines, then commit them",
+            path
+        );
+    }
+    let mut timer = Timer::new("check golden metrics");
+
+    let mut results = Vec::new();
+    for name in [
+        "divided_highway_split",
+        "lane_selection",
+        "left_turn_and_bike_lane",
+        "multiple_left_turn_lanes",
+    ] {
+        let map = crate::import_map(abstio::path(format!("../tests/input/{}.osm", name)));
+        let scenario = border_to_border_trips(&map);
+        results.push(run_scenario(&map, scenario, &mut timer));
+    }
+    {
+        let map = Map::load_synchronously(MapName::seattle("montlake").path(), &mut timer);
+        let mut rng = sim::SimFlags::for_test("golden_metrics").make_rng();
+        let scenario = sim::ScenarioGenerator::proletariat_robot(&map, &mut rng, &mut timer);
+        results.push(run_scenario(&map, scenario, &mut timer));
+    }
+
+    if update {
+        abstio::write_json(path.clone(), &results);
+        println!("Recorded new golden metrics in {}", path);
+        return Ok(());
+    }
+
+    let baselines: Vec<GoldenMetrics> = abstio::maybe_read_json(path, &mut timer)?;
+    let mut problems = Vec::new();
+    for current in &results {
+        match baselines
+            .iter()
+            .find(|x| x.map == current.map && x.scenario == current.scenario)
+        {
+            Some(baseline) => {
+                for problem in compare(baseline, current) {
+                    problems.push(format!(
+                        "{} / {}: {}",
+                        current.map, current.scenario, problem
+                    ));
+                }
+            }
+            None => {
+                problems.push(format!(
+                    "{} / {} has no baseline",
+                    current.map, current.scenario
+                ));
+            }
+        }
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        anyhow::bail!(
+            "{} golden metrics changed. If this is expected, rerun with --update-golden-metrics",
+            problems.len()
+        );
+    }
+    Ok(())
+}
+
+/// Trips between random pairs of borders, with a mix of modes. Trips that can't happen, like
+/// driving out of a border that's only a footway, are just cancelled, which is fine here.
+fn border_to_border_trips(map: &Map) -> Scenario {
+    let mut rng = sim::SimFlags::for_test("golden_metrics").make_rng();
+    let incoming = map.all_incoming_borders();
+    let outgoing = map.all_outgoing_borders();
+    let mut scenario = Scenario::empty(map, "border to border");
+    for idx in 0..300 {
+        let (from, to) = match (incoming.choose(&mut rng), outgoing.choose(&mut rng)) {
+            (Some(from), Some(to)) if from.id != to.id => (from.id, to.id),
+            _ => continue,
+        };
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![IndividTrip::new(
+                // Spread trips over the first 10 minutes, so there's some congestion
+                Time::START_OF_DAY + Duration::seconds(2.0 * idx as f64),
+                TripPurpose::Shopping,
+                TripEndpoint::Border(from),
+                TripEndpoint::Border(to),
+                [TripMode::Drive, TripMode::Bike, TripMode::Walk][idx % 3],
+            )],
+        });
+    }
+    scenario
+}
+
+fn run_scenario(map: &Map, scenario: Scenario, timer: &mut Timer) -> GoldenMetrics {
+    timer.start(format!(
+        "golden metrics for {} / {}",
+        scenario.map_name.describe(),
+        scenario.scenario_name
+    ));
+    let mut opts = sim::SimOptions::new("golden_metrics");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(map, opts);
+    // Bit of an abuse of this, but just need to fix the rng seed.
+    let mut rng = sim::SimFlags::for_test("golden_metrics").make_rng();
+    sim.instantiate(&scenario, map, &mut rng, timer);
+    // Same as prebaking: run a few hours past the end of the day, in case trips start late
+    sim.timed_step(
+        map,
+        sim.get_end_of_day() - Time::START_OF_DAY + Duration::hours(3),
+        &mut None,
+        timer,
+    );
+    timer.stop(format!(
+        "golden metrics for {} / {}",
+        scenario.map_name.describe(),
+        scenario.scenario_name
+    ));
+
+    let analytics = sim.get_analytics();
+    let mut finished_trips = 0;
+    let mut cancelled_trips = 0;
+    let mut trip_times: BTreeMap<TripMode, (usize, f64)> = BTreeMap::new();
+    for (_, _, mode, maybe_duration) in &analytics.finished_trips {
+        if let Some(dt) = maybe_duration {
+            finished_trips += 1;
+            let entry = trip_times.entry(*mode).or_insert((0, 0.0));
+            entry.0 += 1;
+            entry.1 += dt.inner_seconds();
+        } else {
+            cancelled_trips += 1;
+        }
+    }
+
+    let mut intersection_delays = BTreeMap::new();
+    for (i, delays) in &analytics.intersection_delays {
+        if delays.is_empty() {
+            continue;
+        }
+        let total: f64 = delays.iter().map(|(_, _, dt, _)| dt.inner_seconds()).sum();
+        intersection_delays.insert(
+            IntersectionKey::new(map, *i),
+            IntersectionDelay {
+                agents: delays.len(),
+                mean_delay_seconds: total / delays.len() as f64,
+            },
+        );
+    }
+
+    GoldenMetrics {
+        map: scenario.map_name.describe(),
+        scenario: scenario.scenario_name,
+        finished_trips,
+        cancelled_trips,
+        mean_trip_time_seconds: trip_times
+            .into_iter()
+            .map(|(mode, (count, total))| (mode, total / count as f64))
+            .collect(),
+        gridlocked_agents: if sim.is_done() {
+            0
+        } else {
+            sim.num_agents().sum()
+        },
+        intersection_delays,
+    }
+}
+
+/// Describe every metric that changed by more than the tolerance
+fn compare(baseline: &GoldenMetrics, current: &GoldenMetrics) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut check_count = |label: String, before: usize, after: usize| {
+        if !within(before as f64, after as f64, COUNT_TOLERANCE, 1.0) {
+            problems.push(format!(
+                "{} went from {} to {}",
+                label,
+                prettyprint_usize(before),
+                prettyprint_usize(after)
+            ));
+        }
+    };
+    check_count(
+        "finished trips".to_string(),
+        baseline.finished_trips,
+        current.finished_trips,
+    );
+    check_count(
+        "cancelled trips".to_string(),
+        baseline.cancelled_trips,
+        current.cancelled_trips,
+    );
+    for (i, before) in &baseline.intersection_delays {
+        let after = current.intersection_delays.get(i).map(|x| x.agents);
+        check_count(
+            format!("agents delayed at {}", i),
+            before.agents,
+            after.unwrap_or(0),
+        );
+    }
+
+    if current.gridlocked_agents != baseline.gridlocked_agents {
+        problems.push(format!(
+            "gridlocked agents went from {} to {}",
+            prettyprint_usize(baseline.gridlocked_agents),
+            prettyprint_usize(current.gridlocked_agents)
+        ));
+    }
+
+    let mut check_time =
+        |label: String, before: Option<f64>, after: Option<f64>| match (before, after) {
+            (Some(before), Some(after)) => {
+                if !within(before, after, TIME_TOLERANCE, MIN_TIME_TOLERANCE) {
+                    problems.push(format!(
+                        "{} went from {} to {}",
+                        label,
+                        Duration::seconds(before),
+                        Duration::seconds(after)
+                    ));
+                }
+            }
+            (Some(_), None) => {
+                problems.push(format!("{} is missing now", label));
+            }
+            (None, Some(_)) => {
+                problems.push(format!("{} is new", label));
+            }
+            (None, None) => {}
+        };
+    for mode in TripMode::all() {
+        check_time(
+            format!("mean {} trip time", mode.noun()),
+            baseline.mean_trip_time_seconds.get(&mode).cloned(),
+            current.mean_trip_time_seconds.get(&mode).cloned(),
+        );
+    }
+    let all_intersections: BTreeSet<&IntersectionKey> = baseline
+        .intersection_delays
+        .keys()
+        .chain(current.intersection_delays.keys())
+        .collect();
+    for i in all_intersections {
+        check_time(
+            format!("mean delay at {}", i),
+            baseline
+                .intersection_delays
+                .get(i)
+                .map(|x| x.mean_delay_seconds),
+            current
+                .intersection_delays
+                .get(i)
+                .map(|x| x.mean_delay_seconds),
+        );
+    }
+    problems
+}
+
+fn within(before: f64, after: f64, fraction: f64, min_diff: f64) -> bool {
+    (after - before).abs() <= (before.abs() * fraction).max(min_diff)
+}
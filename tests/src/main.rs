@@ -8,19 +8,40 @@ use rand::seq::SliceRandom;
 
 use abstio::{CityName, MapName};
 use abstutil::Timer;
-use geom::{Distance, Duration, Time};
-use map_model::{IntersectionID, Map, Perimeter};
+use geom::{Distance, Duration, Speed, Time};
+use map_model::connectivity::{
+    AccessibilityIndex, AccessibilityOptions, Mode, Spot, WalkingOptions,
+};
+use map_model::raw::RawMap;
+use map_model::{
+    AmenityType, Direction, IntersectionID, LaneType, Map, PathConstraints, Perimeter,
+};
 use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
 
+mod golden_metrics;
+mod migrate;
+mod optimize_filters;
+mod separate_sidewalks;
+
 fn main() -> Result<()> {
     abstutil::logger::setup();
     test_blockfinding()?;
     test_lane_changing(&import_map(abstio::path(
         "../tests/input/lane_selection.osm",
     )))?;
+    test_bikeshare(&import_map(abstio::path("../tests/input/bikeshare.osm")))?;
+    test_trip_export(&import_map(abstio::path("../tests/input/bikeshare.osm")))?;
+    test_accessibility(&import_map(abstio::path(
+        "../tests/input/accessibility.osm",
+    )))?;
     test_map_importer()?;
+    separate_sidewalks::test_separate_sidewalks()?;
+    test_gmns_round_trip()?;
+    migrate::test_id_migration()?;
+    optimize_filters::test_optimize_filters()?;
     check_proposals()?;
     smoke_test()?;
+    golden_metrics::test_golden_metrics()?;
     Ok(())
 }
 
@@ -48,33 +69,45 @@ fn test_map_importer() -> Result<()> {
 
 /// Run the contents of a .osm through the full map importer with default options.
 fn import_map(path: String) -> Map {
+    import_map_with_options(path, |_| {})
+}
+
+/// Like `import_map`, but change some of the default options first.
+fn import_map_with_options(path: String, modify: impl Fn(&mut convert_osm::Options)) -> Map {
     let mut timer = Timer::new("convert synthetic map");
+    let raw = import_raw_map(path, modify, &mut timer);
+    Map::create_from_raw(raw, map_model::RawToMapOptions::default(), &mut timer)
+}
+
+/// Only run the first half of the importer, so the RawMap can be changed before creating a Map.
+fn import_raw_map(
+    path: String,
+    modify: impl Fn(&mut convert_osm::Options),
+    timer: &mut Timer,
+) -> RawMap {
     let name = MapName::new("zz", "oneshot", &abstutil::basename(&path));
     let clip = None;
-    let raw = convert_osm::convert(
-        path,
-        name,
-        clip,
-        convert_osm::Options {
-            map_config: map_model::MapConfig {
-                driving_side: map_model::DrivingSide::Right,
-                bikes_can_use_bus_lanes: true,
-                inferred_sidewalks: true,
-                street_parking_spot_length: Distance::meters(8.0),
-                turn_on_red: false,
-            },
-            onstreet_parking: convert_osm::OnstreetParking::JustOSM,
-            public_offstreet_parking: convert_osm::PublicOffstreetParking::None,
-            private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(0),
-            include_railroads: true,
-            extra_buildings: None,
-            skip_local_roads: false,
-            filter_crosswalks: false,
-            gtfs_url: None,
+    let mut opts = convert_osm::Options {
+        map_config: map_model::MapConfig {
+            driving_side: map_model::DrivingSide::Right,
+            bikes_can_use_bus_lanes: true,
+            inferred_sidewalks: true,
+            street_parking_spot_length: Distance::meters(8.0),
+            turn_on_red: false,
         },
-        &mut timer,
-    );
-    Map::create_from_raw(raw, map_model::RawToMapOptions::default(), &mut timer)
+        onstreet_parking: convert_osm::OnstreetParking::JustOSM,
+        public_offstreet_parking: convert_osm::PublicOffstreetParking::None,
+        private_offstreet_parking: convert_osm::PrivateOffstreetParking::FixedPerBldg(0),
+        include_railroads: true,
+        extra_buildings: None,
+        extra_pois: Vec::new(),
+        skip_local_roads: false,
+        filter_crosswalks: false,
+        separate_sidewalks: false,
+        gtfs_url: None,
+    };
+    modify(&mut opts);
+    convert_osm::convert(path, name, clip, opts, timer)
 }
 
 /// Verify what turns are generated by writing (from lane, to lane, turn type).
@@ -241,6 +274,380 @@ fn test_lane_changing(map: &Map) -> Result<()> {
     Ok(())
 }
 
+/// Ride bikeshare bikes between two small docks, checking that docks fill up and empty out, and
+/// that rebalancing restores them.
+fn test_bikeshare(map: &Map) -> Result<()> {
+    // Find things by position, instead of hardcoding IDs
+    let mut docks: Vec<_> = map.all_bikeshare_docks().iter().collect();
+    docks.sort_by(|a, b| a.pt.x().partial_cmp(&b.pt.x()).unwrap());
+    let mut bldgs: Vec<_> = map.all_buildings().iter().collect();
+    bldgs.sort_by(|a, b| a.label_center.x().partial_cmp(&b.label_center.x()).unwrap());
+    if docks.len() != 2 || bldgs.len() != 2 {
+        anyhow::bail!(
+            "bikeshare.osm should have 2 docks and 2 buildings, but has {} and {}",
+            docks.len(),
+            bldgs.len()
+        );
+    }
+    let (west_dock, east_dock) = (docks[0].id, docks[1].id);
+
+    // Both docks start with 1 of 2 bikes. The first person rides to the east dock, filling it up
+    // and emptying the west one. The others find no bike within walking distance, so they walk.
+    let mut scenario = Scenario::empty(map, "bikeshare");
+    for idx in 0..3 {
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY + Duration::seconds(10.0 * idx as f64),
+                TripPurpose::Shopping,
+                TripEndpoint::Building(bldgs[0].id),
+                TripEndpoint::Building(bldgs[1].id),
+                TripMode::Micromobility,
+            )],
+        });
+    }
+
+    for rebalance in [false, true] {
+        let mut opts = sim::SimOptions::new("test_bikeshare");
+        opts.alerts = sim::AlertHandler::Silence;
+        if rebalance {
+            opts.bikeshare_rebalance_interval = Some(Duration::hours(1));
+        }
+        let mut sim = sim::Sim::new(map, opts);
+        let mut rng = sim::SimFlags::for_test("test_bikeshare").make_rng();
+        sim.instantiate(&scenario, map, &mut rng, &mut Timer::throwaway());
+        if sim.get_bikeshare_dock_state(west_dock) != (1, 2)
+            || sim.get_bikeshare_dock_state(east_dock) != (1, 2)
+        {
+            anyhow::bail!("Bikeshare docks should start half full");
+        }
+        sim.timed_step(map, Duration::hours(2), &mut None, &mut Timer::throwaway());
+        if !sim.is_done() {
+            anyhow::bail!("Bikeshare trips didn't finish");
+        }
+        if sim
+            .get_analytics()
+            .finished_trips
+            .iter()
+            .any(|(_, _, _, dt)| dt.is_none())
+        {
+            anyhow::bail!("A bikeshare trip was cancelled");
+        }
+
+        let expected = if rebalance {
+            [(west_dock, (1, 2)), (east_dock, (1, 2))]
+        } else {
+            [(west_dock, (0, 2)), (east_dock, (2, 2))]
+        };
+        for (dock, state) in expected {
+            let actual = sim.get_bikeshare_dock_state(dock);
+            if actual != state {
+                anyhow::bail!(
+                    "With rebalancing {}, {} has (bikes, capacity) {:?}, but expected {:?}",
+                    rebalance,
+                    dock,
+                    actual,
+                    state
+                );
+            }
+            // The analytics should agree with the live state
+            let last = sim
+                .get_analytics()
+                .bikeshare_dock_occupancy(sim.time(), dock, state.1)
+                .last()
+                .unwrap()
+                .1;
+            if last != actual.0 {
+                anyhow::bail!(
+                    "{} has {} bikes, but analytics recorded {}",
+                    dock,
+                    actual.0,
+                    last
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Walk between the two buildings in bikeshare.osm and export the trip. Every leg with a route
+/// has a distance, but geometry is only traced when asked for.
+fn test_trip_export(map: &Map) -> Result<()> {
+    let mut bldgs: Vec<_> = map.all_buildings().iter().collect();
+    bldgs.sort_by(|a, b| a.label_center.x().partial_cmp(&b.label_center.x()).unwrap());
+    let mut scenario = Scenario::empty(map, "trip_export");
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![IndividTrip::new(
+            Time::START_OF_DAY,
+            TripPurpose::Shopping,
+            TripEndpoint::Building(bldgs[0].id),
+            TripEndpoint::Building(bldgs[1].id),
+            TripMode::Walk,
+        )],
+    });
+
+    let mut opts = sim::SimOptions::new("test_trip_export");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(map, opts);
+    let mut rng = sim::SimFlags::for_test("test_trip_export").make_rng();
+    sim.instantiate(&scenario, map, &mut rng, &mut Timer::throwaway());
+    sim.timed_step(map, Duration::hours(1), &mut None, &mut Timer::throwaway());
+    if !sim.is_done() {
+        anyhow::bail!("The walking trip didn't finish");
+    }
+
+    let without = sim::TripExport::new(&sim, map, false);
+    let with = sim::TripExport::new(&sim, map, true);
+    if without.trips.len() != 1 || without.trips[0].outcome != "finished" {
+        anyhow::bail!("There should be one finished trip to export");
+    }
+    let walking: Vec<_> = without
+        .legs
+        .iter()
+        .zip(with.legs.iter())
+        .filter(|(leg, _)| leg.phase == "walking")
+        .collect();
+    if walking.is_empty() || without.legs.len() != with.legs.len() {
+        anyhow::bail!("Both exports should have the same legs, including some walking");
+    }
+    for (leg, traced) in walking {
+        if leg.distance.unwrap_or(0.0) <= 0.0 || leg.distance != traced.distance {
+            anyhow::bail!(
+                "Leg {} should have the same distance with or without geometry, but has {:?} and \
+                 {:?}",
+                leg.leg,
+                leg.distance,
+                traced.distance
+            );
+        }
+        if leg.geometry.is_some() || traced.geometry.is_none() {
+            anyhow::bail!("Leg {} should only have geometry when asked for", leg.leg);
+        }
+    }
+    Ok(())
+}
+
+/// Check the accessibility index on a one-way street heading east, with a supermarket at the east
+/// end and two apartment buildings to its west.
+fn test_accessibility(map: &Map) -> Result<()> {
+    let mut opts = AccessibilityOptions {
+        modes: vec![Mode::Walk],
+        ..Default::default()
+    };
+    let index = AccessibilityIndex::new(map, &opts, &mut Timer::throwaway());
+    // Find households by position, instead of hardcoding IDs
+    let mut households: Vec<_> = index.households.iter().collect();
+    households.sort_by(|a, b| {
+        let x1 = map.get_b(*a.0).label_center.x();
+        let x2 = map.get_b(*b.0).label_center.x();
+        x1.partial_cmp(&x2).unwrap()
+    });
+    if households.len() != 2 {
+        anyhow::bail!(
+            "accessibility.osm should have 2 households, but has {}",
+            households.len()
+        );
+    }
+    let (far, near) = (households[0].1, households[1].1);
+    let (far_time, near_time) = match (
+        far.closest(Mode::Walk, AmenityType::Supermarket),
+        near.closest(Mode::Walk, AmenityType::Supermarket),
+    ) {
+        (Some(t1), Some(t2)) => (t1, t2),
+        _ => anyhow::bail!("Both households should reach the supermarket"),
+    };
+    if near_time >= far_time || far_time > Duration::minutes(15) {
+        anyhow::bail!(
+            "The supermarket is {} from the far household and {} from the near one",
+            far_time,
+            near_time
+        );
+    }
+
+    // There's only one amenity, so the gravity score just discounts it by distance
+    for (household, time) in [(far, far_time), (near, near_time)] {
+        let expected = 0.5_f64.powf(time / opts.half_life);
+        if (household.score[&Mode::Walk] - expected).abs() > 1e-9 {
+            anyhow::bail!(
+                "Gravity score is {}, but expected {}",
+                household.score[&Mode::Walk],
+                expected
+            );
+        }
+    }
+    let expected = ((far.residents as f64) * far.score[&Mode::Walk]
+        + (near.residents as f64) * near.score[&Mode::Walk])
+        / ((far.residents + near.residents) as f64);
+    if (index.population_weighted_score(Mode::Walk) - expected).abs() > 1e-9 {
+        anyhow::bail!(
+            "Population-weighted score is {}, but expected {}",
+            index.population_weighted_score(Mode::Walk),
+            expected
+        );
+    }
+
+    // With a time limit between the two, only the far household loses access
+    opts.time_limit = (far_time + near_time) / 2.0;
+    let after = AccessibilityIndex::new(map, &opts, &mut Timer::throwaway());
+    let changes = index.changes(&after);
+    if changes.len() != 1
+        || changes[0].household != *households[0].0
+        || changes[0].amenity != AmenityType::Supermarket
+        || changes[0].gained()
+        || changes[0].after.is_some()
+    {
+        anyhow::bail!(
+            "Only the far household should lose access, but got {} changes",
+            changes.len()
+        );
+    }
+
+    // Biking back from the supermarket would mean going the wrong way, so biking times have to
+    // be measured from each household. There's no transit, so transit is just walking there.
+    let supermarket = map
+        .all_buildings()
+        .iter()
+        .find(|b| !b.amenities.is_empty())
+        .unwrap()
+        .id;
+    let limit = Duration::minutes(30);
+    let biking_back = map_model::connectivity::all_vehicle_costs_from(
+        map,
+        vec![Spot::Building(supermarket)],
+        limit,
+        PathConstraints::Bike,
+    );
+    if households.iter().any(|(b, _)| biking_back.contains_key(*b)) {
+        anyhow::bail!(
+            "Long Street should be one-way, so nobody can bike back from the supermarket"
+        );
+    }
+    let opts = AccessibilityOptions {
+        modes: vec![Mode::Bike, Mode::Transit],
+        ..Default::default()
+    };
+    let index = AccessibilityIndex::new(map, &opts, &mut Timer::throwaway());
+    for (b, _) in &households {
+        let household = &index.households[*b];
+        if household
+            .closest(Mode::Bike, AmenityType::Supermarket)
+            .is_none()
+        {
+            anyhow::bail!("{} should be able to bike to the supermarket", b);
+        }
+        let walking = map_model::connectivity::all_walking_costs_from(
+            map,
+            vec![Spot::Building(**b)],
+            limit,
+            WalkingOptions::default(),
+        )
+        .remove(&supermarket);
+        let transit = household.closest(Mode::Transit, AmenityType::Supermarket);
+        if walking.is_none() || transit != walking {
+            anyhow::bail!(
+                "With no transit, {} should take {:?} to walk to the supermarket, but took {:?}",
+                b,
+                walking,
+                transit
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Export an edited map to GMNS, then import the link attributes onto the original map. The
+/// edits should be recovered.
+fn test_gmns_round_trip() -> Result<()> {
+    let path = abstio::path("../tests/input/lane_selection.osm");
+    let mut edited = import_map(path.clone());
+    let r = edited
+        .all_roads()
+        .iter()
+        .find(|r| {
+            r.lanes
+                .iter()
+                .filter(|l| l.dir == Direction::Fwd && l.lane_type == LaneType::Driving)
+                .count()
+                >= 2
+        })
+        .map(|r| r.id)
+        .expect("lane_selection.osm should have a road with multiple driving lanes");
+    let mut edits = edited.get_edits().clone();
+    edits.commands.push(edited.edit_road_cmd(r, |new| {
+        let idx = new
+            .lanes_ltr
+            .iter()
+            .position(|l| l.dir == Direction::Fwd && l.lt == LaneType::Driving)
+            .unwrap();
+        new.lanes_ltr.remove(idx);
+        new.speed_limit = Speed::miles_per_hour(15.0);
+    }));
+    edited.must_apply_edits(edits, &mut Timer::throwaway());
+
+    let dir = format!("{}/gmns_round_trip", std::env::temp_dir().display());
+    map_model::gmns::export(&edited, &dir)?;
+
+    let mut map = import_map(path);
+    // An unedited export shouldn't change anything
+    {
+        let original_dir = format!(
+            "{}/gmns_round_trip_original",
+            std::env::temp_dir().display()
+        );
+        map_model::gmns::export(&map, &original_dir)?;
+        let (edits, problems) = map_model::gmns::import_link_attributes(&map, &original_dir)?;
+        if !edits.commands.is_empty() || !problems.is_empty() {
+            anyhow::bail!(
+                "Importing an unedited GMNS export produced {} edits and problems {:?}",
+                edits.commands.len(),
+                problems
+            );
+        }
+    }
+
+    let (edits, problems) = map_model::gmns::import_link_attributes(&map, &dir)?;
+    if !problems.is_empty() {
+        anyhow::bail!("GMNS round-trip had problems: {:?}", problems);
+    }
+    if edits.commands.len() != 1 {
+        anyhow::bail!(
+            "GMNS round-trip should change 1 road, but produced {} edits",
+            edits.commands.len()
+        );
+    }
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+    let before = edited.get_r(r);
+    let after = map.get_r(r);
+    let num_driving = |road: &map_model::Road| {
+        road.lanes
+            .iter()
+            .filter(|l| l.lane_type == LaneType::Driving)
+            .count()
+    };
+    if num_driving(before) != num_driving(after) {
+        anyhow::bail!(
+            "After a GMNS round-trip, {} has {} driving lanes, but should have {}",
+            r,
+            num_driving(after),
+            num_driving(before)
+        );
+    }
+    if (before.speed_limit - after.speed_limit)
+        .inner_meters_per_second()
+        .abs()
+        > 0.5
+    {
+        anyhow::bail!(
+            "After a GMNS round-trip, {} has speed limit {:.1} m/s, but should have {:.1} m/s",
+            r,
+            after.speed_limit.inner_meters_per_second(),
+            before.speed_limit.inner_meters_per_second()
+        );
+    }
+    Ok(())
+}
+
 /// Generate single blocks and merged LTN-style blocks for some maps, counting the number of
 /// failures. Store in a goldenfile, so somebody can manually do a visual diff if anything changes.
 fn test_blockfinding() -> Result<()> {
@@ -0,0 +1,303 @@
+//! Match IDs between two versions of a small map, where a road was split, another was drawn in the
+//! opposite direction, and another gained lanes. Then translate edits and a scenario between them.
+
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use geom::{Distance, Time};
+use map_model::raw::OriginalRoad;
+use map_model::{
+    osm, BuildingID, DirectedRoadID, Direction, EditCmd, IdMigration, IntersectionID, LaneID,
+    LaneType, Map, Position, RoadID,
+};
+use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
+
+pub fn test_id_migration() -> Result<()> {
+    let old = crate::import_map(abstio::path("../tests/input/migrate_before.osm"));
+    let new = crate::import_map(abstio::path("../tests/input/migrate_after.osm"));
+    let migration = IdMigration::new(&old, &new, &mut Timer::throwaway());
+
+    check_split_road(&old, &new, &migration)?;
+    check_reversed_road(&old, &new, &migration)?;
+    check_lanes(&old, &new, &migration)?;
+    check_edits(&old, &new, &migration)?;
+    check_scenario(&old, &new, &migration)?;
+    Ok(())
+}
+
+fn road(map: &Map, way: i64, i1: i64, i2: i64) -> Result<RoadID> {
+    map.find_r_by_osm_id(OriginalRoad::new(way, (i1, i2)))
+}
+
+fn intersection(map: &Map, node: i64) -> Result<IntersectionID> {
+    map.find_i_by_osm_id(osm::NodeID(node))
+}
+
+fn building(map: &Map, way: i64) -> Option<BuildingID> {
+    map.find_b_by_osm_id(osm::OsmID::Way(osm::WayID(way)))
+}
+
+/// The first lane of some type and direction, from the left
+fn find_lane(map: &Map, r: RoadID, lt: LaneType, dir: Direction) -> Result<LaneID> {
+    map.get_r(r)
+        .lanes
+        .iter()
+        .find(|l| l.lane_type == lt && l.dir == dir)
+        .map(|l| l.id)
+        .ok_or_else(|| anyhow::anyhow!("{} has no {:?} lane going {:?}", r, lt, dir))
+}
+
+/// The side street splits the east half of Main Street. The old road matches the new piece
+/// covering its middle.
+fn check_split_road(old: &Map, new: &Map, migration: &IdMigration) -> Result<()> {
+    let west = road(old, -100, -1, -2)?;
+    if migration.r(west) != Some(road(new, -100, -1, -2)?) {
+        bail!("The unchanged half of Main Street should match by OSM ID");
+    }
+
+    let east = road(old, -100, -2, -3)?;
+    let expected = road(new, -100, -2, -7)?;
+    if migration.r(east) != Some(expected) {
+        bail!(
+            "The split half of Main Street matched {:?}, not {}",
+            migration.r(east),
+            expected
+        );
+    }
+    if migration
+        .matched_by_geometry
+        .get("roads")
+        .cloned()
+        .unwrap_or(0)
+        == 0
+    {
+        bail!("Main Street should be reported as only matched by geometry");
+    }
+    // Positions past the end of the shorter piece are clamped
+    let new_len = new.get_r(expected).length();
+    if migration.dist_along_road(east, old.get_r(east).length()) != Some(new_len) {
+        bail!("The end of the old road should clamp to the end of the new piece");
+    }
+    Ok(())
+}
+
+/// East Street is drawn in the opposite direction now, so lane order, directions, and distances
+/// flip.
+fn check_reversed_road(old: &Map, new: &Map, migration: &IdMigration) -> Result<()> {
+    let old_r = road(old, -102, -3, -6)?;
+    let new_r = road(new, -102, -6, -3)?;
+    if migration.r(old_r) != Some(new_r) {
+        bail!(
+            "East Street matched {:?}, not {}",
+            migration.r(old_r),
+            new_r
+        );
+    }
+    if migration.dist_along_road(old_r, Distance::ZERO) != Some(new.get_r(new_r).length()) {
+        bail!("The start of East Street should be the end of the reversed road");
+    }
+    let dr = DirectedRoadID {
+        road: old_r,
+        dir: Direction::Fwd,
+    };
+    let expected = DirectedRoadID {
+        road: new_r,
+        dir: Direction::Back,
+    };
+    if migration.dr(dr) != Some(expected) {
+        bail!("{} matched {:?}, not {}", dr, migration.dr(dr), expected);
+    }
+
+    // Traffic heading east matches the lane heading east, whichever way the road is drawn
+    for dir in [Direction::Fwd, Direction::Back] {
+        let old_l = find_lane(old, old_r, LaneType::Driving, dir)?;
+        let new_l = find_lane(new, new_r, LaneType::Driving, dir.opposite())?;
+        if migration.l(old_l) != Some(new_l) {
+            bail!("{} matched {:?}, not {}", old_l, migration.l(old_l), new_l);
+        }
+        let dist = Distance::meters(10.0);
+        let pos = migration.pos(Position::new(old_l, dist), new);
+        if pos != Some(Position::new(new_l, new.get_l(new_l).length() - dist)) {
+            bail!("10m along {} matched {:?}", old_l, pos);
+        }
+    }
+    Ok(())
+}
+
+/// Cross Street goes from 2 to 4 lanes. Each old lane matches the first new lane with the same
+/// type and direction.
+fn check_lanes(old: &Map, new: &Map, migration: &IdMigration) -> Result<()> {
+    let old_r = road(old, -101, -4, -2)?;
+    let new_r = road(new, -101, -4, -2)?;
+    if migration.r(old_r) != Some(new_r) {
+        bail!("Cross Street should match by OSM ID");
+    }
+    if migration.i(intersection(old, -2)?) != Some(intersection(new, -2)?) {
+        bail!("The 4-way should match by OSM ID");
+    }
+    if new.get_r(new_r).lanes.len() != old.get_r(old_r).lanes.len() + 2 {
+        bail!("Cross Street should have 2 more lanes in migrate_after.osm");
+    }
+
+    for lane in &old.get_r(old_r).lanes {
+        let expected = find_lane(new, new_r, lane.lane_type, lane.dir)?;
+        if migration.l(lane.id) != Some(expected) {
+            bail!(
+                "{} ({:?} going {:?}) matched {:?}, not {}",
+                lane.id,
+                lane.lane_type,
+                lane.dir,
+                migration.l(lane.id),
+                expected
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Edits to a road with the same number of lanes carry over, flipped if the road was reversed.
+/// Edits to a road whose lanes changed are dropped.
+fn check_edits(old: &Map, new: &Map, migration: &IdMigration) -> Result<()> {
+    let mut edits = old.get_edits().clone();
+
+    // Turn the eastbound driving lane on East Street into a bike lane
+    let east = road(old, -102, -3, -6)?;
+    let before = old.get_r_edit(east);
+    let mut after = before.clone();
+    let idx = after
+        .lanes_ltr
+        .iter()
+        .position(|spec| spec.lt == LaneType::Driving && spec.dir == Direction::Fwd)
+        .unwrap();
+    after.lanes_ltr[idx].lt = LaneType::Biking;
+    edits.commands.push(EditCmd::ChangeRoad {
+        r: east,
+        old: before,
+        new: after,
+    });
+
+    // Any change to Cross Street
+    let cross = road(old, -101, -4, -2)?;
+    let before = old.get_r_edit(cross);
+    let mut after = before.clone();
+    after.speed_limit = after.speed_limit * 0.5;
+    edits.commands.push(EditCmd::ChangeRoad {
+        r: cross,
+        old: before,
+        new: after,
+    });
+
+    let (edits, problems) = edits.migrate(migration, new);
+    if edits.commands.len() != 1 || problems.len() != 1 {
+        bail!(
+            "Only the East Street edit should carry over, but {} commands did, with problems {:?}",
+            edits.commands.len(),
+            problems
+        );
+    }
+    match &edits.commands[0] {
+        EditCmd::ChangeRoad { r, new: after, .. } => {
+            if *r != road(new, -102, -6, -3)? {
+                bail!("The East Street edit moved to {}", r);
+            }
+            let spec = &after.lanes_ltr[after.lanes_ltr.len() - 1 - idx];
+            if spec.lt != LaneType::Biking || spec.dir != Direction::Back {
+                bail!(
+                    "The bike lane should be mirrored and go backwards, but it's {:?} going {:?}",
+                    spec.lt,
+                    spec.dir
+                );
+            }
+        }
+        cmd => bail!("The East Street edit became {:?}", cmd),
+    }
+    Ok(())
+}
+
+/// People with trips to a building that's gone are dropped. Everyone else's trips are translated.
+fn check_scenario(old: &Map, new: &Map, migration: &IdMigration) -> Result<()> {
+    let house = building(old, -110).unwrap();
+    let demolished = building(old, -111).unwrap();
+    let east_lane = find_lane(
+        old,
+        road(old, -102, -3, -6)?,
+        LaneType::Driving,
+        Direction::Fwd,
+    )?;
+
+    let mut scenario = Scenario::empty(old, "migration");
+    for (from, to, mode) in [
+        (
+            TripEndpoint::Border(intersection(old, -1)?),
+            TripEndpoint::Building(house),
+            TripMode::Walk,
+        ),
+        (
+            TripEndpoint::Border(intersection(old, -4)?),
+            TripEndpoint::Building(demolished),
+            TripMode::Walk,
+        ),
+        (
+            TripEndpoint::SuddenlyAppear(Position::new(east_lane, Distance::meters(10.0))),
+            TripEndpoint::Border(intersection(old, -6)?),
+            TripMode::Drive,
+        ),
+    ] {
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY,
+                TripPurpose::Shopping,
+                from,
+                to,
+                mode,
+            )],
+        });
+    }
+
+    let problems = scenario.migrate(migration, new);
+    if problems.len() != 1 || scenario.people.len() != 2 {
+        bail!(
+            "Only the trip to the demolished house should be dropped, but {} people are left, with \
+             problems {:?}",
+            scenario.people.len(),
+            problems
+        );
+    }
+    if &scenario.map_name != new.get_name() {
+        bail!("The scenario should be for {}", new.get_name().describe());
+    }
+
+    let trip = &scenario.people[0].trips[0];
+    if trip.origin != TripEndpoint::Border(intersection(new, -1)?)
+        || trip.destination != TripEndpoint::Building(building(new, -110).unwrap())
+    {
+        bail!(
+            "The trip home became {:?} to {:?}",
+            trip.origin,
+            trip.destination
+        );
+    }
+
+    let trip = &scenario.people[1].trips[0];
+    let new_lane = find_lane(
+        new,
+        road(new, -102, -6, -3)?,
+        LaneType::Driving,
+        Direction::Back,
+    )?;
+    let expected = Position::new(
+        new_lane,
+        new.get_l(new_lane).length() - Distance::meters(10.0),
+    );
+    if trip.origin != TripEndpoint::SuddenlyAppear(expected)
+        || trip.destination != TripEndpoint::Border(intersection(new, -6)?)
+    {
+        bail!(
+            "The trip along East Street became {:?} to {:?}",
+            trip.origin,
+            trip.destination
+        );
+    }
+    Ok(())
+}
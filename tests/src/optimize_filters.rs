@@ -0,0 +1,164 @@
+//! Run the filter optimizer on one neighborhood, split into quarters by two streets crossing in
+//! the middle. Each of the four arms leads to a different main road, so every pair of arms is a
+//! rat-run. Filtering any three arms stops all of them, without cutting anything off.
+
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use geom::PolyLine;
+use ltn_model::{
+    find_rat_runs, optimize_filters, FilterLayout, ModalFilters, Neighborhood, NeighborhoodID,
+    Partitioning,
+};
+use map_model::raw::{OriginalRoad, RawTransitRoute, RawTransitStop};
+use map_model::{Direction, LaneType, Map, PathConstraints, RawToMapOptions, RoadID};
+
+pub fn test_optimize_filters() -> Result<()> {
+    let path = abstio::path("../tests/input/optimize_filters.osm");
+    let map = crate::import_map(path.clone());
+    let layouts = optimize(&map, 3)?;
+    if layouts.len() != 3 {
+        bail!("Expected 3 alternative layouts, but got {}", layouts.len());
+    }
+    check_layouts(&map, &layouts, None)?;
+
+    // Run a bus along the west arm. The only way left is to filter the other three arms.
+    let west_arm = map.find_r_by_osm_id(OriginalRoad::new(-111, (-7, -9)))?;
+    let map = add_bus_route(&map, path, west_arm);
+    if map.all_transit_routes().len() != 1 {
+        bail!("The bus route along the west arm of Through Lane wasn't created");
+    }
+    let layouts = optimize(&map, 3)?;
+    if layouts.len() != 1 {
+        bail!(
+            "With the west arm off-limits, only one layout should work, but got {}",
+            layouts.len()
+        );
+    }
+    check_layouts(&map, &layouts, Some(west_arm))?;
+    Ok(())
+}
+
+fn neighborhood(map: &Map) -> Result<(Partitioning, NeighborhoodID)> {
+    let partitioning = Partitioning::seed_using_heuristics(map, &mut Timer::throwaway());
+    let ids: Vec<NeighborhoodID> = partitioning.all_neighborhoods().keys().cloned().collect();
+    if ids.len() != 1 {
+        bail!(
+            "The square between the main roads should be the only neighborhood, but found {}",
+            ids.len()
+        );
+    }
+    Ok((partitioning, ids[0]))
+}
+
+fn optimize(map: &Map, max_alternatives: usize) -> Result<Vec<FilterLayout>> {
+    let (partitioning, id) = neighborhood(map)?;
+    let filters = ModalFilters::default();
+    let before = Neighborhood::new(map, &partitioning, &filters, id);
+    if find_rat_runs(map, &before, &filters, &mut Timer::throwaway())
+        .paths
+        .is_empty()
+    {
+        bail!("There should be rat-runs through the neighborhood before filtering");
+    }
+    Ok(optimize_filters(
+        map,
+        &partitioning,
+        &filters,
+        &[id],
+        max_alternatives,
+        &mut Timer::throwaway(),
+    ))
+}
+
+/// Every layout must stop every rat-run, keep every cell connected, and leave `transit_road`
+/// alone. The fewest filters come first.
+fn check_layouts(map: &Map, layouts: &[FilterLayout], transit_road: Option<RoadID>) -> Result<()> {
+    let (partitioning, id) = neighborhood(map)?;
+    if layouts[0].new_filters != 3 {
+        bail!(
+            "The best layout should filter 3 of the 4 arms, but has {} filters",
+            layouts[0].new_filters
+        );
+    }
+    for pair in layouts.windows(2) {
+        if (pair[0].remaining_rat_runs, pair[0].new_filters)
+            > (pair[1].remaining_rat_runs, pair[1].new_filters)
+        {
+            bail!("Layouts aren't ranked by the number of filters");
+        }
+    }
+
+    for (idx, layout) in layouts.iter().enumerate() {
+        let filters = &layout.modal_filters;
+        if layout.new_filters != filters.roads.len() {
+            bail!(
+                "Layout {} claims {} new filters, but has {}",
+                idx,
+                layout.new_filters,
+                filters.roads.len()
+            );
+        }
+        if let Some(r) = transit_road {
+            if filters.roads.contains_key(&r) {
+                bail!("Layout {} filters {}, which a bus uses", idx, r);
+            }
+        }
+
+        let neighborhood = Neighborhood::new(map, &partitioning, filters, id);
+        if neighborhood.cells.iter().any(|c| c.is_disconnected()) {
+            bail!("Layout {} disconnects a cell", idx);
+        }
+        let rat_runs = find_rat_runs(map, &neighborhood, filters, &mut Timer::throwaway());
+        if layout.remaining_rat_runs != 0 || !rat_runs.paths.is_empty() {
+            bail!(
+                "Layout {} should stop every rat-run, but {} remain",
+                idx,
+                rat_runs.paths.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Import the map again, with a bus route between two stops along one road.
+fn add_bus_route(map: &Map, path: String, r: RoadID) -> Map {
+    // The stops go along the sidewalk next to the lane heading forwards. The raw map uses the
+    // same coordinates as the Map created from it.
+    let sidewalk = map
+        .get_r(r)
+        .lanes
+        .iter()
+        .find(|l| l.lane_type == LaneType::Sidewalk && l.dir == Direction::Fwd)
+        .unwrap();
+    let mut timer = Timer::throwaway();
+    let mut raw = crate::import_raw_map(path, |_| {}, &mut timer);
+    let mut stops = Vec::new();
+    let mut shape = Vec::new();
+    for (idx, fraction) in [1.0 / 3.0, 2.0 / 3.0].into_iter().enumerate() {
+        let position = sidewalk
+            .lane_center_pts
+            .must_dist_along(sidewalk.length() * fraction)
+            .0;
+        let gtfs_id = format!("stop{}", idx);
+        raw.transit_stops.insert(
+            gtfs_id.clone(),
+            RawTransitStop {
+                gtfs_id: gtfs_id.clone(),
+                position,
+                name: gtfs_id.clone(),
+            },
+        );
+        stops.push(gtfs_id);
+        shape.push(position);
+    }
+    raw.transit_routes.push(RawTransitRoute {
+        long_name: "Through Lane shuttle".to_string(),
+        short_name: "1".to_string(),
+        gtfs_id: "shuttle".to_string(),
+        shape: PolyLine::must_new(shape),
+        stops,
+        route_type: PathConstraints::Bus,
+    });
+    Map::create_from_raw(raw, RawToMapOptions::default(), &mut timer)
+}
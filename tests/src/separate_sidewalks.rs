@@ -0,0 +1,104 @@
+//! Import a small map with sidewalks mapped as separate ways, and a crossing node partway along a
+//! road, using `convert_osm::Options::separate_sidewalks`.
+
+use anyhow::{bail, Result};
+
+use map_model::raw::OriginalRoad;
+use map_model::{osm, CrossingType, Direction, LaneType, Map, RoadID, TurnType};
+
+pub fn test_separate_sidewalks() -> Result<()> {
+    let map = crate::import_map_with_options(
+        abstio::path("../tests/input/separate_sidewalks.osm"),
+        |opts| {
+            opts.separate_sidewalks = true;
+        },
+    );
+
+    check_snapped_sidewalks(&map)?;
+    check_mid_block_crossing(&map)?;
+    Ok(())
+}
+
+fn sidewalks(map: &Map, r: RoadID) -> Vec<Direction> {
+    map.get_r(r)
+        .lanes
+        .iter()
+        .filter(|l| l.lane_type == LaneType::Sidewalk)
+        .map(|l| l.dir)
+        .collect()
+}
+
+/// The footways become sidewalk lanes on the road they run alongside, instead of separate roads.
+fn check_snapped_sidewalks(map: &Map) -> Result<()> {
+    for way in [-200, -201, -202] {
+        if map
+            .all_roads()
+            .iter()
+            .any(|r| r.orig_id.osm_way_id == osm::WayID(way))
+        {
+            bail!("The separate sidewalk {} was imported as a road", way);
+        }
+    }
+
+    // Without the sidewalk on the north side, Quiet Lane would get both sidewalks inferred
+    let quiet_lane = map.find_r_by_osm_id(OriginalRoad::new(-101, (-4, -5)))?;
+    if sidewalks(map, quiet_lane) != vec![Direction::Fwd] {
+        bail!(
+            "Quiet Lane should only have a sidewalk on the right, but has {:?}",
+            sidewalks(map, quiet_lane)
+        );
+    }
+    Ok(())
+}
+
+/// Main Street is split at the zebra crossing. The crossings at the other intersections don't
+/// match any crossing node, so they're unmarked.
+fn check_mid_block_crossing(map: &Map) -> Result<()> {
+    if map
+        .find_r_by_osm_id(OriginalRoad::new(-100, (-1, -3)))
+        .is_ok()
+    {
+        bail!("Main Street wasn't split at the crossing");
+    }
+    for (i1, i2) in [(-1, -2), (-2, -3)] {
+        let r = map.find_r_by_osm_id(OriginalRoad::new(-100, (i1, i2)))?;
+        if sidewalks(map, r).len() != 2 {
+            bail!(
+                "Each piece of Main Street should have both sidewalks, but {} has {:?}",
+                r,
+                sidewalks(map, r)
+            );
+        }
+    }
+
+    let crossings = |node| -> Result<Vec<(TurnType, Option<CrossingType>)>> {
+        let i = map.find_i_by_osm_id(osm::NodeID(node))?;
+        Ok(map
+            .get_i(i)
+            .turns
+            .iter()
+            .filter(|t| t.turn_type.pedestrian_crossing())
+            .map(|t| (t.turn_type, t.crossing_type(map)))
+            .collect())
+    };
+    let mid_block = crossings(-2)?;
+    if mid_block != vec![(TurnType::Crosswalk, Some(CrossingType::Marked))] {
+        bail!(
+            "The mid-block crossing should be one marked crosswalk, but it's {:?}",
+            mid_block
+        );
+    }
+    let corner = crossings(-3)?;
+    if corner.is_empty()
+        || corner
+            .iter()
+            .any(|(turn_type, _)| *turn_type != TurnType::UnmarkedCrossing)
+    {
+        bail!(
+            "The corner of Main and Cross Street should only have unmarked crossings, but has \
+             {:?}",
+            corner
+        );
+    }
+    Ok(())
+}